@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use hex;
 
 use beserial::Deserialize;
-use database::{Environment, ReadTransaction, WriteTransaction};
+use database::{Database, Environment, ReadTransaction, WriteTransaction};
 use database as db;
 use hash::Blake2bHash;
 use keys::Address;
@@ -16,28 +16,28 @@ use primitives::policy;
 use primitives::transaction::{Transaction, TransactionFlags};
 
 use crate::tree::AccountsTree;
+use crate::accounts_diff::AccountsDiff;
 use crate::accounts_proof::AccountsProof;
+use crate::range_proof::RangeProof;
 
 #[derive(Debug)]
 pub struct Accounts<'env> {
     env: &'env Environment,
     tree: AccountsTree<'env>,
+    nonce_db: Database<'env>,
 }
 
 impl<'env> Accounts<'env> {
+    const NONCE_DB_NAME: &'static str = "AccountNonces";
+
     pub fn new(env: &'env Environment) -> Self {
-        return Accounts { env, tree: AccountsTree::new(env) };
+        let nonce_db = env.open_database(Self::NONCE_DB_NAME.to_string());
+        return Accounts { env, tree: AccountsTree::new(env), nonce_db };
     }
 
     pub fn init(&self, txn: &mut WriteTransaction, network_id: NetworkId) {
         let network_info = get_network_info(network_id).unwrap();
-        let account_bytes = hex::decode(&network_info.genesis_accounts).unwrap();
-        let reader = &mut &account_bytes[..];
-        let count = u16::deserialize(reader).unwrap();
-
-        for _ in 0..count {
-            let address = Address::deserialize(reader).unwrap();
-            let account = Account::deserialize(reader).unwrap();
+        for (address, account) in Accounts::genesis_accounts(network_id) {
             self.tree.put_batch(txn, &address, account);
         }
         self.tree.finalize_batch(txn);
@@ -51,6 +51,23 @@ impl<'env> Accounts<'env> {
                    "Genesis AccountHash mismatch");
     }
 
+    /// Returns the initial account allocation of the given network, i.e. the accounts
+    /// that are put into the tree by `init` before the genesis block body is applied.
+    pub fn genesis_accounts(network_id: NetworkId) -> Vec<(Address, Account)> {
+        let network_info = get_network_info(network_id).unwrap();
+        let account_bytes = hex::decode(&network_info.genesis_accounts).unwrap();
+        let reader = &mut &account_bytes[..];
+        let count = u16::deserialize(reader).unwrap();
+
+        let mut accounts = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let address = Address::deserialize(reader).unwrap();
+            let account = Account::deserialize(reader).unwrap();
+            accounts.push((address, account));
+        }
+        accounts
+    }
+
     pub fn get(&self, address: &Address, txn_option: Option<&db::Transaction>) -> Account {
         return match txn_option {
             Some(txn) => self.tree.get(txn, address),
@@ -58,6 +75,35 @@ impl<'env> Accounts<'env> {
         }.unwrap_or(Account::INITIAL);
     }
 
+    /// Returns the part of the balance of `address` that can be spent at `height`.
+    /// For vesting contracts, this excludes the funds that are still locked by the vesting schedule.
+    /// HTLCs are reported with their full balance: how much of it can be withdrawn depends on the
+    /// pre-image or the signatures presented, not on the height.
+    pub fn get_spendable_balance(&self, address: &Address, height: u32, txn_option: Option<&db::Transaction>) -> Coin {
+        return match self.get(address, txn_option) {
+            Account::Vesting(contract) => contract.balance.checked_sub(contract.min_cap(height)).unwrap_or(Coin::ZERO),
+            Account::HTLC(contract) => contract.balance,
+            Account::Basic(account) => account.balance,
+        };
+    }
+
+    /// Returns the number of transactions sent from `address` in the committed blocks.
+    /// The nonces are stored next to the accounts tree and do not affect the accounts hash.
+    pub fn get_nonce(&self, address: &Address, txn_option: Option<&db::Transaction>) -> u32 {
+        return match txn_option {
+            Some(txn) => txn.get(&self.nonce_db, address.as_bytes()),
+            None => ReadTransaction::new(self.env).get(&self.nonce_db, address.as_bytes())
+        }.unwrap_or(0);
+    }
+
+    /// Returns all accounts of the given type. This is a full scan of the accounts tree.
+    pub fn get_accounts_by_type(&self, account_type: AccountType, txn_option: Option<&db::Transaction>) -> Vec<(Address, Account)> {
+        return match txn_option {
+            Some(txn) => self.tree.get_accounts_by_type(txn, account_type),
+            None => self.tree.get_accounts_by_type(&ReadTransaction::new(self.env), account_type)
+        };
+    }
+
     pub fn hash(&self, txn_option: Option<&db::Transaction>) -> Blake2bHash {
         return match txn_option {
             Some(txn) => self.tree.root_hash(txn),
@@ -102,6 +148,7 @@ impl<'env> Accounts<'env> {
         for transaction in &body.transactions {
             self.process_transaction(txn, &transaction.sender, Some(transaction.sender_type), transaction, block_height,
                                      |account, transaction, block_height| account.with_outgoing_transaction(transaction, block_height))?;
+            self.increment_nonce(txn, &transaction.sender);
         }
 
         // Process recipient accounts.
@@ -158,6 +205,7 @@ impl<'env> Accounts<'env> {
         for transaction in &body.transactions {
             self.process_transaction(txn, &transaction.sender, Some(transaction.sender_type), transaction, block_height,
                                      |account, transaction, block_height| account.without_outgoing_transaction(transaction, block_height))?;
+            self.decrement_nonce(txn, &transaction.sender);
         }
 
         self.tree.finalize_batch(txn);
@@ -179,6 +227,20 @@ impl<'env> Accounts<'env> {
         return Ok(());
     }
 
+    fn increment_nonce(&self, txn: &mut WriteTransaction, address: &Address) {
+        let nonce = self.get_nonce(address, Some(txn));
+        txn.put(&self.nonce_db, address.as_bytes(), &(nonce + 1));
+    }
+
+    fn decrement_nonce(&self, txn: &mut WriteTransaction, address: &Address) {
+        // Databases created before nonces were tracked have no nonce for senders of older blocks.
+        match self.get_nonce(address, Some(txn)) {
+            0 => {},
+            1 => txn.remove(&self.nonce_db, address.as_bytes()),
+            nonce => txn.put(&self.nonce_db, address.as_bytes(), &(nonce - 1)),
+        }
+    }
+
     fn process_miner_reward<F>(&self, txn: &mut WriteTransaction, body: &BlockBody, block_height: u32, account_op: F) -> Result<(), AccountError>
         where F: Fn(Account, &Transaction, u32) -> Result<Account, AccountError> {
 
@@ -264,4 +326,31 @@ impl<'env> Accounts<'env> {
     pub fn get_accounts_proof(&self, txn: &db::Transaction, addresses: &Vec<Address>) -> AccountsProof {
         self.tree.get_accounts_proof(txn, addresses)
     }
+
+    /// Returns the number of accounts tree nodes at each depth, starting with the root node at depth 0.
+    pub fn node_count_by_depth(&self, txn: &db::Transaction) -> Vec<usize> {
+        self.tree.node_count_by_depth(txn)
+    }
+
+    /// Proves all accounts with an address in `[start, end)`.
+    pub fn get_range_proof(&self, txn: &db::Transaction, start: &Address, end: &Address) -> RangeProof {
+        self.tree.get_range_proof(txn, start, end)
+    }
+
+    /// Computes the accounts that changed between the committed state and the state seen by `txn`.
+    /// Only the latest version of the tree is stored, so `from_root` and `to_root` must match
+    /// these two states (in either order). Returns `None` otherwise.
+    pub fn diff(&self, from_root: &Blake2bHash, to_root: &Blake2bHash, txn: &db::Transaction) -> Option<AccountsDiff> {
+        let committed_txn = ReadTransaction::new(self.env);
+        let committed_root = self.tree.root_hash(&committed_txn);
+        let txn_root = self.tree.root_hash(txn);
+
+        if from_root == &committed_root && to_root == &txn_root {
+            return Some(self.tree.diff(&committed_txn, txn));
+        }
+        if from_root == &txn_root && to_root == &committed_root {
+            return Some(self.tree.diff(txn, &committed_txn));
+        }
+        return None;
+    }
 }
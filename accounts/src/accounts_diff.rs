@@ -0,0 +1,23 @@
+use keys::Address;
+use primitives::account::Account;
+
+/// The terminal nodes that differ between two states of the accounts tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccountsDiff {
+    /// Accounts that only exist in the new state.
+    pub added: Vec<(Address, Account)>,
+    /// Accounts that exist in both states, with their new value.
+    pub changed: Vec<(Address, Account)>,
+    /// Accounts that only exist in the old state, with their old value.
+    pub removed: Vec<(Address, Account)>,
+}
+
+impl AccountsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.added.len() + self.changed.len() + self.removed.len()
+    }
+}
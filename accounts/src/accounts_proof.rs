@@ -1,4 +1,4 @@
-use beserial::{Serialize, Deserialize};
+use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError};
 use crate::tree::AccountsTreeNode;
 use crate::tree::AccountsTreeNodeChild;
 use crate::tree::AddressNibbles;
@@ -8,7 +8,7 @@ use hash::Hash;
 use hash::Blake2bHash;
 use keys::Address;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize)]
 pub struct AccountsProof {
     #[beserial(len_type(u16))]
     nodes: Vec<AccountsTreeNode>,
@@ -16,22 +16,78 @@ pub struct AccountsProof {
     verified: bool
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountsProofError {
+    TooManyNodes { count: usize, max: usize },
+    InvalidProof,
+}
+
+impl Deserialize for AccountsProof {
+    fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
+        AccountsProof::deserialize_with_max_nodes(reader, AccountsProof::MAX_NODES)
+    }
+}
+
 impl AccountsProof {
+    /// Maximum number of nodes we accept in a proof from an untrusted source.
+    pub const MAX_NODES: usize = 10000;
+
     pub(crate) fn new(nodes : Vec<AccountsTreeNode>) -> AccountsProof {
         return AccountsProof { nodes, verified: false };
     }
 
-    pub fn verify(&mut self) -> bool {
+    /// Deserializes a proof, rejecting it before reading any node if it has more than `max_nodes` nodes.
+    pub fn deserialize_with_max_nodes<R: ReadBytesExt>(reader: &mut R, max_nodes: usize) -> Result<Self, SerializingError> {
+        let count: u16 = Deserialize::deserialize(reader)?;
+        if count as usize > max_nodes {
+            return Err(SerializingError::InvalidValue);
+        }
+
+        let mut nodes = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            nodes.push(Deserialize::deserialize(reader)?);
+        }
+        return Ok(AccountsProof::new(nodes));
+    }
+
+    pub fn verify(&mut self) -> Result<(), AccountsProofError> {
+        self.verify_with_max_nodes(AccountsProof::MAX_NODES)
+    }
+
+    pub fn verify_with_max_nodes(&mut self, max_nodes: usize) -> Result<(), AccountsProofError> {
+        self.verified = false;
+
+        // Check the proof size first, this check is cheap.
+        if self.nodes.len() > max_nodes {
+            return Err(AccountsProofError::TooManyNodes { count: self.nodes.len(), max: max_nodes });
+        }
+
+        if !AccountsProof::verify_nodes(&self.nodes) {
+            return Err(AccountsProofError::InvalidProof);
+        }
+        self.verified = true;
+        return Ok(());
+    }
+
+    /// Checks that the given nodes (in post order) form a valid subtree of the accounts tree.
+    pub(crate) fn verify_nodes(nodes: &[AccountsTreeNode]) -> bool {
+        AccountsProof::verify_nodes_counting_steps(nodes).0
+    }
+
+    /// Like `verify_nodes`, but also returns how often a child was taken off the stack, which bounds the work done.
+    fn verify_nodes_counting_steps(nodes: &[AccountsTreeNode]) -> (bool, usize) {
+        let mut steps = 0;
         let mut children: Vec<AccountsTreeNode> = Vec::new();
-        for node in &self.nodes {
+        for node in nodes {
             // If node is a branch node, validate its children.
             if node.is_branch() {
                 while let Some(child) = children.pop() {
+                    steps += 1;
                     if node.prefix().is_prefix_of(child.prefix()) {
                         let hash = child.hash::<Blake2bHash>();
                         // If the child is not valid, return false.
-                        if node.get_child_hash(child.prefix()).unwrap() != &hash || &node.get_child_prefix(child.prefix()).unwrap() != child.prefix() {
-                            return false;
+                        if node.get_child_hash(child.prefix()) != Some(&hash) || node.get_child_prefix(child.prefix()).as_ref() != Some(child.prefix()) {
+                            return (false, steps);
                         }
                     } else {
                         children.push(child);
@@ -42,9 +98,7 @@ impl AccountsProof {
             children.push(node.clone());
         }
         let root_nibbles : AddressNibbles = "".parse().unwrap();
-        let valid = children.len() == 1 && children[0].prefix() == &root_nibbles && children[0].is_branch();
-        self.verified = valid;
-        return valid;
+        return (children.len() == 1 && children[0].prefix() == &root_nibbles && children[0].is_branch(), steps);
     }
 
     pub fn get_account(&self, address: &Address) -> Option<Account> {
@@ -117,7 +171,7 @@ fn it_can_verify() {
 
     // The first proof proves the 4 terminal nodes (T1, T2, T3 and T4)
     let mut proof1 = AccountsProof::new(vec![t1.clone(), t3.clone(), t4.clone(), b2.clone(), t2.clone(), b1.clone(), r1.clone()]);
-    assert!(proof1.verify());
+    assert_eq!(proof1.verify(), Ok(()));
     assert_eq!(account1, proof1.get_account(&address1).unwrap());
     assert_eq!(account2, proof1.get_account(&address2).unwrap());
     assert_eq!(account3, proof1.get_account(&address3).unwrap());
@@ -125,7 +179,7 @@ fn it_can_verify() {
 
     // The second proof proves the 2 leftmost terminal nodes (T1 and T3)
     let mut proof2 = AccountsProof::new(vec![t1.clone(), t3.clone(), b2.clone(), b1.clone(), r1.clone()]);
-    assert!(proof2.verify());
+    assert_eq!(proof2.verify(), Ok(()));
     assert_eq!(account1, proof2.get_account(&address1).unwrap());
     assert_eq!(account3, proof2.get_account(&address3).unwrap());
     assert_eq!(None, proof2.get_account(&address2));
@@ -133,7 +187,7 @@ fn it_can_verify() {
 
     // The third proof just proves T4
     let mut proof3 = AccountsProof::new(vec![t4.clone(), b2.clone(), b1.clone(), r1.clone()]);
-    assert!(proof3.verify());
+    assert_eq!(proof3.verify(), Ok(()));
     assert_eq!(account4, proof3.get_account(&address4).unwrap());
     assert_eq!(None, proof3.get_account(&address1));
     assert_eq!(None, proof3.get_account(&address2));
@@ -142,3 +196,46 @@ fn it_can_verify() {
     // must return the correct root hash
     assert!(proof1.root_hash() == r1.hash());
 }
+
+#[test]
+fn it_limits_the_number_of_nodes() {
+    let node = |i: usize| {
+        let prefix: AddressNibbles = format!("{:040x}", i).parse().unwrap();
+        AccountsTreeNode::new_terminal(prefix, Account::Basic(BasicAccount { balance: 1.into() }))
+    };
+    let max_nodes = 100;
+
+    let at_limit = AccountsProof::new((0..max_nodes).map(node).collect()).serialize_to_vec();
+    let proof = AccountsProof::deserialize_with_max_nodes(&mut &at_limit[..], max_nodes).unwrap();
+    assert_eq!(proof.nodes.len(), max_nodes);
+
+    let over_limit = AccountsProof::new((0..max_nodes + 1).map(node).collect()).serialize_to_vec();
+    assert_eq!(AccountsProof::deserialize_with_max_nodes(&mut &over_limit[..], max_nodes).err(), Some(SerializingError::InvalidValue));
+
+    // The limit is enforced on verification as well.
+    let mut proof = AccountsProof::new((0..max_nodes + 1).map(node).collect());
+    assert_eq!(proof.verify_with_max_nodes(max_nodes), Err(AccountsProofError::TooManyNodes { count: max_nodes + 1, max: max_nodes }));
+}
+
+#[test]
+fn it_verifies_large_adversarial_proofs_in_linear_time() {
+    // Many unrelated terminal nodes followed by branches that don't cover them
+    // maximize the work done on the stack of pending children.
+    let adversarial_proof = |count: usize| {
+        let mut nodes: Vec<AccountsTreeNode> = (0..count / 2).map(|i| {
+            let prefix: AddressNibbles = format!("{:040x}", i).parse().unwrap();
+            AccountsTreeNode::new_terminal(prefix, Account::Basic(BasicAccount { balance: 1.into() }))
+        }).collect();
+        nodes.extend((0..count - count / 2).map(|i| {
+            let prefix: AddressNibbles = format!("f{:039x}", i).parse().unwrap();
+            AccountsTreeNode::new_branch(prefix, Default::default())
+        }));
+        AccountsProof::new(nodes)
+    };
+
+    let count = AccountsProof::MAX_NODES;
+    let (valid, steps) = AccountsProof::verify_nodes_counting_steps(&adversarial_proof(count).nodes);
+    assert!(!valid);
+    // Every node is taken off the stack at most once, plus once per branch for the node that ends its children.
+    assert!(steps <= 2 * count, "{} steps for {} nodes", steps, count);
+}
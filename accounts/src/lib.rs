@@ -13,6 +13,8 @@ extern crate nimiq_network_primitives as network_primitives;
 pub mod tree;
 pub mod accounts;
 pub mod accounts_proof;
+pub mod range_proof;
+pub mod accounts_diff;
 
 pub use self::accounts::Accounts;
 
@@ -0,0 +1,149 @@
+use std::collections::BTreeSet;
+
+use beserial::{Serialize, Deserialize};
+use crate::accounts_proof::AccountsProof;
+use crate::tree::AccountsTreeNode;
+use crate::tree::AddressNibbles;
+use primitives::account::Account;
+use hash::Hash;
+use hash::Blake2bHash;
+use keys::Address;
+
+/// Proves the set of all accounts with an address in `[start, end)`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RangeProof {
+    start: Address,
+    end: Address,
+    #[beserial(len_type(u16))]
+    nodes: Vec<AccountsTreeNode>,
+    #[beserial(skip)]
+    verified: bool
+}
+
+impl RangeProof {
+    pub(crate) fn new(start: Address, end: Address, nodes: Vec<AccountsTreeNode>) -> RangeProof {
+        return RangeProof { start, end, nodes, verified: false };
+    }
+
+    pub fn verify(&mut self) -> bool {
+        let valid = AccountsProof::verify_nodes(&self.nodes) && self.is_complete();
+        self.verified = valid;
+        return valid;
+    }
+
+    /// Checks that every node whose subtree overlaps the range is part of the proof.
+    /// Nodes outside of the range are only committed to by their hash in the parent.
+    fn is_complete(&self) -> bool {
+        let start = AddressNibbles::from(&self.start);
+        let end = AddressNibbles::from(&self.end);
+
+        let prefixes: BTreeSet<&AddressNibbles> = self.nodes.iter().map(|node| node.prefix()).collect();
+        for node in &self.nodes {
+            for child in node.iter_children() {
+                let child_prefix = node.prefix() + &child.suffix;
+                if RangeProof::overlaps(&child_prefix, &start, &end) && !prefixes.contains(&&child_prefix) {
+                    return false;
+                }
+            }
+        }
+        return true;
+    }
+
+    /// Returns true if any address starting with `prefix` lies within `[start, end)`.
+    pub(crate) fn overlaps(prefix: &AddressNibbles, start: &AddressNibbles, end: &AddressNibbles) -> bool {
+        if start >= end {
+            return false;
+        }
+        let len = prefix.len();
+
+        // The largest address below the prefix must not be smaller than start.
+        if &start.slice(0, len) > prefix {
+            return false;
+        }
+
+        // The smallest address below the prefix must be smaller than end.
+        let end_prefix = end.slice(0, len);
+        if prefix < &end_prefix {
+            return true;
+        }
+        let end_suffix = end.suffix_from(len);
+        return prefix == &end_prefix && (0..end_suffix.len()).any(|i| end_suffix.get(i) != Some(0));
+    }
+
+    pub fn get_accounts(&self) -> Vec<(Address, Account)> {
+        assert!(self.verified, "RangeProof must be verified before retrieving accounts. Call verify() first.");
+
+        let start = AddressNibbles::from(&self.start);
+        let end = AddressNibbles::from(&self.end);
+
+        let mut accounts = Vec::new();
+        for node in &self.nodes {
+            if let AccountsTreeNode::TerminalNode { prefix, account } = node {
+                if RangeProof::overlaps(prefix, &start, &end) {
+                    let address = Address::from(hex::decode(prefix.to_string()).unwrap().as_slice());
+                    accounts.push((address, account.clone()));
+                }
+            }
+        }
+        return accounts;
+    }
+
+    pub fn start(&self) -> &Address {
+        &self.start
+    }
+
+    pub fn end(&self) -> &Address {
+        &self.end
+    }
+
+    pub fn root_hash(&self) -> Blake2bHash {
+        return (&self.nodes[self.nodes.len() - 1]).hash();
+    }
+}
+
+#[test]
+fn it_can_verify_range_proofs() {
+    use database::volatile::VolatileEnvironment;
+    use database::WriteTransaction;
+    use primitives::account::BasicAccount;
+    use crate::tree::AccountsTree;
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let tree = AccountsTree::new(&env);
+    let mut txn = WriteTransaction::new(&env);
+
+    let addresses: Vec<Address> = ["0011111111111111111111111111111111111111", "0020000000000000000000000000000000000000",
+        "0022222222222222222222222222222222222222", "0033333333333333333333333333333333333333", "1000000000000000000000000000000000000000"]
+        .iter()
+        .map(|s| Address::from(hex::decode(s).unwrap().as_slice()))
+        .collect();
+    for (i, address) in addresses.iter().enumerate() {
+        tree.put(&mut txn, address, Account::Basic(BasicAccount { balance: (i as u64 + 1).into() }));
+    }
+
+    // The range [0020.., 0033..) covers the second and third account.
+    let mut proof = tree.get_range_proof(&txn, &addresses[1], &addresses[3]);
+    assert!(proof.verify());
+    assert_eq!(proof.root_hash(), tree.root_hash(&txn));
+    let accounts: Vec<Address> = proof.get_accounts().into_iter().map(|(address, _)| address).collect();
+    assert_eq!(accounts, vec![addresses[1].clone(), addresses[2].clone()]);
+
+    // An empty range still proves the root.
+    let mut proof = tree.get_range_proof(&txn, &addresses[1], &addresses[1]);
+    assert!(proof.verify());
+    assert!(proof.get_accounts().is_empty());
+
+    // Omitting a terminal node in range must fail.
+    let mut proof = tree.get_range_proof(&txn, &addresses[0], &addresses[4]);
+    assert!(proof.verify());
+    let terminal = proof.nodes.iter().position(|node| node.prefix() == &AddressNibbles::from(&addresses[2])).unwrap();
+    proof.nodes.remove(terminal);
+    assert!(!proof.verify());
+
+    // Omitting a whole subtree in range must fail as well.
+    let mut proof = tree.get_range_proof(&txn, &addresses[0], &addresses[4]);
+    proof.nodes.retain(|node| !"002".parse::<AddressNibbles>().unwrap().is_prefix_of(node.prefix()));
+    assert!(!proof.verify());
+
+    txn.abort();
+}
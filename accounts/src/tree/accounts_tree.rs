@@ -1,9 +1,12 @@
+use crate::accounts_diff::AccountsDiff;
 use crate::accounts_proof::AccountsProof;
+use crate::range_proof::RangeProof;
 use database::{Database, Transaction, WriteTransaction, Environment};
 use hash::{Hash, Blake2bHash};
 use keys::Address;
-use primitives::account::Account;
+use primitives::account::{Account, AccountType};
 use std::cmp::min;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use super::{AccountsTreeNode, AddressNibbles, NO_CHILDREN};
 
@@ -180,20 +183,20 @@ impl<'env> AccountsTree<'env> {
             // If the prefix fully matches, we have found the requested node.
             // If the prefix does not fully match, the requested address is not part of this node.
             // Include the node in the proof nevertheless to prove that the account doesn't exist.
-            if node.prefix().is_prefix_of(prefix) || node.prefix() == prefix {
+            if !node.prefix().is_prefix_of(prefix) || node.prefix() == prefix {
                 include_node = true;
                 i += 1;
                 continue;
             }
 
             if let Some(child_prefix) = node.get_child_prefix(prefix) {
-                let mut child_node: AccountsTreeNode = txn.get(&self.db, &child_prefix).unwrap();
+                let child_node: AccountsTreeNode = txn.get(&self.db, &child_prefix).unwrap();
 
                 // Group addresses with same prefix:
                 // Because of our ordering, they have to be located next to the current prefix.
                 // Hence, we iterate over the next prefixes, until we don't find commonalities anymore.
                 // In the next main iteration we can skip those we already requested here.
-                let mut sub_prefixes = vec![ prefixes[0].clone() ];
+                let mut sub_prefixes = vec![ prefix.clone() ];
                 // Find other prefixes to descend into this tree as well.
                 for j in i+1..prefixes.len() {
                     // Since we ordered prefixes, there can't be any other prefixes with commonalities.
@@ -210,7 +213,6 @@ impl<'env> AccountsTree<'env> {
             } else {
                 // No child node exists with the requested prefix. Include the current node to prove the absence of the requested account.
                 include_node = true;
-                i += 1;
             }
             i += 1;
         }
@@ -223,6 +225,68 @@ impl<'env> AccountsTree<'env> {
         return include_node;
     }
 
+    pub fn get_range_proof(&self, txn: &Transaction, start: &Address, end: &Address) -> RangeProof {
+        let start_nibbles = AddressNibbles::from(start);
+        let end_nibbles = AddressNibbles::from(end);
+
+        let mut nodes = Vec::new();
+        self.get_range_proof_rec(txn, &self.get_root(txn).unwrap(), &start_nibbles, &end_nibbles, &mut nodes);
+        return RangeProof::new(start.clone(), end.clone(), nodes);
+    }
+
+    fn get_range_proof_rec(&self, txn: &Transaction, node: &AccountsTreeNode, start: &AddressNibbles, end: &AddressNibbles, nodes: &mut Vec<AccountsTreeNode>) {
+        // Descend into all children overlapping the range (in order), the others are proven by their hash.
+        for child in node.iter_children() {
+            let child_prefix = node.prefix() + &child.suffix;
+            if RangeProof::overlaps(&child_prefix, start, end) {
+                let child_node: AccountsTreeNode = txn.get(&self.db, &child_prefix).unwrap();
+                self.get_range_proof_rec(txn, &child_node, start, end, nodes);
+            }
+        }
+        nodes.push(node.clone());
+    }
+
+    /// Computes the accounts that differ between the tree as seen by `from_txn` and by `to_txn`.
+    pub fn diff(&self, from_txn: &Transaction, to_txn: &Transaction) -> AccountsDiff {
+        let mut old_accounts = BTreeMap::new();
+        let mut new_accounts = BTreeMap::new();
+        self.collect_changed_terminals(from_txn, to_txn, &self.get_root(from_txn).unwrap(), &mut old_accounts);
+        self.collect_changed_terminals(to_txn, from_txn, &self.get_root(to_txn).unwrap(), &mut new_accounts);
+
+        let mut diff = AccountsDiff::default();
+        for (prefix, new_account) in new_accounts {
+            let address = Address::from(hex::decode(prefix.to_string()).unwrap().as_slice());
+            match old_accounts.remove(&prefix) {
+                Some(ref old_account) if old_account == &new_account => {},
+                Some(_) => diff.changed.push((address, new_account)),
+                None => diff.added.push((address, new_account)),
+            }
+        }
+        for (prefix, old_account) in old_accounts {
+            let address = Address::from(hex::decode(prefix.to_string()).unwrap().as_slice());
+            diff.removed.push((address, old_account));
+        }
+        return diff;
+    }
+
+    fn collect_changed_terminals(&self, txn: &Transaction, other_txn: &Transaction, node: &AccountsTreeNode, accounts: &mut BTreeMap<AddressNibbles, Account>) {
+        if let AccountsTreeNode::TerminalNode { prefix, account } = node {
+            accounts.insert(prefix.clone(), account.clone());
+            return;
+        }
+
+        // Subtrees with the same hash at the same position are identical in both states.
+        for child in node.iter_children() {
+            let child_prefix = node.prefix() + &child.suffix;
+            let other_child: Option<AccountsTreeNode> = other_txn.get(&self.db, &child_prefix);
+            if other_child.map_or(false, |other| other.hash::<Blake2bHash>() == child.hash) {
+                continue;
+            }
+            let child_node: AccountsTreeNode = txn.get(&self.db, &child_prefix).unwrap();
+            self.collect_changed_terminals(txn, other_txn, &child_node, accounts);
+        }
+    }
+
     pub fn get(&self, txn: &Transaction, address: &Address) -> Option<Account> {
         if let AccountsTreeNode::TerminalNode { account, .. } = txn.get(&self.db, &AddressNibbles::from(address))? {
             return Some(account);
@@ -230,6 +294,47 @@ impl<'env> AccountsTree<'env> {
         return None;
     }
 
+    /// Returns all accounts of the given type. This is a full scan of the tree.
+    pub fn get_accounts_by_type(&self, txn: &Transaction, account_type: AccountType) -> Vec<(Address, Account)> {
+        let mut accounts = Vec::new();
+        self.collect_accounts_by_type(txn, &self.get_root(txn).unwrap(), account_type, &mut accounts);
+        return accounts;
+    }
+
+    fn collect_accounts_by_type(&self, txn: &Transaction, node: &AccountsTreeNode, account_type: AccountType, accounts: &mut Vec<(Address, Account)>) {
+        if let AccountsTreeNode::TerminalNode { prefix, account } = node {
+            if account.account_type() == account_type {
+                let address = Address::from(hex::decode(prefix.to_string()).unwrap().as_slice());
+                accounts.push((address, account.clone()));
+            }
+            return;
+        }
+
+        for child in node.iter_children() {
+            let child_node: AccountsTreeNode = txn.get(&self.db, &(node.prefix() + &child.suffix)).unwrap();
+            self.collect_accounts_by_type(txn, &child_node, account_type, accounts);
+        }
+    }
+
+    /// Returns the number of nodes at each depth of the tree, starting with the root node at depth 0.
+    pub fn node_count_by_depth(&self, txn: &Transaction) -> Vec<usize> {
+        let mut counts = Vec::new();
+        self.count_nodes_by_depth(txn, &self.get_root(txn).unwrap(), 0, &mut counts);
+        return counts;
+    }
+
+    fn count_nodes_by_depth(&self, txn: &Transaction, node: &AccountsTreeNode, depth: usize, counts: &mut Vec<usize>) {
+        if counts.len() <= depth {
+            counts.push(0);
+        }
+        counts[depth] += 1;
+
+        for child in node.iter_children() {
+            let child_node: AccountsTreeNode = txn.get(&self.db, &(node.prefix() + &child.suffix)).unwrap();
+            self.count_nodes_by_depth(txn, &child_node, depth + 1, counts);
+        }
+    }
+
     fn get_root(&self, txn: &Transaction) -> Option<AccountsTreeNode> {
         let node = txn.get(&self.db, &AddressNibbles::empty());
         return node;
@@ -113,7 +113,11 @@ impl AddressNibbles {
     }
 
     pub(crate) fn suffix(&self, start: u8) -> AddressNibbles {
-        return self.slice(start as usize, self.len());
+        return self.suffix_from(start as usize);
+    }
+
+    pub(crate) fn suffix_from(&self, start: usize) -> AddressNibbles {
+        return self.slice(start, self.len());
     }
 }
 
@@ -306,4 +310,29 @@ mod tests {
         let an6: AddressNibbles = "2da3183636aae21c2710b5bd4486903f8541fb80".parse().unwrap();
         assert_eq!(an5.common_prefix(&an6), "2da".parse().unwrap());
     }
+
+    #[test]
+    fn it_can_slice_within_at_and_beyond_the_length() {
+        let an: AddressNibbles = "cfb98637bcae43c13323eaa1731ced2b716962fd".parse().unwrap();
+
+        // Within the nibble length.
+        assert_eq!(an.slice(3, 7).to_string(), "9863");
+        assert_eq!(an.slice(3, 7).len(), 4);
+        assert_eq!(an.suffix_from(35), "962fd".parse().unwrap());
+
+        // At the nibble length.
+        assert_eq!(an.slice(39, 40).to_string(), "d");
+        assert_eq!(an.slice(40, 40), AddressNibbles::empty());
+        assert_eq!(an.suffix_from(40), AddressNibbles::empty());
+
+        // Beyond the nibble length.
+        assert_eq!(an.slice(38, 100).to_string(), "fd");
+        assert_eq!(an.slice(41, 100), AddressNibbles::empty());
+        assert_eq!(an.suffix_from(100), AddressNibbles::empty());
+
+        let odd: AddressNibbles = "cfb".parse().unwrap();
+        assert_eq!(odd.suffix_from(1).to_string(), "fb");
+        assert_eq!(odd.slice(2, 10).to_string(), "b");
+        assert_eq!(odd.suffix_from(3), AddressNibbles::empty());
+    }
 }
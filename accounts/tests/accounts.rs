@@ -5,6 +5,7 @@ use nimiq_database::volatile::VolatileEnvironment;
 use nimiq_database::WriteTransaction;
 use nimiq_hash::{Blake2bHash, Hash};
 use nimiq_keys::{Address, KeyPair};
+use nimiq_network_primitives::networks::get_network_info;
 use nimiq_primitives::account::{Account, AccountError, AccountTransactionInteraction, AccountType, PrunedAccount};
 use nimiq_primitives::block::{Block, BlockBody, BlockHeader, BlockInterlink, TargetCompact};
 use nimiq_primitives::coin::Coin;
@@ -348,6 +349,260 @@ fn can_generate_accounts_proof() {
     write_block_txn.commit();
 
     let mut read_accs_txn = ReadTransaction::new(&env);
-    assert!(accounts.get_accounts_proof(&mut read_accs_txn, &vec![ address_miner1.clone() ]).verify());
-    assert!(accounts.get_accounts_proof(&mut read_accs_txn, &vec![ address_miner1.clone(), address_miner2.clone(), address_recipient1 ]).verify());
+    assert!(accounts.get_accounts_proof(&mut read_accs_txn, &vec![ address_miner1.clone() ]).verify().is_ok());
+    assert!(accounts.get_accounts_proof(&mut read_accs_txn, &vec![ address_miner1.clone(), address_miner2.clone(), address_recipient1 ]).verify().is_ok());
+}
+
+#[test]
+fn it_proves_accounts_of_a_small_tree() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let accounts = Accounts::new(&env);
+    let address_miner = Address::from([1u8; Address::SIZE]);
+    let address_recipient1 = Address::from([0x13u8; Address::SIZE]);
+    let address_recipient2 = Address::from([0x17u8; Address::SIZE]);
+    let address_absent = Address::from([0x15u8; Address::SIZE]);
+
+    // Three accounts, two of which share a branch node.
+    let tx1 = Transaction::new_basic(address_miner.clone(), address_recipient1.clone(), Coin::from(5), Coin::from(3), 1, NetworkId::Main);
+    let tx2 = Transaction::new_basic(address_miner.clone(), address_recipient2.clone(), Coin::from(7), Coin::from(11), 1, NetworkId::Main);
+    let body = BlockBody { miner: address_miner.clone(), extra_data: Vec::new(), transactions: vec![tx1, tx2], pruned_accounts: Vec::new() };
+
+    let mut txn = WriteTransaction::new(&env);
+    assert!(accounts.commit_block_body(&mut txn, &body, 1).is_ok());
+    txn.commit();
+
+    let txn = ReadTransaction::new(&env);
+    let mut proof = accounts.get_accounts_proof(&txn, &vec![address_recipient2.clone(), address_miner.clone()]);
+    assert_eq!(proof.verify(), Ok(()));
+    assert_eq!(proof.root_hash(), accounts.hash(Some(&txn)));
+    assert_eq!(proof.get_account(&address_miner), Some(accounts.get(&address_miner, Some(&txn))));
+    assert_eq!(proof.get_account(&address_recipient2), Some(accounts.get(&address_recipient2, Some(&txn))));
+    assert_eq!(proof.get_account(&address_recipient1), None);
+
+    // Absent accounts are proven by the branch node their address leads to.
+    let mut proof = accounts.get_accounts_proof(&txn, &vec![address_absent.clone(), address_recipient1.clone()]);
+    assert_eq!(proof.verify(), Ok(()));
+    assert_eq!(proof.root_hash(), accounts.hash(Some(&txn)));
+    assert_eq!(proof.get_account(&address_recipient1), Some(accounts.get(&address_recipient1, Some(&txn))));
+    assert_eq!(proof.get_account(&address_absent), None);
+
+    // An absent account doesn't hide the next requested account in the same branch.
+    let mut proof = accounts.get_accounts_proof(&txn, &vec![address_absent.clone(), address_recipient2.clone()]);
+    assert_eq!(proof.verify(), Ok(()));
+    assert_eq!(proof.root_hash(), accounts.hash(Some(&txn)));
+    assert_eq!(proof.get_account(&address_recipient2), Some(accounts.get(&address_recipient2, Some(&txn))));
+    assert_eq!(proof.get_account(&address_absent), None);
+}
+
+#[test]
+fn it_exposes_the_genesis_allocation() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let accounts = Accounts::new(&env);
+    let mut txn = WriteTransaction::new(&env);
+    accounts.init(&mut txn, NetworkId::Main);
+
+    let genesis_accounts = Accounts::genesis_accounts(NetworkId::Main);
+    assert!(!genesis_accounts.is_empty());
+
+    // The genesis miner additionally receives the block reward, so skip it.
+    let genesis_miner = get_network_info(NetworkId::Main).unwrap().genesis_block.body.as_ref().unwrap().miner.clone();
+    for (address, account) in genesis_accounts.iter() {
+        if *address != genesis_miner {
+            assert_eq!(accounts.get(address, Some(&txn)), *account);
+        }
+    }
+
+    txn.abort();
+}
+
+#[test]
+fn it_can_diff_the_committed_state_against_a_transaction() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let accounts = Accounts::new(&env);
+    let address_miner = Address::from([1u8; Address::SIZE]);
+    let address_recipient = Address::from([2u8; Address::SIZE]);
+    let address_other = Address::from([3u8; Address::SIZE]);
+
+    let mut body = BlockBody {
+        miner: address_other.clone(),
+        extra_data: Vec::new(),
+        transactions: Vec::new(),
+        pruned_accounts: Vec::new()
+    };
+    {
+        let mut txn = WriteTransaction::new(&env);
+        assert!(accounts.commit_block_body(&mut txn, &body, 1).is_ok());
+        body.miner = address_miner.clone();
+        assert!(accounts.commit_block_body(&mut txn, &body, 2).is_ok());
+        txn.commit();
+    }
+    let before = accounts.hash(None);
+
+    // Transfer funds from the miner to a new account, the miner also receives the block reward.
+    body.transactions = vec![Transaction::new_basic(
+        address_miner.clone(),
+        address_recipient.clone(),
+        10.into(),
+        Coin::ZERO,
+        1,
+        NetworkId::Main
+    )];
+    let mut txn = WriteTransaction::new(&env);
+    assert!(accounts.commit_block_body(&mut txn, &body, 3).is_ok());
+    let after = accounts.hash(Some(&txn));
+
+    let diff = accounts.diff(&before, &after, &txn).unwrap();
+    assert_eq!(diff.len(), 2);
+    assert_eq!(diff.added, vec![(address_recipient.clone(), accounts.get(&address_recipient, Some(&txn)))]);
+    assert_eq!(diff.changed, vec![(address_miner.clone(), accounts.get(&address_miner, Some(&txn)))]);
+    assert!(diff.removed.is_empty());
+
+    // The reverse diff removes the new account again.
+    let diff = accounts.diff(&after, &before, &txn).unwrap();
+    assert_eq!(diff.removed.len(), 1);
+    assert_eq!(diff.removed[0].0, address_recipient);
+    assert_eq!(diff.changed.len(), 1);
+    assert!(diff.added.is_empty());
+
+    // Unknown roots can't be diffed.
+    assert!(accounts.diff(&Blake2bHash::default(), &after, &txn).is_none());
+
+    txn.abort();
+}
+
+#[test]
+fn it_tracks_sender_nonces() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let accounts = Accounts::new(&env);
+    let address_miner = Address::from([1u8; Address::SIZE]);
+    let address_recipient = Address::from([2u8; Address::SIZE]);
+
+    let mut body = BlockBody {
+        miner: address_miner.clone(),
+        extra_data: Vec::new(),
+        transactions: Vec::new(),
+        pruned_accounts: Vec::new()
+    };
+
+    {
+        let mut txn = WriteTransaction::new(&env);
+        assert!(accounts.commit_block_body(&mut txn, &body, 1).is_ok());
+        txn.commit();
+    }
+    assert_eq!(accounts.get_nonce(&address_miner, None), 0);
+
+    let tx1 = Transaction::new_basic(address_miner.clone(), address_recipient.clone(), 10.into(), Coin::ZERO, 1, NetworkId::Main);
+    let tx2 = Transaction::new_basic(address_miner.clone(), address_recipient.clone(), 20.into(), Coin::ZERO, 1, NetworkId::Main);
+    body.transactions = vec![tx1, tx2];
+    let hash1 = accounts.hash(None);
+
+    {
+        let mut txn = WriteTransaction::new(&env);
+        assert!(accounts.commit_block_body(&mut txn, &body, 2).is_ok());
+        txn.commit();
+    }
+    assert_eq!(accounts.get_nonce(&address_miner, None), 2);
+    assert_eq!(accounts.get_nonce(&address_recipient, None), 0);
+
+    {
+        let mut txn = WriteTransaction::new(&env);
+        assert!(accounts.revert_block_body(&mut txn, &body, 2).is_ok());
+        txn.commit();
+    }
+    assert_eq!(accounts.get_nonce(&address_miner, None), 0);
+    assert_eq!(hash1, accounts.hash(None));
+}
+
+#[test]
+fn it_reverts_transactions_of_senders_without_nonce() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let accounts = Accounts::new(&env);
+    let address_miner = Address::from([1u8; Address::SIZE]);
+    let address_recipient = Address::from([2u8; Address::SIZE]);
+
+    let mut body = BlockBody {
+        miner: address_miner.clone(),
+        extra_data: Vec::new(),
+        transactions: Vec::new(),
+        pruned_accounts: Vec::new()
+    };
+
+    {
+        let mut txn = WriteTransaction::new(&env);
+        assert!(accounts.commit_block_body(&mut txn, &body, 1).is_ok());
+        txn.commit();
+    }
+    let hash1 = accounts.hash(None);
+
+    let tx = Transaction::new_basic(address_miner.clone(), address_recipient.clone(), 10.into(), Coin::ZERO, 1, NetworkId::Main);
+    body.transactions = vec![tx];
+    {
+        let mut txn = WriteTransaction::new(&env);
+        assert!(accounts.commit_block_body(&mut txn, &body, 2).is_ok());
+        txn.commit();
+    }
+
+    // Databases created before nonces were tracked have no nonce for the sender.
+    {
+        let nonce_db = env.open_database("AccountNonces".to_string());
+        let mut txn = WriteTransaction::new(&env);
+        txn.remove(&nonce_db, address_miner.as_bytes());
+        txn.commit();
+    }
+    assert_eq!(accounts.get_nonce(&address_miner, None), 0);
+
+    {
+        let mut txn = WriteTransaction::new(&env);
+        assert!(accounts.revert_block_body(&mut txn, &body, 2).is_ok());
+        txn.commit();
+    }
+    assert_eq!(accounts.get_nonce(&address_miner, None), 0);
+    assert_eq!(hash1, accounts.hash(None));
+}
+
+#[test]
+fn it_computes_the_spendable_balance_of_vesting_contracts() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let accounts = Accounts::new(&env);
+    let key_pair = KeyPair::generate();
+    let address = Address::from(&key_pair.public);
+    let mut body = BlockBody {
+        miner: address.clone(),
+        extra_data: Vec::new(),
+        transactions: Vec::new(),
+        pruned_accounts: Vec::new()
+    };
+
+    // Give a block reward
+    {
+        let mut txn = WriteTransaction::new(&env);
+        assert!(accounts.commit_block_body(&mut txn, &body, 1).is_ok());
+        txn.commit();
+    }
+    assert_eq!(accounts.get_spendable_balance(&address, 1, None), policy::block_reward_at(1));
+
+    // Create vesting contract releasing 50 every 10 blocks, starting at block 2
+    let mut data: Vec<u8> = Vec::with_capacity(Address::SIZE + 16);
+    address.serialize(&mut data).unwrap();
+    2u32.serialize(&mut data).unwrap();
+    10u32.serialize(&mut data).unwrap();
+    Coin::from(50).serialize(&mut data).unwrap();
+    let mut tx_create = Transaction::new_contract_creation(data, address.clone(), AccountType::Basic, AccountType::Vesting, Coin::from(100), Coin::from(0), 1, NetworkId::Dummy);
+    tx_create.proof = SignatureProof::from(key_pair.public, key_pair.sign(&tx_create.serialize_content())).serialize_to_vec();
+    let contract_address = tx_create.contract_creation_address();
+    body.transactions = vec![tx_create];
+    {
+        let mut txn = WriteTransaction::new(&env);
+        assert!(accounts.commit_block_body(&mut txn, &body, 2).is_ok());
+        txn.commit();
+    }
+
+    assert_eq!(accounts.get(&contract_address, None).balance(), Coin::from(100));
+    assert_eq!(accounts.get_spendable_balance(&contract_address, 1, None), Coin::ZERO);
+    assert_eq!(accounts.get_spendable_balance(&contract_address, 11, None), Coin::ZERO);
+    assert_eq!(accounts.get_spendable_balance(&contract_address, 12, None), Coin::from(50));
+
+    // Vesting schedule completed
+    assert_eq!(accounts.get_spendable_balance(&contract_address, 22, None), Coin::from(100));
+    assert_eq!(accounts.get_spendable_balance(&contract_address, 1000, None), Coin::from(100));
 }
@@ -4,7 +4,7 @@ use nimiq_accounts::tree::AccountsTree;
 use nimiq_database::volatile::VolatileEnvironment;
 use nimiq_database::WriteTransaction;
 use nimiq_keys::Address;
-use nimiq_primitives::account::{Account, BasicAccount};
+use nimiq_primitives::account::{Account, AccountType, BasicAccount, VestingContract};
 use nimiq_primitives::coin::Coin;
 
 #[test]
@@ -216,3 +216,53 @@ fn it_can_merge_nodes_while_pruning() {
 
     txn.abort();
 }
+
+#[test]
+fn it_can_get_accounts_by_type() {
+    let address1 = Address::from(&hex::decode("0000000000000000000000000000000000000000").unwrap()[..]);
+    let account1 = Account::Basic(BasicAccount { balance: 5.into() });
+    let address2 = Address::from(&hex::decode("1000000000000000000000000000000000000000").unwrap()[..]);
+    let account2 = Account::Vesting(VestingContract::new(1000.into(), address1.clone(), 1, 100, 500.into(), 1000.into()));
+    let address3 = Address::from(&hex::decode("1200000000000000000000000000000000000000").unwrap()[..]);
+    let account3 = Account::Basic(BasicAccount { balance: 55555555.into() });
+    let address4 = Address::from(&hex::decode("1230000000000000000000000000000000000000").unwrap()[..]);
+    let account4 = Account::Vesting(VestingContract::new(2000.into(), address3.clone(), 10, 1, 2000.into(), 2000.into()));
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let tree = AccountsTree::new(&env);
+    let mut txn = WriteTransaction::new(&env);
+
+    tree.put(&mut txn, &address1, account1.clone());
+    tree.put(&mut txn, &address2, account2.clone());
+    tree.put(&mut txn, &address3, account3.clone());
+    tree.put(&mut txn, &address4, account4.clone());
+
+    let vesting = tree.get_accounts_by_type(&txn, AccountType::Vesting);
+    assert_eq!(vesting, vec![(address2, account2), (address4, account4)]);
+
+    let basic = tree.get_accounts_by_type(&txn, AccountType::Basic);
+    assert_eq!(basic, vec![(address1, account1), (address3, account3)]);
+
+    assert!(tree.get_accounts_by_type(&txn, AccountType::HTLC).is_empty());
+
+    txn.abort();
+}
+
+#[test]
+fn it_can_count_nodes_by_depth() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let tree = AccountsTree::new(&env);
+    let mut txn = WriteTransaction::new(&env);
+
+    // The empty tree only consists of the root node.
+    assert_eq!(tree.node_count_by_depth(&txn), vec![1]);
+
+    // Resulting tree: root -> [0000.., 1 -> [10.., 12..]]
+    for address in &["0000000000000000000000000000000000000000", "1000000000000000000000000000000000000000", "1200000000000000000000000000000000000000"] {
+        let address = Address::from(&hex::decode(address).unwrap()[..]);
+        tree.put(&mut txn, &address, Account::Basic(BasicAccount { balance: 5.into() }));
+    }
+    assert_eq!(tree.node_count_by_depth(&txn), vec![1, 2, 2]);
+
+    txn.abort();
+}
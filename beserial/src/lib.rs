@@ -53,6 +53,8 @@ impl std::fmt::Display for SerializingError {
     }
 }
 
+impl std::error::Error for SerializingError {}
+
 impl From<std::io::Error> for SerializingError {
     fn from(io_error: std::io::Error) -> Self {
         warn!("I/O error: {}", io_error);
@@ -0,0 +1,31 @@
+#![feature(test)]
+
+extern crate test;
+
+use std::sync::Arc;
+
+use test::Bencher;
+
+use nimiq_blockchain::Blockchain;
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_network_primitives::time::NetworkTime;
+use nimiq_primitives::networks::NetworkId;
+
+#[bench]
+fn bench_next_target_cached(b: &mut Bencher) {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    blockchain.get_next_target(None);
+    b.iter(|| blockchain.get_next_target(None));
+}
+
+#[bench]
+fn bench_next_target_uncached(b: &mut Bencher) {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let mut blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    // Targets are cached per block time, so cycling through more block times than the cache holds misses on every call.
+    b.iter(|| {
+        blockchain.block_time = blockchain.block_time % 1000 + 1;
+        blockchain.get_next_target(None)
+    });
+}
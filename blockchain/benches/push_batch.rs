@@ -0,0 +1,70 @@
+#![feature(test)]
+
+extern crate test;
+
+use std::env;
+use std::fs;
+use std::sync::Arc;
+
+use test::Bencher;
+
+use beserial::Deserialize;
+use nimiq_blockchain::{Blockchain, PushResult};
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_network_primitives::time::NetworkTime;
+use nimiq_primitives::block::Block;
+use nimiq_primitives::networks::NetworkId;
+
+/// Number of blocks pushed per iteration.
+const BLOCK_COUNT: usize = 2000;
+
+/// Mining valid proofs of work for this many blocks takes far too long, so the benchmarks push main chain
+/// blocks read from the file in `NIMIQ_BENCH_BLOCKS`: one hex encoded block per line, starting at height 2.
+fn load_blocks() -> Option<Vec<Block>> {
+    let path = match env::var("NIMIQ_BENCH_BLOCKS") {
+        Ok(path) => path,
+        Err(_) => {
+            eprintln!("NIMIQ_BENCH_BLOCKS is not set, skipping benchmark");
+            return None;
+        }
+    };
+
+    let blocks: Vec<Block> = fs::read_to_string(path).unwrap()
+        .lines()
+        .take(BLOCK_COUNT)
+        .map(|line| Block::deserialize_from_vec(&hex::decode(line.trim()).unwrap()).unwrap())
+        .collect();
+    assert_eq!(blocks.len(), BLOCK_COUNT, "NIMIQ_BENCH_BLOCKS contains less than {} blocks", BLOCK_COUNT);
+    Some(blocks)
+}
+
+#[bench]
+fn bench_push_batch(b: &mut Bencher) {
+    let blocks = match load_blocks() {
+        Some(blocks) => blocks,
+        None => return,
+    };
+
+    b.iter(|| {
+        let env = VolatileEnvironment::new(10).unwrap();
+        let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+        let results = blockchain.push_batch(blocks.clone());
+        assert!(results.iter().all(|result| *result == PushResult::Extended));
+    });
+}
+
+#[bench]
+fn bench_push_single(b: &mut Bencher) {
+    let blocks = match load_blocks() {
+        Some(blocks) => blocks,
+        None => return,
+    };
+
+    b.iter(|| {
+        let env = VolatileEnvironment::new(10).unwrap();
+        let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+        for block in blocks.iter() {
+            assert_eq!(blockchain.push(block.clone()), PushResult::Extended);
+        }
+    });
+}
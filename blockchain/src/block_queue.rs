@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+
+use hash::{Blake2bHash, Hash};
+use primitives::block::Block;
+
+/// Holds incoming blocks ordered by height and, within a height, by whether they link up with the block
+/// taken before, so that blocks arriving out of order can be applied in sequence. Holds at most `capacity` blocks.
+///
+/// Unlike the `OrphanPool`, which buffers blocks until their missing predecessor arrives, the queue only decides
+/// the order in which blocks are pushed. Queued blocks that still can't be connected end up in the orphan pool.
+#[derive(Debug)]
+pub struct BlockQueue {
+    /// Queued blocks and their hashes by height, in the order they were queued.
+    blocks: BTreeMap<u32, Vec<(Blake2bHash, Block)>>,
+    len: usize,
+    capacity: usize,
+    /// Hash of the block taken last. A queued successor of it is taken before its siblings.
+    last_hash: Option<Blake2bHash>,
+}
+
+impl BlockQueue {
+    pub fn new(capacity: usize) -> Self {
+        BlockQueue {
+            blocks: BTreeMap::new(),
+            len: 0,
+            capacity,
+            last_hash: None,
+        }
+    }
+
+    /// Adds a block unless it is already queued or the queue is full. Returns whether the block was added.
+    pub fn push(&mut self, block: Block) -> bool {
+        if self.len >= self.capacity {
+            return false;
+        }
+
+        let hash: Blake2bHash = block.header.hash();
+        let queued = self.blocks.entry(block.header.height).or_insert_with(Vec::new);
+        if queued.iter().any(|(queued_hash, _)| *queued_hash == hash) {
+            return false;
+        }
+        queued.push((hash, block));
+        self.len += 1;
+        true
+    }
+
+    /// Removes and returns a queued block with the lowest height. Among blocks of the same height,
+    /// a successor of the block taken last is preferred, otherwise the block queued first is taken.
+    pub fn pop(&mut self) -> Option<Block> {
+        let height = *self.blocks.keys().next()?;
+        let queued = self.blocks.get_mut(&height).unwrap();
+        let i = match self.last_hash {
+            Some(ref last_hash) => queued.iter().position(|(_, block)| block.header.prev_hash == *last_hash).unwrap_or(0),
+            None => 0,
+        };
+        let (hash, block) = queued.remove(i);
+        if queued.is_empty() {
+            self.blocks.remove(&height);
+        }
+        self.len -= 1;
+        self.last_hash = Some(hash);
+        Some(block)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
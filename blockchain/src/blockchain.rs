@@ -1,35 +1,72 @@
 use std::cmp;
-use std::time::Instant;
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::fmt;
+use std::time::{Duration, Instant};
 use std::sync::Arc;
+use std::thread;
 
 use bigdecimal::BigDecimal;
-use parking_lot::{MappedRwLockReadGuard, Mutex, RwLock, RwLockReadGuard};
+use num_traits::ToPrimitive;
+use parking_lot::{Condvar, MappedRwLockReadGuard, Mutex, RwLock, RwLockReadGuard};
 
 use accounts::Accounts;
+use accounts::accounts_proof::AccountsProof;
 use database::{Environment, Transaction, ReadTransaction, WriteTransaction};
 use hash::{Blake2bHash, Hash};
+use keys::{Address, KeyPair};
+use network_primitives::address::PeerId;
 use network_primitives::networks::get_network_info;
 use network_primitives::time::NetworkTime;
-use primitives::account::AccountError;
+use primitives::account::{Account, AccountError, AccountTransactionInteraction};
 use primitives::block::{Block, BlockHeader, BlockError, Target, TargetCompact, Difficulty};
+use primitives::coin::Coin;
 use primitives::networks::NetworkId;
 use primitives::policy;
+use primitives::transaction::TransactionError;
 use utils::iterators::Merge;
-use utils::observer::Notifier;
+use utils::observer::{Listener, ListenerHandle, Notifier};
+use utils::rate_limit::RateLimit;
 use utils::unique_ptr::UniquePtr;
 
-use crate::{chain_info::ChainInfo, chain_store::ChainStore, chain_store::Direction, chain_proof::ChainProof, transaction_cache::TransactionCache};
+use crate::{block_queue::BlockQueue, blockchain_config::BlockchainConfig, chain_info::ChainInfo, chain_store::ChainStore, chain_store::Direction, chain_proof::{CancellationToken, ChainProof}, fork_choice::{ForkChoice, HeaviestChain}, head_certificate::HeadCertificate, orphan_pool::OrphanPool, push_stats::{PushCounters, PushStats}, target_cache::TargetCache, transaction_cache::TransactionCache};
 #[cfg(feature = "metrics")]
 use crate::chain_metrics::BlockchainMetrics;
 
 pub struct Blockchain<'env> {
     env: &'env Environment,
     pub network_id: NetworkId,
+    /// How far (in milliseconds) a block's timestamp may be ahead of the network time.
+    /// Defaults to `policy::BLOCK_MAX_FUTURE_DRIFT`, nodes with imperfect clocks can loosen it.
+    pub max_future_drift: u64,
+    /// Block time (in seconds) the difficulty adjustment aims for. Defaults to `policy::BLOCK_TIME`,
+    /// private networks can change it to produce blocks faster or slower.
+    pub block_time: u32,
+    /// Maximum number of orphan blocks buffered until their predecessor becomes known.
+    pub max_orphans: usize,
     network_time: Arc<NetworkTime>,
+    /// Limits the warnings about a skewed local clock, see `network_time_offset`.
+    clock_skew_warnings: Mutex<RateLimit>,
+    config: BlockchainConfig,
+    /// Decides whether to rebranch to a fork. Set at construction, see `with_fork_choice`.
+    fork_choice: Box<dyn ForkChoice>,
     pub notifier: RwLock<Notifier<'env, BlockchainEvent>>,
     chain_store: ChainStore<'env>,
     state: RwLock<BlockchainState<'env>>,
+    /// Last computed chain proof and the head hash it was computed for. Kept outside of `state`, so that
+    /// caching a proof doesn't need the state write lock and thus never blocks readers of the chain state.
+    /// The proof becomes stale when the head changes and is recomputed lazily by `get_chain_proof`.
+    chain_proof: RwLock<Option<(Blake2bHash, ChainProof)>>,
     push_lock: Mutex<()>,
+    /// Blocks mined by these addresses are rejected. Empty by default.
+    denied_miners: RwLock<HashSet<Address>>,
+    orphans: Mutex<OrphanPool>,
+    block_queue: Mutex<BlockQueue>,
+    /// Held while the block queue is drained, so that queued blocks are pushed in order.
+    drain_lock: Mutex<()>,
+    /// Signalled when a block is queued, wakes up `wait_and_process_queue`.
+    queue_ready: Condvar,
+    push_counters: PushCounters,
 
     #[cfg(feature = "metrics")]
     pub metrics: BlockchainMetrics,
@@ -40,7 +77,18 @@ struct BlockchainState<'env> {
     transaction_cache: TransactionCache,
     main_chain: ChainInfo,
     head_hash: Blake2bHash,
-    chain_proof: Option<ChainProof>,
+    /// Mutable under the read lock, since `get_next_target` only holds that.
+    next_target_cache: Mutex<TargetCache>,
+}
+
+/// Result of `extend_batch`.
+struct BatchOutcome {
+    /// Blocks committed to the main chain, in order.
+    applied: Vec<(Blake2bHash, ChainInfo)>,
+    /// Why the block after the applied ones was rejected, if it was.
+    rejected: Option<PushError>,
+    /// Blocks that still need to be pushed one by one.
+    remaining: Vec<Block>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -51,38 +99,219 @@ pub enum PushResult {
     Extended,
     Rebranched,
     Forked,
+    ForkExtended,
+}
+
+/// The block a `PushError` refers to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PushErrorContext {
+    pub block_hash: Blake2bHash,
+    pub height: u32,
+}
+
+impl PushErrorContext {
+    pub fn new(block_hash: Blake2bHash, height: u32) -> Self {
+        PushErrorContext { block_hash, height }
+    }
+}
+
+impl<'a> From<&'a Block> for PushErrorContext {
+    fn from(block: &'a Block) -> Self {
+        PushErrorContext::new(block.header.hash(), block.header.height)
+    }
+}
+
+impl fmt::Display for PushErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "block {} at height #{}", self.block_hash, self.height)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum PushError {
-    InvalidBlock(BlockError),
-    InvalidSuccessor,
-    DifficultyMismatch,
-    DuplicateTransaction,
-    AccountsError(AccountError),
-    InvalidFork,
+    /// The context is `None` if the error was converted from a `BlockError`, e.g. by `?`.
+    InvalidBlock(BlockError, Option<PushErrorContext>),
+    InvalidSuccessor(PushErrorContext),
+    DifficultyMismatch(PushErrorContext),
+    DuplicateTransaction(PushErrorContext),
+    /// The context is `None` if the error was converted from an `AccountError`, e.g. by `?`.
+    AccountsError(AccountError, Option<PushErrorContext>),
+    AccountsHashMismatch(Option<PushErrorContext>),
+    InvalidFork(PushErrorContext),
+    /// Refers to the genesis block.
+    CannotRevertGenesis(PushErrorContext),
+    DeniedMiner(PushErrorContext),
+    TimestampManipulation(PushErrorContext),
+    TimestampNotAfterMedian(PushErrorContext),
+    TimestampInFuture(PushErrorContext),
+    /// Refers to the head block, which is below the requested height.
+    InvalidHeight(PushErrorContext),
+    BlockOnMainChain(PushErrorContext),
+    /// Adopting the fork would revert or apply more blocks than `BlockchainConfig::max_rebranch_depth`.
+    /// The block itself is valid and kept as a fork.
+    RebranchTooDeep(PushErrorContext),
+}
+
+impl PushError {
+    pub fn invalid_block(e: BlockError, context: PushErrorContext) -> Self {
+        PushError::from(e).with_context(context)
+    }
+
+    /// Wraps an error from committing a block to the accounts. A mismatching accounts hash is reported as `AccountsHashMismatch`.
+    pub fn accounts_error(e: AccountError, context: PushErrorContext) -> Self {
+        PushError::from(e).with_context(context)
+    }
+
+    /// Attaches `context` to an error converted without one, other errors are returned unchanged.
+    pub fn with_context(self, context: PushErrorContext) -> Self {
+        match self {
+            PushError::InvalidBlock(e, None) => PushError::InvalidBlock(e, Some(context)),
+            PushError::AccountsError(e, None) => PushError::AccountsError(e, Some(context)),
+            PushError::AccountsHashMismatch(None) => PushError::AccountsHashMismatch(Some(context)),
+            e => e,
+        }
+    }
+
+    /// The block this error refers to, `None` if the error was converted without one.
+    pub fn context(&self) -> Option<&PushErrorContext> {
+        match self {
+            PushError::InvalidBlock(_, context) => context.as_ref(),
+            PushError::AccountsError(_, context) => context.as_ref(),
+            PushError::AccountsHashMismatch(context) => context.as_ref(),
+            PushError::InvalidSuccessor(context) => Some(context),
+            PushError::DifficultyMismatch(context) => Some(context),
+            PushError::DuplicateTransaction(context) => Some(context),
+            PushError::InvalidFork(context) => Some(context),
+            PushError::CannotRevertGenesis(context) => Some(context),
+            PushError::DeniedMiner(context) => Some(context),
+            PushError::TimestampManipulation(context) => Some(context),
+            PushError::TimestampNotAfterMedian(context) => Some(context),
+            PushError::TimestampInFuture(context) => Some(context),
+            PushError::InvalidHeight(context) => Some(context),
+            PushError::BlockOnMainChain(context) => Some(context),
+            PushError::RebranchTooDeep(context) => Some(context),
+        }
+    }
+}
+
+impl fmt::Display for PushError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PushError::InvalidBlock(e, _) => write!(f, "invalid block ({})", e)?,
+            PushError::InvalidSuccessor(_) => write!(f, "not a valid successor")?,
+            PushError::DifficultyMismatch(_) => write!(f, "difficulty mismatch")?,
+            PushError::DuplicateTransaction(_) => write!(f, "transaction already included")?,
+            PushError::AccountsError(e, _) => write!(f, "commit failed ({})", e)?,
+            PushError::AccountsHashMismatch(_) => write!(f, "accounts hash mismatch")?,
+            PushError::InvalidFork(_) => write!(f, "invalid fork")?,
+            PushError::CannotRevertGenesis(_) => write!(f, "cannot revert the genesis block")?,
+            PushError::DeniedMiner(_) => write!(f, "miner is denied")?,
+            PushError::TimestampManipulation(_) => write!(f, "timestamp manipulation in difficulty window")?,
+            PushError::TimestampNotAfterMedian(_) => write!(f, "timestamp not after median time past")?,
+            PushError::TimestampInFuture(_) => write!(f, "timestamp ahead of network time")?,
+            PushError::InvalidHeight(_) => write!(f, "height above the head")?,
+            PushError::BlockOnMainChain(_) => write!(f, "block is on the main chain")?,
+            PushError::RebranchTooDeep(_) => write!(f, "rebranch too deep")?,
+        }
+        if let Some(context) = self.context() {
+            write!(f, " - {}", context)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<BlockError> for PushError {
+    fn from(e: BlockError) -> Self {
+        PushError::InvalidBlock(e, None)
+    }
+}
+
+impl From<AccountError> for PushError {
+    fn from(e: AccountError) -> Self {
+        match e {
+            AccountError::AccountsHashMismatch => PushError::AccountsHashMismatch(None),
+            e => PushError::AccountsError(e, None),
+        }
+    }
+}
+
+impl error::Error for PushError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            PushError::InvalidBlock(e, _) => Some(e),
+            PushError::AccountsError(e, _) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Reasons for rejecting a transaction before admitting it to the mempool.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TxAdmissionError {
+    Invalid(TransactionError),
+    /// The transaction is outside its validity window at the next block height.
+    Expired,
+    AlreadyMined,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum BlockchainEvent {
     Extended(Blake2bHash, UniquePtr<Block>),
     Rebranched(Vec<(Blake2bHash, Block)>, Vec<(Blake2bHash, Block)>),
+    /// A fork was created or extended by the given block, the main chain is unchanged.
+    Forked(Blake2bHash, UniquePtr<Block>),
+}
+
+impl BlockchainEvent {
+    /// Whether the event adds or removes a main chain block with a height in `[from, to]`, unbounded if `to` is `None`.
+    /// Forks leave the main chain untouched and never match.
+    pub fn touches_heights(&self, from: u32, to: Option<u32>) -> bool {
+        let in_range = |height: u32| height >= from && to.map_or(true, |to| height <= to);
+        match self {
+            BlockchainEvent::Extended(_, block) => in_range(block.header.height),
+            BlockchainEvent::Rebranched(reverted, adopted) => reverted.iter().chain(adopted.iter())
+                .any(|(_, block)| in_range(block.header.height)),
+            BlockchainEvent::Forked(_, _) => false,
+        }
+    }
 }
 
 impl<'env> Blockchain<'env> {
-    const NIPOPOW_M: u32 = 240;
-    const NIPOPOW_K: u32 = 120;
-    const NIPOPOW_DELTA: f64 = 0.15;
+    pub const NIPOPOW_M: u32 = 240;
+    pub const NIPOPOW_K: u32 = 120;
+    pub const NIPOPOW_DELTA: f64 = 0.15;
+    pub const ADAPTIVE_LOCATORS_MAX: usize = 64;
+    /// Network time offsets (in milliseconds) beyond this threshold are logged as a warning.
+    pub const MAX_NETWORK_TIME_OFFSET: u64 = 60 * 1000;
+    /// Number of `get_next_target` results kept in memory.
+    const NEXT_TARGET_CACHE_SIZE: usize = 128;
+    /// Maximum number of blocks waiting in the block queue.
+    pub const MAX_QUEUED_BLOCKS: usize = 1024;
+    /// How long the thread started by `spawn_queue_drain` waits for queued blocks before checking
+    /// whether the blockchain is still alive.
+    const QUEUE_DRAIN_INTERVAL: Duration = Duration::from_secs(1);
+    /// Default for `max_orphans`.
+    pub const DEFAULT_MAX_ORPHANS: usize = 256;
 
     pub fn new(env: &'env Environment, network_id: NetworkId, network_time: Arc<NetworkTime>) -> Self {
+        Self::with_fork_choice(env, network_id, network_time, Box::new(HeaviestChain))
+    }
+
+    /// Like `new`, but uses the given rule instead of `HeaviestChain` to decide between competing chains.
+    pub fn with_fork_choice(env: &'env Environment, network_id: NetworkId, network_time: Arc<NetworkTime>, fork_choice: Box<dyn ForkChoice>) -> Self {
+        Self::with_config(env, network_id, network_time, fork_choice, BlockchainConfig::default())
+    }
+
+    /// Like `with_fork_choice`, but with non-default settings.
+    pub fn with_config(env: &'env Environment, network_id: NetworkId, network_time: Arc<NetworkTime>, fork_choice: Box<dyn ForkChoice>, config: BlockchainConfig) -> Self {
         let chain_store = ChainStore::new(env);
         match chain_store.get_head(None) {
-            Some(head_hash) => Blockchain::load(env, network_time, network_id, chain_store, head_hash),
-            None => Blockchain::init(env, network_time, network_id, chain_store)
+            Some(head_hash) => Blockchain::load(env, network_time, network_id, chain_store, head_hash, fork_choice, config),
+            None => Blockchain::init(env, network_time, network_id, chain_store, fork_choice, config)
         }
     }
 
-    fn load(env: &'env Environment, network_time: Arc<NetworkTime>, network_id: NetworkId, chain_store: ChainStore<'env>, head_hash: Blake2bHash) -> Self {
+    fn load(env: &'env Environment, network_time: Arc<NetworkTime>, network_id: NetworkId, chain_store: ChainStore<'env>, head_hash: Blake2bHash, fork_choice: Box<dyn ForkChoice>, config: BlockchainConfig) -> Self {
         // Check that the correct genesis block is stored.
         let network_info = get_network_info(network_id).unwrap();
         let genesis_info = chain_store.get_chain_info(&network_info.genesis_hash, false, None);
@@ -99,19 +328,43 @@ impl<'env> Blockchain<'env> {
         assert_eq!(main_chain.head.header.accounts_hash, accounts.hash(None),
             "Inconsistent chain/accounts state. Reset your consensus database.");
 
-        // Initialize TransactionCache.
-        let mut transaction_cache = TransactionCache::new();
-        let blocks = chain_store.get_blocks_backward(&head_hash, transaction_cache.missing_blocks() - 1, true, None);
-        for block in blocks.iter().rev() {
-            transaction_cache.push_block(block);
-        }
-        transaction_cache.push_block(&main_chain.head);
-        assert_eq!(transaction_cache.missing_blocks(), policy::TRANSACTION_VALIDITY_WINDOW.saturating_sub(main_chain.head.header.height));
+        // Initialize TransactionCache. Restore the persisted cache if it matches the head,
+        // otherwise rebuild it from the stored blocks.
+        let expected_missing_blocks = policy::TRANSACTION_VALIDITY_WINDOW.saturating_sub(main_chain.head.header.height);
+        let transaction_cache = match chain_store.get_transaction_cache(None) {
+            Some(ref cache) if !cache.is_empty() && cache.head_hash() == head_hash
+                && cache.missing_blocks() == expected_missing_blocks => cache.clone(),
+            _ => {
+                let mut transaction_cache = TransactionCache::new();
+                transaction_cache.push_block(&main_chain.head);
+                let txn = ReadTransaction::new(env);
+                let blocks = chain_store.block_cursor(&head_hash, Direction::Backward, true, &txn);
+                for block in blocks.take(transaction_cache.missing_blocks() as usize) {
+                    transaction_cache.prepend_block(&block);
+                }
+                transaction_cache
+            }
+        };
+        assert_eq!(transaction_cache.missing_blocks(), expected_missing_blocks);
+
+        // Restore the persisted chain proof if it was computed for the current head and we serve chain proofs.
+        let chain_proof = if config.serve_chain_proofs() {
+            chain_store.get_chain_proof(None)
+                .filter(|(proof_head_hash, _)| proof_head_hash == &head_hash)
+        } else {
+            None
+        };
 
         Blockchain {
             env,
             network_id,
+            max_future_drift: policy::BLOCK_MAX_FUTURE_DRIFT,
+            block_time: policy::BLOCK_TIME,
+            max_orphans: Self::DEFAULT_MAX_ORPHANS,
             network_time,
+            clock_skew_warnings: Mutex::new(RateLimit::new_per_minute(1)),
+            config,
+            fork_choice,
             notifier: RwLock::new(Notifier::new()),
             chain_store,
             state: RwLock::new(BlockchainState {
@@ -119,16 +372,23 @@ impl<'env> Blockchain<'env> {
                 transaction_cache,
                 main_chain,
                 head_hash,
-                chain_proof: None,
+                next_target_cache: Mutex::new(TargetCache::new(Self::NEXT_TARGET_CACHE_SIZE)),
             }),
+            chain_proof: RwLock::new(chain_proof),
             push_lock: Mutex::new(()),
+            denied_miners: RwLock::new(HashSet::new()),
+            orphans: Mutex::new(OrphanPool::new()),
+            block_queue: Mutex::new(BlockQueue::new(Self::MAX_QUEUED_BLOCKS)),
+            drain_lock: Mutex::new(()),
+            queue_ready: Condvar::new(),
+            push_counters: PushCounters::default(),
 
             #[cfg(feature = "metrics")]
             metrics: BlockchainMetrics::default(),
         }
     }
 
-    fn init(env: &'env Environment, network_time: Arc<NetworkTime>, network_id: NetworkId, chain_store: ChainStore<'env>) -> Self {
+    fn init(env: &'env Environment, network_time: Arc<NetworkTime>, network_id: NetworkId, chain_store: ChainStore<'env>, fork_choice: Box<dyn ForkChoice>, config: BlockchainConfig) -> Self {
         // Initialize chain & accounts with genesis block.
         let network_info = get_network_info(network_id).expect(&format!("No NetworkInfo for network {:?}", network_id));
         let main_chain = ChainInfo::initial(network_info.genesis_block.clone());
@@ -150,7 +410,13 @@ impl<'env> Blockchain<'env> {
         Blockchain {
             env,
             network_id,
+            max_future_drift: policy::BLOCK_MAX_FUTURE_DRIFT,
+            block_time: policy::BLOCK_TIME,
+            max_orphans: Self::DEFAULT_MAX_ORPHANS,
             network_time,
+            clock_skew_warnings: Mutex::new(RateLimit::new_per_minute(1)),
+            config,
+            fork_choice,
             notifier: RwLock::new(Notifier::new()),
             chain_store,
             state: RwLock::new(BlockchainState {
@@ -158,9 +424,16 @@ impl<'env> Blockchain<'env> {
                 transaction_cache,
                 main_chain,
                 head_hash,
-                chain_proof: None,
+                next_target_cache: Mutex::new(TargetCache::new(Self::NEXT_TARGET_CACHE_SIZE)),
             }),
+            chain_proof: RwLock::new(None),
             push_lock: Mutex::new(()),
+            denied_miners: RwLock::new(HashSet::new()),
+            orphans: Mutex::new(OrphanPool::new()),
+            block_queue: Mutex::new(BlockQueue::new(Self::MAX_QUEUED_BLOCKS)),
+            drain_lock: Mutex::new(()),
+            queue_ready: Condvar::new(),
+            push_counters: PushCounters::default(),
 
             #[cfg(feature = "metrics")]
             metrics: BlockchainMetrics::default(),
@@ -168,54 +441,142 @@ impl<'env> Blockchain<'env> {
     }
 
     pub fn push(&self, block: Block) -> PushResult {
-        // We expect full blocks (with body).
-        assert!(block.body.is_some(), "Block body expected");
+        self.push_with_origin(block, None)
+    }
 
-        // Check (sort of) intrinsic block invariants.
-        let info = get_network_info(self.network_id).unwrap();
-        if let Err(e) = block.verify(self.network_time.now(), self.network_id, info.genesis_block.header.hash()) {
-            warn!("Rejecting block - verification failed ({:?})", e);
-            #[cfg(feature = "metrics")]
-            self.metrics.note_invalid_block();
-            return PushResult::Invalid(PushError::InvalidBlock(e))
+    /// Like `push`, but remembers the peer that sent the block, so that a single peer can't fill up the orphan pool.
+    pub fn push_from(&self, block: Block, origin: &PeerId) -> PushResult {
+        self.push_with_origin(block, Some(origin))
+    }
+
+    fn push_with_origin(&self, block: Block, origin: Option<&PeerId>) -> PushResult {
+        if let Err(e) = self.verify_block(&block) {
+            return PushResult::Invalid(e);
+        }
+        self.push_verified(block, origin)
+    }
+
+    /// Pushes a block that passed `verify_block` and re-pushes the orphans waiting for it.
+    fn push_verified(&self, block: Block, origin: Option<&PeerId>) -> PushResult {
+        let hash: Blake2bHash = block.header.hash();
+        let result = self.push_block(block, origin);
+        if Self::is_stored(&result) {
+            self.push_orphans(hash);
+        }
+        return result;
+    }
+
+    /// Queues a block to be pushed by `process_queue`, e.g. from the thread started by `spawn_queue_drain`.
+    /// The block is checked on its own (including its proof-of-work) before it is queued, so that invalid blocks
+    /// don't take up space in the queue. Queued blocks are pushed in order of their height, see `BlockQueue`.
+    /// Returns `Ok(false)` if the block is already queued or the queue is full.
+    pub fn enqueue_block(&self, block: Block) -> Result<bool, PushError> {
+        self.verify_block(&block)?;
+
+        let added = self.block_queue.lock().push(block);
+        if added {
+            self.queue_ready.notify_one();
+        } else {
+            debug!("Not queueing block - already queued or queue full");
+        }
+        Ok(added)
+    }
+
+    /// Number of blocks waiting in the block queue.
+    pub fn queue_len(&self) -> usize {
+        self.block_queue.lock().len()
+    }
+
+    /// Pushes all queued blocks, lowest height first. Returns the results in the order the blocks were pushed.
+    /// Blocks queued while this runs are pushed as well.
+    pub fn process_queue(&self) -> Vec<PushResult> {
+        let _lock = self.drain_lock.lock();
+        let mut results = Vec::new();
+        loop {
+            // Only hold the queue lock while taking a block, so that blocks can be queued during the push.
+            let block = match self.block_queue.lock().pop() {
+                Some(block) => block,
+                None => break,
+            };
+            // Queued blocks were verified by `enqueue_block` already.
+            results.push(self.push_verified(block, None));
+        }
+        results
+    }
+
+    /// Waits up to `timeout` for a block to be queued, then pushes all queued blocks like `process_queue`.
+    pub fn wait_and_process_queue(&self, timeout: Duration) -> Vec<PushResult> {
+        {
+            let mut queue = self.block_queue.lock();
+            if queue.is_empty() {
+                self.queue_ready.wait_for(&mut queue, timeout);
+            }
+        }
+        self.process_queue()
+    }
+
+    /// Number of orphan blocks currently buffered.
+    pub fn orphan_count(&self) -> usize {
+        self.orphans.lock().len()
+    }
+
+    fn is_stored(result: &PushResult) -> bool {
+        match result {
+            PushResult::Extended | PushResult::Rebranched | PushResult::Forked | PushResult::ForkExtended => true,
+            _ => false,
+        }
+    }
+
+    /// Re-pushes buffered orphans whose predecessor is the newly stored block `hash`.
+    fn push_orphans(&self, hash: Blake2bHash) {
+        let mut queue = vec![hash];
+        while let Some(prev_hash) = queue.pop() {
+            let successors = self.orphans.lock().take_successors(&prev_hash);
+            for block in successors {
+                let hash: Blake2bHash = block.header.hash();
+                debug!("Re-pushing orphan block {}", hash);
+                if Self::is_stored(&self.push_block(block, None)) {
+                    queue.push(hash);
+                }
+            }
         }
 
+        // Orphans too far below the main chain will never be accepted.
+        self.orphans.lock().prune(self.height(), Instant::now());
+    }
+
+    /// Pushes a block that passed `verify_block` without re-pushing orphans.
+    fn push_block(&self, block: Block, origin: Option<&PeerId>) -> PushResult {
         // Only one push operation at a time.
         let lock = self.push_lock.lock();
 
         // Check if we already know this block.
         let hash: Blake2bHash = block.header.hash();
         if self.chain_store.get_chain_info(&hash, false, None).is_some() {
-            #[cfg(feature = "metrics")]
-            self.metrics.note_known_block();
+            self.push_counters.note_known_block();
             return PushResult::Known;
         }
 
         // Check if the block's immediate predecessor is part of the chain.
         let prev_info_opt = self.chain_store.get_chain_info(&block.header.prev_hash, false, None);
         if prev_info_opt.is_none() {
-            warn!("Rejecting block - unknown predecessor");
-            #[cfg(feature = "metrics")]
-            self.metrics.note_orphan_block();
+            let head_height = self.state.read().main_chain.head.header.height;
+            let now = Instant::now();
+            let mut orphans = self.orphans.lock();
+            orphans.prune(head_height, now);
+            if orphans.add(block, origin, head_height, self.max_orphans, now) {
+                debug!("Buffering block {} - unknown predecessor", hash);
+            } else {
+                warn!("Rejecting block - unknown predecessor");
+            }
+            self.push_counters.note_orphan_block();
             return PushResult::Orphan;
         }
 
         // Check that the block is a valid successor of its predecessor.
         let prev_info = prev_info_opt.unwrap();
-        if !block.is_immediate_successor_of(&prev_info.head) {
-            warn!("Rejecting block - not a valid successor");
-            #[cfg(feature = "metrics")]
-            self.metrics.note_invalid_block();
-            return PushResult::Invalid(PushError::InvalidSuccessor);
-        }
-
-        // Check that the difficulty is correct.
-        let next_target = self.get_next_target(Some(&block.header.prev_hash));
-        if block.header.n_bits != TargetCompact::from(next_target) {
-            warn!("Rejecting block - difficulty mismatch");
-            #[cfg(feature = "metrics")]
-            self.metrics.note_invalid_block();
-            return PushResult::Invalid(PushError::DifficultyMismatch);
+        if let Err(e) = self.verify_successor(&block, &prev_info, None) {
+            return PushResult::Invalid(e);
         }
 
         // Block looks good, create ChainInfo.
@@ -226,9 +587,9 @@ impl<'env> Blockchain<'env> {
             return self.extend(hash, chain_info, prev_info);
         }
 
-        // Otherwise, check if the new chain is harder than our current main chain.
-        if chain_info.total_difficulty > self.state.read().main_chain.total_difficulty {
-            // A fork has become the hardest chain, rebranch to it.
+        // Otherwise, let the fork choice rule decide whether the new chain replaces our main chain.
+        if self.fork_choice.should_rebranch(&self.state.read().main_chain, &chain_info) {
+            // A fork has become the preferred chain, rebranch to it.
             return self.rebranch(hash, chain_info);
         }
 
@@ -238,38 +599,334 @@ impl<'env> Blockchain<'env> {
         self.chain_store.put_chain_info(&mut txn, &hash, &chain_info, true);
         txn.commit();
 
-        #[cfg(feature = "metrics")]
-        self.metrics.note_forked_block();
+        let event = BlockchainEvent::Forked(hash, UniquePtr::new(&chain_info.head));
+        self.notifier.read().notify(event);
+
+        self.push_counters.note_forked_block();
+
+        // If the predecessor is not on the main chain, it is part of an existing fork.
+        if !prev_info.on_main_chain {
+            return PushResult::ForkExtended;
+        }
         return PushResult::Forked;
     }
 
+    /// Checks the block on its own, i.e. its timestamp, intrinsic invariants and miner.
+    fn verify_block(&self, block: &Block) -> Result<(), PushError> {
+        // We expect full blocks (with body).
+        assert!(block.body.is_some(), "Block body expected");
+
+        // Warn about a skewed local clock, it will cause blocks to be rejected.
+        self.network_time_offset();
+
+        let now = self.network_time.now();
+
+        // Check (sort of) intrinsic block invariants.
+        let info = get_network_info(self.network_id).unwrap();
+        #[cfg(feature = "metrics")]
+        let verify_start = Instant::now();
+        let verify_result = block.verify_with_drift(now, self.max_future_drift, self.network_id, info.genesis_block.header.hash());
+        #[cfg(feature = "metrics")]
+        self.metrics.push_timings().verify.observe(verify_start.elapsed());
+        match verify_result {
+            // A timestamp too far ahead of the network time would skew the difficulty adjustment.
+            Err(BlockError::FromTheFuture) => {
+                warn!("Rejecting block - timestamp {}ms ahead of network time", block.header.timestamp_in_millis() - now);
+                self.push_counters.note_invalid_block();
+                return Err(PushError::TimestampInFuture(block.into()));
+            },
+            Err(e) => {
+                warn!("Rejecting block - verification failed ({})", e);
+                self.push_counters.note_invalid_block();
+                return Err(PushError::invalid_block(e, block.into()));
+            },
+            Ok(()) => {},
+        }
+        if block.header.timestamp_in_millis() > now {
+            trace!("Accepting block {}ms ahead of network time due to drift allowance of {}ms",
+                block.header.timestamp_in_millis() - now, self.max_future_drift);
+        }
+
+        // Check that the miner is not on the deny list.
+        if self.denied_miners.read().contains(&block.body.as_ref().unwrap().miner) {
+            warn!("Rejecting block - miner is denied");
+            self.push_counters.note_invalid_block();
+            return Err(PushError::DeniedMiner(block.into()));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the block is a valid successor of `prev_info`: header linkage, difficulty and timestamps.
+    /// Reads from `txn_option` if given, so that blocks not committed yet can be checked.
+    fn verify_successor(&self, block: &Block, prev_info: &ChainInfo, txn_option: Option<&Transaction>) -> Result<(), PushError> {
+        // Check that the header links up with the predecessor.
+        if !block.is_immediate_successor_of(&prev_info.head) {
+            warn!("Rejecting block - not a valid successor");
+            self.push_counters.note_invalid_block();
+            return Err(PushError::InvalidSuccessor(block.into()));
+        }
+
+        // Check that the difficulty is correct.
+        #[cfg(feature = "metrics")]
+        let difficulty_start = Instant::now();
+        let next_target = match txn_option {
+            // Targets on top of uncommitted blocks must not end up in the target cache.
+            Some(_) => self.compute_next_target_for(prev_info, txn_option),
+            None => self.get_next_target(Some(&block.header.prev_hash)),
+        };
+        #[cfg(feature = "metrics")]
+        self.metrics.push_timings().difficulty.observe(difficulty_start.elapsed());
+        if block.header.n_bits != TargetCompact::from(next_target) {
+            warn!("Rejecting block - difficulty mismatch");
+            self.push_counters.note_invalid_block();
+            return Err(PushError::DifficultyMismatch(block.into()));
+        }
+
+        // Check the timestamp against the preceding blocks.
+        if let Err(e) = self.verify_timestamps(&block.header, prev_info, txn_option) {
+            warn!("Rejecting block - {}", e);
+            self.push_counters.note_invalid_block();
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Checks the timestamp of `header` against the preceding blocks, starting at `prev_info`.
+    /// Blocks below the activation height of these rules are always accepted.
+    fn verify_timestamps(&self, header: &BlockHeader, prev_info: &ChainInfo, txn_option: Option<&Transaction>) -> Result<(), PushError> {
+        if header.height < self.timestamp_rules_height() {
+            return Ok(());
+        }
+
+        // Check that the timestamps of the difficulty window ending at this block are not (nearly) equal.
+        let window_tail = self.get_difficulty_window_tail(prev_info, txn_option);
+        if !Self::verify_window_timespan(header, &window_tail.head.header) {
+            return Err(PushError::TimestampManipulation(PushErrorContext::new(header.hash(), header.height)));
+        }
+
+        // Check that the timestamp is after the median timestamp of the preceding blocks.
+        let mut prev_headers = vec![prev_info.head.header.clone()];
+        prev_headers.extend(self.chain_store
+            .get_blocks_backward(&header.prev_hash, policy::MEDIAN_TIME_PAST_WINDOW - 1, false, txn_option)
+            .into_iter()
+            .map(|prev_block| prev_block.header));
+        if !Self::verify_median_time_past(header, &prev_headers) {
+            return Err(PushError::TimestampNotAfterMedian(PushErrorContext::new(header.hash(), header.height)));
+        }
+
+        Ok(())
+    }
+
+    /// Height from which blocks have to pass the timestamp checks of `verify_timestamps`.
+    fn timestamp_rules_height(&self) -> u32 {
+        self.config.timestamp_rules_height()
+            .unwrap_or_else(|| get_network_info(self.network_id).unwrap().timestamp_rules_height)
+    }
+
+    /// Pushes a batch of blocks in order, e.g. from a sync response.
+    /// Blocks that are already stored are reported as `Known` without verifying them again.
+    pub fn push_many(&self, blocks: Vec<Block>) -> Vec<PushResult> {
+        let mut results = Vec::with_capacity(blocks.len());
+        for block in blocks {
+            // Sync responses may overlap with what we already have, skip the PoW check for those.
+            let hash: Blake2bHash = block.header.hash();
+            if self.chain_store.get_chain_info(&hash, false, None).is_some() {
+                self.push_counters.note_known_block();
+                results.push(PushResult::Known);
+                continue;
+            }
+
+            results.push(self.push(block));
+        }
+        return results;
+    }
+
+    /// Pushes a sequence of blocks, e.g. during the initial sync. Consecutive blocks extending the main chain
+    /// are committed under a single write transaction, so the head only moves once all of them have been applied.
+    /// Starting at the first block that doesn't simply extend the main chain (e.g. because it forks), blocks are pushed one by one.
+    pub fn push_batch(&self, blocks: Vec<Block>) -> Vec<PushResult> {
+        let mut results = Vec::with_capacity(blocks.len());
+
+        let outcome = {
+            // Only one push operation at a time.
+            let _lock = self.push_lock.lock();
+            self.extend_batch(blocks)
+        };
+
+        // Notify about the applied blocks now that they have been committed.
+        for (hash, chain_info) in outcome.applied.iter() {
+            let event = BlockchainEvent::Extended(hash.clone(), UniquePtr::new(&chain_info.head));
+            self.notifier.read().notify(event);
+
+            self.push_counters.note_extended_block();
+            results.push(PushResult::Extended);
+        }
+        for (hash, _) in outcome.applied {
+            self.push_orphans(hash);
+        }
+
+        if let Some(e) = outcome.rejected {
+            results.push(PushResult::Invalid(e));
+        }
+        for block in outcome.remaining {
+            results.push(self.push(block));
+        }
+        return results;
+    }
+
+    /// Applies the leading blocks that extend the main chain under a single write transaction and commits them.
+    /// Stops at the first block that doesn't extend the batch. The push lock must be held.
+    fn extend_batch(&self, blocks: Vec<Block>) -> BatchOutcome {
+        let mut applied: Vec<(Blake2bHash, ChainInfo)> = Vec::new();
+        let mut rejected = None;
+        let mut remaining = Vec::new();
+
+        let (start_hash, mut main_chain, mut cache_txn) = {
+            let state = self.state.read();
+            (state.head_hash.clone(), state.main_chain.clone(), state.transaction_cache.clone())
+        };
+
+        let mut txn = WriteTransaction::new(self.env);
+        let mut blocks = blocks.into_iter();
+        while let Some(block) = blocks.next() {
+            let hash: Blake2bHash = block.header.hash();
+
+            // Leave known blocks, forks and orphans to `push`.
+            let head_hash = applied.last().map(|(hash, _)| hash).unwrap_or(&start_hash);
+            if block.header.prev_hash != *head_hash || self.chain_store.get_chain_info(&hash, false, Some(&txn)).is_some() {
+                remaining.push(block);
+                break;
+            }
+
+            let mut chain_info = {
+                let prev_info = applied.last().map(|(_, chain_info)| chain_info).unwrap_or(&main_chain);
+                if let Err(e) = self.verify_block(&block).and_then(|_| self.verify_successor(&block, prev_info, Some(&txn))) {
+                    rejected = Some(e);
+                    break;
+                }
+                prev_info.next(block)
+            };
+
+            // Check transactions against the blocks before and earlier in the batch to prevent replay.
+            #[cfg(feature = "metrics")]
+            let cache_start = Instant::now();
+            let is_replay = cache_txn.contains_any(&chain_info.head);
+            #[cfg(feature = "metrics")]
+            self.metrics.push_timings().transaction_cache.observe(cache_start.elapsed());
+            if is_replay {
+                warn!("Rejecting block - transaction already included");
+                self.push_counters.note_invalid_block();
+                rejected = Some(PushError::DuplicateTransaction(PushErrorContext::new(hash, chain_info.head.header.height)));
+                break;
+            }
+
+            // Commit block to AccountsTree.
+            #[cfg(feature = "metrics")]
+            let commit_start = Instant::now();
+            let commit_result = self.state.read().accounts.commit_block(&mut txn, &chain_info.head);
+            #[cfg(feature = "metrics")]
+            self.metrics.push_timings().accounts_commit.observe(commit_start.elapsed());
+            if let Err(e) = commit_result {
+                warn!("Rejecting block - commit failed: {}", e);
+                self.push_counters.note_invalid_block();
+
+                // The failed commit may have left partial changes, so apply the blocks before it again.
+                txn.abort();
+                let applied_blocks = applied.into_iter().map(|(_, chain_info)| chain_info.head).collect();
+                let mut outcome = self.extend_batch(applied_blocks);
+                outcome.rejected = Some(PushError::accounts_error(e, PushErrorContext::new(hash, chain_info.head.header.height)));
+                outcome.remaining = blocks.collect();
+                return outcome;
+            }
+
+            cache_txn.push_block(&chain_info.head);
+
+            #[cfg(feature = "metrics")]
+            let store_start = Instant::now();
+            {
+                let prev_info = match applied.last_mut() {
+                    Some((_, prev_info)) => prev_info,
+                    None => &mut main_chain,
+                };
+                prev_info.main_chain_successor = Some(hash.clone());
+                self.chain_store.put_chain_info(&mut txn, &chain_info.head.header.prev_hash, prev_info, false);
+            }
+            chain_info.on_main_chain = true;
+            self.chain_store.put_chain_info(&mut txn, &hash, &chain_info, true);
+            #[cfg(feature = "metrics")]
+            self.metrics.push_timings().store_write.observe(store_start.elapsed());
+
+            applied.push((hash, chain_info));
+        }
+        remaining.extend(blocks);
+
+        match applied.last() {
+            Some((head_hash, head_info)) => {
+                self.chain_store.set_head(&mut txn, head_hash);
+
+                // Acquire write lock.
+                let mut state = self.state.write();
+
+                state.transaction_cache = cache_txn;
+
+                state.main_chain = head_info.clone();
+                state.head_hash = head_hash.clone();
+
+                txn.commit();
+            },
+            None => txn.abort(),
+        }
+
+        BatchOutcome { applied, rejected, remaining }
+    }
+
     fn extend(&self, block_hash: Blake2bHash, mut chain_info: ChainInfo, mut prev_info: ChainInfo) -> PushResult {
         let mut txn = WriteTransaction::new(self.env);
         {
             let state = self.state.read();
 
             // Check transactions against TransactionCache to prevent replay.
-            if state.transaction_cache.contains_any(&chain_info.head) {
+            #[cfg(feature = "metrics")]
+            let cache_start = Instant::now();
+            let is_replay = state.transaction_cache.contains_any(&chain_info.head);
+            #[cfg(feature = "metrics")]
+            self.metrics.push_timings().transaction_cache.observe(cache_start.elapsed());
+            if is_replay {
                 warn!("Rejecting block - transaction already included");
                 txn.abort();
-                #[cfg(feature = "metrics")]
-                self.metrics.note_invalid_block();
-                return PushResult::Invalid(PushError::DuplicateTransaction);
+                self.push_counters.note_invalid_block();
+                return PushResult::Invalid(PushError::DuplicateTransaction(PushErrorContext::new(block_hash, chain_info.head.header.height)));
             }
 
             // Commit block to AccountsTree.
-            if let Err(e) = state.accounts.commit_block(&mut txn, &chain_info.head) {
+            #[cfg(feature = "metrics")]
+            let commit_start = Instant::now();
+            let commit_result = state.accounts.commit_block(&mut txn, &chain_info.head);
+            #[cfg(feature = "metrics")]
+            self.metrics.push_timings().accounts_commit.observe(commit_start.elapsed());
+            if let Err(e) = commit_result {
                 warn!("Rejecting block - commit failed: {}", e);
                 txn.abort();
-                #[cfg(feature = "metrics")]
-                self.metrics.note_invalid_block();
-                return PushResult::Invalid(PushError::AccountsError(e));
+                self.push_counters.note_invalid_block();
+                return PushResult::Invalid(PushError::accounts_error(e, PushErrorContext::new(block_hash, chain_info.head.header.height)));
+            }
+
+            // Make sure the resulting state matches the one committed to in the header.
+            if chain_info.head.header.accounts_hash != state.accounts.hash(Some(&txn)) {
+                warn!("Rejecting block - accounts hash mismatch");
+                txn.abort();
+                self.push_counters.note_invalid_block();
+                return PushResult::Invalid(PushError::AccountsHashMismatch(Some(PushErrorContext::new(block_hash, chain_info.head.header.height))));
             }
         }
 
         chain_info.on_main_chain = true;
         prev_info.main_chain_successor = Some(block_hash.clone());
 
+        #[cfg(feature = "metrics")]
+        let store_start = Instant::now();
         self.chain_store.put_chain_info(&mut txn, &block_hash, &chain_info, true);
         self.chain_store.put_chain_info(&mut txn, &chain_info.head.header.prev_hash, &prev_info, false);
         self.chain_store.set_head(&mut txn, &block_hash);
@@ -283,18 +940,17 @@ impl<'env> Blockchain<'env> {
             state.main_chain = chain_info;
             state.head_hash = block_hash;
 
-            state.chain_proof = None;
-
             txn.commit();
         }
+        #[cfg(feature = "metrics")]
+        self.metrics.push_timings().store_write.observe(store_start.elapsed());
 
         // Give up write lock before notifying.
         let state = self.state.read();
         let event = BlockchainEvent::Extended(state.head_hash.clone(), UniquePtr::new(&state.main_chain.head));
         self.notifier.read().notify(event);
 
-        #[cfg(feature = "metrics")]
-        self.metrics.note_extended_block();
+        self.push_counters.note_extended_block();
         return PushResult::Extended;
     }
 
@@ -320,6 +976,25 @@ impl<'env> Blockchain<'env> {
 
         debug!("Found common ancestor {} at height #{}, {} blocks up", current.0, current.1.head.header.height, fork_chain.len());
 
+        // Refuse deep reorgs before doing any accounts work. Keep the block as a fork, so we don't download it again.
+        let revert_depth = self.state.read().main_chain.head.header.height - current.1.head.header.height;
+        let max_rebranch_depth = self.config.max_rebranch_depth();
+        if fork_chain.len() as u32 > max_rebranch_depth || revert_depth > max_rebranch_depth {
+            warn!("Not rebranching to block {} - rebranch too deep ({} blocks to revert, {} to apply)", fork_chain[0].0, revert_depth, fork_chain.len());
+            let (hash, chain_info) = fork_chain.swap_remove(0);
+            let mut txn = WriteTransaction::new(self.env);
+            self.chain_store.put_chain_info(&mut txn, &hash, &chain_info, true);
+            txn.commit();
+
+            let context = PushErrorContext::new(hash.clone(), chain_info.head.header.height);
+            let event = BlockchainEvent::Forked(hash, UniquePtr::new(&chain_info.head));
+            self.notifier.read().notify(event);
+
+            // The block itself is valid, so it is counted as a fork block rather than an invalid one.
+            self.push_counters.note_forked_block();
+            return PushResult::Invalid(PushError::RebranchTooDeep(context));
+        }
+
         // Revert AccountsTree & TransactionCache to the common ancestor state.
         let mut revert_chain: Vec<(Blake2bHash, ChainInfo)> = vec![];
         let mut ancestor = current;
@@ -334,22 +1009,11 @@ impl<'env> Blockchain<'env> {
             current = (state.head_hash.clone(), state.main_chain.clone());
 
             while current.0 != ancestor.0 {
-                if let Err(e) = state.accounts.revert_block(&mut write_txn, &current.1.head) {
-                    panic!("Failed to revert main chain while rebranching - {}", e);
-                }
-
-                cache_txn.revert_block(&current.1.head);
-
-                let prev_hash = current.1.head.header.prev_hash.clone();
-                let prev_info = self.chain_store
-                    .get_chain_info(&prev_hash, true, Some(&read_txn))
-                    .expect("Corrupted store: Failed to find main chain predecessor while rebranching");
-
-                assert_eq!(prev_info.head.header.accounts_hash, state.accounts.hash(Some(&write_txn)),
-                           "Failed to revert main chain while rebranching - inconsistent state");
-
+                // Our own main chain failing to revert means that the store is corrupted.
+                let prev = self.revert_main_chain_block(&state, &mut write_txn, &mut cache_txn, &current, &read_txn)
+                    .unwrap_or_else(|e| panic!("Failed to revert main chain - {}", e));
                 revert_chain.push(current);
-                current = (prev_hash, prev_info);
+                current = prev;
             }
 
             // Fetch missing blocks for TransactionCache.
@@ -366,23 +1030,21 @@ impl<'env> Blockchain<'env> {
             assert_eq!(cache_txn.missing_blocks(), policy::TRANSACTION_VALIDITY_WINDOW.saturating_sub(ancestor.1.head.header.height));
 
             // Check each fork block against TransactionCache & commit to AccountsTree.
-            for fork_block in fork_chain.iter().rev() {
+            for (i, fork_block) in fork_chain.iter().enumerate().rev() {
                 if cache_txn.contains_any(&fork_block.1.head) {
                     warn!("Failed to apply fork block while rebranching - transaction already included");
-                    // TODO delete invalid fork from store
                     write_txn.abort();
-                    #[cfg(feature = "metrics")]
-                    self.metrics.note_invalid_block();
-                    return PushResult::Invalid(PushError::InvalidFork);
+                    self.remove_invalid_fork(&fork_block.0, fork_block.1.head.header.height);
+                    self.push_counters.note_invalid_block();
+                    return PushResult::Invalid(PushError::InvalidFork(PushErrorContext::new(fork_block.0.clone(), fork_block.1.head.header.height)));
                 }
 
                 if let Err(e) = state.accounts.commit_block(&mut write_txn, &fork_block.1.head) {
                     warn!("Failed to apply fork block while rebranching - {}", e);
-                    // TODO delete invalid fork from store
                     write_txn.abort();
-                    #[cfg(feature = "metrics")]
-                    self.metrics.note_invalid_block();
-                    return PushResult::Invalid(PushError::InvalidFork);
+                    self.remove_invalid_fork(&fork_block.0, fork_block.1.head.header.height);
+                    self.push_counters.note_invalid_block();
+                    return PushResult::Invalid(PushError::InvalidFork(PushErrorContext::new(fork_block.0.clone(), fork_block.1.head.header.height)));
                 }
 
                 cache_txn.push_block(&fork_block.1.head);
@@ -427,9 +1089,6 @@ impl<'env> Blockchain<'env> {
 
             state.main_chain = fork_chain[0].1.clone();
             state.head_hash = fork_chain[0].0.clone();
-
-            // Reset chain proof.
-            state.chain_proof = None;
         }
 
         // Give up write lock before notifying.
@@ -444,30 +1103,359 @@ impl<'env> Blockchain<'env> {
         let event = BlockchainEvent::Rebranched(reverted_blocks, adopted_blocks);
         self.notifier.read().notify(event);
 
-        #[cfg(feature = "metrics")]
-        self.metrics.note_rebranched_block();
+        self.push_counters.note_rebranched_block();
         return PushResult::Rebranched;
     }
 
-    pub fn get_next_target(&self, head_hash: Option<&Blake2bHash>) -> Target {
-        let state = self.state.read();
+    /// Removes a fork block that failed to apply and all blocks built on it from the store,
+    /// so that dead forks don't accumulate.
+    fn remove_invalid_fork(&self, hash: &Blake2bHash, height: u32) {
+        let blocks = self.fork_descendants(hash, height, None);
+        debug!("Removing invalid fork block {} and {} descendants from the store", hash, blocks.len() - 1);
+        let mut txn = WriteTransaction::new(self.env);
+        for (block_hash, block_height) in blocks.iter() {
+            self.chain_store.remove_chain_info(&mut txn, block_hash, *block_height);
+        }
+        txn.commit();
+    }
 
-        let chain_info;
-        let head_info = match head_hash {
-            Some(hash) => {
-                chain_info = self.chain_store
-                    .get_chain_info(hash, false, None)
-                    .expect("Failed to compute next target - unknown head_hash");
-                &chain_info
-            }
-            None => &state.main_chain
-        };
+    /// Returns the fork block `hash` at `height` and all stored blocks built on it, with their heights.
+    fn fork_descendants(&self, hash: &Blake2bHash, height: u32, txn_option: Option<&Transaction>) -> Vec<(Blake2bHash, u32)> {
+        // Fork blocks don't know their successors, so collect the descendants height by height.
+        let mut height = height;
+        let mut descendants = vec![(hash.clone(), height)];
+        let mut parents: HashSet<Blake2bHash> = HashSet::new();
+        parents.insert(hash.clone());
+        while !parents.is_empty() {
+            height += 1;
+            let children: HashSet<Blake2bHash> = self.chain_store
+                .get_blocks_at(height, false, txn_option)
+                .into_iter()
+                .filter(|(_, block)| parents.contains(&block.header.prev_hash))
+                .map(|(child_hash, _)| child_hash)
+                .collect();
+            descendants.extend(children.iter().map(|child_hash| (child_hash.clone(), height)));
+            parents = children;
+        }
+        descendants
+    }
+
+    /// Reverts the main chain head `current` in `write_txn` and `cache_txn` and returns its predecessor.
+    /// On error, the caller has to abort `write_txn`.
+    fn revert_main_chain_block(&self, state: &BlockchainState<'env>, write_txn: &mut WriteTransaction, cache_txn: &mut TransactionCache,
+                               current: &(Blake2bHash, ChainInfo), read_txn: &ReadTransaction) -> Result<(Blake2bHash, ChainInfo), PushError> {
+        if let Err(e) = state.accounts.revert_block(write_txn, &current.1.head) {
+            warn!("Failed to revert main chain block {} - {}", current.0, e);
+            return Err(PushError::accounts_error(e, PushErrorContext::new(current.0.clone(), current.1.head.header.height)));
+        }
+
+        cache_txn.revert_block(&current.1.head);
+
+        let prev_hash = current.1.head.header.prev_hash.clone();
+        let prev_info = self.chain_store
+            .get_chain_info(&prev_hash, true, Some(read_txn))
+            .expect("Corrupted store: Failed to find main chain predecessor");
+
+        assert_eq!(prev_info.head.header.accounts_hash, state.accounts.hash(Some(&write_txn)),
+                   "Failed to revert main chain - inconsistent state");
+
+        Ok((prev_hash, prev_info))
+    }
+
+    /// Returns the transactions of the given reverted blocks that are still valid on top of the
+    /// current head, e.g. to restore them to the mempool after a rebranch. Signatures are not
+    /// checked again as the blocks have already been verified.
+    pub fn filter_revived_transactions(&self, reverted: &[(Blake2bHash, Block)]) -> Vec<primitives::transaction::Transaction> {
+        let state = self.state.read();
+        let block_height = state.main_chain.head.header.height + 1;
+
+        let mut sender_accounts: HashMap<Address, Account> = HashMap::new();
+        let mut transactions = Vec::new();
+        for (_, block) in reverted {
+            let body = match block.body {
+                Some(ref body) => body,
+                None => continue,
+            };
+
+            for tx in body.transactions.iter() {
+                // Check if the transaction has expired or has been included in the new main chain.
+                if !tx.is_valid_at(block_height) || state.transaction_cache.contains(&tx.hash()) {
+                    continue;
+                }
+
+                // Check if the transaction is still valid for the recipient.
+                let recipient_account = state.accounts.get(&tx.recipient, None);
+                if recipient_account.account_type() != tx.recipient_type
+                    || recipient_account.with_incoming_transaction(tx, block_height).is_err() {
+                    continue;
+                }
+
+                // Check if the sender can still afford the transaction, including the revived transactions before it.
+                let sender_account = sender_accounts.entry(tx.sender.clone())
+                    .or_insert_with(|| state.accounts.get(&tx.sender, None));
+                if sender_account.account_type() != tx.sender_type {
+                    continue;
+                }
+                if let Ok(account) = sender_account.with_outgoing_transaction(tx, block_height) {
+                    *sender_account = account;
+                    transactions.push(tx.clone());
+                }
+            }
+        }
+        transactions
+    }
+
+    /// Reverts the head block, making its predecessor the new head. The reverted block is
+    /// kept in the store as a fork block. Listeners are notified with a `Rebranched` event without adopted blocks.
+    pub fn revert_head(&self) -> Result<Block, PushError> {
+        let _lock = self.push_lock.lock();
+
+        let read_txn = ReadTransaction::new(self.env);
+        let mut write_txn = WriteTransaction::new(self.env);
+        let mut cache_txn;
+        let head_hash;
+        let mut head_info;
+        let prev_hash;
+        let mut prev_info;
+        {
+            let state = self.state.read();
+            if state.main_chain.head.header.height == 1 {
+                write_txn.abort();
+                return Err(PushError::CannotRevertGenesis(PushErrorContext::new(state.head_hash.clone(), 1)));
+            }
+
+            cache_txn = state.transaction_cache.clone();
+            let head = (state.head_hash.clone(), state.main_chain.clone());
+            let prev = match self.revert_main_chain_block(&state, &mut write_txn, &mut cache_txn, &head, &read_txn) {
+                Ok(prev) => prev,
+                Err(e) => {
+                    write_txn.abort();
+                    return Err(e);
+                }
+            };
+
+            // Refill the TransactionCache at the tail.
+            let blocks = self.chain_store.get_blocks_backward(&cache_txn.tail_hash(), cache_txn.missing_blocks(), true, Some(&read_txn));
+            for block in blocks.iter() {
+                cache_txn.prepend_block(block);
+            }
+            assert_eq!(cache_txn.missing_blocks(), policy::TRANSACTION_VALIDITY_WINDOW.saturating_sub(prev.1.head.header.height));
+
+            head_hash = head.0;
+            head_info = head.1;
+            prev_hash = prev.0;
+            prev_info = prev.1;
+        }
+
+        {
+            // Acquire write lock.
+            let mut state = self.state.write();
+
+            head_info.on_main_chain = false;
+            head_info.main_chain_successor = None;
+            self.chain_store.put_chain_info(&mut write_txn, &head_hash, &head_info, false);
 
+            prev_info.main_chain_successor = None;
+            self.chain_store.put_chain_info(&mut write_txn, &prev_hash, &prev_info, false);
+            self.chain_store.set_head(&mut write_txn, &prev_hash);
+
+            // Commit transaction & update head.
+            write_txn.commit();
+            state.transaction_cache = cache_txn;
+
+            state.main_chain = prev_info;
+            state.head_hash = prev_hash;
+        }
+
+        // Give up write lock before notifying.
+        let event = BlockchainEvent::Rebranched(vec![(head_hash, head_info.head.clone())], vec![]);
+        self.notifier.read().notify(event);
+
+        return Ok(head_info.head);
+    }
+
+    /// Removes the fork block `hash` and all blocks built on it from the store.
+    /// Returns the number of removed blocks. Main chain blocks must be reverted first.
+    pub fn purge_block(&self, hash: &Blake2bHash) -> Result<usize, PushError> {
+        let _lock = self.push_lock.lock();
+
+        let read_txn = ReadTransaction::new(self.env);
+        let root_info = match self.chain_store.get_chain_info(hash, false, Some(&read_txn)) {
+            Some(chain_info) => chain_info,
+            None => return Ok(0),
+        };
+        if root_info.on_main_chain {
+            return Err(PushError::BlockOnMainChain(PushErrorContext::new(hash.clone(), root_info.head.header.height)));
+        }
+
+        let purged = self.fork_descendants(hash, root_info.head.header.height, Some(&read_txn));
+
+        debug!("Purging block {} and {} descendants from the store", hash, purged.len() - 1);
+        let mut txn = WriteTransaction::new(self.env);
+        for (block_hash, block_height) in purged.iter() {
+            self.chain_store.remove_chain_info(&mut txn, block_hash, *block_height);
+        }
+        txn.commit();
+
+        return Ok(purged.len());
+    }
+
+    /// Reverts the main chain down to the block at `height`, which becomes the new head.
+    /// The reverted blocks are kept in the store as fork blocks and returned in ascending order.
+    /// Listeners are notified with a `Rebranched` event without adopted blocks.
+    pub fn revert_to_height(&self, height: u32) -> Result<Vec<(Blake2bHash, Block)>, PushError> {
+        let _lock = self.push_lock.lock();
+
+        let read_txn = ReadTransaction::new(self.env);
+        let mut write_txn = WriteTransaction::new(self.env);
+        let mut cache_txn;
+        let mut revert_chain: Vec<(Blake2bHash, ChainInfo)> = vec![];
+        let mut target;
+        {
+            let state = self.state.read();
+            if height < 1 {
+                write_txn.abort();
+                let genesis_hash = get_network_info(self.network_id).unwrap().genesis_hash.clone();
+                return Err(PushError::CannotRevertGenesis(PushErrorContext::new(genesis_hash, 1)));
+            }
+            if height > state.main_chain.head.header.height {
+                write_txn.abort();
+                return Err(PushError::InvalidHeight(PushErrorContext::new(state.head_hash.clone(), state.main_chain.head.header.height)));
+            }
+
+            cache_txn = state.transaction_cache.clone();
+            // XXX Get rid of the .clone() here.
+            target = (state.head_hash.clone(), state.main_chain.clone());
+
+            while target.1.head.header.height > height {
+                let prev = match self.revert_main_chain_block(&state, &mut write_txn, &mut cache_txn, &target, &read_txn) {
+                    Ok(prev) => prev,
+                    Err(e) => {
+                        write_txn.abort();
+                        return Err(e);
+                    }
+                };
+                revert_chain.push(target);
+                target = prev;
+            }
+
+            // Fetch missing blocks for TransactionCache.
+            if !revert_chain.is_empty() {
+                let start_hash = if cache_txn.is_empty() {
+                    revert_chain.last().unwrap().0.clone()
+                } else {
+                    cache_txn.tail_hash()
+                };
+                let blocks = self.chain_store.get_blocks_backward(&start_hash, cache_txn.missing_blocks(), true, Some(&read_txn));
+                for block in blocks.iter() {
+                    cache_txn.prepend_block(block);
+                }
+            }
+            assert_eq!(cache_txn.missing_blocks(), policy::TRANSACTION_VALIDITY_WINDOW.saturating_sub(target.1.head.header.height));
+        }
+
+        if revert_chain.is_empty() {
+            write_txn.abort();
+            return Ok(vec![]);
+        }
+
+        {
+            // Acquire write lock.
+            let mut state = self.state.write();
+
+            // Unset onMainChain flag / mainChainSuccessor on the reverted blocks.
+            for reverted_block in revert_chain.iter_mut() {
+                reverted_block.1.on_main_chain = false;
+                reverted_block.1.main_chain_successor = None;
+                self.chain_store.put_chain_info(&mut write_txn, &reverted_block.0, &reverted_block.1, false);
+            }
+
+            target.1.main_chain_successor = None;
+            self.chain_store.put_chain_info(&mut write_txn, &target.0, &target.1, false);
+            self.chain_store.set_head(&mut write_txn, &target.0);
+
+            // Commit transaction & update head.
+            write_txn.commit();
+            state.transaction_cache = cache_txn;
+
+            state.main_chain = target.1;
+            state.head_hash = target.0;
+        }
+
+        // Give up write lock before notifying.
+        let mut reverted_blocks = Vec::with_capacity(revert_chain.len());
+        for (hash, chain_info) in revert_chain.into_iter().rev() {
+            reverted_blocks.push((hash, chain_info.head));
+        }
+        let event = BlockchainEvent::Rebranched(reverted_blocks.clone(), vec![]);
+        self.notifier.read().notify(event);
+
+        return Ok(reverted_blocks);
+    }
+
+    /// Checks whether a transaction can be admitted to the mempool: it must be intrinsically valid,
+    /// valid at the next block height and not yet included in the main chain.
+    /// Account balances are not checked here.
+    pub fn check_transaction(&self, tx: &primitives::transaction::Transaction) -> Result<(), TxAdmissionError> {
+        tx.verify(self.network_id).map_err(TxAdmissionError::Invalid)?;
+
+        let state = self.state.read();
+        if !tx.is_valid_at(state.main_chain.head.header.height + 1) {
+            return Err(TxAdmissionError::Expired);
+        }
+        if state.transaction_cache.contains(&tx.hash()) {
+            return Err(TxAdmissionError::AlreadyMined);
+        }
+        Ok(())
+    }
+
+    pub fn get_next_target(&self, head_hash: Option<&Blake2bHash>) -> Target {
+        let state = self.state.read();
+
+        // Targets are cached per block time, so that changing `block_time` doesn't return stale targets.
+        let cache_key = head_hash.unwrap_or(&state.head_hash).clone();
+        if let Some(target) = state.next_target_cache.lock().get(&cache_key, self.block_time) {
+            return target;
+        }
+
+        let chain_info;
+        let head_info = match head_hash {
+            Some(hash) => {
+                chain_info = self.chain_store
+                    .get_chain_info(hash, false, None)
+                    .expect("Failed to compute next target - unknown head_hash");
+                &chain_info
+            }
+            None => &state.main_chain
+        };
+
+        let target = self.compute_next_target_for(head_info, None);
+        state.next_target_cache.lock().insert(cache_key, self.block_time, target.clone());
+        target
+    }
+
+    /// Computes the target of the successor of `head_info` without going through the target cache.
+    fn compute_next_target_for(&self, head_info: &ChainInfo, txn_option: Option<&Transaction>) -> Target {
+        let tail_info = self.get_difficulty_window_tail(head_info, txn_option);
+
+        let head = &head_info.head.header;
+        let tail = &tail_info.head.header;
+        assert!(head.height - tail.height == policy::DIFFICULTY_BLOCK_WINDOW
+            || (head.height <= policy::DIFFICULTY_BLOCK_WINDOW && tail.height == 1),
+            "Failed to compute next target - invalid head/tail block");
+
+        let delta_total_difficulty = &head_info.total_difficulty - &tail_info.total_difficulty;
+        Self::compute_next_target_with_block_time(head, tail, delta_total_difficulty, self.block_time)
+    }
+
+    /// Returns the tail of the difficulty window ending at `head_info`, i.e. the block `DIFFICULTY_BLOCK_WINDOW`
+    /// blocks before it on its chain (or the genesis block).
+    fn get_difficulty_window_tail(&self, head_info: &ChainInfo, txn_option: Option<&Transaction>) -> ChainInfo {
         let tail_height = 1u32.max(head_info.head.header.height.saturating_sub(policy::DIFFICULTY_BLOCK_WINDOW));
         let tail_info;
         if head_info.on_main_chain {
             tail_info = self.chain_store
-                .get_chain_info_at(tail_height, false, None)
+                .get_chain_info_at(tail_height, false, txn_option)
                 .expect("Failed to compute next target - tail block not found");
         } else {
             let mut prev_info;
@@ -477,7 +1465,7 @@ impl<'env> Blockchain<'env> {
             while {
                 // Loop condition
                 prev_info = self.chain_store
-                    .get_chain_info(&prev_hash, false, None)
+                    .get_chain_info(&prev_hash, false, txn_option)
                     .expect("Failed to compute next target - fork predecessor not found");
                 prev_hash = prev_info.head.header.prev_hash.clone();
 
@@ -486,36 +1474,88 @@ impl<'env> Blockchain<'env> {
 
             if prev_info.on_main_chain && prev_info.head.header.height > tail_height {
                 tail_info = self.chain_store
-                    .get_chain_info_at(tail_height, false, None)
+                    .get_chain_info_at(tail_height, false, txn_option)
                     .expect("Failed to compute next target - tail block not found");
             } else {
                 tail_info = prev_info;
             }
         }
+        tail_info
+    }
 
-        let head = &head_info.head.header;
-        let tail = &tail_info.head.header;
-        assert!(head.height - tail.height == policy::DIFFICULTY_BLOCK_WINDOW
-            || (head.height <= policy::DIFFICULTY_BLOCK_WINDOW && tail.height == 1),
-            "Failed to compute next target - invalid head/tail block");
+    /// Checks that a full difficulty window from `tail` to `head` spans at least
+    /// `policy::DIFFICULTY_MIN_WINDOW_TIMESPAN` seconds. Windows that are not full yet are always accepted.
+    pub fn verify_window_timespan(head: &BlockHeader, tail: &BlockHeader) -> bool {
+        if head.height < tail.height + policy::DIFFICULTY_BLOCK_WINDOW {
+            return true;
+        }
+        head.timestamp.saturating_sub(tail.timestamp) >= policy::DIFFICULTY_MIN_WINDOW_TIMESPAN
+    }
 
-        let mut delta_total_difficulty = &head_info.total_difficulty - &tail_info.total_difficulty;
-        let mut actual_time = head.timestamp - tail.timestamp;
+    /// Checks that the timestamp of `header` is strictly greater than the median timestamp of the
+    /// `policy::MEDIAN_TIME_PAST_WINDOW` blocks before it. `prev_headers` are the preceding headers, newest first.
+    /// Near the genesis block, where fewer preceding blocks exist, the check is skipped.
+    pub fn verify_median_time_past(header: &BlockHeader, prev_headers: &[BlockHeader]) -> bool {
+        let window = policy::MEDIAN_TIME_PAST_WINDOW as usize;
+        if prev_headers.len() < window {
+            return true;
+        }
+
+        let mut timestamps: Vec<u32> = prev_headers[..window].iter().map(|prev_header| prev_header.timestamp).collect();
+        timestamps.sort();
+        header.timestamp > timestamps[window / 2]
+    }
+
+    /// Checks that `n_bits` is the required target for a block on top of `prev_hash`,
+    /// e.g. to re-check a mining template before accepting a share.
+    pub fn validate_template_difficulty(&self, prev_hash: &Blake2bHash, n_bits: TargetCompact) -> bool {
+        if !self.contains(prev_hash, true) {
+            return false;
+        }
+        return TargetCompact::from(self.get_next_target(Some(prev_hash))) == n_bits;
+    }
+
+    /// Checks that the timestamp of a mining template `header` is valid on top of its predecessor,
+    /// i.e. after the median time past and not flattening the difficulty window.
+    pub fn validate_template_timestamp(&self, header: &BlockHeader) -> bool {
+        let prev_info = match self.chain_store.get_chain_info(&header.prev_hash, false, None) {
+            Some(prev_info) => prev_info,
+            None => return false,
+        };
+        self.verify_timestamps(header, &prev_info, None).is_ok()
+    }
+
+    /// Computes the next target given the head and tail of the difficulty window and the
+    /// total difficulty accumulated in between.
+    pub fn compute_next_target(head: &BlockHeader, tail: &BlockHeader, delta_total_difficulty: Difficulty) -> Target {
+        Self::compute_next_target_with_block_time(head, tail, delta_total_difficulty, policy::BLOCK_TIME)
+    }
 
-        // Simulate that the Policy.BLOCK_TIME was achieved for the blocks before the genesis block, i.e. we simulate
+    /// Like `compute_next_target`, but adjusts towards `block_time` (in seconds) instead of `policy::BLOCK_TIME`.
+    pub fn compute_next_target_with_block_time(head: &BlockHeader, tail: &BlockHeader, mut delta_total_difficulty: Difficulty, block_time: u32) -> Target {
+        // Timestamps are only monotonic between immediate successors, so guard against a head
+        // that is older than the tail (e.g. on a malformed fork).
+        if head.timestamp < tail.timestamp {
+            warn!("Head timestamp {} is before tail timestamp {} while computing next target", head.timestamp, tail.timestamp);
+        }
+        let mut actual_time = head.timestamp.saturating_sub(tail.timestamp);
+
+        // Simulate that the block time was achieved for the blocks before the genesis block, i.e. we simulate
         // a sliding window that starts before the genesis block. Assume difficulty = 1 for these blocks.
         if head.height <= policy::DIFFICULTY_BLOCK_WINDOW {
-            actual_time += (policy::DIFFICULTY_BLOCK_WINDOW - head.height + 1) * policy::BLOCK_TIME;
+            actual_time = actual_time.saturating_add((policy::DIFFICULTY_BLOCK_WINDOW - head.height + 1) * block_time);
             delta_total_difficulty += BigDecimal::from(policy::DIFFICULTY_BLOCK_WINDOW - head.height + 1).into();
         }
+        let actual_time = actual_time.max(1);
 
         // Compute the target adjustment factor.
-        let expected_time = policy::DIFFICULTY_BLOCK_WINDOW * policy::BLOCK_TIME;
+        let expected_time = policy::DIFFICULTY_BLOCK_WINDOW * block_time;
         let mut adjustment = actual_time as f64 / expected_time as f64;
 
         // Clamp the adjustment factor to [1 / MAX_ADJUSTMENT_FACTOR, MAX_ADJUSTMENT_FACTOR].
         adjustment = adjustment.max(1f64 / policy::DIFFICULTY_MAX_ADJUSTMENT_FACTOR);
         adjustment = adjustment.min(policy::DIFFICULTY_MAX_ADJUSTMENT_FACTOR);
+        debug_assert!(adjustment > 0f64 && adjustment.is_finite(), "Invalid target adjustment factor");
 
         // Compute the next target.
         let average_difficulty = BigDecimal::from(delta_total_difficulty) / BigDecimal::from(policy::DIFFICULTY_BLOCK_WINDOW);
@@ -537,12 +1577,165 @@ impl<'env> Blockchain<'env> {
         return Target::from(n_bits);
     }
 
-    pub fn get_block_locators(&self, max_count: usize) -> Vec<Blake2bHash> {
+    /// Returns the difficulty corresponding to `get_next_target`. Note that the target has already
+    /// been reduced to `n_bits` precision, so this is the difficulty a valid next block must meet
+    /// and not the exact result of the difficulty adjustment.
+    pub fn get_next_difficulty(&self, head_hash: Option<&Blake2bHash>) -> Difficulty {
+        Difficulty::from(self.get_next_target(head_hash))
+    }
+
+    /// Estimates how long it will take the network to produce `n` blocks at the next difficulty,
+    /// based on the hash rate observed over the last difficulty window.
+    pub fn expected_time_to_blocks(&self, n: u32) -> Duration {
+        let next_difficulty = BigDecimal::from(self.get_next_difficulty(None));
+
+        let state = self.state.read();
+        let head_info = &state.main_chain;
+        let head = &head_info.head.header;
+
+        let tail_info = self.get_difficulty_window_tail(head_info, None);
+        let tail = &tail_info.head.header;
+
+        let mut delta_total_difficulty = &head_info.total_difficulty - &tail_info.total_difficulty;
+        let mut actual_time = head.timestamp.saturating_sub(tail.timestamp);
+
+        // Assume the blocks before the genesis block were produced at difficulty 1 in the block time,
+        // just like get_next_target does.
+        if head.height <= policy::DIFFICULTY_BLOCK_WINDOW {
+            let missing_blocks = policy::DIFFICULTY_BLOCK_WINDOW - head.height + 1;
+            actual_time = actual_time.saturating_add(missing_blocks.saturating_mul(self.block_time));
+            delta_total_difficulty += BigDecimal::from(missing_blocks).into();
+        }
+
+        let expected_secs = next_difficulty * BigDecimal::from(n) * BigDecimal::from(actual_time) / BigDecimal::from(delta_total_difficulty);
+        let expected_millis = expected_secs.to_f64()
+            .expect("Failed to compute expected time - invalid decimal") * 1000f64;
+        Duration::from_millis(expected_millis.round() as u64)
+    }
+
+    /// Returns (height, timestamp, difficulty) of the last `n` main chain blocks, newest first.
+    /// `n` is clamped to the height of the chain.
+    pub fn difficulty_history(&self, n: u32) -> Vec<(u32, u32, Difficulty)> {
+        // Open the read transaction under the state lock, so that it matches the head.
+        let (head, txn) = {
+            let state = self.state.read();
+            (state.main_chain.head.header.clone(), ReadTransaction::new(self.env))
+        };
+
+        let n = cmp::min(n, head.height);
+        let mut history = Vec::with_capacity(n as usize);
+        let mut header = head;
+        for i in 0..n {
+            history.push((header.height, header.timestamp, Difficulty::from(header.n_bits)));
+            if i + 1 == n {
+                break;
+            }
+            header = self.chain_store
+                .get_chain_info(&header.prev_hash, false, Some(&txn))
+                .expect("Corrupted store: main chain predecessor not found")
+                .head.header;
+        }
+        history
+    }
+
+    /// Returns the main chain blocks feeding the next retargeting, oldest first, each with the difficulty of its `n_bits`.
+    /// These are the blocks after the tail of the difficulty window up to the head, the tail only contributes its timestamp.
+    /// Near the genesis block, the window has less than `policy::DIFFICULTY_BLOCK_WINDOW` blocks.
+    pub fn difficulty_window(&self) -> Vec<(BlockHeader, Difficulty)> {
+        // Open the read transaction under the state lock, so that it matches the head.
+        let (head, tail_height, txn) = {
+            let state = self.state.read();
+            let txn = ReadTransaction::new(self.env);
+            let tail_info = self.get_difficulty_window_tail(&state.main_chain, Some(&txn));
+            (state.main_chain.head.header.clone(), tail_info.head.header.height, txn)
+        };
+
+        let mut window = Vec::with_capacity((head.height - tail_height) as usize);
+        let mut header = head;
+        while header.height > tail_height {
+            let prev_header = self.chain_store
+                .get_chain_info(&header.prev_hash, false, Some(&txn))
+                .expect("Corrupted store: main chain predecessor not found")
+                .head.header;
+            let difficulty = Difficulty::from(header.n_bits);
+            window.push((header, difficulty));
+            header = prev_header;
+        }
+        window.reverse();
+        window
+    }
+
+    /// Annualized inflation rate at the current height, assuming the current block reward
+    /// is paid every `block_time` seconds for a year.
+    pub fn inflation_rate(&self) -> f64 {
+        let height = self.height();
+        let blocks_per_year = 365.25 * 24.0 * 60.0 * 60.0 / f64::from(self.block_time);
+        let reward = u64::from(policy::block_reward_at(height)) as f64;
+        let supply = u64::from(policy::total_supply_at(height)) as f64;
+        reward * blocks_per_year / supply
+    }
+
+    /// Total supply after the block at `height` has been mined: the initial supply plus the rewards of all blocks up to `height`.
+    /// Rewards are computed exactly like they are minted, so this matches the coins in the accounts tree.
+    pub fn supply_at_height(&self, height: u32) -> Coin {
+        policy::total_supply_at(height)
+    }
+
+    /// Total supply at the current head.
+    pub fn current_supply(&self) -> Coin {
+        self.supply_at_height(self.height())
+    }
+
+    /// Number of blocks pushed so far, by outcome.
+    pub fn push_stats(&self) -> PushStats {
+        self.push_counters.snapshot()
+    }
+
+    /// Expected number of superblocks of at least the given depth on the main chain,
+    /// to compare against the actual `super_block_counts`.
+    pub fn expected_superblocks(&self, depth: u8) -> f64 {
+        f64::from(self.height()) / 2f64.powi(i32::from(depth))
+    }
+
+    /// Number of blocks we are behind a peer with the given head height (e.g. from its handshake).
+    pub fn blocks_behind(&self, peer_head_height: u32) -> u32 {
+        peer_head_height.saturating_sub(self.height())
+    }
+
+    /// Estimated time to catch up with a peer with the given head height when processing
+    /// `blocks_per_sec` blocks per second.
+    pub fn estimated_sync_time(&self, peer_head_height: u32, blocks_per_sec: f64) -> Duration {
+        let blocks_behind = self.blocks_behind(peer_head_height);
+        if blocks_behind == 0 || blocks_per_sec <= 0f64 {
+            return Duration::from_secs(0);
+        }
+        let millis = f64::from(blocks_behind) / blocks_per_sec * 1000f64;
+        Duration::from_millis(millis.round() as u64)
+    }
+
+    /// Returns at most `max_count` block locators starting at `start_hash`, or at the head if none is given.
+    /// The genesis hash is always the last locator.
+    pub fn get_block_locators(&self, max_count: usize, start_hash: Option<&Blake2bHash>) -> Vec<Blake2bHash> {
+        let network_info = get_network_info(self.network_id).unwrap();
+
+        let (mut hash, start_height) = match start_hash {
+            Some(start_hash) => match self.chain_store.get_block(start_hash, false, None) {
+                Some(block) => (start_hash.clone(), block.header.height),
+                None => return vec![network_info.genesis_hash.clone()],
+            },
+            None => (self.head_hash(), self.height()),
+        };
+
         // Push top 10 hashes first, then back off exponentially.
-        let mut hash = self.head_hash();
         let mut locators = vec![hash.clone()];
 
-        for _ in 0..cmp::min(10, self.height()) {
+        // The genesis block has no predecessor.
+        for _ in 0..cmp::min(10, start_height - 1) {
+            // Respect max count.
+            if locators.len() >= max_count {
+                break;
+            }
+
             let block = self.chain_store.get_block(&hash, false, None);
             match block {
                 Some(block) => {
@@ -554,8 +1747,12 @@ impl<'env> Blockchain<'env> {
         }
 
         let mut step = 2;
-        let mut height = self.height().saturating_sub(10 + step);
-        let mut opt_block = self.chain_store.get_block_at(height);
+        let mut height = start_height.saturating_sub(10 + step);
+        let mut opt_block = if locators.len() < max_count {
+            self.chain_store.get_block_at(height)
+        } else {
+            None
+        };
         while let Some(block) = opt_block {
             locators.push(block.header.hash());
 
@@ -575,7 +1772,6 @@ impl<'env> Blockchain<'env> {
         }
 
         // Push the genesis block hash.
-        let network_info = get_network_info(self.network_id).unwrap();
         if locators.is_empty() || locators.last().unwrap() != &network_info.genesis_hash {
             // Respect max count, make space for genesis hash if necessary
             if locators.len() >= max_count {
@@ -587,6 +1783,54 @@ impl<'env> Blockchain<'env> {
         locators
     }
 
+    /// Returns block locators for the main chain, dense near the head and exponentially sparser
+    /// towards genesis. See `block_locator_heights` for the heights used.
+    pub fn get_block_locators_adaptive(&self) -> Vec<Blake2bHash> {
+        // Open the read transaction under the state lock, so that it matches the head.
+        let (head_height, txn) = {
+            let state = self.state.read();
+            (state.main_chain.head.header.height, ReadTransaction::new(self.env))
+        };
+
+        Self::block_locator_heights(head_height).iter()
+            .map(|&height| self.chain_store
+                .get_chain_info_at(height, false, Some(&txn))
+                .expect("Corrupted store: main chain block not found")
+                .head.header.hash())
+            .collect()
+    }
+
+    /// The heights of the block locators for a main chain with the given head height `h`:
+    /// the 10 most recent blocks `h, h-1, ..., h-9`, then `h-9-2^i` for `i = 1, 2, ...`
+    /// as long as this is above 1, and finally the genesis block at height 1.
+    /// The result never has more than `ADAPTIVE_LOCATORS_MAX` entries.
+    pub fn block_locator_heights(head_height: u32) -> Vec<u32> {
+        let mut heights = Vec::new();
+        let mut height = head_height;
+        while height > 1 && heights.len() < 10 {
+            heights.push(height);
+            height -= 1;
+        }
+
+        if height > 1 {
+            let base = head_height - 9;
+            let mut step = 2u32;
+            while heights.len() < Self::ADAPTIVE_LOCATORS_MAX - 1 {
+                match base.checked_sub(step) {
+                    Some(height) if height > 1 => heights.push(height),
+                    _ => break,
+                }
+                step = match step.checked_mul(2) {
+                    Some(step) => step,
+                    None => break,
+                };
+            }
+        }
+
+        heights.push(1);
+        heights
+    }
+
     pub fn contains(&self, hash: &Blake2bHash, include_forks: bool) -> bool {
         match self.chain_store.get_chain_info(hash, false, None) {
             Some(chain_info) => include_forks || chain_info.on_main_chain,
@@ -594,25 +1838,218 @@ impl<'env> Blockchain<'env> {
         }
     }
 
+    /// Returns the hash and height of the main chain block that includes the transaction `tx_hash`, and the
+    /// index of the transaction in its body. Only the last `policy::TRANSACTION_VALIDITY_WINDOW` blocks are
+    /// covered, since the block is looked up in the transaction cache. Older transactions return `None`.
+    pub fn get_transaction_info_by_hash(&self, tx_hash: &Blake2bHash) -> Option<(Blake2bHash, u32, u16)> {
+        // Hold the state lock, so that the block can't be reverted while we read it.
+        let state = self.state.read();
+        let block_hash = state.transaction_cache.find_block(tx_hash)?;
+        let block = self.chain_store.get_block(&block_hash, true, None)?;
+        let index = block.body.as_ref()?.transactions.iter()
+            .position(|transaction| transaction.hash::<Blake2bHash>() == *tx_hash)?;
+        Some((block_hash, block.header.height, index as u16))
+    }
+
+    /// Returns the first of the peer's `locators` that is on our main chain, together with its height.
+    /// Locators are ordered from the peer's head backwards, so this is the highest block we have in common.
+    pub fn find_common_block(&self, locators: &[Blake2bHash]) -> Option<(Blake2bHash, u32)> {
+        let txn = ReadTransaction::new(self.env);
+        for locator in locators {
+            if let Some(chain_info) = self.chain_store.get_chain_info(locator, false, Some(&txn)) {
+                if chain_info.on_main_chain {
+                    return Some((locator.clone(), chain_info.head.header.height));
+                }
+            }
+        }
+        None
+    }
+
+    /// The genesis block is always returned without a body, see `get_block`.
     pub fn get_block_at(&self, height: u32, include_body: bool) -> Option<Block> {
-        self.chain_store.get_chain_info_at(height, include_body, None).map(|chain_info| chain_info.head)
+        self.chain_store.get_chain_info_at(height, include_body, None)
+            .map(|chain_info| Self::without_genesis_body(chain_info.head))
     }
 
+    /// Returns the accounts hash committed to by the main chain block at `height`, or `None` if there is no such block.
+    pub fn accounts_hash_at(&self, height: u32) -> Option<Blake2bHash> {
+        self.chain_store.get_chain_info_at(height, false, None)
+            .map(|chain_info| chain_info.head.header.accounts_hash)
+    }
+
+    /// The genesis block has no transaction body: its state is defined by the genesis account
+    /// allocation only. It is therefore always returned with `body = None`, regardless of `include_body`.
     pub fn get_block(&self, hash: &Blake2bHash, include_forks: bool, include_body: bool) -> Option<Block> {
         let chain_info_opt = self.chain_store.get_chain_info(hash, include_body, None);
         if chain_info_opt.is_some() {
             let chain_info = chain_info_opt.unwrap();
             if chain_info.on_main_chain || include_forks {
-                return Some(chain_info.head);
+                return Some(Self::without_genesis_body(chain_info.head));
             }
         }
         None
     }
 
+    fn without_genesis_body(mut block: Block) -> Block {
+        if block.header.height == 1 {
+            block.body = None;
+        }
+        block
+    }
+
     pub fn get_blocks(&self, start_block_hash: &Blake2bHash, count: u32, include_body: bool, direction: Direction) -> Vec<Block> {
         self.chain_store.get_blocks(start_block_hash, count, include_body, direction, None)
     }
 
+    /// Removes the bodies of all main chain blocks except for the most recent `retain` ones.
+    /// Superblocks are kept since NiPoPoW proofs are built from them. At least `min_retained_block_bodies`
+    /// bodies are kept, since reverting blocks and checking for duplicate transactions needs them.
+    /// Returns the number of pruned block bodies.
+    pub fn prune_block_bodies(&self, retain: u32) -> u32 {
+        // Prevent the main chain from changing underneath us.
+        let _lock = self.push_lock.lock();
+
+        let prune_height = self.height().saturating_sub(u32::max(retain, self.min_retained_block_bodies()));
+        if prune_height < 2 {
+            return 0;
+        }
+
+        let mut txn = WriteTransaction::new(self.env);
+        let mut successor = self.chain_store
+            .get_chain_info_at(prune_height + 1, false, Some(&txn))
+            .expect("Corrupted store: Failed to find main chain block while pruning");
+
+        let mut num_pruned = 0;
+        while successor.head.header.height > 2 {
+            let hash = successor.head.header.prev_hash.clone();
+
+            // A block is a superblock iff it is referenced by its successor's interlink.
+            let is_super_block = successor.head.interlink.hashes.contains(&hash);
+            if !is_super_block {
+                if !self.chain_store.remove_block_body(&mut txn, &hash) {
+                    // Already pruned by a previous run, everything below is pruned as well.
+                    break;
+                }
+                num_pruned += 1;
+            }
+
+            successor = self.chain_store
+                .get_chain_info(&hash, false, Some(&txn))
+                .expect("Corrupted store: Failed to find main chain predecessor while pruning");
+        }
+
+        txn.commit();
+        num_pruned
+    }
+
+    /// Number of most recent main chain blocks whose bodies `prune_block_bodies` always keeps:
+    /// the transaction validity window and the blocks a rebranch may revert.
+    pub fn min_retained_block_bodies(&self) -> u32 {
+        cmp::max(policy::TRANSACTION_VALIDITY_WINDOW, self.config.max_rebranch_depth())
+    }
+
+    /// Finds the main chain block that pruned the account at `address` and returns its hash and height.
+    /// Only the last `policy::TRANSACTION_VALIDITY_WINDOW` blocks are searched, and blocks whose bodies
+    /// were removed by `prune_block_bodies` end the search early.
+    pub fn pruning_block_of(&self, address: &Address) -> Option<(Blake2bHash, u32)> {
+        let head_hash = self.head_hash();
+        self.chain_store.find_pruning_block(&head_hash, address, policy::TRANSACTION_VALIDITY_WINDOW, None)
+    }
+
+    /// Returns the transactions sent or received by `address` in the last `max_blocks` main chain blocks,
+    /// newest first, together with the height of the block that included them. The search stops at the first
+    /// block whose body was removed by `prune_block_bodies`. Also returns the height of the oldest searched block.
+    pub fn transactions_for_address(&self, address: &Address, max_blocks: u32) -> (Vec<(primitives::transaction::Transaction, u32)>, u32) {
+        // Open the read transaction under the state lock, so that it matches the head.
+        let (head_hash, head_height, txn) = {
+            let state = self.state.read();
+            (state.head_hash.clone(), state.main_chain.head.header.height, ReadTransaction::new(self.env))
+        };
+
+        let head = self.chain_store.get_block(&head_hash, true, Some(&txn));
+        let blocks = head.into_iter()
+            .chain(self.chain_store.block_cursor(&head_hash, Direction::Backward, true, &txn))
+            .take(max_blocks as usize);
+
+        let mut transactions = Vec::new();
+        let mut searched_height = head_height + 1;
+        for block in blocks {
+            let body = match block.body {
+                Some(body) => body,
+                None => break,
+            };
+
+            searched_height = block.header.height;
+            for transaction in body.transactions {
+                if &transaction.sender == address || &transaction.recipient == address {
+                    transactions.push((transaction, searched_height));
+                }
+            }
+        }
+        (transactions, searched_height)
+    }
+
+    pub fn config(&self) -> &BlockchainConfig {
+        &self.config
+    }
+
+    /// The offset of the network time to the local system time in milliseconds.
+    /// A skewed clock is warned about at most once per minute, since this is checked on every push.
+    pub fn network_time_offset(&self) -> i64 {
+        let offset = self.network_time.offset();
+        if offset.abs() as u64 > Self::MAX_NETWORK_TIME_OFFSET && self.clock_skew_warnings.lock().note_single() {
+            warn!("Network time is off by {}ms from local system time, check your system clock", offset);
+        }
+        offset
+    }
+
+    /// Returns the miners that produced more than one of the blocks stored at `height`, together with
+    /// the hashes of their blocks. Blocks whose body has been pruned are not considered.
+    pub fn detect_equivocation(&self, height: u32) -> Vec<(Address, Vec<Blake2bHash>)> {
+        let mut blocks_by_miner: HashMap<Address, Vec<Blake2bHash>> = HashMap::new();
+        for (hash, block) in self.chain_store.get_blocks_at(height, true, None) {
+            if let Some(body) = block.body {
+                blocks_by_miner.entry(body.miner).or_insert_with(Vec::new).push(hash);
+            }
+        }
+
+        let mut equivocations: Vec<(Address, Vec<Blake2bHash>)> = blocks_by_miner.into_iter()
+            .filter(|(_, hashes)| hashes.len() > 1)
+            .collect();
+        equivocations.sort_by(|a, b| a.0.cmp(&b.0));
+        equivocations
+    }
+
+    /// Rejects all future blocks mined by `miner`, e.g. for a compromised key in a private network.
+    /// Returns false if the miner was already denied.
+    pub fn add_denied_miner(&self, miner: Address) -> bool {
+        self.denied_miners.write().insert(miner)
+    }
+
+    /// Returns false if the miner was not denied.
+    pub fn remove_denied_miner(&self, miner: &Address) -> bool {
+        self.denied_miners.write().remove(miner)
+    }
+
+    /// Returns the tips of forks whose height is at least `head_height - within`
+    /// as (hash, height, total difficulty), ordered by height.
+    pub fn recent_fork_tips(&self, within: u32) -> Vec<(Blake2bHash, u32, Difficulty)> {
+        let min_height = self.height().saturating_sub(within);
+        self.chain_store.get_fork_tips(min_height, None).into_iter()
+            .map(|(hash, chain_info)| (hash, chain_info.head.header.height, chain_info.total_difficulty))
+            .collect()
+    }
+
+    /// Registers a listener that only receives the `Extended` and `Rebranched` events touching heights `[from, to]`,
+    /// see `BlockchainEvent::touches_heights`. Deregister it from `notifier` with the returned handle.
+    pub fn subscribe_range<T: Listener<BlockchainEvent> + 'env>(&self, from: u32, to: Option<u32>, listener: T) -> ListenerHandle {
+        self.notifier.write().register(move |event: &BlockchainEvent| {
+            if event.touches_heights(from, to) {
+                listener.on_event(event);
+            }
+        })
+    }
+
     pub fn head_hash(&self) -> Blake2bHash {
         self.state.read().head_hash.clone()
     }
@@ -626,6 +2063,20 @@ impl<'env> Blockchain<'env> {
         RwLockReadGuard::map(guard, |s| &s.main_chain.head)
     }
 
+    /// The cumulative difficulty of the main chain. The default `HeaviestChain` fork choice only replaces
+    /// the main chain with a fork whose `chain_work` is strictly greater, other `ForkChoice` rules may differ.
+    /// `Difficulty` serializes to the same bytes for equal values on all nodes.
+    pub fn chain_work(&self) -> Difficulty {
+        self.state.read().main_chain.total_difficulty.clone()
+    }
+
+    /// Signs the current head with `key_pair`, see `HeadCertificate`. The certificate is issued at the current network time.
+    pub fn head_certificate(&self, key_pair: &KeyPair) -> HeadCertificate {
+        let state = self.state.read();
+        let head = &state.main_chain.head.header;
+        HeadCertificate::new(self.network_id, state.head_hash.clone(), head.height, state.main_chain.total_difficulty.clone(), self.network_time.now(), key_pair)
+    }
+
     pub fn total_work(&self) -> MappedRwLockReadGuard<Difficulty> {
         let guard = self.state.read();
         RwLockReadGuard::map(guard, |s| &s.main_chain.total_work)
@@ -636,38 +2087,135 @@ impl<'env> Blockchain<'env> {
         RwLockReadGuard::map(guard, |s| &s.accounts)
     }
 
+    /// Returns a proof for the accounts of the given addresses at the current head.
+    /// Addresses without an account are proven to be absent.
+    pub fn get_accounts_proof(&self, addresses: &[Address]) -> AccountsProof {
+        let state = self.state.read();
+        let txn = ReadTransaction::new(self.env);
+        state.accounts.get_accounts_proof(&txn, &addresses.to_vec())
+    }
+
     pub fn transaction_cache(&self) -> MappedRwLockReadGuard<TransactionCache> {
         let guard = self.state.read();
         RwLockReadGuard::map(guard, |s| &s.transaction_cache)
     }
 
+    /// Persists the transaction cache, so that `load` does not need to rebuild it from the stored blocks.
+    /// Call this when shutting down, a cache that wasn't persisted is rebuilt on the next start.
+    pub fn persist_transaction_cache(&self) {
+        // Hold the push lock so that the cache matches the stored head.
+        let _lock = self.push_lock.lock();
+        let state = self.state.read();
+        let mut txn = WriteTransaction::new(self.env);
+        self.chain_store.put_transaction_cache(&mut txn, &state.transaction_cache);
+        txn.commit();
+    }
 
     /* NiPoPoW prover */
 
-    pub fn get_chain_proof(&self) -> ChainProof {
-        let mut state = self.state.write();
-        if state.chain_proof.is_none() {
-            let start = Instant::now();
-            let chain_proof = self.prove(&state.main_chain.head, Self::NIPOPOW_M, Self::NIPOPOW_K, Self::NIPOPOW_DELTA);
-            trace!("Chain proof took {}ms to compute (prefix={}, suffix={})", utils::time::duration_as_millis(&(Instant::now() - start)), chain_proof.prefix.len(), chain_proof.suffix.len());
-            state.chain_proof = Some(chain_proof);
+    /// Returns `None` if serving chain proofs is disabled.
+    pub fn get_chain_proof(&self) -> Option<ChainProof> {
+        self.get_chain_proof_cancellable(&CancellationToken::new())
+    }
+
+    /// Like `get_chain_proof`, but aborts and returns `None` once `cancel` is triggered.
+    /// The state lock is not held while the proof is computed.
+    pub fn get_chain_proof_cancellable(&self, cancel: &CancellationToken) -> Option<ChainProof> {
+        if !self.config.serve_chain_proofs() {
+            return None;
+        }
+
+        if let Some(chain_proof) = self.cached_chain_proof() {
+            return Some(chain_proof);
         }
-        // XXX Get rid of the clone here? ChainProof is typically >1mb.
-        state.chain_proof.as_ref().unwrap().clone()
+
+        let (head_hash, chain_proof) = self.compute_chain_proof(cancel)?;
+        self.store_chain_proof(&head_hash, chain_proof.clone());
+        Some(chain_proof)
     }
 
-    fn prove(&self, head: &Block, m: u32, k: u32, delta: f64) -> ChainProof {
+    /// Returns the cached chain proof if it was computed for the current head, without computing one.
+    /// Returns `None` if serving chain proofs is disabled.
+    pub fn cached_chain_proof(&self) -> Option<ChainProof> {
+        if !self.config.serve_chain_proofs() {
+            return None;
+        }
+
+        let head_hash = self.head_hash();
+        match *self.chain_proof.read() {
+            // XXX Get rid of the clone here? ChainProof is typically >1mb.
+            Some((ref proof_head_hash, ref chain_proof)) if proof_head_hash == &head_hash => Some(chain_proof.clone()),
+            _ => None,
+        }
+    }
+
+    /// Computes a chain proof for the current head without caching it.
+    /// Returns the head hash the proof was computed for.
+    pub fn compute_chain_proof(&self, cancel: &CancellationToken) -> Option<(Blake2bHash, ChainProof)> {
+        // Open the read transaction under the state lock, so that it matches the head.
+        let (head_hash, head, txn) = {
+            let state = self.state.read();
+            (state.head_hash.clone(), state.main_chain.head.clone(), ReadTransaction::new(self.env))
+        };
+
+        let start = Instant::now();
+        let chain_proof = self.prove(&head, Self::NIPOPOW_M, Self::NIPOPOW_K, Self::NIPOPOW_DELTA, &txn, cancel)?;
+        trace!("Chain proof took {}ms to compute (prefix={}, suffix={})", utils::time::duration_as_millis(&(Instant::now() - start)), chain_proof.prefix.len(), chain_proof.suffix.len());
+        Some((head_hash, chain_proof))
+    }
+
+    /// Caches and persists a chain proof computed for `head_hash`, so that `load` can restore it.
+    /// The proof is discarded if the head has changed since or serving chain proofs is disabled.
+    /// While a block is being pushed, the proof is only cached, not persisted.
+    pub fn store_chain_proof(&self, head_hash: &Blake2bHash, chain_proof: ChainProof) -> bool {
+        if !self.config.serve_chain_proofs() {
+            return false;
+        }
+
+        // Don't wait for the push lock: it is held while listeners are notified, which may request a proof.
+        let lock = self.push_lock.try_lock();
+        let state = self.state.read();
+        if &state.head_hash != head_hash {
+            debug!("Discarding chain proof for {}, head changed to {}", head_hash, state.head_hash);
+            return false;
+        }
+
+        // The head can't change while we hold the push lock, so the persisted proof matches the stored head.
+        if lock.is_some() {
+            let mut txn = WriteTransaction::new(self.env);
+            self.chain_store.put_chain_proof(&mut txn, head_hash, &chain_proof);
+            txn.commit();
+        } else {
+            trace!("Not persisting chain proof for {}, a block is being pushed", head_hash);
+        }
+
+        *self.chain_proof.write() = Some((head_hash.clone(), chain_proof));
+        true
+    }
+
+    fn prove(&self, head: &Block, m: u32, k: u32, delta: f64, txn: &Transaction, cancel: &CancellationToken) -> Option<ChainProof> {
+        // The genesis block (height 1) proves a chain that has not been extended yet.
+        if head.header.height == 1 {
+            let mut genesis_block = head.clone();
+            genesis_block.body = None;
+            return Some(ChainProof { prefix: vec![genesis_block], suffix: vec![] });
+        }
+
         let mut prefix = vec![];
         let mut start_height = 1u32;
 
-        let txn = ReadTransaction::new(self.env);
         let head_info = self.chain_store
-            .get_chain_info_at(u32::max(head.header.height.saturating_sub(k), 1), false, Some(&txn))
+            .get_chain_info_at(u32::max(head.header.height.saturating_sub(k), 1), false, Some(txn))
             .expect("Failed to compute chain proof - prefix head block not found");
         let max_depth = head_info.super_block_counts.get_candidate_depth(m);
 
         for depth in (0..=max_depth).rev() {
-            let super_chain = self.get_super_chain(depth, &head_info, start_height, Some(&txn));
+            if cancel.is_cancelled() {
+                debug!("Chain proof computation cancelled");
+                return None;
+            }
+
+            let super_chain = self.get_super_chain(depth, &head_info, start_height, Some(txn));
             if super_chain.is_good(depth, m, delta) {
                 assert!(super_chain.0.len() >= m as usize, "Good superchain too short");
                 trace!("Found good superchain at depth {} with length {} (#{} - #{})", depth, super_chain.0.len(), start_height, head_info.head.header.height);
@@ -681,9 +2229,9 @@ impl<'env> Blockchain<'env> {
             prefix = merged.collect();
         }
 
-        let suffix = self.get_header_chain(head.header.height - head_info.head.header.height, &head, Some(&txn));
+        let suffix = self.get_header_chain(head.header.height - head_info.head.header.height, &head, Some(txn));
 
-        ChainProof { prefix, suffix }
+        Some(ChainProof { prefix, suffix })
     }
 
     fn get_super_chain(&self, depth: u8, head_info: &ChainInfo, tail_height: u32, txn_option: Option<&Transaction>) -> SuperChain {
@@ -751,20 +2299,40 @@ impl<'env> Blockchain<'env> {
     }
 }
 
-struct SuperChain(Vec<ChainInfo>);
+impl Blockchain<'static> {
+    /// Starts a thread that pushes queued blocks as they arrive, see `enqueue_block`.
+    /// The thread exits once the blockchain is dropped.
+    pub fn spawn_queue_drain(this: &Arc<Self>) -> thread::JoinHandle<()> {
+        let weak = Arc::downgrade(this);
+        thread::spawn(move || {
+            while let Some(blockchain) = weak.upgrade() {
+                blockchain.wait_and_process_queue(Self::QUEUE_DRAIN_INTERVAL);
+            }
+        })
+    }
+}
+
+pub(crate) struct SuperChain(Vec<ChainInfo>);
 impl SuperChain {
     pub fn is_good(&self, depth: u8, m: u32, delta: f64) -> bool {
         self.has_super_quality(depth, m, delta) && self.has_multi_level_quality(depth, m, delta)
     }
 
     fn has_super_quality(&self, depth: u8, m: u32, delta: f64) -> bool {
-        let length = self.0.len();
+        let heights: Vec<u32> = self.0.iter().map(|chain_info| chain_info.head.header.height).collect();
+        SuperChain::has_super_quality_at(&heights, depth, m, delta)
+    }
+
+    /// The superquality condition for a superchain given by the heights of its blocks, in ascending order.
+    /// Shared with the chain proof verifier, which only has the blocks of a proof.
+    pub(crate) fn has_super_quality_at(heights: &[u32], depth: u8, m: u32, delta: f64) -> bool {
+        let length = heights.len();
         if length < m as usize {
             return false;
         }
 
         for i in m as usize..=length {
-            let underlying_length = self.0[length - 1].head.header.height - self.0[length - i].head.header.height + 1;
+            let underlying_length = heights[length - 1] - heights[length - i] + 1;
             if !SuperChain::is_locally_good(i as u32, underlying_length, depth, delta) {
                 return false;
             }
@@ -799,7 +2367,7 @@ impl SuperChain {
         return true;
     }
 
-    fn is_locally_good(super_length: u32, underlying_length: u32, depth: u8, delta: f64) -> bool {
+    pub(crate) fn is_locally_good(super_length: u32, underlying_length: u32, depth: u8, delta: f64) -> bool {
         super_length as f64 > (1f64 - delta) * 2f64.powi(-(depth as i32)) * underlying_length as f64
     }
 }
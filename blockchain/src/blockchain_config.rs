@@ -0,0 +1,51 @@
+use primitives::policy;
+
+/// Settings of a `Blockchain` that are fixed once it is constructed, see `Blockchain::with_config`.
+#[derive(Debug, Clone)]
+pub struct BlockchainConfig {
+    max_rebranch_depth: u32,
+    serve_chain_proofs: bool,
+    timestamp_rules_height: Option<u32>,
+}
+
+impl BlockchainConfig {
+    /// Default for `max_rebranch_depth`.
+    pub const DEFAULT_MAX_REBRANCH_DEPTH: u32 = policy::DIFFICULTY_BLOCK_WINDOW;
+
+    /// Maximum number of blocks reverted or applied in a single rebranch. Deeper forks are stored but not adopted.
+    pub fn max_rebranch_depth(&self) -> u32 {
+        self.max_rebranch_depth
+    }
+
+    pub fn set_max_rebranch_depth(&mut self, max_rebranch_depth: u32) {
+        self.max_rebranch_depth = max_rebranch_depth;
+    }
+
+    /// Whether chain proofs are computed, cached and served. Nodes that only relay blocks can disable this.
+    pub fn serve_chain_proofs(&self) -> bool {
+        self.serve_chain_proofs
+    }
+
+    pub fn set_serve_chain_proofs(&mut self, serve_chain_proofs: bool) {
+        self.serve_chain_proofs = serve_chain_proofs;
+    }
+
+    /// Overrides `NetworkInfo::timestamp_rules_height`, e.g. to enforce the timestamp rules on a private network.
+    pub fn timestamp_rules_height(&self) -> Option<u32> {
+        self.timestamp_rules_height
+    }
+
+    pub fn set_timestamp_rules_height(&mut self, timestamp_rules_height: Option<u32>) {
+        self.timestamp_rules_height = timestamp_rules_height;
+    }
+}
+
+impl Default for BlockchainConfig {
+    fn default() -> Self {
+        BlockchainConfig {
+            max_rebranch_depth: Self::DEFAULT_MAX_REBRANCH_DEPTH,
+            serve_chain_proofs: true,
+            timestamp_rules_height: None,
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use parking_lot::{MappedRwLockReadGuard, RwLock, RwLockReadGuard};
+
+use accounts::Accounts;
+use database::{Environment, WriteTransaction};
+use hash::Blake2bHash;
+use network_primitives::networks::get_network_info;
+use primitives::block::Block;
+use primitives::networks::NetworkId;
+
+use crate::blockchain::BlockchainEvent;
+use crate::chain_info::ChainInfo;
+use crate::chain_store::ChainStore;
+
+/// A read-only copy of a primary `Blockchain` that is kept up to date by applying its
+/// `BlockchainEvent`s. Blocks are trusted and not verified again.
+pub struct BlockchainMirror<'env> {
+    env: &'env Environment,
+    pub network_id: NetworkId,
+    chain_store: ChainStore<'env>,
+    state: RwLock<MirrorState<'env>>,
+}
+
+struct MirrorState<'env> {
+    accounts: Accounts<'env>,
+    main_chain: ChainInfo,
+    head_hash: Blake2bHash,
+}
+
+impl<'env> BlockchainMirror<'env> {
+    pub fn new(env: &'env Environment, network_id: NetworkId) -> Self {
+        let chain_store = ChainStore::new(env);
+        let accounts = Accounts::new(env);
+
+        let (main_chain, head_hash) = match chain_store.get_head(None) {
+            Some(head_hash) => {
+                let main_chain = chain_store
+                    .get_chain_info(&head_hash, true, None)
+                    .expect("Failed to load mirrored main chain. Reset your mirror database.");
+                assert_eq!(main_chain.head.header.accounts_hash, accounts.hash(None),
+                    "Inconsistent mirrored chain/accounts state. Reset your mirror database.");
+                (main_chain, head_hash)
+            },
+            None => {
+                let network_info = get_network_info(network_id).expect(&format!("No NetworkInfo for network {:?}", network_id));
+                let main_chain = ChainInfo::initial(network_info.genesis_block.clone());
+                let head_hash = network_info.genesis_hash.clone();
+
+                let mut txn = WriteTransaction::new(env);
+                accounts.init(&mut txn, network_id);
+                chain_store.put_chain_info(&mut txn, &head_hash, &main_chain, true);
+                chain_store.set_head(&mut txn, &head_hash);
+                txn.commit();
+
+                (main_chain, head_hash)
+            },
+        };
+
+        BlockchainMirror {
+            env,
+            network_id,
+            chain_store,
+            state: RwLock::new(MirrorState {
+                accounts,
+                main_chain,
+                head_hash,
+            }),
+        }
+    }
+
+    /// Applies an event emitted by the primary blockchain.
+    pub fn on_event(&self, event: &BlockchainEvent) {
+        match event {
+            BlockchainEvent::Extended(hash, block) => self.extend(hash, block.as_ref().clone()),
+            BlockchainEvent::Rebranched(reverted_blocks, adopted_blocks) => self.rebranch(reverted_blocks, adopted_blocks),
+            // The mirror only follows the main chain, adopted fork blocks are part of `Rebranched`.
+            BlockchainEvent::Forked(..) => {},
+        }
+    }
+
+    fn extend(&self, block_hash: &Blake2bHash, block: Block) {
+        let mut state = self.state.write();
+        assert_eq!(block.header.prev_hash, state.head_hash, "Mirror out of sync: block does not extend the mirrored head");
+
+        let mut txn = WriteTransaction::new(self.env);
+        if let Err(e) = state.accounts.commit_block(&mut txn, &block) {
+            panic!("Mirror out of sync: failed to commit block {} - {}", block_hash, e);
+        }
+
+        let mut prev_info = state.main_chain.clone();
+        let mut chain_info = prev_info.next(block);
+        chain_info.on_main_chain = true;
+        prev_info.main_chain_successor = Some(block_hash.clone());
+
+        self.chain_store.put_chain_info(&mut txn, block_hash, &chain_info, true);
+        self.chain_store.put_chain_info(&mut txn, &chain_info.head.header.prev_hash, &prev_info, false);
+        self.chain_store.set_head(&mut txn, block_hash);
+        txn.commit();
+
+        state.main_chain = chain_info;
+        state.head_hash = block_hash.clone();
+    }
+
+    fn rebranch(&self, reverted_blocks: &[(Blake2bHash, Block)], adopted_blocks: &[(Blake2bHash, Block)]) {
+        let mut state = self.state.write();
+        let mut txn = WriteTransaction::new(self.env);
+
+        // Revert the main chain down to the common ancestor, starting at the head.
+        for (hash, block) in reverted_blocks.iter().rev() {
+            if let Err(e) = state.accounts.revert_block(&mut txn, block) {
+                panic!("Mirror out of sync: failed to revert block {} - {}", hash, e);
+            }
+
+            let mut chain_info = self.chain_store
+                .get_chain_info(hash, false, Some(&txn))
+                .expect("Mirror out of sync: reverted block not found");
+            chain_info.on_main_chain = false;
+            chain_info.main_chain_successor = None;
+            self.chain_store.put_chain_info(&mut txn, hash, &chain_info, false);
+        }
+
+        // Apply the adopted blocks on top of the common ancestor.
+        // A plain revert (e.g. `Blockchain::revert_to_height`) adopts nothing, the ancestor becomes the new head then.
+        let ancestor_hash = adopted_blocks.first()
+            .or_else(|| reverted_blocks.first())
+            .expect("Rebranch without any blocks")
+            .1.header.prev_hash.clone();
+        let mut prev = (ancestor_hash.clone(), self.chain_store
+            .get_chain_info(&ancestor_hash, false, Some(&txn))
+            .expect("Mirror out of sync: common ancestor not found"));
+
+        if adopted_blocks.is_empty() {
+            prev.1.main_chain_successor = None;
+            self.chain_store.put_chain_info(&mut txn, &prev.0, &prev.1, false);
+        }
+
+        for (hash, block) in adopted_blocks.iter() {
+            if let Err(e) = state.accounts.commit_block(&mut txn, block) {
+                panic!("Mirror out of sync: failed to commit block {} - {}", hash, e);
+            }
+
+            let mut chain_info = prev.1.next(block.clone());
+            chain_info.on_main_chain = true;
+            prev.1.main_chain_successor = Some(hash.clone());
+
+            self.chain_store.put_chain_info(&mut txn, &prev.0, &prev.1, false);
+            self.chain_store.put_chain_info(&mut txn, hash, &chain_info, true);
+            prev = (hash.clone(), chain_info);
+        }
+
+        self.chain_store.set_head(&mut txn, &prev.0);
+        txn.commit();
+
+        state.head_hash = prev.0;
+        state.main_chain = prev.1;
+    }
+
+    pub fn get_block_at(&self, height: u32, include_body: bool) -> Option<Block> {
+        self.chain_store.get_chain_info_at(height, include_body, None).map(|chain_info| chain_info.head)
+    }
+
+    pub fn get_block(&self, hash: &Blake2bHash, include_forks: bool, include_body: bool) -> Option<Block> {
+        let chain_info = self.chain_store.get_chain_info(hash, include_body, None)?;
+        if chain_info.on_main_chain || include_forks {
+            return Some(chain_info.head);
+        }
+        None
+    }
+
+    pub fn contains(&self, hash: &Blake2bHash, include_forks: bool) -> bool {
+        match self.chain_store.get_chain_info(hash, false, None) {
+            Some(chain_info) => include_forks || chain_info.on_main_chain,
+            None => false
+        }
+    }
+
+    pub fn head_hash(&self) -> Blake2bHash {
+        self.state.read().head_hash.clone()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.state.read().main_chain.head.header.height
+    }
+
+    pub fn head(&self) -> MappedRwLockReadGuard<Block> {
+        let guard = self.state.read();
+        RwLockReadGuard::map(guard, |s| &s.main_chain.head)
+    }
+
+    pub fn accounts(&self) -> MappedRwLockReadGuard<Accounts<'env>> {
+        let guard = self.state.read();
+        RwLockReadGuard::map(guard, |s| &s.accounts)
+    }
+}
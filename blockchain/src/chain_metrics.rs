@@ -1,87 +1,73 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::default::Default;
-use crate::PushResult;
+use std::time::Duration;
 
-#[derive(Default)]
-pub struct BlockchainMetrics {
-    block_invalid_count: AtomicUsize,
-    block_orphan_count: AtomicUsize,
-    block_known_count: AtomicUsize,
-    block_extended_count: AtomicUsize,
-    block_rebranched_count: AtomicUsize,
-    block_forked_count: AtomicUsize,
-}
-
-impl BlockchainMetrics {
-    #[inline]
-    pub fn note(&self, push_result: PushResult) {
-        match push_result {
-            PushResult::Invalid(_) => self.note_invalid_block(),
-            PushResult::Orphan => self.note_orphan_block(),
-            PushResult::Known => self.note_known_block(),
-            PushResult::Extended => self.note_extended_block(),
-            PushResult::Rebranched => self.note_rebranched_block(),
-            PushResult::Forked => self.note_forked_block(),
-        }
-    }
-
-    #[inline]
-    pub fn note_invalid_block(&self) {
-        self.block_invalid_count.fetch_add(1, Ordering::Release);
-    }
+const DURATION_BUCKET_COUNT: usize = 10;
 
-    #[inline]
-    pub fn block_invalid_count(&self) -> usize {
-        self.block_invalid_count.load(Ordering::Acquire)
-    }
-
-    #[inline]
-    pub fn note_orphan_block(&self) {
-        self.block_orphan_count.fetch_add(1, Ordering::Release);
-    }
-
-    #[inline]
-    pub fn block_orphan_count(&self) -> usize {
-        self.block_orphan_count.load(Ordering::Acquire)
-    }
+/// Upper bounds of the histogram buckets in microseconds.
+pub const DURATION_BUCKETS_MICROS: [u64; DURATION_BUCKET_COUNT] = [100, 500, 1_000, 5_000, 10_000, 50_000, 100_000, 500_000, 1_000_000, 5_000_000];
 
-    #[inline]
-    pub fn note_known_block(&self) {
-        self.block_known_count.fetch_add(1, Ordering::Release);
-    }
+#[derive(Default)]
+pub struct DurationHistogram {
+    // One counter per bucket plus one for durations above the largest bound.
+    buckets: [AtomicUsize; DURATION_BUCKET_COUNT + 1],
+    count: AtomicUsize,
+    sum_nanos: AtomicU64,
+}
 
+impl DurationHistogram {
     #[inline]
-    pub fn block_known_count(&self) -> usize {
-        self.block_known_count.load(Ordering::Acquire)
+    pub fn observe(&self, duration: Duration) {
+        let micros = duration.as_secs() * 1_000_000 + u64::from(duration.subsec_micros());
+        let bucket = DURATION_BUCKETS_MICROS.iter()
+            .position(|&bound| micros <= bound)
+            .unwrap_or(DURATION_BUCKETS_MICROS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Release);
+        self.count.fetch_add(1, Ordering::Release);
+        let nanos = duration.as_secs() * 1_000_000_000 + u64::from(duration.subsec_nanos());
+        self.sum_nanos.fetch_add(nanos, Ordering::Release);
     }
 
-    #[inline]
-    pub fn note_extended_block(&self) {
-        self.block_extended_count.fetch_add(1, Ordering::Release);
+    /// Returns the cumulative count for each bucket in `DURATION_BUCKETS_MICROS`,
+    /// followed by the total count (the `+Inf` bucket).
+    pub fn cumulative_counts(&self) -> Vec<usize> {
+        let mut total = 0;
+        self.buckets.iter().map(|bucket| {
+            total += bucket.load(Ordering::Acquire);
+            total
+        }).collect()
     }
 
     #[inline]
-    pub fn block_extended_count(&self) -> usize {
-        self.block_extended_count.load(Ordering::Acquire)
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Acquire)
     }
 
     #[inline]
-    pub fn note_rebranched_block(&self) {
-        self.block_rebranched_count.fetch_add(1, Ordering::Release);
+    pub fn sum(&self) -> Duration {
+        let nanos = self.sum_nanos.load(Ordering::Acquire);
+        Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
     }
+}
 
-    #[inline]
-    pub fn block_rebranched_count(&self) -> usize {
-        self.block_rebranched_count.load(Ordering::Acquire)
-    }
+#[derive(Default)]
+pub struct PushTimings {
+    pub verify: DurationHistogram,
+    pub difficulty: DurationHistogram,
+    pub transaction_cache: DurationHistogram,
+    pub accounts_commit: DurationHistogram,
+    pub store_write: DurationHistogram,
+}
 
-    #[inline]
-    pub fn note_forked_block(&self) {
-        self.block_forked_count.fetch_add(1, Ordering::Release);
-    }
+/// Push timings. The per-outcome block counts are always tracked, see `Blockchain::push_stats`.
+#[derive(Default)]
+pub struct BlockchainMetrics {
+    push_timings: PushTimings,
+}
 
+impl BlockchainMetrics {
     #[inline]
-    pub fn block_forked_count(&self) -> usize {
-        self.block_forked_count.load(Ordering::Acquire)
+    pub fn push_timings(&self) -> &PushTimings {
+        &self.push_timings
     }
-}
\ No newline at end of file
+}
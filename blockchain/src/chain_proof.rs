@@ -1,10 +1,473 @@
-use beserial::{Deserialize, Serialize};
-use primitives::block::{Block, BlockHeader};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use database::{FromDatabaseValue, IntoDatabaseValue};
+use hash::{Blake2bHash, Hash};
+use network_primitives::networks::get_network_info;
+use primitives::block::{Block, BlockBody, BlockHeader, BlockInterlink, Difficulty, Target, TargetCompact};
+use primitives::networks::NetworkId;
+
+use crate::blockchain::{Blockchain, SuperChain};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ChainProof {
     #[beserial(len_type(u16))]
     pub prefix: Vec<Block>,
     #[beserial(len_type(u16))]
     pub suffix: Vec<BlockHeader>
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainProofError {
+    SuffixTooLong { length: usize, max: usize },
+    PrefixTooLong { length: usize, max: usize },
+    EmptyPrefix,
+    InvalidPrefixChain,
+    InvalidSuffixChain,
+    InvalidPoW,
+    InvalidGenesis,
+    InvalidInterlink,
+    /// A block of a superchain the prover had to include is missing from the prefix.
+    IncompleteSuperchain,
+}
+
+impl ChainProof {
+    /// Number of suffix headers we accept on top of `NIPOPOW_K`.
+    pub const SUFFIX_MARGIN: usize = 10;
+    pub const MAX_SUFFIX_LENGTH: usize = Blockchain::NIPOPOW_K as usize + ChainProof::SUFFIX_MARGIN;
+    // XXX Generous bound: one superchain of length m for each of the first 64 depths.
+    pub const MAX_PREFIX_LENGTH: usize = Blockchain::NIPOPOW_M as usize * 64;
+    /// Proofs whose serialized size exceeds this are not served, they wouldn't fit into a message
+    /// of the default maximum size together with the message header.
+    pub const MAX_SERIALIZED_SIZE: usize = network_primitives::MAX_MESSAGE_SIZE - network_primitives::MESSAGE_HEADER_SIZE;
+
+    /// The size of the serialized proof in bytes, computed without serializing it.
+    pub fn serialized_size(&self) -> usize {
+        Serialize::serialized_size(self)
+    }
+
+    /// Whether the proof is too large to be sent, see `MAX_SERIALIZED_SIZE`.
+    pub fn is_oversized(&self) -> bool {
+        self.serialized_size() > ChainProof::MAX_SERIALIZED_SIZE
+    }
+
+    /// The hash of the head block this proof was computed for, i.e. the last header of the suffix
+    /// (or the last block of the prefix if the suffix is empty). `None` if the proof is empty.
+    pub fn head_hash(&self) -> Option<Blake2bHash> {
+        match self.suffix.last() {
+            Some(header) => Some(header.hash()),
+            None => self.prefix.last().map(|block| block.header.hash()),
+        }
+    }
+
+    /// Whether this proof was computed for the current head of `blockchain`, e.g. to check
+    /// proofs that were served from an external cache.
+    pub fn is_current(&self, blockchain: &Blockchain) -> bool {
+        self.head_hash().map_or(false, |head_hash| head_hash == blockchain.head_hash())
+    }
+
+    pub fn verify(&self) -> Result<(), ChainProofError> {
+        self.verify_with_limits(ChainProof::MAX_SUFFIX_LENGTH, ChainProof::MAX_PREFIX_LENGTH)
+    }
+
+    pub fn verify_with_limits(&self, max_suffix_length: usize, max_prefix_length: usize) -> Result<(), ChainProofError> {
+        // Check the proof dimensions first, these checks are cheap.
+        if self.suffix.len() > max_suffix_length {
+            return Err(ChainProofError::SuffixTooLong { length: self.suffix.len(), max: max_suffix_length });
+        }
+        if self.prefix.len() > max_prefix_length {
+            return Err(ChainProofError::PrefixTooLong { length: self.prefix.len(), max: max_prefix_length });
+        }
+        if self.prefix.is_empty() {
+            return Err(ChainProofError::EmptyPrefix);
+        }
+
+        // Check that the prefix is a valid interlink chain.
+        for i in 1..self.prefix.len() {
+            if !ChainProof::is_interlink_successor_of(&self.prefix[i], &self.prefix[i - 1]) {
+                return Err(ChainProofError::InvalidPrefixChain);
+            }
+        }
+
+        // Check that the suffix is a valid header chain that connects to the prefix.
+        let mut prev_header = &self.prefix[self.prefix.len() - 1].header;
+        for header in self.suffix.iter() {
+            if !header.is_immediate_successor_of(prev_header) {
+                return Err(ChainProofError::InvalidSuffixChain);
+            }
+            prev_header = header;
+        }
+
+        // Only now do the expensive proof-of-work checks.
+        // XXX The genesis block does not need to meet its own target.
+        for block in self.prefix.iter().filter(|block| block.header.height > 1) {
+            if !block.header.verify_proof_of_work() {
+                return Err(ChainProofError::InvalidPoW);
+            }
+        }
+        for header in self.suffix.iter() {
+            if !header.verify_proof_of_work() {
+                return Err(ChainProofError::InvalidPoW);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether this proof shows a better chain than `other`, using the NiPoPoW "max valid chain" rule:
+    /// the prefixes are scored by their best superchain above their lowest common ancestor.
+    /// Equal scores are decided by the difficulty of the suffixes. Both proofs must have been verified.
+    pub fn is_better_than(&self, other: &ChainProof) -> bool {
+        let m = Blockchain::NIPOPOW_M;
+        let lca_height = ChainProof::lowest_common_ancestor_height(&self.prefix, &other.prefix);
+        let own_score = ChainProof::prefix_score(&self.prefix, lca_height, m);
+        let other_score = ChainProof::prefix_score(&other.prefix, lca_height, m);
+        if own_score != other_score {
+            return own_score > other_score;
+        }
+        ChainProof::suffix_difficulty(&self.suffix) > ChainProof::suffix_difficulty(&other.suffix)
+    }
+
+    fn lowest_common_ancestor_height(prefix: &[Block], other_prefix: &[Block]) -> u32 {
+        let other_hashes: HashSet<Blake2bHash> = other_prefix.iter().map(|block| block.header.hash()).collect();
+        prefix.iter().rev()
+            .find(|block| other_hashes.contains(&block.header.hash()))
+            .map_or(1, |block| block.header.height)
+    }
+
+    /// The maximum of `2^depth * length` over the superchains of the prefix starting at `lca_height`,
+    /// only considering depths with at least `m` blocks (and depth 0).
+    fn prefix_score(prefix: &[Block], lca_height: u32, m: u32) -> f64 {
+        let mut counts: Vec<u32> = Vec::new();
+        for block in prefix.iter().filter(|block| block.header.height >= lca_height) {
+            let depth = Target::from(&block.header.pow()).get_depth() as usize;
+            if counts.len() <= depth {
+                counts.resize(depth + 1, 0);
+            }
+            counts[depth] += 1;
+        }
+
+        let mut max_score = 0f64;
+        let mut length = 0u32;
+        for depth in (0..counts.len()).rev() {
+            length += counts[depth];
+            if length >= m || depth == 0 {
+                max_score = f64::max(max_score, 2f64.powi(depth as i32) * length as f64);
+            }
+        }
+        max_score
+    }
+
+    fn suffix_difficulty(suffix: &[BlockHeader]) -> Difficulty {
+        suffix.iter().fold(Difficulty::from(0u64), |sum, header| &sum + &Difficulty::from(header.n_bits))
+    }
+
+    fn is_interlink_successor_of(block: &Block, predecessor: &Block) -> bool {
+        // Check that the height is higher than the predecessor's height.
+        if block.header.height <= predecessor.header.height {
+            return false;
+        }
+
+        // Check that the timestamp is greater or equal to the predecessor's timestamp.
+        if block.header.timestamp < predecessor.header.timestamp {
+            return false;
+        }
+
+        // Check that the predecessor is referenced either directly or via the interlink.
+        let prev_hash: Blake2bHash = predecessor.header.hash();
+        return block.header.prev_hash == prev_hash || block.interlink.hashes.contains(&prev_hash);
+    }
+
+    /// Serializes the proof in a compact form: header fields that can be derived from the previous
+    /// header are omitted and interlink hashes are replaced by references to hashes already known
+    /// from the previous prefix block.
+    pub fn serialize_compressed(&self) -> Vec<u8> {
+        let mut v = Vec::new();
+        self.write_compressed(&mut v).expect("Failed to serialize compressed chain proof");
+        v
+    }
+
+    pub fn deserialize_compressed(bytes: &[u8]) -> Result<ChainProof, SerializingError> {
+        ChainProof::read_compressed(&mut &bytes[..])
+    }
+
+    fn write_compressed<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, SerializingError> {
+        let mut size = 0;
+        let mut genesis_hash: Option<Blake2bHash> = None;
+        let mut prev: Option<&Block> = None;
+
+        size += Serialize::serialize(&(self.prefix.len() as u16), writer)?;
+        for block in self.prefix.iter() {
+            let interlink_derived = genesis_hash.as_ref()
+                .map_or(false, |genesis_hash| block.interlink.hash(genesis_hash.clone()) == block.header.interlink_hash);
+            size += compression::write_header(writer, &block.header, prev.map(|prev| &prev.header), interlink_derived)?;
+            size += compression::write_interlink(writer, &block.interlink, &block.header.prev_hash, prev)?;
+            size += Serialize::serialize(&block.body, writer)?;
+
+            if genesis_hash.is_none() && block.header.height == 1 {
+                genesis_hash = Some(block.header.hash());
+            }
+            prev = Some(block);
+        }
+
+        let mut prev_header = prev.map(|prev| &prev.header);
+        size += Serialize::serialize(&(self.suffix.len() as u16), writer)?;
+        for header in self.suffix.iter() {
+            size += compression::write_header(writer, header, prev_header, false)?;
+            prev_header = Some(header);
+        }
+
+        Ok(size)
+    }
+
+    fn read_compressed<R: ReadBytesExt>(reader: &mut R) -> Result<ChainProof, SerializingError> {
+        let mut genesis_hash: Option<Blake2bHash> = None;
+
+        let prefix_len: u16 = Deserialize::deserialize(reader)?;
+        let mut prefix: Vec<Block> = Vec::with_capacity(prefix_len as usize);
+        for _ in 0..prefix_len {
+            let (mut header, interlink_derived) = compression::read_header(reader, prefix.last().map(|prev| &prev.header))?;
+            let interlink = compression::read_interlink(reader, &header.prev_hash, prefix.last())?;
+            let body: Option<BlockBody> = Deserialize::deserialize(reader)?;
+            if interlink_derived {
+                let genesis_hash = genesis_hash.clone().ok_or(SerializingError::InvalidValue)?;
+                header.interlink_hash = interlink.hash(genesis_hash);
+            }
+
+            if genesis_hash.is_none() && header.height == 1 {
+                genesis_hash = Some(header.hash());
+            }
+            prefix.push(Block { header, interlink, body });
+        }
+
+        let suffix_len: u16 = Deserialize::deserialize(reader)?;
+        let mut suffix: Vec<BlockHeader> = Vec::with_capacity(suffix_len as usize);
+        for _ in 0..suffix_len {
+            let (header, interlink_derived) = {
+                let prev_header = suffix.last().or_else(|| prefix.last().map(|prev| &prev.header));
+                compression::read_header(reader, prev_header)?
+            };
+            if interlink_derived {
+                return Err(SerializingError::InvalidValue);
+            }
+            suffix.push(header);
+        }
+
+        Ok(ChainProof { prefix, suffix })
+    }
+}
+
+impl IntoDatabaseValue for ChainProof {
+    fn database_byte_size(&self) -> usize {
+        return Serialize::serialized_size(self);
+    }
+
+    fn copy_into_database(&self, mut bytes: &mut [u8]) {
+        Serialize::serialize(&self, &mut bytes).unwrap();
+    }
+}
+
+impl FromDatabaseValue for ChainProof {
+    fn copy_from_database(bytes: &[u8]) -> io::Result<Self> where Self: Sized {
+        let mut cursor = io::Cursor::new(bytes);
+        return Ok(Deserialize::deserialize(&mut cursor)?);
+    }
+}
+
+/// Verifies a chain proof received from an untrusted peer: the proof must be well-formed (see `ChainProof::verify`),
+/// start at the genesis block of `network_id`, have valid interlinks and contain every block of the superchains
+/// that the prover had to include. Omitting blocks is only accepted where a locally good superchain covers them.
+pub fn verify_chain_proof(proof: &ChainProof, network_id: NetworkId) -> Result<(), ChainProofError> {
+    proof.verify()?;
+
+    // The prefix must start at the genesis block of the expected network.
+    let genesis_hash = &get_network_info(network_id).ok_or(ChainProofError::InvalidGenesis)?.genesis_hash;
+    if &proof.prefix[0].header.hash::<Blake2bHash>() != genesis_hash {
+        return Err(ChainProofError::InvalidGenesis);
+    }
+
+    // Check that the interlinks of all prefix blocks match their headers.
+    for block in proof.prefix.iter().skip(1) {
+        if block.header.interlink_hash != block.interlink.hash(genesis_hash.clone()) {
+            return Err(ChainProofError::InvalidInterlink);
+        }
+    }
+
+    verify_superchains(&proof.prefix, Blockchain::NIPOPOW_M, Blockchain::NIPOPOW_DELTA)
+}
+
+/// Walks the superchains of the prefix from the deepest level down like the prover does and checks
+/// that each of them is fully contained in the prefix. A level only shortens the levels below it
+/// if it satisfies the superquality condition.
+fn verify_superchains(prefix: &[Block], m: u32, delta: f64) -> Result<(), ChainProofError> {
+    let blocks: HashMap<Blake2bHash, &Block> = prefix.iter().map(|block| (block.header.hash(), block)).collect();
+    let depths: Vec<u8> = prefix.iter().map(|block| Target::from(&block.header.pow()).get_depth()).collect();
+    let head = &prefix[prefix.len() - 1];
+    let head_depth = depths[depths.len() - 1];
+    let max_depth = depths.iter().cloned().max().unwrap_or(0);
+
+    let mut start_height = 1u32;
+    for depth in (0..=max_depth).rev() {
+        // Collect the superchain at this depth by following the interlinks, like `get_super_chain`.
+        let mut chain: Vec<&Block> = vec![];
+        if head_depth >= depth {
+            chain.push(head);
+        }
+
+        let mut current = head;
+        let mut j = i16::max(depth as i16 - Target::from(current.header.n_bits).get_depth() as i16, -1);
+        while j < current.interlink.hashes.len() as i16 && current.header.height > start_height {
+            let reference = if j < 0 {
+                &current.header.prev_hash
+            } else {
+                &current.interlink.hashes[j as usize]
+            };
+
+            current = blocks.get(reference).ok_or(ChainProofError::IncompleteSuperchain)?;
+            chain.push(current);
+            j = i16::max(depth as i16 - Target::from(current.header.n_bits).get_depth() as i16, -1);
+        }
+        chain.reverse();
+
+        // The prover only uses the multi-level quality in addition to this condition,
+        // so it never skips blocks that this check would require.
+        let heights: Vec<u32> = chain.iter().map(|block| block.header.height).collect();
+        if SuperChain::has_super_quality_at(&heights, depth, m, delta) {
+            start_height = u32::max(start_height, chain[chain.len() - m as usize].header.height);
+        }
+    }
+
+    Ok(())
+}
+
+/// Allows aborting a chain proof computation, e.g. on shutdown.
+#[derive(Debug, Default)]
+pub struct CancellationToken(AtomicBool);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken(AtomicBool::new(false))
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+mod compression {
+    use super::*;
+
+    // Header fields that are omitted because they can be derived.
+    const PREV_HASH_DERIVED: u8 = 0x01;
+    const HEIGHT_DERIVED: u8 = 0x02;
+    const N_BITS_UNCHANGED: u8 = 0x04;
+    const VERSION_UNCHANGED: u8 = 0x08;
+    const INTERLINK_HASH_DERIVED: u8 = 0x10;
+
+    // Interlink hashes are either references into the known hashes or one of these.
+    const REPEAT_PREVIOUS: u8 = 0xfe;
+    const LITERAL: u8 = 0xff;
+
+    pub(super) fn write_header<W: WriteBytesExt>(writer: &mut W, header: &BlockHeader, prev: Option<&BlockHeader>, interlink_derived: bool) -> Result<usize, SerializingError> {
+        let mut flags = 0u8;
+        if let Some(prev) = prev {
+            if header.prev_hash == prev.hash::<Blake2bHash>() { flags |= PREV_HASH_DERIVED; }
+            if Some(header.height) == prev.height.checked_add(1) { flags |= HEIGHT_DERIVED; }
+            if header.n_bits == prev.n_bits { flags |= N_BITS_UNCHANGED; }
+            if header.version == prev.version { flags |= VERSION_UNCHANGED; }
+        }
+        if interlink_derived { flags |= INTERLINK_HASH_DERIVED; }
+
+        let mut size = Serialize::serialize(&flags, writer)?;
+        if flags & VERSION_UNCHANGED == 0 { size += Serialize::serialize(&header.version, writer)?; }
+        if flags & PREV_HASH_DERIVED == 0 { size += Serialize::serialize(&header.prev_hash, writer)?; }
+        if flags & INTERLINK_HASH_DERIVED == 0 { size += Serialize::serialize(&header.interlink_hash, writer)?; }
+        size += Serialize::serialize(&header.body_hash, writer)?;
+        size += Serialize::serialize(&header.accounts_hash, writer)?;
+        if flags & N_BITS_UNCHANGED == 0 { size += Serialize::serialize(&header.n_bits, writer)?; }
+        if flags & HEIGHT_DERIVED == 0 { size += Serialize::serialize(&header.height, writer)?; }
+        size += Serialize::serialize(&header.timestamp, writer)?;
+        size += Serialize::serialize(&header.nonce, writer)?;
+        Ok(size)
+    }
+
+    /// Returns the header and whether its interlink hash still needs to be derived from the interlink.
+    pub(super) fn read_header<R: ReadBytesExt>(reader: &mut R, prev: Option<&BlockHeader>) -> Result<(BlockHeader, bool), SerializingError> {
+        let flags: u8 = Deserialize::deserialize(reader)?;
+        let derived = PREV_HASH_DERIVED | HEIGHT_DERIVED | N_BITS_UNCHANGED | VERSION_UNCHANGED;
+        if flags & !(derived | INTERLINK_HASH_DERIVED) != 0 || (prev.is_none() && flags & derived != 0) {
+            return Err(SerializingError::InvalidValue);
+        }
+
+        let version: u16 = if flags & VERSION_UNCHANGED != 0 { prev.unwrap().version } else { Deserialize::deserialize(reader)? };
+        let prev_hash: Blake2bHash = if flags & PREV_HASH_DERIVED != 0 { prev.unwrap().hash() } else { Deserialize::deserialize(reader)? };
+        let interlink_hash: Blake2bHash = if flags & INTERLINK_HASH_DERIVED != 0 { Blake2bHash::default() } else { Deserialize::deserialize(reader)? };
+        let body_hash: Blake2bHash = Deserialize::deserialize(reader)?;
+        let accounts_hash: Blake2bHash = Deserialize::deserialize(reader)?;
+        let n_bits: TargetCompact = if flags & N_BITS_UNCHANGED != 0 { prev.unwrap().n_bits } else { Deserialize::deserialize(reader)? };
+        let height: u32 = if flags & HEIGHT_DERIVED != 0 {
+            prev.unwrap().height.checked_add(1).ok_or(SerializingError::Overflow)?
+        } else {
+            Deserialize::deserialize(reader)?
+        };
+        let timestamp: u32 = Deserialize::deserialize(reader)?;
+        let nonce: u32 = Deserialize::deserialize(reader)?;
+
+        let header = BlockHeader { version, prev_hash, interlink_hash, body_hash, accounts_hash, n_bits, height, timestamp, nonce };
+        Ok((header, flags & INTERLINK_HASH_DERIVED != 0))
+    }
+
+    /// Hashes that interlink entries can refer to: the block's own predecessor,
+    /// the previous prefix block and its interlink.
+    fn known_hashes(prev_hash: &Blake2bHash, prev: Option<&Block>) -> Vec<Blake2bHash> {
+        let mut known = vec![prev_hash.clone()];
+        if let Some(prev) = prev {
+            known.push(prev.header.hash());
+            known.extend(prev.interlink.hashes.iter().cloned());
+        }
+        known.truncate(REPEAT_PREVIOUS as usize);
+        known
+    }
+
+    pub(super) fn write_interlink<W: WriteBytesExt>(writer: &mut W, interlink: &BlockInterlink, prev_hash: &Blake2bHash, prev: Option<&Block>) -> Result<usize, SerializingError> {
+        let known = known_hashes(prev_hash, prev);
+
+        let mut size = Serialize::serialize(&(interlink.hashes.len() as u8), writer)?;
+        let mut previous = prev_hash;
+        for hash in interlink.hashes.iter() {
+            if hash == previous {
+                size += Serialize::serialize(&REPEAT_PREVIOUS, writer)?;
+            } else if let Some(i) = known.iter().position(|known| known == hash) {
+                size += Serialize::serialize(&(i as u8), writer)?;
+            } else {
+                size += Serialize::serialize(&LITERAL, writer)?;
+                size += Serialize::serialize(hash, writer)?;
+            }
+            previous = hash;
+        }
+        Ok(size)
+    }
+
+    pub(super) fn read_interlink<R: ReadBytesExt>(reader: &mut R, prev_hash: &Blake2bHash, prev: Option<&Block>) -> Result<BlockInterlink, SerializingError> {
+        let known = known_hashes(prev_hash, prev);
+
+        let count: u8 = Deserialize::deserialize(reader)?;
+        let mut hashes: Vec<Blake2bHash> = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let tag: u8 = Deserialize::deserialize(reader)?;
+            let hash = match tag {
+                LITERAL => Deserialize::deserialize(reader)?,
+                REPEAT_PREVIOUS => hashes.last().unwrap_or(prev_hash).clone(),
+                i => known.get(i as usize).ok_or(SerializingError::InvalidValue)?.clone(),
+            };
+            hashes.push(hash);
+        }
+        Ok(BlockInterlink::new(hashes, prev_hash))
+    }
+}
@@ -1,8 +1,11 @@
 use database::{Database, DatabaseFlags, Environment, ReadTransaction, Transaction, WriteTransaction};
 use hash::Blake2bHash;
+use keys::Address;
 use primitives::block::Block;
 
 use crate::chain_info::ChainInfo;
+use crate::chain_proof::ChainProof;
+use crate::transaction_cache::TransactionCache;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
 pub enum Direction {
@@ -15,21 +18,26 @@ pub struct ChainStore<'env> {
     env: &'env Environment,
     chain_db: Database<'env>,
     block_db: Database<'env>,
-    height_idx: Database<'env>
+    height_idx: Database<'env>,
+    chain_proof_db: Database<'env>,
 }
 
 impl<'env> ChainStore<'env> {
     const CHAIN_DB_NAME: &'static str = "ChainData";
     const BLOCK_DB_NAME: &'static str = "Block";
     const HEIGHT_IDX_NAME: &'static str = "HeightIdx";
+    const CHAIN_PROOF_DB_NAME: &'static str = "ChainProof";
     const HEAD_KEY: &'static str = "head";
+    const TRANSACTION_CACHE_KEY: &'static str = "transactionCache";
+    const CHAIN_PROOF_KEY: &'static str = "proof";
 
     pub fn new(env: &'env Environment) -> Self {
         let chain_db = env.open_database(Self::CHAIN_DB_NAME.to_string());
         let block_db = env.open_database(Self::BLOCK_DB_NAME.to_string());
         let height_idx = env.open_database_with_flags(Self::HEIGHT_IDX_NAME.to_string(),
             DatabaseFlags::DUPLICATE_KEYS | DatabaseFlags::DUP_FIXED_SIZE_VALUES);
-        return ChainStore { env, chain_db, block_db, height_idx };
+        let chain_proof_db = env.open_database(Self::CHAIN_PROOF_DB_NAME.to_string());
+        return ChainStore { env, chain_db, block_db, height_idx, chain_proof_db };
     }
 
     pub fn get_head(&self, txn_option: Option<&Transaction>) -> Option<Blake2bHash> {
@@ -43,6 +51,39 @@ impl<'env> ChainStore<'env> {
         txn.put(&self.chain_db, ChainStore::HEAD_KEY, hash);
     }
 
+    /// Returns the persisted transaction cache. It might not match the current head.
+    pub fn get_transaction_cache(&self, txn_option: Option<&Transaction>) -> Option<TransactionCache> {
+        return match txn_option {
+            Some(txn) => txn.get(&self.chain_db, ChainStore::TRANSACTION_CACHE_KEY),
+            None => ReadTransaction::new(self.env).get(&self.chain_db, ChainStore::TRANSACTION_CACHE_KEY)
+        };
+    }
+
+    pub fn put_transaction_cache(&self, txn: &mut WriteTransaction, transaction_cache: &TransactionCache) {
+        txn.put_reserve(&self.chain_db, ChainStore::TRANSACTION_CACHE_KEY, transaction_cache);
+    }
+
+    /// Returns the persisted chain proof and the head hash it was computed for. It might not match the current head.
+    pub fn get_chain_proof(&self, txn_option: Option<&Transaction>) -> Option<(Blake2bHash, ChainProof)> {
+        let read_txn: ReadTransaction;
+        let txn = match txn_option {
+            Some(txn) => txn,
+            None => {
+                read_txn = ReadTransaction::new(self.env);
+                &read_txn
+            }
+        };
+
+        let head_hash: Blake2bHash = txn.get(&self.chain_proof_db, ChainStore::HEAD_KEY)?;
+        let chain_proof: ChainProof = txn.get(&self.chain_proof_db, ChainStore::CHAIN_PROOF_KEY)?;
+        return Some((head_hash, chain_proof));
+    }
+
+    pub fn put_chain_proof(&self, txn: &mut WriteTransaction, head_hash: &Blake2bHash, chain_proof: &ChainProof) {
+        txn.put(&self.chain_proof_db, ChainStore::HEAD_KEY, head_hash);
+        txn.put_reserve(&self.chain_proof_db, ChainStore::CHAIN_PROOF_KEY, chain_proof);
+    }
+
     pub fn get_chain_info(&self, hash: &Blake2bHash, include_body: bool, txn_option: Option<&Transaction>) -> Option<ChainInfo> {
         let read_txn: ReadTransaction;
         let txn = match txn_option {
@@ -83,6 +124,24 @@ impl<'env> ChainStore<'env> {
         txn.put(&self.height_idx, &height, hash);
     }
 
+    /// Removes a block, including its body and height index entry, from the store.
+    pub fn remove_chain_info(&self, txn: &mut WriteTransaction, hash: &Blake2bHash, height: u32) {
+        txn.remove(&self.chain_db, hash);
+        txn.remove(&self.block_db, hash);
+        txn.remove_item(&self.height_idx, &height, hash);
+    }
+
+    /// Removes the body of a block from the store, keeping its chain data. Returns false if no body was stored.
+    pub fn remove_block_body(&self, txn: &mut WriteTransaction, hash: &Blake2bHash) -> bool {
+        let block: Option<Block> = txn.get(&self.block_db, hash);
+        if block.is_none() {
+            return false;
+        }
+
+        txn.remove(&self.block_db, hash);
+        return true;
+    }
+
     pub fn get_chain_info_at(&self, block_height: u32, include_body: bool, txn_option: Option<&Transaction>) -> Option<ChainInfo> {
         let read_txn: ReadTransaction;
         let txn = match txn_option {
@@ -127,6 +186,69 @@ impl<'env> ChainStore<'env> {
         return Some(chain_info);
     }
 
+    /// Returns all stored blocks at the given height, i.e. the main chain block and any fork blocks.
+    pub fn get_blocks_at(&self, block_height: u32, include_body: bool, txn_option: Option<&Transaction>) -> Vec<(Blake2bHash, Block)> {
+        let read_txn: ReadTransaction;
+        let txn = match txn_option {
+            Some(txn) => txn,
+            None => {
+                read_txn = ReadTransaction::new(self.env);
+                &read_txn
+            }
+        };
+
+        let mut blocks = Vec::new();
+        let mut cursor = txn.cursor(&self.height_idx);
+        let mut block_hash_opt = cursor.seek_key::<u32, Blake2bHash>(&block_height);
+        while let Some(block_hash) = block_hash_opt {
+            let block = self.get_block(&block_hash, include_body, Some(&txn))
+                .or_else(|| self.get_block(&block_hash, false, Some(&txn)))
+                .expect("Corrupted store: ChainInfo referenced from index not found");
+            blocks.push((block_hash, block));
+            block_hash_opt = cursor.next_duplicate::<u32, Blake2bHash>().map(|(_, hash)| hash);
+        }
+        return blocks;
+    }
+
+    /// Returns the blocks at or above `min_height` that are not on the main chain and have no successor,
+    /// i.e. the tips of forks. Blocks are scanned height by height until the highest stored height.
+    pub fn get_fork_tips(&self, min_height: u32, txn_option: Option<&Transaction>) -> Vec<(Blake2bHash, ChainInfo)> {
+        let read_txn: ReadTransaction;
+        let txn = match txn_option {
+            Some(txn) => txn,
+            None => {
+                read_txn = ReadTransaction::new(self.env);
+                &read_txn
+            }
+        };
+
+        let mut tips: Vec<(Blake2bHash, ChainInfo)> = Vec::new();
+        let mut cursor = txn.cursor(&self.height_idx);
+        let mut height = u32::max(min_height, 1);
+        // Every stored block has a stored predecessor, so there are no gaps in the height index.
+        while let Some(mut block_hash) = cursor.seek_key::<u32, Blake2bHash>(&height) {
+            loop {
+                let chain_info: ChainInfo = txn
+                    .get(&self.chain_db, &block_hash)
+                    .expect("Corrupted store: ChainInfo referenced from index not found");
+
+                // The predecessor of this block is not a tip anymore.
+                tips.retain(|(hash, _)| hash != &chain_info.head.header.prev_hash);
+                if !chain_info.on_main_chain {
+                    tips.push((block_hash, chain_info));
+                }
+
+                block_hash = match cursor.next_duplicate::<u32, Blake2bHash>() {
+                    Some((_, hash)) => hash,
+                    None => break
+                };
+            }
+            height += 1;
+        }
+
+        return tips;
+    }
+
     pub fn get_block(&self, hash: &Blake2bHash, include_body: bool, txn_option: Option<&Transaction>) -> Option<Block> {
         let read_txn: ReadTransaction;
         let txn = match txn_option {
@@ -149,6 +271,33 @@ impl<'env> ChainStore<'env> {
             .map(|chain_info| chain_info.head)
     }
 
+    /// Searches the block `start_block_hash` and up to `max_depth - 1` of its predecessors for a block
+    /// that pruned the account at `address`. The search ends at the first block without a stored body.
+    pub fn find_pruning_block(&self, start_block_hash: &Blake2bHash, address: &Address, max_depth: u32, txn_option: Option<&Transaction>) -> Option<(Blake2bHash, u32)> {
+        let read_txn: ReadTransaction;
+        let txn = match txn_option {
+            Some(txn) => txn,
+            None => {
+                read_txn = ReadTransaction::new(self.env);
+                &read_txn
+            }
+        };
+
+        let mut hash = start_block_hash.clone();
+        for _ in 0..max_depth {
+            let block = self.get_block(&hash, true, Some(&txn))?;
+            let body = block.body.as_ref()?;
+            if body.pruned_accounts.iter().any(|pruned_account| &pruned_account.address == address) {
+                return Some((hash, block.header.height));
+            }
+            if block.header.height <= 1 {
+                break;
+            }
+            hash = block.header.prev_hash;
+        }
+        None
+    }
+
     pub fn get_blocks_backward(&self, start_block_hash: &Blake2bHash, count: u32, include_body: bool, txn_option: Option<&Transaction>) -> Vec<Block> {
         let read_txn: ReadTransaction;
         let txn = match txn_option {
@@ -213,6 +362,25 @@ impl<'env> ChainStore<'env> {
         return blocks;
     }
 
+    /// Returns an iterator over the blocks before (`Backward`) or the main chain blocks after (`Forward`)
+    /// the given start block, loading one block at a time. The start block itself is not included.
+    pub fn block_cursor<'a>(&'a self, start_block_hash: &Blake2bHash, direction: Direction, include_body: bool, txn: &'a Transaction<'env>) -> BlockCursor<'a, 'env> {
+        let next_hash = match direction {
+            Direction::Forward => self.get_chain_info(start_block_hash, false, Some(txn))
+                .and_then(|chain_info| chain_info.main_chain_successor),
+            Direction::Backward => self.get_block(start_block_hash, false, Some(txn))
+                .map(|block| block.header.prev_hash),
+        };
+
+        BlockCursor {
+            chain_store: self,
+            txn,
+            next_hash,
+            direction,
+            include_body,
+        }
+    }
+
     pub fn get_blocks(&self, start_block_hash: &Blake2bHash, count: u32, include_body: bool, direction: Direction, txn_option: Option<&Transaction>) -> Vec<Block> {
         match direction {
             Direction::Forward => self.get_blocks_forward(start_block_hash, count, include_body, txn_option),
@@ -220,3 +388,31 @@ impl<'env> ChainStore<'env> {
         }
     }
 }
+
+pub struct BlockCursor<'a, 'env: 'a> {
+    chain_store: &'a ChainStore<'env>,
+    txn: &'a Transaction<'env>,
+    next_hash: Option<Blake2bHash>,
+    direction: Direction,
+    include_body: bool,
+}
+
+impl<'a, 'env> Iterator for BlockCursor<'a, 'env> {
+    type Item = Block;
+
+    fn next(&mut self) -> Option<Block> {
+        let hash = self.next_hash.take()?;
+        match self.direction {
+            Direction::Forward => {
+                let chain_info = self.chain_store.get_chain_info(&hash, self.include_body, Some(self.txn))?;
+                self.next_hash = chain_info.main_chain_successor;
+                Some(chain_info.head)
+            },
+            Direction::Backward => {
+                let block = self.chain_store.get_block(&hash, self.include_body, Some(self.txn))?;
+                self.next_hash = Some(block.header.prev_hash.clone());
+                Some(block)
+            },
+        }
+    }
+}
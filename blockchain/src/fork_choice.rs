@@ -0,0 +1,18 @@
+use crate::chain_info::ChainInfo;
+
+/// Decides whether a fork should replace the main chain.
+pub trait ForkChoice: Send + Sync {
+    /// Returns whether to rebranch from `main_chain` to the fork ending in `candidate`.
+    /// Only called for blocks that don't extend the main chain.
+    fn should_rebranch(&self, main_chain: &ChainInfo, candidate: &ChainInfo) -> bool;
+}
+
+/// The default fork choice: the chain with the highest total difficulty wins, ties keep the main chain.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeaviestChain;
+
+impl ForkChoice for HeaviestChain {
+    fn should_rebranch(&self, main_chain: &ChainInfo, candidate: &ChainInfo) -> bool {
+        candidate.total_difficulty > main_chain.total_difficulty
+    }
+}
@@ -0,0 +1,52 @@
+use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use hash::Blake2bHash;
+use keys::{KeyPair, PublicKey, Signature};
+use primitives::block::Difficulty;
+use primitives::networks::NetworkId;
+
+/// A statement about the current head of a node's main chain, signed with the node's key.
+/// Light clients that trust a node can accept this instead of a chain proof.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeadCertificate {
+    pub network_id: NetworkId,
+    pub head_hash: Blake2bHash,
+    pub height: u32,
+    pub total_difficulty: Difficulty,
+    /// Network time (in milliseconds) at which the certificate was issued.
+    pub issued_at: u64,
+    pub signature: Signature,
+}
+
+impl HeadCertificate {
+    /// Prefixed to the signed data, so that the signature can't be mistaken for one over another payload.
+    const SIGNATURE_PREFIX: &'static [u8] = b"Nimiq Head Certificate";
+
+    pub fn new(network_id: NetworkId, head_hash: Blake2bHash, height: u32, total_difficulty: Difficulty, issued_at: u64, key_pair: &KeyPair) -> Self {
+        let signature = key_pair.sign(&HeadCertificate::signed_data(network_id, &head_hash, height, &total_difficulty, issued_at));
+        HeadCertificate { network_id, head_hash, height, total_difficulty, issued_at, signature }
+    }
+
+    /// Checks that the certificate was signed by `public_key` for the given network.
+    pub fn verify(&self, public_key: &PublicKey, network_id: NetworkId) -> bool {
+        if self.network_id != network_id {
+            return false;
+        }
+        let data = HeadCertificate::signed_data(self.network_id, &self.head_hash, self.height, &self.total_difficulty, self.issued_at);
+        public_key.verify(&self.signature, &data)
+    }
+
+    /// Checks that the certificate was issued at most `max_age` milliseconds before `now` and not after it.
+    pub fn is_fresh(&self, now: u64, max_age: u64) -> bool {
+        self.issued_at <= now && now - self.issued_at <= max_age
+    }
+
+    fn signed_data(network_id: NetworkId, head_hash: &Blake2bHash, height: u32, total_difficulty: &Difficulty, issued_at: u64) -> Vec<u8> {
+        let mut data = HeadCertificate::SIGNATURE_PREFIX.to_vec();
+        network_id.serialize(&mut data).unwrap();
+        head_hash.serialize(&mut data).unwrap();
+        height.serialize(&mut data).unwrap();
+        total_difficulty.serialize(&mut data).unwrap();
+        issued_at.serialize(&mut data).unwrap();
+        data
+    }
+}
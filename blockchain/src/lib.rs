@@ -6,6 +6,7 @@ extern crate log;
 extern crate nimiq_accounts as accounts;
 extern crate nimiq_primitives as primitives;
 extern crate nimiq_hash as hash;
+extern crate nimiq_keys as keys;
 extern crate nimiq_database as database;
 extern crate nimiq_network_primitives as network_primitives;
 extern crate nimiq_utils as utils;
@@ -13,11 +14,24 @@ extern crate nimiq_utils as utils;
 pub mod chain_info;
 pub mod chain_store;
 pub mod blockchain;
+pub mod blockchain_config;
+pub mod blockchain_mirror;
 pub mod super_block_counts;
 pub mod transaction_cache;
 #[cfg(feature = "metrics")]
 pub mod chain_metrics;
 pub mod chain_proof;
+pub mod head_certificate;
+pub mod orphan_pool;
+pub mod target_cache;
+pub mod block_queue;
+pub mod fork_choice;
+pub mod push_stats;
 
-pub use self::blockchain::{Blockchain, BlockchainEvent, PushResult, PushError};
+pub use self::blockchain::{Blockchain, BlockchainEvent, PushResult, PushError, PushErrorContext, TxAdmissionError};
+pub use self::blockchain_config::BlockchainConfig;
+pub use self::blockchain_mirror::BlockchainMirror;
 pub use self::chain_store::Direction;
+pub use self::fork_choice::{ForkChoice, HeaviestChain};
+pub use self::head_certificate::HeadCertificate;
+pub use self::push_stats::PushStats;
@@ -0,0 +1,158 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use hash::{Blake2bHash, Hash};
+use network_primitives::address::PeerId;
+use primitives::block::Block;
+use primitives::policy;
+
+/// Buffers blocks whose predecessor is not known yet, keyed by the hash of the missing predecessor.
+/// When the pool is full, the oldest orphan makes room for a new one.
+#[derive(Debug, Default)]
+pub struct OrphanPool {
+    blocks: HashMap<Blake2bHash, Vec<Orphan>>,
+    /// Hashes of the buffered blocks and of their missing predecessors, oldest first.
+    order: VecDeque<(Blake2bHash, Blake2bHash)>,
+    /// Number of buffered blocks per peer that sent them.
+    origins: HashMap<PeerId, usize>,
+}
+
+#[derive(Debug)]
+struct Orphan {
+    block: Block,
+    hash: Blake2bHash,
+    origin: Option<PeerId>,
+    added: Instant,
+}
+
+impl OrphanPool {
+    /// Orphans are dropped after this time, their predecessor is unlikely to arrive anymore.
+    pub const MAX_AGE: Duration = Duration::from_secs(10 * 60);
+    /// Maximum number of orphans buffered for a single peer.
+    pub const MAX_PER_ORIGIN: usize = 64;
+    /// Orphans more than this many blocks above the head are rejected, a single `GetBlocks` response can't connect them.
+    pub const MAX_HEIGHT_AHEAD: u32 = 500;
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds an orphan block sent by `origin` (`None` for local blocks), unless it is already buffered, more than
+    /// `MAX_HEIGHT_AHEAD` blocks above `head_height` or `origin` already has `MAX_PER_ORIGIN` orphans buffered.
+    /// If the pool holds `max_count` blocks, the oldest ones are evicted. Returns whether the block was added.
+    pub fn add(&mut self, block: Block, origin: Option<&PeerId>, head_height: u32, max_count: usize, now: Instant) -> bool {
+        if max_count == 0 || block.header.height > head_height.saturating_add(Self::MAX_HEIGHT_AHEAD) {
+            return false;
+        }
+
+        if let Some(origin) = origin {
+            if self.origins.get(origin).map_or(false, |count| *count >= Self::MAX_PER_ORIGIN) {
+                return false;
+            }
+        }
+
+        let hash: Blake2bHash = block.header.hash();
+        let prev_hash = block.header.prev_hash.clone();
+        if self.blocks.get(&prev_hash).map_or(false, |siblings| siblings.iter().any(|sibling| sibling.hash == hash)) {
+            return false;
+        }
+
+        while self.len() >= max_count {
+            self.evict_oldest();
+        }
+
+        if let Some(origin) = origin {
+            *self.origins.entry(origin.clone()).or_insert(0) += 1;
+        }
+        self.order.push_back((hash.clone(), prev_hash.clone()));
+        self.blocks.entry(prev_hash).or_insert_with(Vec::new).push(Orphan {
+            block,
+            hash,
+            origin: origin.cloned(),
+            added: now,
+        });
+        true
+    }
+
+    /// Removes and returns the buffered successors of the block `prev_hash`.
+    pub fn take_successors(&mut self, prev_hash: &Blake2bHash) -> Vec<Block> {
+        let orphans = self.blocks.remove(prev_hash).unwrap_or_default();
+        if orphans.is_empty() {
+            return Vec::new();
+        }
+
+        self.order.retain(|(_, orphan_prev_hash)| orphan_prev_hash != prev_hash);
+        let mut blocks = Vec::with_capacity(orphans.len());
+        for orphan in orphans {
+            self.forget_origin(&orphan.origin);
+            blocks.push(orphan.block);
+        }
+        blocks
+    }
+
+    /// Removes orphans that are more than `TRANSACTION_VALIDITY_WINDOW` blocks below `head_height`
+    /// or were added more than `MAX_AGE` before `now`.
+    pub fn prune(&mut self, head_height: u32, now: Instant) {
+        let min_height = head_height.saturating_sub(policy::TRANSACTION_VALIDITY_WINDOW);
+        let mut removed = Vec::new();
+        self.blocks.retain(|_, orphans| {
+            orphans.retain(|orphan| {
+                let keep = orphan.block.header.height > min_height && orphan.added + Self::MAX_AGE > now;
+                if !keep {
+                    removed.push((orphan.hash.clone(), orphan.origin.clone()));
+                }
+                keep
+            });
+            !orphans.is_empty()
+        });
+
+        if removed.is_empty() {
+            return;
+        }
+        self.order.retain(|(hash, _)| !removed.iter().any(|(removed_hash, _)| removed_hash == hash));
+        for (_, origin) in removed {
+            self.forget_origin(&origin);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    fn evict_oldest(&mut self) {
+        let (hash, prev_hash) = match self.order.pop_front() {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        let mut origin = None;
+        if let Some(siblings) = self.blocks.get_mut(&prev_hash) {
+            if let Some(i) = siblings.iter().position(|sibling| sibling.hash == hash) {
+                origin = siblings.remove(i).origin;
+            }
+            if siblings.is_empty() {
+                self.blocks.remove(&prev_hash);
+            }
+        }
+        self.forget_origin(&origin);
+    }
+
+    fn forget_origin(&mut self, origin: &Option<PeerId>) {
+        if let Some(origin) = origin {
+            let remaining = match self.origins.get_mut(origin) {
+                Some(count) => {
+                    *count -= 1;
+                    *count
+                },
+                None => return,
+            };
+            if remaining == 0 {
+                self.origins.remove(origin);
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Snapshot of the number of blocks pushed to the blockchain, by outcome.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PushStats {
+    pub invalid: usize,
+    pub orphan: usize,
+    pub known: usize,
+    pub extended: usize,
+    pub rebranched: usize,
+    /// Blocks that created or extended a fork.
+    pub forked: usize,
+}
+
+/// Always-on counters behind `PushStats`.
+#[derive(Default)]
+pub(crate) struct PushCounters {
+    invalid: AtomicUsize,
+    orphan: AtomicUsize,
+    known: AtomicUsize,
+    extended: AtomicUsize,
+    rebranched: AtomicUsize,
+    forked: AtomicUsize,
+}
+
+impl PushCounters {
+    #[inline]
+    pub fn note_invalid_block(&self) {
+        self.invalid.fetch_add(1, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn note_orphan_block(&self) {
+        self.orphan.fetch_add(1, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn note_known_block(&self) {
+        self.known.fetch_add(1, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn note_extended_block(&self) {
+        self.extended.fetch_add(1, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn note_rebranched_block(&self) {
+        self.rebranched.fetch_add(1, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn note_forked_block(&self) {
+        self.forked.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn snapshot(&self) -> PushStats {
+        PushStats {
+            invalid: self.invalid.load(Ordering::Acquire),
+            orphan: self.orphan.load(Ordering::Acquire),
+            known: self.known.load(Ordering::Acquire),
+            extended: self.extended.load(Ordering::Acquire),
+            rebranched: self.rebranched.load(Ordering::Acquire),
+            forked: self.forked.load(Ordering::Acquire),
+        }
+    }
+}
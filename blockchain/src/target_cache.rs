@@ -0,0 +1,70 @@
+use std::collections::{HashMap, VecDeque};
+
+use hash::Blake2bHash;
+use primitives::block::Target;
+
+/// Caches the next target following a block, keyed by the hash of that block and the block time (in seconds)
+/// the difficulty adjustment aims for. The target only depends on the chain ending at that block, so entries
+/// stay valid when the head moves. Holds at most `capacity` entries, the least recently used one is evicted first.
+#[derive(Debug)]
+pub struct TargetCache {
+    targets: HashMap<(Blake2bHash, u32), Target>,
+    /// Least recently used key first.
+    order: VecDeque<(Blake2bHash, u32)>,
+    capacity: usize,
+}
+
+impl TargetCache {
+    pub fn new(capacity: usize) -> Self {
+        TargetCache {
+            targets: HashMap::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn get(&mut self, hash: &Blake2bHash, block_time: u32) -> Option<Target> {
+        let key = (hash.clone(), block_time);
+        let target = self.targets.get(&key)?.clone();
+        self.touch(&key);
+        Some(target)
+    }
+
+    pub fn insert(&mut self, hash: Blake2bHash, block_time: u32, target: Target) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let key = (hash, block_time);
+        if self.targets.insert(key.clone(), target).is_some() {
+            self.touch(&key);
+            return;
+        }
+
+        if self.order.len() == self.capacity {
+            let evicted = self.order.pop_front().unwrap();
+            self.targets.remove(&evicted);
+        }
+        self.order.push_back(key);
+    }
+
+    pub fn clear(&mut self) {
+        self.targets.clear();
+        self.order.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    fn touch(&mut self, key: &(Blake2bHash, u32)) {
+        if let Some(i) = self.order.iter().position(|k| k == key) {
+            let key = self.order.remove(i).unwrap();
+            self.order.push_back(key);
+        }
+    }
+}
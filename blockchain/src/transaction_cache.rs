@@ -1,13 +1,17 @@
 use std::collections::{HashSet, VecDeque};
+use std::io;
 
+use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use database::{FromDatabaseValue, IntoDatabaseValue};
 use hash::{Blake2bHash, Hash};
 use primitives::block::Block;
 use primitives::policy;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct BlockDescriptor {
     hash: Blake2bHash,
     prev_hash: Blake2bHash,
+    #[beserial(len_type(u16))]
     transaction_hashes: Vec<Blake2bHash>
 }
 
@@ -42,6 +46,16 @@ impl TransactionCache {
         self.transaction_hashes.contains(&transaction_hash)
     }
 
+    /// Returns the hash of the cached block that includes the given transaction.
+    pub fn find_block(&self, transaction_hash: &Blake2bHash) -> Option<Blake2bHash> {
+        if !self.contains(transaction_hash) {
+            return None;
+        }
+        self.block_order.iter()
+            .find(|descriptor| descriptor.transaction_hashes.contains(transaction_hash))
+            .map(|descriptor| descriptor.hash.clone())
+    }
+
     pub fn contains_any(&self, block: &Block) -> bool {
         for transaction in block.body.as_ref().unwrap().transactions.iter() {
             if self.contains(&transaction.hash()) {
@@ -112,3 +126,67 @@ impl TransactionCache {
         self.block_order.front().as_ref().unwrap().hash.clone()
     }
 }
+
+// Only the block descriptors are serialized, the set of transaction hashes is rebuilt from them.
+impl Serialize for TransactionCache {
+    fn serialize<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, SerializingError> {
+        let mut size = Serialize::serialize(&(self.block_order.len() as u16), writer)?;
+        for descriptor in self.block_order.iter() {
+            size += Serialize::serialize(descriptor, writer)?;
+        }
+        Ok(size)
+    }
+
+    fn serialized_size(&self) -> usize {
+        let mut size = Serialize::serialized_size(&(self.block_order.len() as u16));
+        for descriptor in self.block_order.iter() {
+            size += Serialize::serialized_size(descriptor);
+        }
+        size
+    }
+}
+
+impl Deserialize for TransactionCache {
+    fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
+        let count: u16 = Deserialize::deserialize(reader)?;
+        if u32::from(count) > policy::TRANSACTION_VALIDITY_WINDOW {
+            return Err(SerializingError::InvalidValue);
+        }
+
+        let mut cache = TransactionCache::new();
+        for _ in 0..count {
+            let descriptor: BlockDescriptor = Deserialize::deserialize(reader)?;
+
+            // Blocks must form a chain and transactions must be unique, just like in `push_block`.
+            if let Some(prev) = cache.block_order.back() {
+                if descriptor.prev_hash != prev.hash {
+                    return Err(SerializingError::InvalidValue);
+                }
+            }
+            for hash in &descriptor.transaction_hashes {
+                if !cache.transaction_hashes.insert(hash.clone()) {
+                    return Err(SerializingError::InvalidValue);
+                }
+            }
+            cache.block_order.push_back(descriptor);
+        }
+        Ok(cache)
+    }
+}
+
+impl IntoDatabaseValue for TransactionCache {
+    fn database_byte_size(&self) -> usize {
+        return self.serialized_size();
+    }
+
+    fn copy_into_database(&self, mut bytes: &mut [u8]) {
+        Serialize::serialize(&self, &mut bytes).unwrap();
+    }
+}
+
+impl FromDatabaseValue for TransactionCache {
+    fn copy_from_database(bytes: &[u8]) -> io::Result<Self> where Self: Sized {
+        let mut cursor = io::Cursor::new(bytes);
+        return Ok(Deserialize::deserialize(&mut cursor)?);
+    }
+}
@@ -0,0 +1,43 @@
+use beserial::Deserialize;
+use nimiq_blockchain::block_queue::BlockQueue;
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_primitives::block::Block;
+
+fn block(prev_hash: Blake2bHash, height: u32) -> Block {
+    let mut block = Block::deserialize_from_vec(&hex::decode(crate::blockchain::BLOCK_2).unwrap()).unwrap();
+    block.header.prev_hash = prev_hash;
+    block.header.height = height;
+    block
+}
+
+#[test]
+fn it_pops_blocks_by_height_then_linkage() {
+    let mut queue = BlockQueue::new(10);
+    let parent = block(Blake2bHash::from([1u8; Blake2bHash::SIZE]), 10);
+    let sibling = block(Blake2bHash::from([2u8; Blake2bHash::SIZE]), 11);
+    let child = block(parent.header.hash(), 11);
+    let grandchild = block(child.header.hash(), 12);
+
+    assert!(queue.push(grandchild.clone()));
+    assert!(queue.push(sibling.clone()));
+    assert!(queue.push(child.clone()));
+    assert!(queue.push(parent.clone()));
+    assert!(!queue.push(child.clone()));
+    assert_eq!(queue.len(), 4);
+
+    // The child was queued after its sibling, but links up with the parent taken before.
+    assert_eq!(queue.pop(), Some(parent));
+    assert_eq!(queue.pop(), Some(child));
+    assert_eq!(queue.pop(), Some(sibling));
+    assert_eq!(queue.pop(), Some(grandchild));
+    assert_eq!(queue.pop(), None);
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn it_rejects_blocks_when_full() {
+    let mut queue = BlockQueue::new(1);
+    assert!(queue.push(block(Blake2bHash::from([1u8; Blake2bHash::SIZE]), 10)));
+    assert!(!queue.push(block(Blake2bHash::from([2u8; Blake2bHash::SIZE]), 10)));
+    assert_eq!(queue.len(), 1);
+}
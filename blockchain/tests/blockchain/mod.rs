@@ -1,62 +1,102 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use atomic::{Atomic, Ordering};
 
 use beserial::{Deserialize, Serialize};
-use nimiq_blockchain::{Blockchain, BlockchainEvent, PushError, PushResult};
+use nimiq_accounts::Accounts;
+use nimiq_blockchain::{Blockchain, BlockchainConfig, BlockchainEvent, BlockchainMirror, ForkChoice, HeaviestChain, PushError, PushErrorContext, PushResult, PushStats, TxAdmissionError};
+use nimiq_blockchain::chain_info::ChainInfo;
+use nimiq_blockchain::chain_store::ChainStore;
 use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_database::{Environment, WriteTransaction};
 use nimiq_hash::{Hash, Blake2bHash};
 use nimiq_keys::{Address, KeyPair, PrivateKey};
+use nimiq_network_primitives::networks::get_network_info;
 use nimiq_network_primitives::time::NetworkTime;
 use nimiq_primitives::account::{AccountError, AccountType};
-use nimiq_primitives::block::{Block, BlockError};
+use nimiq_primitives::block::{Block, BlockError, BlockHeader, Difficulty, Target, TargetCompact};
+use nimiq_primitives::coin::Coin;
 use nimiq_primitives::networks::NetworkId;
-use nimiq_primitives::transaction::{SignatureProof, Transaction};
+use nimiq_primitives::policy;
+use nimiq_primitives::transaction::{SignatureProof, Transaction, TransactionError};
+use nimiq_utils::unique_ptr::UniquePtr;
 
-const BLOCK_2: &str = "0001264aaf8a4f9828a76c550635da078eb466306a189fcc03710bee9f649c869d120492e3986e75ac0d1466b5d6a7694c86839767a30980f8ba0d8c6e48631bc9cdd8a3eb957567d76963ad10d11e65453f763928fb9619e5f396a0906e946cce3ca7fcbb5fb2e35055de071e868381ba426a8d79d97cb48dab8345baeb9a9abb091f010000000000025ad23a98000046fe0180010000000000000000000000000000000000000000184d696e65642077697468206c6f766520627920526963687900000000";
-const BLOCK_3: &str = "0001bab534467866d83060b1af0b3493dd0f97d7071b16e1562cf4b18bdf73e71ccb4aa1fea2b8cdf2a63411776c6391a7659aef4dd25317a615499c7b461e9a0405385dbed68e76f74317cc6f4cd40db832eb71b8338fad024ddbb88f9abc79f199dd6a3500aeb5479eb460afeab3363783e243a6e551536c3c01c8fca21d7afbbb1f00fddd000000035ad23a980000968102c0010000000000000000000000000000000000000000184d696e65642077697468206c6f76652062792054616d6d6f00000000";
-const BLOCK_4: &str = "0001622b0536bbe764a5723f17cde03d2fa2b67a3f42f7cab082c72222eb1e48db7a607f7686d7636b500cfa620567ede30a15a12f69e22d35dd004bbdbfcaefc12520428a900c8dfb339b99aebb1d14cc4d5cebedf562aa1806f272deecbf3c5263b62534d1cda41d1a7bf70a6850c6c82936adb9b2ef66b7421ca3c55664c1417f1f00fbb7000000045ad23a9800022dc60280bab534467866d83060b1af0b3493dd0f97d7071b16e1562cf4b18bdf73e71ccb0100000000000000000000000000000000000000001b4d696e65642077697468206c6f7665206279204372697374696e6100000000";
-const BLOCK_5: &str = "000184d5a44ba5ae9961837e7fb19c176a19f77b2e0655873149017351e17b622cef4aa1fea2b8cdf2a63411776c6391a7659aef4dd25317a615499c7b461e9a0405b32082f43aae5c61bf1171e85650b550bcc2b8d020365619ecaeb924c4562770cbadc05e0c4117bf975bc3d7e55d2f3a13efe1a9baf17c0b2c3c42faee9414b31f00f98c000000055ad23a9800013f5602c0010000000000000000000000000000000000000000174d696e65642077697468206c6f7665206279204174756100000000";
+pub(crate) const BLOCK_2: &str = "0001264aaf8a4f9828a76c550635da078eb466306a189fcc03710bee9f649c869d120492e3986e75ac0d1466b5d6a7694c86839767a30980f8ba0d8c6e48631bc9cdd8a3eb957567d76963ad10d11e65453f763928fb9619e5f396a0906e946cce3ca7fcbb5fb2e35055de071e868381ba426a8d79d97cb48dab8345baeb9a9abb091f010000000000025ad23a98000046fe0180010000000000000000000000000000000000000000184d696e65642077697468206c6f766520627920526963687900000000";
+pub(crate) const BLOCK_3: &str = "0001bab534467866d83060b1af0b3493dd0f97d7071b16e1562cf4b18bdf73e71ccb4aa1fea2b8cdf2a63411776c6391a7659aef4dd25317a615499c7b461e9a0405385dbed68e76f74317cc6f4cd40db832eb71b8338fad024ddbb88f9abc79f199dd6a3500aeb5479eb460afeab3363783e243a6e551536c3c01c8fca21d7afbbb1f00fddd000000035ad23a980000968102c0010000000000000000000000000000000000000000184d696e65642077697468206c6f76652062792054616d6d6f00000000";
+pub(crate) const BLOCK_4: &str = "0001622b0536bbe764a5723f17cde03d2fa2b67a3f42f7cab082c72222eb1e48db7a607f7686d7636b500cfa620567ede30a15a12f69e22d35dd004bbdbfcaefc12520428a900c8dfb339b99aebb1d14cc4d5cebedf562aa1806f272deecbf3c5263b62534d1cda41d1a7bf70a6850c6c82936adb9b2ef66b7421ca3c55664c1417f1f00fbb7000000045ad23a9800022dc60280bab534467866d83060b1af0b3493dd0f97d7071b16e1562cf4b18bdf73e71ccb0100000000000000000000000000000000000000001b4d696e65642077697468206c6f7665206279204372697374696e6100000000";
+pub(crate) const BLOCK_5: &str = "000184d5a44ba5ae9961837e7fb19c176a19f77b2e0655873149017351e17b622cef4aa1fea2b8cdf2a63411776c6391a7659aef4dd25317a615499c7b461e9a0405b32082f43aae5c61bf1171e85650b550bcc2b8d020365619ecaeb924c4562770cbadc05e0c4117bf975bc3d7e55d2f3a13efe1a9baf17c0b2c3c42faee9414b31f00f98c000000055ad23a9800013f5602c0010000000000000000000000000000000000000000174d696e65642077697468206c6f7665206279204174756100000000";
+
+fn block_from_hex(hex_str: &str) -> Block {
+    Block::deserialize_from_vec(&hex::decode(hex_str).unwrap()).unwrap()
+}
+
+fn new_blockchain<'env>(env: &'env Environment) -> Blockchain<'env> {
+    Blockchain::new(env, NetworkId::Main, Arc::new(NetworkTime::new()))
+}
 
 #[test]
 fn it_can_load_a_stored_chain() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    let block = block_from_hex(BLOCK_2);
     let hash = block.header.hash();
 
     {
-        let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+        let blockchain = Arc::new(new_blockchain(&env));
         let status = blockchain.push(block);
         assert_eq!(status, PushResult::Extended);
     }
 
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(new_blockchain(&env));
     assert_eq!(blockchain.height(), 2);
     assert_eq!(blockchain.head_hash(), hash);
 }
 
+#[test]
+#[should_panic(expected = "Inconsistent chain/accounts state")]
+fn it_rejects_an_inconsistent_accounts_state_on_load() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let genesis_hash = new_blockchain(&env).head_hash();
+
+    // Store a head whose accounts hash does not match the stored accounts state.
+    let mut block = block_from_hex(BLOCK_2);
+    block.header.accounts_hash = Blake2bHash::from([1u8; Blake2bHash::SIZE]);
+    let hash: Blake2bHash = block.header.hash();
+    let store = ChainStore::new(&env);
+    let mut info = store.get_chain_info(&genesis_hash, true, None).unwrap().next(block);
+    info.on_main_chain = true;
+    let mut txn = WriteTransaction::new(&env);
+    store.put_chain_info(&mut txn, &hash, &info, true);
+    store.set_head(&mut txn, &hash);
+    txn.commit();
+
+    new_blockchain(&env);
+}
+
 #[test]
 fn it_can_extend_the_main_chain() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(new_blockchain(&env));
 
-    let mut block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    let mut block = block_from_hex(BLOCK_2);
     let mut status = blockchain.push(block);
     assert_eq!(status, PushResult::Extended);
 
-    block = Block::deserialize_from_vec(&hex::decode(BLOCK_3).unwrap()).unwrap();
+    block = block_from_hex(BLOCK_3);
     status = blockchain.push(block);
     assert_eq!(status, PushResult::Extended);
 
-    block = Block::deserialize_from_vec(&hex::decode(BLOCK_4).unwrap()).unwrap();
+    block = block_from_hex(BLOCK_4);
     status = blockchain.push(block);
     assert_eq!(status, PushResult::Extended);
 
-    block = Block::deserialize_from_vec(&hex::decode(BLOCK_5).unwrap()).unwrap();
+    block = block_from_hex(BLOCK_5);
     status = blockchain.push(block);
     assert_eq!(status, PushResult::Extended);
 }
@@ -66,9 +106,9 @@ fn it_detects_known_blocks() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(new_blockchain(&env));
 
-    let block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    let block = block_from_hex(BLOCK_2);
     let mut status = blockchain.push(block.clone());
     assert_eq!(status, PushResult::Extended);
 
@@ -81,24 +121,112 @@ fn it_rejects_orphan_blocks() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(new_blockchain(&env));
 
-    let block = Block::deserialize_from_vec(&hex::decode(BLOCK_3).unwrap()).unwrap();
+    let block = block_from_hex(BLOCK_3);
     let status = blockchain.push(block);
     assert_eq!(status, PushResult::Orphan);
 }
 
+#[test]
+fn it_pushes_queued_blocks_in_order() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    let blocks: Vec<Block> = [BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5].iter()
+        .map(|block| block_from_hex(block))
+        .collect();
+    let expected_heights = Arc::new(Atomic::new(2u32));
+    let expected_heights1 = expected_heights.clone();
+    blockchain.notifier.write().register(move |e: &BlockchainEvent| {
+        if let BlockchainEvent::Extended(_, block) = e {
+            assert_eq!(block.header.height, expected_heights1.load(Ordering::Relaxed));
+            expected_heights1.store(block.header.height + 1, Ordering::Relaxed);
+        }
+    });
+
+    for i in &[2, 0, 3, 1] {
+        assert_eq!(blockchain.enqueue_block(blocks[*i].clone()), Ok(true));
+    }
+    assert_eq!(blockchain.enqueue_block(blocks[0].clone()), Ok(false));
+
+    // Blocks with an invalid proof-of-work are rejected before they are queued.
+    let mut invalid = blocks[0].clone();
+    invalid.header.nonce += 1;
+    assert!(blockchain.enqueue_block(invalid).is_err());
+    assert_eq!(blockchain.queue_len(), 4);
+    assert_eq!(blockchain.height(), 1);
+
+    assert_eq!(blockchain.wait_and_process_queue(Duration::from_secs(10)), vec![PushResult::Extended; 4]);
+    assert_eq!(blockchain.queue_len(), 0);
+    assert_eq!(blockchain.height(), 5);
+    assert_eq!(expected_heights.load(Ordering::Relaxed), 6);
+    assert!(blockchain.wait_and_process_queue(Duration::from_millis(10)).is_empty());
+}
+
+#[test]
+fn it_re_pushes_orphans_once_their_predecessor_is_known() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(new_blockchain(&env));
+
+    let block3 = block_from_hex(BLOCK_3);
+    let block4 = block_from_hex(BLOCK_4);
+    assert_eq!(blockchain.push(block4.clone()), PushResult::Orphan);
+    assert_eq!(blockchain.push(block3), PushResult::Orphan);
+    assert_eq!(blockchain.push(block4), PushResult::Orphan);
+    assert_eq!(blockchain.orphan_count(), 2);
+
+    let block2 = block_from_hex(BLOCK_2);
+    assert_eq!(blockchain.push(block2), PushResult::Extended);
+    assert_eq!(blockchain.height(), 4);
+    assert_eq!(blockchain.orphan_count(), 0);
+}
+
+#[test]
+fn it_caps_the_number_of_buffered_orphans() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let mut blockchain = new_blockchain(&env);
+    blockchain.max_orphans = 1;
+
+    let block3 = block_from_hex(BLOCK_3);
+    let block4 = block_from_hex(BLOCK_4);
+    let block5 = block_from_hex(BLOCK_5);
+    assert_eq!(blockchain.push(block3.clone()), PushResult::Orphan);
+    assert_eq!(blockchain.orphan_count(), 1);
+
+    // The newer orphan evicts the older one.
+    assert_eq!(blockchain.push(block5), PushResult::Orphan);
+    assert_eq!(blockchain.orphan_count(), 1);
+
+    let block2 = block_from_hex(BLOCK_2);
+    assert_eq!(blockchain.push(block2), PushResult::Extended);
+    assert_eq!(blockchain.height(), 2);
+    assert_eq!(blockchain.orphan_count(), 1);
+
+    assert_eq!(blockchain.push(block3), PushResult::Extended);
+    assert_eq!(blockchain.push(block4), PushResult::Extended);
+    assert_eq!(blockchain.height(), 5);
+    assert_eq!(blockchain.orphan_count(), 0);
+}
+
 #[test]
 fn it_rejects_intrisically_invalid_blocks() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(new_blockchain(&env));
 
-    let mut block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    let mut block = block_from_hex(BLOCK_2);
     block.header.nonce = 1;
+    let context = PushErrorContext::from(&block);
     let status = blockchain.push(block);
-    assert_eq!(status, PushResult::Invalid(PushError::InvalidBlock(BlockError::InvalidPoW)));
+    assert_eq!(status, PushResult::Invalid(PushError::InvalidBlock(BlockError::InvalidPoW, Some(context))));
 }
 
 #[test]
@@ -106,14 +234,15 @@ fn it_rejects_invalid_successors() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(new_blockchain(&env));
 
-    let mut block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    let mut block = block_from_hex(BLOCK_2);
     block.header.timestamp = 5000;
     block.header.nonce = 54095;
+    let context = PushErrorContext::from(&block);
 
     let status = blockchain.push(block);
-    assert_eq!(status, PushResult::Invalid(PushError::InvalidSuccessor));
+    assert_eq!(status, PushResult::Invalid(PushError::InvalidSuccessor(context)));
 }
 
 #[test]
@@ -121,14 +250,159 @@ fn it_rejects_blocks_with_invalid_difficulty() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let blockchain = Arc::new(new_blockchain(&env));
 
-    let mut block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    let mut block = block_from_hex(BLOCK_2);
     block.header.n_bits = 0x1f051234.into();
     block.header.nonce = 51485;
+    let context = PushErrorContext::from(&block);
 
     let status = blockchain.push(block);
-    assert_eq!(status, PushResult::Invalid(PushError::DifficultyMismatch));
+    assert_eq!(status, PushResult::Invalid(PushError::DifficultyMismatch(context)));
+}
+
+#[test]
+fn it_finds_transactions_by_hash() {
+    crate::setup();
+
+    let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
+    let miner = Address::from(&keypair.public);
+    let env = VolatileEnvironment::new(10).unwrap();
+
+    let mut tx1 = Transaction::new_basic(
+        miner.clone(),
+        [2u8; Address::SIZE].into(),
+        10.into(),
+        0.into(),
+        1,
+        NetworkId::Main
+    );
+    tx1.proof = SignatureProof::from(keypair.public.clone(), keypair.sign(&tx1.serialize_content())).serialize_to_vec();
+    let mut tx2 = Transaction::new_basic(
+        miner.clone(),
+        [3u8; Address::SIZE].into(),
+        5.into(),
+        0.into(),
+        2,
+        NetworkId::Main
+    );
+    tx2.proof = SignatureProof::from(keypair.public.clone(), keypair.sign(&tx2.serialize_content())).serialize_to_vec();
+    let mut tx3 = Transaction::new_basic(
+        miner.clone(),
+        [4u8; Address::SIZE].into(),
+        5.into(),
+        0.into(),
+        2,
+        NetworkId::Main
+    );
+    tx3.proof = SignatureProof::from(keypair.public.clone(), keypair.sign(&tx3.serialize_content())).serialize_to_vec();
+
+    let hash3: Blake2bHash;
+    let hash4: Blake2bHash;
+    {
+        let blockchain = new_blockchain(&env);
+        let block2 = crate::next_block(&blockchain)
+            .with_miner(miner.clone())
+            .with_nonce(34932)
+            .build();
+        assert_eq!(blockchain.push(block2), PushResult::Extended);
+
+        let block3 = crate::next_block(&blockchain)
+            .with_miner(miner.clone())
+            .with_transactions(vec![tx1.clone()])
+            .with_nonce(23026)
+            .build();
+        hash3 = block3.header.hash();
+        assert_eq!(blockchain.push(block3), PushResult::Extended);
+        assert_eq!(blockchain.get_transaction_info_by_hash(&tx1.hash()), Some((hash3.clone(), 3, 0)));
+
+        // Transactions are ordered by recipient within the block.
+        let block4 = crate::next_block(&blockchain)
+            .with_transactions(vec![tx2.clone(), tx3.clone()])
+            .mine();
+        hash4 = block4.header.hash();
+        assert_eq!(blockchain.push(block4), PushResult::Extended);
+        assert_eq!(blockchain.get_transaction_info_by_hash(&tx3.hash()), Some((hash4.clone(), 4, 1)));
+    }
+
+    // The transaction cache wasn't persisted, so it is rebuilt from the store on load.
+    let blockchain = new_blockchain(&env);
+    assert_eq!(blockchain.get_transaction_info_by_hash(&tx1.hash()), Some((hash3, 3, 0)));
+    assert_eq!(blockchain.get_transaction_info_by_hash(&tx2.hash()), Some((hash4.clone(), 4, 0)));
+    assert_eq!(blockchain.get_transaction_info_by_hash(&tx3.hash()), Some((hash4, 4, 1)));
+    assert_eq!(blockchain.get_transaction_info_by_hash(&Blake2bHash::from([1u8; Blake2bHash::SIZE])), None);
+}
+
+#[test]
+fn it_persists_the_transaction_cache_on_request() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_2)), PushResult::Extended);
+    let head_hash = blockchain.head_hash();
+    assert!(ChainStore::new(&env).get_transaction_cache(None).is_none());
+
+    blockchain.persist_transaction_cache();
+    assert_eq!(ChainStore::new(&env).get_transaction_cache(None).unwrap().head_hash(), head_hash);
+}
+
+#[test]
+fn it_lists_the_transactions_of_an_address() {
+    crate::setup();
+
+    let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    let miner = Address::from(&keypair.public);
+    let block2 = crate::next_block(&blockchain)
+        .with_miner(miner.clone())
+        .with_nonce(34932)
+        .build();
+    assert_eq!(blockchain.push(block2), PushResult::Extended);
+
+    let mut tx_out = Transaction::new_basic(
+        miner.clone(),
+        [2u8; Address::SIZE].into(),
+        10.into(),
+        0.into(),
+        1,
+        NetworkId::Main
+    );
+    tx_out.proof = SignatureProof::from(keypair.public.clone(), keypair.sign(&tx_out.serialize_content())).serialize_to_vec();
+    let block3 = crate::next_block(&blockchain)
+        .with_miner(miner.clone())
+        .with_transactions(vec![tx_out.clone()])
+        .with_nonce(23026)
+        .build();
+    assert_eq!(blockchain.push(block3), PushResult::Extended);
+
+    let mut tx4 = Transaction::new_basic(
+        miner.clone(),
+        [5u8; Address::SIZE].into(),
+        5.into(),
+        0.into(),
+        2,
+        NetworkId::Main
+    );
+    tx4.proof = SignatureProof::from(keypair.public.clone(), keypair.sign(&tx4.serialize_content())).serialize_to_vec();
+    let block4 = crate::next_block(&blockchain)
+        .with_transactions(vec![tx4.clone()])
+        .mine();
+    assert_eq!(blockchain.push(block4), PushResult::Extended);
+
+    // Newest blocks first, limited to the given number of blocks.
+    assert_eq!(blockchain.transactions_for_address(&miner, 10), (vec![(tx4.clone(), 4), (tx_out.clone(), 3)], 1));
+    assert_eq!(blockchain.transactions_for_address(&miner, 1), (vec![(tx4.clone(), 4)], 4));
+    assert_eq!(blockchain.transactions_for_address(&miner, 2), (vec![(tx4.clone(), 4), (tx_out.clone(), 3)], 3));
+
+    // Transactions are found by recipient as well.
+    assert_eq!(blockchain.transactions_for_address(&[2u8; Address::SIZE].into(), 10), (vec![(tx_out, 3)], 1));
+    assert_eq!(blockchain.transactions_for_address(&[5u8; Address::SIZE].into(), 10), (vec![(tx4, 4)], 1));
+    assert!(blockchain.transactions_for_address(&[4u8; Address::SIZE].into(), 10).0.is_empty());
+    assert_eq!(blockchain.transactions_for_address(&miner, 0), (vec![], 5));
 }
 
 #[test]
@@ -138,7 +412,7 @@ fn it_rejects_blocks_with_duplicate_transactions() {
     let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    let blockchain = new_blockchain(&env);
 
     let miner = Address::from(&keypair.public);
     let block2 = crate::next_block(&blockchain)
@@ -172,8 +446,10 @@ fn it_rejects_blocks_with_duplicate_transactions() {
         .with_transactions(vec![tx])
         .with_nonce(6471)
         .build();
+    let context = PushErrorContext::from(&block4);
+    assert_eq!(context.height, 4);
     status = blockchain.push(block4);
-    assert_eq!(status, PushResult::Invalid(PushError::DuplicateTransaction));
+    assert_eq!(status, PushResult::Invalid(PushError::DuplicateTransaction(context)));
 }
 
 #[test]
@@ -183,7 +459,7 @@ fn it_rejects_blocks_if_body_cannot_be_applied() {
     let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    let blockchain = new_blockchain(&env);
 
     let miner = Address::from(&keypair.public);
     let block2 = crate::next_block(&blockchain)
@@ -209,8 +485,9 @@ fn it_rejects_blocks_if_body_cannot_be_applied() {
         .with_transactions(vec![tx.clone()])
         .with_nonce(31302)
         .build();
+    let context = PushErrorContext::from(&block3);
     status = blockchain.push(block3);
-    assert_eq!(status, PushResult::Invalid(PushError::AccountsError(AccountError::InsufficientFunds)));
+    assert_eq!(status, PushResult::Invalid(PushError::AccountsError(AccountError::InsufficientFunds, Some(context))));
 
     // Tx with wrong sender type
     tx = Transaction::new_basic(
@@ -228,22 +505,66 @@ fn it_rejects_blocks_if_body_cannot_be_applied() {
         .with_transactions(vec![tx.clone()])
         .with_nonce(127678)
         .build();
+    let context = PushErrorContext::from(&block3);
     status = blockchain.push(block3);
-    assert_eq!(status, PushResult::Invalid(PushError::AccountsError(AccountError::TypeMismatch)));
+    assert_eq!(status, PushResult::Invalid(PushError::AccountsError(AccountError::TypeMismatch, Some(context))));
+}
+
+#[test]
+fn it_rejects_blocks_with_mismatched_accounts_hash() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let head_hash = blockchain.head_hash();
+    let accounts_hash = blockchain.accounts().hash(None);
+
+    let mut block = crate::next_block(&blockchain).build();
+    block.header.accounts_hash = [1u8; Blake2bHash::SIZE].into();
+    crate::mine_header(&mut block.header);
+    let context = PushErrorContext::from(&block);
+
+    let status = blockchain.push(block);
+    assert_eq!(status, PushResult::Invalid(PushError::AccountsHashMismatch(Some(context))));
+    assert_eq!(blockchain.head_hash(), head_hash);
+    assert_eq!(blockchain.accounts().hash(None), accounts_hash);
 }
 
 #[test]
 fn it_detects_fork_blocks() {
+    crate::setup();
+
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    let blockchain = new_blockchain(&env);
 
     let mut block = crate::next_block(&blockchain)
         .with_nonce(83054)
         .build();
     assert_eq!(blockchain.push(block), PushResult::Extended);
 
-    block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    block = block_from_hex(BLOCK_2);
+    assert_eq!(blockchain.push(block), PushResult::Forked);
+}
+
+#[test]
+fn it_distinguishes_new_forks_from_fork_extensions() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    for nonce in [83054, 23192, 39719].iter() {
+        let block = crate::next_block(&blockchain)
+            .with_nonce(*nonce)
+            .build();
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+
+    let mut block = block_from_hex(BLOCK_2);
     assert_eq!(blockchain.push(block), PushResult::Forked);
+
+    block = block_from_hex(BLOCK_3);
+    assert_eq!(blockchain.push(block), PushResult::ForkExtended);
 }
 
 #[test]
@@ -251,7 +572,7 @@ fn it_rebranches_to_the_harder_chain() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    let blockchain = new_blockchain(&env);
 
     let block1_2 = crate::next_block(&blockchain)
         .with_nonce(83054)
@@ -267,15 +588,15 @@ fn it_rebranches_to_the_harder_chain() {
         .with_nonce(39719)
         .build();
 
-    let block2_2 = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    let block2_2 = block_from_hex(BLOCK_2);
     assert_eq!(blockchain.push(block2_2.clone()), PushResult::Forked);
 
-    let block2_3 = Block::deserialize_from_vec(&hex::decode(BLOCK_3).unwrap()).unwrap();
+    let block2_3 = block_from_hex(BLOCK_3);
     assert_eq!(blockchain.push(block2_3.clone()), PushResult::Rebranched);
 
     assert_eq!(blockchain.push(block1_4.clone()), PushResult::Rebranched);
 
-    let block2_4 = Block::deserialize_from_vec(&hex::decode(BLOCK_4).unwrap()).unwrap();
+    let block2_4 = block_from_hex(BLOCK_4);
 
     let listener_called = Arc::new(Atomic::new(false));
     let reverted_blocks = Arc::new(vec![(block1_2.header.hash(), block1_2), (block1_3.header.hash(), block1_3), (block1_4.header.hash(), block1_4)]);
@@ -290,36 +611,1667 @@ fn it_rebranches_to_the_harder_chain() {
     assert!(listener_called.load(Ordering::Relaxed));
 }
 
+#[test]
+fn it_finds_the_common_block_of_locators() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let genesis_hash = blockchain.head_hash();
+    let fork_block = crate::next_block(&blockchain)
+        .with_nonce(83054)
+        .build();
+    let fork_hash: Blake2bHash = fork_block.header.hash();
+
+    let block2 = block_from_hex(BLOCK_2);
+    let hash2: Blake2bHash = block2.header.hash();
+    assert_eq!(blockchain.push(block2), PushResult::Extended);
+    let block3 = block_from_hex(BLOCK_3);
+    let hash3: Blake2bHash = block3.header.hash();
+    assert_eq!(blockchain.push(block3), PushResult::Extended);
+    assert_eq!(blockchain.push(fork_block), PushResult::Forked);
+
+    // Unknown blocks and forks are skipped.
+    let unknown_hash = Blake2bHash::from([1u8; Blake2bHash::SIZE]);
+    let locators = vec![unknown_hash.clone(), fork_hash.clone(), hash3.clone(), hash2.clone(), genesis_hash.clone()];
+    assert_eq!(blockchain.find_common_block(&locators), Some((hash3, 3)));
+    assert_eq!(blockchain.find_common_block(&locators[3..]), Some((hash2, 2)));
+    assert_eq!(blockchain.find_common_block(&[fork_hash, genesis_hash.clone()]), Some((genesis_hash, 1)));
+    assert_eq!(blockchain.find_common_block(&[unknown_hash]), None);
+    assert_eq!(blockchain.find_common_block(&[]), None);
+}
+
+#[test]
+fn it_notifies_about_forks() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    let block1_2 = crate::next_block(&blockchain)
+        .with_nonce(83054)
+        .build();
+    assert_eq!(blockchain.push(block1_2), PushResult::Extended);
+
+    let block2_2 = block_from_hex(BLOCK_2);
+    let hash2_2: Blake2bHash = block2_2.header.hash();
+    let listener_called = Arc::new(Atomic::new(false));
+    let listener_called1 = listener_called.clone();
+    let expected_block = block2_2.clone();
+    blockchain.notifier.write().register(move |e: &BlockchainEvent| {
+        match e {
+            BlockchainEvent::Forked(hash, block) => {
+                assert_eq!(*hash, hash2_2);
+                assert_eq!(*block.as_ref(), expected_block);
+            },
+            e => panic!("Unexpected event {:?}", e),
+        }
+        listener_called1.store(true, Ordering::Relaxed);
+    });
+
+    assert_eq!(blockchain.push(block2_2), PushResult::Forked);
+    assert!(listener_called.load(Ordering::Relaxed));
+}
+
+#[test]
+fn it_can_mirror_a_blockchain_from_events() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let mirror_env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let mirror = Arc::new(BlockchainMirror::new(&mirror_env, NetworkId::Main));
+
+    let mirror1 = mirror.clone();
+    blockchain.notifier.write().register(move |e: &BlockchainEvent| mirror1.on_event(e));
+
+    for nonce in [83054, 23192].iter() {
+        let block = crate::next_block(&blockchain)
+            .with_nonce(*nonce)
+            .build();
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+    assert_eq!(mirror.height(), 3);
+    assert_eq!(mirror.head_hash(), blockchain.head_hash());
+
+    // Rebranch to the harder chain, the mirror follows.
+    for block in [BLOCK_2, BLOCK_3, BLOCK_4].iter() {
+        let block = block_from_hex(block);
+        blockchain.push(block);
+    }
+    assert_eq!(blockchain.height(), 4);
+
+    assert_eq!(mirror.height(), blockchain.height());
+    assert_eq!(mirror.head_hash(), blockchain.head_hash());
+    assert_eq!(mirror.accounts().hash(None), blockchain.accounts().hash(None));
+    assert_eq!(*mirror.head(), *blockchain.head());
+    for height in 1..=4 {
+        assert_eq!(mirror.get_block_at(height, true), blockchain.get_block_at(height, true));
+    }
+}
+
+#[test]
+fn it_can_mirror_a_revert_without_adopted_blocks() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let mirror_env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let mirror = Arc::new(BlockchainMirror::new(&mirror_env, NetworkId::Main));
+
+    let mirror1 = mirror.clone();
+    blockchain.notifier.write().register(move |e: &BlockchainEvent| mirror1.on_event(e));
+
+    for block in [BLOCK_2, BLOCK_3, BLOCK_4].iter() {
+        let block = block_from_hex(block);
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+    assert_eq!(mirror.height(), 4);
+
+    // Reverting emits a rebranch without any adopted blocks.
+    let reverted = blockchain.revert_to_height(2).unwrap();
+    assert_eq!(reverted.len(), 2);
+
+    assert_eq!(mirror.height(), 2);
+    assert_eq!(mirror.head_hash(), blockchain.head_hash());
+    assert_eq!(mirror.accounts().hash(None), blockchain.accounts().hash(None));
+    assert_eq!(*mirror.head(), *blockchain.head());
+    assert_eq!(mirror.get_block_at(3, false), None);
+}
+
 #[test]
 fn it_can_compute_chain_proofs() {
     crate::setup();
 
     let env = VolatileEnvironment::new(10).unwrap();
-    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    let blockchain = new_blockchain(&env);
 
-    let proof = blockchain.get_chain_proof();
+    let proof = blockchain.get_chain_proof().unwrap();
     assert_eq!(proof.prefix.len(), 1);
     assert_eq!(proof.prefix[0].header.hash::<Blake2bHash>(), blockchain.head_hash());
     assert!(proof.suffix.is_empty());
 
-    let mut block = Block::deserialize_from_vec(&hex::decode(BLOCK_2).unwrap()).unwrap();
+    let mut block = block_from_hex(BLOCK_2);
     let mut status = blockchain.push(block);
     assert_eq!(status, PushResult::Extended);
 
-    let proof = blockchain.get_chain_proof();
+    let proof = blockchain.get_chain_proof().unwrap();
     assert_eq!(proof.prefix.len(), 1);
     assert_eq!(proof.prefix[0].header.height, 1);
     assert_eq!(proof.suffix.len(), 1);
     assert_eq!(proof.suffix[0].hash::<Blake2bHash>(), blockchain.head_hash());
 
-    block = Block::deserialize_from_vec(&hex::decode(BLOCK_3).unwrap()).unwrap();
+    block = block_from_hex(BLOCK_3);
     status = blockchain.push(block);
     assert_eq!(status, PushResult::Extended);
 
-    let proof = blockchain.get_chain_proof();
+    let proof = blockchain.get_chain_proof().unwrap();
     assert_eq!(proof.prefix.len(), 1);
     assert_eq!(proof.prefix[0].header.height, 1);
     assert_eq!(proof.suffix.len(), 2);
     assert_eq!(proof.suffix[0].height, 2);
     assert_eq!(proof.suffix[1].hash::<Blake2bHash>(), blockchain.head_hash());
 }
+
+#[cfg(feature = "metrics")]
+#[test]
+fn it_records_push_phase_timings() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(new_blockchain(&env));
+
+    let block = block_from_hex(BLOCK_2);
+    let status = blockchain.push(block);
+    assert_eq!(status, PushResult::Extended);
+
+    let timings = blockchain.metrics.push_timings();
+    for histogram in &[&timings.verify, &timings.difficulty, &timings.transaction_cache, &timings.accounts_commit, &timings.store_write] {
+        assert_eq!(histogram.count(), 1);
+        assert!(histogram.sum() > Duration::from_secs(0));
+        assert_eq!(*histogram.cumulative_counts().last().unwrap(), 1);
+    }
+}
+
+#[test]
+fn it_estimates_the_time_to_produce_blocks() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    // A fresh chain is at steady state: difficulty 1 and one block per Policy.BLOCK_TIME.
+    let window_time = Duration::from_secs(u64::from(policy::DIFFICULTY_BLOCK_WINDOW * policy::BLOCK_TIME));
+    assert_eq!(blockchain.expected_time_to_blocks(policy::DIFFICULTY_BLOCK_WINDOW), window_time);
+    assert_eq!(blockchain.expected_time_to_blocks(1), Duration::from_secs(u64::from(policy::BLOCK_TIME)));
+    assert_eq!(blockchain.expected_time_to_blocks(0), Duration::from_secs(0));
+}
+
+#[test]
+fn it_computes_the_next_difficulty() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    // At steady state the next difficulty is the minimal difficulty 1.
+    let target = blockchain.get_next_target(None);
+    let difficulty = blockchain.get_next_difficulty(None);
+    assert_eq!(difficulty, Difficulty::from(1));
+    assert_eq!(difficulty, Difficulty::from(target.clone()));
+    assert_eq!(TargetCompact::from(difficulty), TargetCompact::from(target));
+
+    let head_hash = blockchain.head_hash();
+    assert_eq!(blockchain.get_next_difficulty(Some(&head_hash)), blockchain.get_next_difficulty(None));
+}
+
+#[test]
+fn it_enforces_the_median_time_past() {
+    crate::setup();
+
+    let window = policy::MEDIAN_TIME_PAST_WINDOW;
+    let mut prev_headers = Vec::new();
+    for i in 0..window {
+        let mut prev_header = BlockHeader::default();
+        prev_header.height = 1000 - i;
+        prev_header.timestamp = 100000 - i * policy::BLOCK_TIME;
+        prev_headers.push(prev_header);
+    }
+    let median = 100000 - (window / 2) * policy::BLOCK_TIME;
+
+    let mut header = BlockHeader::default();
+    header.height = 1001;
+    header.timestamp = median + 1;
+    assert!(Blockchain::verify_median_time_past(&header, &prev_headers));
+    header.timestamp = median;
+    assert!(!Blockchain::verify_median_time_past(&header, &prev_headers));
+
+    // The order of the preceding timestamps doesn't matter.
+    prev_headers.swap(0, (window - 1) as usize);
+    assert!(!Blockchain::verify_median_time_past(&header, &prev_headers));
+    header.timestamp = median + 1;
+    assert!(Blockchain::verify_median_time_past(&header, &prev_headers));
+
+    // A miner stalling the timestamp is rejected once the median catches up.
+    for prev_header in prev_headers.iter_mut() {
+        prev_header.timestamp = 100000;
+    }
+    header.timestamp = 100000;
+    assert!(!Blockchain::verify_median_time_past(&header, &prev_headers));
+
+    // With fewer preceding blocks (near genesis), the check is skipped.
+    assert!(Blockchain::verify_median_time_past(&header, &prev_headers[..(window - 1) as usize]));
+    assert!(Blockchain::verify_median_time_past(&header, &[]));
+
+    // The first mainnet blocks share the genesis timestamp and are still accepted.
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    for block in &[BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5] {
+        let block = block_from_hex(block);
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+}
+
+#[test]
+fn it_adjusts_the_difficulty_towards_the_configured_block_time() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let mut blockchain = new_blockchain(&env);
+    assert_eq!(blockchain.block_time, policy::BLOCK_TIME);
+
+    // The window before the genesis block is simulated at the configured block time.
+    blockchain.block_time = 10;
+    assert_eq!(blockchain.get_next_difficulty(None), Difficulty::from(1));
+
+    // Blocks produced at the policy block time at an average difficulty of 1024.
+    let mut tail = BlockHeader::default();
+    tail.height = 1000;
+    tail.timestamp = 100000;
+    let mut head = tail.clone();
+    head.height = tail.height + policy::DIFFICULTY_BLOCK_WINDOW;
+    head.timestamp = tail.timestamp + policy::DIFFICULTY_BLOCK_WINDOW * policy::BLOCK_TIME;
+    let delta_total_difficulty = Difficulty::from(1024 * u64::from(policy::DIFFICULTY_BLOCK_WINDOW));
+
+    let target = Blockchain::compute_next_target_with_block_time(&head, &tail, delta_total_difficulty.clone(), policy::BLOCK_TIME);
+    assert_eq!(Difficulty::from(target.clone()), Difficulty::from(1024));
+    assert_eq!(Blockchain::compute_next_target(&head, &tail, delta_total_difficulty.clone()), target);
+
+    // Aiming for faster blocks lowers the difficulty, aiming for slower blocks raises it.
+    let target = Blockchain::compute_next_target_with_block_time(&head, &tail, delta_total_difficulty.clone(), policy::BLOCK_TIME / 2);
+    assert_eq!(Difficulty::from(target), Difficulty::from(512));
+    let target = Blockchain::compute_next_target_with_block_time(&head, &tail, delta_total_difficulty, policy::BLOCK_TIME * 2);
+    assert_eq!(Difficulty::from(target), Difficulty::from(2048));
+
+    // On a chain taller than the difficulty window, `get_next_target` adjusts towards the configured block time.
+    let env = VolatileEnvironment::new(10).unwrap();
+    let genesis_timestamp = get_network_info(NetworkId::Main).unwrap().genesis_block.header.timestamp;
+    store_main_chain(&env, policy::DIFFICULTY_BLOCK_WINDOW + 1, |i| genesis_timestamp + (i + 1) * policy::BLOCK_TIME);
+    let mut blockchain = new_blockchain(&env);
+    let head = blockchain.head().header.clone();
+    let tail = blockchain.get_block_at(head.height - policy::DIFFICULTY_BLOCK_WINDOW, false).unwrap().header;
+    let store = ChainStore::new(&env);
+    let delta_total_difficulty = &store.get_chain_info(&head.hash(), false, None).unwrap().total_difficulty
+        - &store.get_chain_info(&tail.hash(), false, None).unwrap().total_difficulty;
+
+    let default_target = blockchain.get_next_target(None);
+    assert_eq!(default_target, Blockchain::compute_next_target(&head, &tail, delta_total_difficulty.clone()));
+
+    blockchain.block_time = policy::BLOCK_TIME * 2;
+    let target = blockchain.get_next_target(None);
+    assert_eq!(target, Blockchain::compute_next_target_with_block_time(&head, &tail, delta_total_difficulty, policy::BLOCK_TIME * 2));
+    assert!(Difficulty::from(target) > Difficulty::from(default_target.clone()));
+
+    // Going back to the policy block time doesn't return the target cached for the other block time.
+    blockchain.block_time = policy::BLOCK_TIME;
+    assert_eq!(blockchain.get_next_target(None), default_target);
+}
+
+#[test]
+fn it_rejects_flat_timestamps_across_the_difficulty_window() {
+    crate::setup();
+
+    let mut tail = BlockHeader::default();
+    tail.height = 1000;
+    tail.timestamp = 100000;
+    let mut head = tail.clone();
+    head.height = tail.height + policy::DIFFICULTY_BLOCK_WINDOW;
+
+    // All blocks in the window share the same timestamp.
+    assert!(!Blockchain::verify_window_timespan(&head, &tail));
+    head.timestamp = tail.timestamp + policy::DIFFICULTY_MIN_WINDOW_TIMESPAN - 1;
+    assert!(!Blockchain::verify_window_timespan(&head, &tail));
+    head.timestamp = tail.timestamp + policy::DIFFICULTY_MIN_WINDOW_TIMESPAN;
+    assert!(Blockchain::verify_window_timespan(&head, &tail));
+
+    // Windows that are not full yet (near genesis) are not checked.
+    head.timestamp = tail.timestamp;
+    head.height = tail.height + policy::DIFFICULTY_BLOCK_WINDOW - 1;
+    assert!(Blockchain::verify_window_timespan(&head, &tail));
+
+    // The first mainnet blocks share the genesis timestamp and are still accepted.
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let block = block_from_hex(BLOCK_2);
+    assert_eq!(block.header.timestamp, blockchain.head().header.timestamp);
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+}
+
+/// Stores `count` blocks on top of the stored head (or the genesis block) as the main chain, keeping the accounts state.
+/// `timestamp` gives the timestamp of the i-th stored block, starting at 0.
+fn store_main_chain<F: Fn(u32) -> u32>(env: &Environment, count: u32, timestamp: F) {
+    let (head_hash, accounts_hash) = {
+        let blockchain = new_blockchain(env);
+        (blockchain.head_hash(), blockchain.accounts().hash(None))
+    };
+    let block2 = block_from_hex(BLOCK_2);
+
+    let store = ChainStore::new(env);
+    let mut prev_hash = head_hash;
+    let mut prev_info = store.get_chain_info(&prev_hash, true, None).unwrap();
+    let mut txn = WriteTransaction::new(env);
+    for i in 0..count {
+        let mut block = block2.clone();
+        block.header.prev_hash = prev_hash.clone();
+        block.header.height = prev_info.head.header.height + 1;
+        block.header.timestamp = timestamp(i);
+        block.header.accounts_hash = accounts_hash.clone();
+        let hash: Blake2bHash = block.header.hash();
+        let mut info = prev_info.next(block);
+        info.on_main_chain = true;
+        store.put_chain_info(&mut txn, &hash, &info, true);
+        prev_hash = hash;
+        prev_info = info;
+    }
+    store.set_head(&mut txn, &prev_hash);
+    txn.commit();
+}
+
+fn template_on(blockchain: &Blockchain, timestamp: u32) -> BlockHeader {
+    let mut header = blockchain.head().header.clone();
+    header.prev_hash = blockchain.head_hash();
+    header.height += 1;
+    header.timestamp = timestamp;
+    header
+}
+
+fn with_timestamp_rules_at<'env>(env: &'env Environment, height: Option<u32>) -> Blockchain<'env> {
+    let mut config = BlockchainConfig::default();
+    config.set_timestamp_rules_height(height);
+    Blockchain::with_config(env, NetworkId::Main, Arc::new(NetworkTime::new()), Box::new(HeaviestChain), config)
+}
+
+#[test]
+fn it_enforces_the_median_time_past_from_the_activation_height() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    // All blocks share the same timestamp. The difficulty window is not full yet, so only the median time past applies.
+    store_main_chain(&env, 15, |_| 100000);
+
+    // The rules are not active on mainnet.
+    let blockchain = new_blockchain(&env);
+    assert!(blockchain.validate_template_timestamp(&template_on(&blockchain, 100000)));
+    drop(blockchain);
+
+    let blockchain = with_timestamp_rules_at(&env, Some(1));
+    assert!(!blockchain.validate_template_timestamp(&template_on(&blockchain, 100000)));
+    assert!(blockchain.validate_template_timestamp(&template_on(&blockchain, 100001)));
+    drop(blockchain);
+
+    // Blocks below the activation height are not checked.
+    let blockchain = with_timestamp_rules_at(&env, Some(18));
+    assert!(blockchain.validate_template_timestamp(&template_on(&blockchain, 100000)));
+}
+
+#[test]
+fn it_enforces_the_window_timespan_from_the_activation_height() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    // The timestamps only increase every other block, so a full window spans half of the minimum timespan.
+    let count = policy::DIFFICULTY_BLOCK_WINDOW + 5;
+    store_main_chain(&env, count, |i| 100000 + i / 2);
+
+    // The rules are not active on mainnet.
+    let blockchain = new_blockchain(&env);
+    let head_timestamp = blockchain.head().header.timestamp;
+    assert!(blockchain.validate_template_timestamp(&template_on(&blockchain, head_timestamp + 1)));
+    drop(blockchain);
+
+    // The template is after the median time past, but the window ending at it is too short.
+    let blockchain = with_timestamp_rules_at(&env, Some(1));
+    let tail_timestamp = blockchain.get_block_at(blockchain.height() - policy::DIFFICULTY_BLOCK_WINDOW, false).unwrap().header.timestamp;
+    assert!(head_timestamp + 1 < tail_timestamp + policy::DIFFICULTY_MIN_WINDOW_TIMESPAN);
+    assert!(!blockchain.validate_template_timestamp(&template_on(&blockchain, head_timestamp + 1)));
+    assert!(!blockchain.validate_template_timestamp(&template_on(&blockchain, tail_timestamp + policy::DIFFICULTY_MIN_WINDOW_TIMESPAN - 1)));
+    assert!(blockchain.validate_template_timestamp(&template_on(&blockchain, tail_timestamp + policy::DIFFICULTY_MIN_WINDOW_TIMESPAN)));
+    drop(blockchain);
+
+    // Blocks below the activation height are not checked.
+    let blockchain = with_timestamp_rules_at(&env, Some(count + 3));
+    assert!(blockchain.validate_template_timestamp(&template_on(&blockchain, head_timestamp + 1)));
+}
+
+#[test]
+fn it_clamps_the_window_time_if_timestamps_are_not_monotonic() {
+    crate::setup();
+
+    let mut tail = BlockHeader::default();
+    tail.height = 1000;
+    tail.timestamp = 100000;
+    let mut head = tail.clone();
+    head.height = tail.height + policy::DIFFICULTY_BLOCK_WINDOW;
+    head.timestamp = tail.timestamp - 5000;
+
+    // The head is older than the tail, this must not underflow but be treated as the shortest
+    // possible window, i.e. the maximum upwards adjustment of the difficulty.
+    let delta_total_difficulty = Difficulty::from(u64::from(policy::DIFFICULTY_BLOCK_WINDOW));
+    let target = Blockchain::compute_next_target(&head, &tail, delta_total_difficulty.clone());
+    let difficulty = Difficulty::from(target);
+    assert_eq!(difficulty, Difficulty::from(2));
+
+    head.timestamp = tail.timestamp;
+    assert_eq!(Blockchain::compute_next_target(&head, &tail, delta_total_difficulty), Target::from(difficulty));
+}
+
+#[test]
+fn it_keeps_super_blocks_when_pruning_bodies() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    {
+        let blockchain = new_blockchain(&env);
+        for block in [BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5].iter() {
+            let block = block_from_hex(block);
+            assert_eq!(blockchain.push(block), PushResult::Extended);
+        }
+    }
+
+    // Bury the mainnet blocks below the bodies that are always retained.
+    let blockchain = new_blockchain(&env);
+    let retained = blockchain.min_retained_block_bodies();
+    assert_eq!(retained, policy::TRANSACTION_VALIDITY_WINDOW);
+    drop(blockchain);
+    store_main_chain(&env, retained, |i| 100000 + i * policy::BLOCK_TIME);
+    let blockchain = new_blockchain(&env);
+    let head_height = blockchain.height();
+
+    // Retaining fewer bodies than the minimum only prunes below the minimum.
+    let num_pruned = blockchain.prune_block_bodies(1);
+
+    // Only blocks that are not referenced by their successor's interlink lose their body.
+    let mut expected_pruned = 0;
+    for height in 2..=head_height - retained {
+        let block = blockchain.get_block_at(height, true).unwrap();
+        let successor = blockchain.get_block_at(height + 1, false).unwrap();
+        let is_super_block = successor.interlink.hashes.contains(&block.header.hash());
+        assert_eq!(block.body.is_some(), is_super_block);
+        if !is_super_block {
+            expected_pruned += 1;
+        }
+    }
+    assert_eq!(num_pruned, expected_pruned);
+    for height in head_height - retained + 1..=head_height {
+        assert!(blockchain.get_block_at(height, true).unwrap().body.is_some());
+    }
+
+    // Pruning again is a no-op.
+    assert_eq!(blockchain.prune_block_bodies(1), 0);
+
+    // Searching the history of an address stops at the first pruned body.
+    let mut oldest_body = head_height;
+    while blockchain.get_block_at(oldest_body - 1, true).unwrap().body.is_some() {
+        oldest_body -= 1;
+    }
+    assert_eq!(blockchain.transactions_for_address(&Address::from([4u8; Address::SIZE]), head_height), (vec![], oldest_body));
+
+    // The chain proof can still be built from the remaining blocks.
+    assert!(blockchain.get_chain_proof().is_some());
+    drop(blockchain);
+
+    // Blocks that a rebranch may revert keep their bodies as well.
+    let mut config = BlockchainConfig::default();
+    config.set_max_rebranch_depth(policy::TRANSACTION_VALIDITY_WINDOW * 2);
+    let blockchain = Blockchain::with_config(&env, NetworkId::Main, Arc::new(NetworkTime::new()), Box::new(HeaviestChain), config);
+    assert_eq!(blockchain.min_retained_block_bodies(), policy::TRANSACTION_VALIDITY_WINDOW * 2);
+    assert_eq!(blockchain.prune_block_bodies(0), 0);
+}
+
+#[test]
+fn it_reports_the_network_time_offset() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let network_time = Arc::new(NetworkTime::with_offset(5000));
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::clone(&network_time));
+    assert_eq!(blockchain.network_time_offset(), 5000);
+
+    network_time.set_offset(-(Blockchain::MAX_NETWORK_TIME_OFFSET as i64) - 1);
+    assert_eq!(blockchain.network_time_offset(), -(Blockchain::MAX_NETWORK_TIME_OFFSET as i64) - 1);
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn it_skips_verification_of_known_blocks_in_batches() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    let block2 = block_from_hex(BLOCK_2);
+    let block3 = block_from_hex(BLOCK_3);
+    assert_eq!(blockchain.push(block2.clone()), PushResult::Extended);
+    assert_eq!(blockchain.metrics.push_timings().verify.count(), 1);
+
+    // Only the new block is verified, the overlapping one is skipped.
+    let results = blockchain.push_many(vec![block2, block3]);
+    assert_eq!(results, vec![PushResult::Known, PushResult::Extended]);
+    assert_eq!(blockchain.metrics.push_timings().verify.count(), 2);
+    assert_eq!(blockchain.push_stats().known, 1);
+    assert_eq!(blockchain.height(), 3);
+}
+
+#[test]
+fn it_computes_the_inflation_rate() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    assert!((blockchain.inflation_rate() - 0.919588388).abs() < 1e-6);
+
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_2)), PushResult::Extended);
+    assert!((blockchain.inflation_rate() - 0.919586561).abs() < 1e-6);
+
+    // Twice the block time halves the number of rewards per year.
+    let mut blockchain = blockchain;
+    blockchain.block_time = policy::BLOCK_TIME * 2;
+    assert!((blockchain.inflation_rate() - 0.919586561 / 2.0).abs() < 1e-6);
+}
+
+#[test]
+fn it_counts_push_results() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    assert_eq!(blockchain.push_stats(), PushStats::default());
+
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_2)), PushResult::Extended);
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_2)), PushResult::Known);
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_4)), PushResult::Orphan);
+    // Also applies the buffered block 4.
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_3)), PushResult::Extended);
+
+    let mut block5 = block_from_hex(BLOCK_5);
+    block5.header.nonce = block5.header.nonce.wrapping_add(1);
+    match blockchain.push(block5) {
+        PushResult::Invalid(_) => (),
+        result => panic!("Unexpected push result: {:?}", result),
+    }
+
+    assert_eq!(blockchain.push_stats(), PushStats {
+        invalid: 1,
+        orphan: 1,
+        known: 1,
+        extended: 3,
+        rebranched: 0,
+        forked: 0,
+    });
+}
+
+#[test]
+fn it_computes_the_supply_at_a_height() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    for &(height, supply) in &[(0, 252000000000000u64), (1, 252000440597534), (2, 252000881194963), (5000, 254201675369298),
+            (100000, 295538674723488), (52888983, 2099999999996000), (52888984, 2100000000000000), (60000000, 2100000000000000)] {
+        assert_eq!(blockchain.supply_at_height(height), Coin::from(supply));
+    }
+    for &height in &[2, 5001, 48692960] {
+        assert_eq!(u64::from(blockchain.supply_at_height(height)) - u64::from(blockchain.supply_at_height(height - 1)),
+                   u64::from(policy::block_reward_at(height)));
+    }
+
+    assert_eq!(blockchain.current_supply(), blockchain.supply_at_height(1));
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_2)), PushResult::Extended);
+    assert_eq!(blockchain.current_supply(), Coin::from(252000881194963u64));
+}
+
+#[test]
+fn it_looks_up_accounts_hashes_by_height() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    assert_eq!(blockchain.accounts_hash_at(1), Some(blockchain.accounts().hash(None)));
+
+    let block2 = block_from_hex(BLOCK_2);
+    let block3 = block_from_hex(BLOCK_3);
+    assert_eq!(blockchain.push(block2.clone()), PushResult::Extended);
+    assert_eq!(blockchain.push(block3), PushResult::Extended);
+
+    assert_eq!(blockchain.accounts_hash_at(3), Some(blockchain.accounts().hash(None)));
+    assert_eq!(blockchain.accounts_hash_at(2), Some(block2.header.accounts_hash.clone()));
+    assert_eq!(blockchain.accounts_hash_at(0), None);
+    assert_eq!(blockchain.accounts_hash_at(4), None);
+
+    // Reverted blocks are no longer on the main chain.
+    blockchain.revert_head().unwrap();
+    assert_eq!(blockchain.accounts_hash_at(3), None);
+    assert_eq!(blockchain.accounts_hash_at(2), Some(blockchain.accounts().hash(None)));
+}
+
+#[test]
+fn it_returns_the_difficulty_window() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let block2 = block_from_hex(BLOCK_2);
+    {
+        let blockchain = new_blockchain(&env);
+        assert!(blockchain.difficulty_window().is_empty());
+
+        // Near genesis, the window is truncated.
+        for block in &[BLOCK_2, BLOCK_3, BLOCK_4] {
+            assert_eq!(blockchain.push(block_from_hex(block)), PushResult::Extended);
+        }
+        let window = blockchain.difficulty_window();
+        assert_eq!(window.iter().map(|(header, _)| header.height).collect::<Vec<u32>>(), vec![2, 3, 4]);
+        assert_eq!(window[0], (block2.header.clone(), Difficulty::from(block2.header.n_bits)));
+        blockchain.revert_to_height(1).unwrap();
+    }
+
+    // Store a main chain longer than the window on top of the genesis block.
+    store_main_chain(&env, policy::DIFFICULTY_BLOCK_WINDOW + 10, |_| block2.header.timestamp);
+
+    let blockchain = new_blockchain(&env);
+    let head_height = blockchain.height();
+    assert_eq!(head_height, policy::DIFFICULTY_BLOCK_WINDOW + 11);
+
+    let window = blockchain.difficulty_window();
+    assert_eq!(window.len(), policy::DIFFICULTY_BLOCK_WINDOW as usize);
+    assert_eq!(window[0].0.height, head_height - policy::DIFFICULTY_BLOCK_WINDOW + 1);
+    assert_eq!(window.last().unwrap().0, blockchain.head().header);
+    assert!(window.iter().all(|(header, difficulty)| *difficulty == Difficulty::from(header.n_bits)));
+}
+
+#[test]
+fn it_validates_template_difficulty() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    let genesis_hash = blockchain.head_hash();
+    let template_n_bits = TargetCompact::from(0x1f010000);
+    assert!(blockchain.validate_template_difficulty(&genesis_hash, template_n_bits));
+    assert!(!blockchain.validate_template_difficulty(&[1u8; Blake2bHash::SIZE].into(), template_n_bits));
+
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_2)), PushResult::Extended);
+
+    // The difficulty changed, a template on the new head must use the new target.
+    let head_hash = blockchain.head_hash();
+    assert!(!blockchain.validate_template_difficulty(&head_hash, template_n_bits));
+    assert!(blockchain.validate_template_difficulty(&head_hash, TargetCompact::from(0x1f00fddd)));
+}
+
+#[test]
+fn it_computes_the_expected_number_of_superblocks() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_2)), PushResult::Extended);
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_3)), PushResult::Extended);
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_4)), PushResult::Extended);
+
+    assert_eq!(blockchain.expected_superblocks(0), 4.0);
+    assert_eq!(blockchain.expected_superblocks(1), 2.0);
+    assert_eq!(blockchain.expected_superblocks(2), 1.0);
+    assert_eq!(blockchain.expected_superblocks(3), 0.5);
+}
+
+#[test]
+fn it_can_revert_the_head() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let genesis_hash = blockchain.head_hash();
+    assert_eq!(blockchain.revert_head(), Err(PushError::CannotRevertGenesis(PushErrorContext::new(genesis_hash, 1))));
+
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_2)), PushResult::Extended);
+    let head_hash = blockchain.head_hash();
+    let accounts_hash = blockchain.accounts().hash(None);
+    let missing_blocks = blockchain.transaction_cache().missing_blocks();
+
+    let block = block_from_hex(BLOCK_3);
+    assert_eq!(blockchain.push(block.clone()), PushResult::Extended);
+
+    let listener_called = Arc::new(Atomic::new(false));
+    let listener_called1 = listener_called.clone();
+    let reverted_blocks = vec![(block.header.hash::<Blake2bHash>(), block.clone())];
+    blockchain.notifier.write().register(move |e: &BlockchainEvent| {
+        assert_eq!(*e, BlockchainEvent::Rebranched(reverted_blocks.clone(), vec![]));
+        listener_called1.store(true, Ordering::Relaxed);
+    });
+
+    assert_eq!(blockchain.revert_head(), Ok(block.clone()));
+    assert!(listener_called.load(Ordering::Relaxed));
+
+    assert_eq!(blockchain.head_hash(), head_hash);
+    assert_eq!(blockchain.height(), 2);
+    assert_eq!(blockchain.accounts().hash(None), accounts_hash);
+    assert_eq!(blockchain.transaction_cache().missing_blocks(), missing_blocks);
+    assert!(blockchain.contains(&block.header.hash(), true));
+    assert!(!blockchain.contains(&block.header.hash(), false));
+
+    // The state survives a restart.
+    let blockchain = new_blockchain(&env);
+    assert_eq!(blockchain.head_hash(), head_hash);
+    assert_eq!(blockchain.accounts().hash(None), accounts_hash);
+}
+
+#[test]
+fn it_reports_accounts_errors_when_reverting() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let block2 = block_from_hex(BLOCK_2);
+    let hash2: Blake2bHash = block2.header.hash();
+    assert_eq!(blockchain.push(block2.clone()), PushResult::Extended);
+
+    // The miner spends the block reward behind the blockchain's back, so that it can't be reverted.
+    let miner = block2.body.as_ref().unwrap().miner.clone();
+    let reward = policy::block_reward_at(2);
+    let mut body = block2.body.clone().unwrap();
+    body.miner = Address::from([2u8; Address::SIZE]);
+    body.transactions = vec![Transaction::new_basic(miner, Address::from([3u8; Address::SIZE]), reward, Coin::ZERO, 3, NetworkId::Main)];
+    let accounts = Accounts::new(&env);
+    let mut txn = WriteTransaction::new(&env);
+    accounts.commit_block_body(&mut txn, &body, 3).unwrap();
+    txn.commit();
+    let accounts_hash = accounts.hash(None);
+
+    match blockchain.revert_head() {
+        Err(PushError::AccountsError(_, context)) => assert_eq!(context, Some(PushErrorContext::new(hash2.clone(), 2))),
+        result => panic!("Unexpected result {:?}", result),
+    }
+    match blockchain.revert_to_height(1) {
+        Err(PushError::AccountsError(_, context)) => assert_eq!(context, Some(PushErrorContext::new(hash2.clone(), 2))),
+        result => panic!("Unexpected result {:?}", result),
+    }
+
+    // Nothing was reverted.
+    assert_eq!(blockchain.head_hash(), hash2);
+    assert_eq!(blockchain.accounts().hash(None), accounts_hash);
+}
+
+#[test]
+fn it_filters_revived_transactions() {
+    crate::setup();
+
+    let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    let miner = Address::from(&keypair.public);
+    let block2 = crate::next_block(&blockchain)
+        .with_miner(miner.clone())
+        .with_nonce(34932)
+        .build();
+    assert_eq!(blockchain.push(block2), PushResult::Extended);
+
+    // Still affordable by the miner of block 2.
+    let valid_tx = Transaction::new_basic(miner.clone(), [2u8; Address::SIZE].into(), 10.into(), 0.into(), 1, NetworkId::Main);
+    // Exceeds the funds of the miner on the new main chain.
+    let invalid_tx = Transaction::new_basic(miner.clone(), [3u8; Address::SIZE].into(), 1000000000.into(), 0.into(), 1, NetworkId::Main);
+
+    let mut reverted_block = crate::next_block(&blockchain).build();
+    reverted_block.body.as_mut().unwrap().transactions = vec![valid_tx.clone(), invalid_tx];
+    let reverted = vec![(reverted_block.header.hash(), reverted_block)];
+
+    assert_eq!(blockchain.filter_revived_transactions(&reverted), vec![valid_tx]);
+}
+
+#[test]
+fn it_computes_adaptive_block_locators() {
+    crate::setup();
+
+    assert_eq!(Blockchain::block_locator_heights(1), vec![1]);
+    assert_eq!(Blockchain::block_locator_heights(5), vec![5, 4, 3, 2, 1]);
+    assert_eq!(Blockchain::block_locator_heights(11), vec![11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1]);
+    assert_eq!(Blockchain::block_locator_heights(100), vec![100, 99, 98, 97, 96, 95, 94, 93, 92, 91, 89, 87, 83, 75, 59, 27, 1]);
+
+    let heights = Blockchain::block_locator_heights(u32::max_value());
+    assert!(heights.len() <= Blockchain::ADAPTIVE_LOCATORS_MAX);
+    assert_eq!(heights[0], u32::max_value());
+    assert_eq!(*heights.last().unwrap(), 1);
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let genesis_hash = blockchain.head_hash();
+    assert_eq!(blockchain.get_block_locators_adaptive(), vec![genesis_hash.clone()]);
+
+    let mut hashes = vec![genesis_hash];
+    for block in &[BLOCK_2, BLOCK_3, BLOCK_4] {
+        assert_eq!(blockchain.push(block_from_hex(block)), PushResult::Extended);
+        hashes.insert(0, blockchain.head_hash());
+    }
+    assert_eq!(blockchain.get_block_locators_adaptive(), hashes);
+}
+
+#[test]
+fn it_respects_max_count_for_block_locators() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let genesis_hash = blockchain.head_hash();
+
+    let mut hashes = vec![genesis_hash.clone()];
+    for block in &[BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5] {
+        assert_eq!(blockchain.push(block_from_hex(block)), PushResult::Extended);
+        hashes.insert(0, blockchain.head_hash());
+    }
+
+    assert_eq!(blockchain.get_block_locators(1, None), vec![genesis_hash.clone()]);
+    assert_eq!(blockchain.get_block_locators(3, None), vec![hashes[0].clone(), hashes[1].clone(), genesis_hash.clone()]);
+    assert_eq!(blockchain.get_block_locators(1000, None), hashes);
+
+    // Start at an older block.
+    assert_eq!(blockchain.get_block_locators(1000, Some(&hashes[2])), hashes[2..].to_vec());
+    assert_eq!(blockchain.get_block_locators(1000, Some(&genesis_hash)), vec![genesis_hash.clone()]);
+    assert_eq!(blockchain.get_block_locators(1000, Some(&Blake2bHash::default())), vec![genesis_hash]);
+}
+
+#[test]
+fn it_reports_greater_chain_work_for_harder_chains() {
+    crate::setup();
+
+    let env1 = VolatileEnvironment::new(10).unwrap();
+    let light = new_blockchain(&env1);
+    assert_eq!(light.push(block_from_hex(BLOCK_2)), PushResult::Extended);
+
+    let env2 = VolatileEnvironment::new(10).unwrap();
+    let heavy = new_blockchain(&env2);
+    for block in &[BLOCK_2, BLOCK_3] {
+        assert_eq!(heavy.push(block_from_hex(block)), PushResult::Extended);
+    }
+
+    assert!(heavy.chain_work() > light.chain_work());
+
+    // The serialized form round-trips to the same value.
+    let work = heavy.chain_work();
+    assert_eq!(Difficulty::deserialize_from_vec(&work.serialize_to_vec()).unwrap(), work);
+}
+
+#[test]
+fn it_computes_how_far_behind_a_peer_it_is() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    assert_eq!(blockchain.push(block_from_hex(BLOCK_2)), PushResult::Extended);
+
+    // Behind.
+    assert_eq!(blockchain.blocks_behind(1002), 1000);
+    assert_eq!(blockchain.estimated_sync_time(1002, 40.0), Duration::from_secs(25));
+    assert_eq!(blockchain.estimated_sync_time(1002, 3.0), Duration::from_millis(333333));
+
+    // Ahead.
+    assert_eq!(blockchain.blocks_behind(1), 0);
+    assert_eq!(blockchain.estimated_sync_time(1, 40.0), Duration::from_secs(0));
+
+    // Equal.
+    assert_eq!(blockchain.blocks_behind(2), 0);
+    assert_eq!(blockchain.estimated_sync_time(2, 40.0), Duration::from_secs(0));
+}
+
+#[test]
+fn it_checks_transactions_for_mempool_admission() {
+    crate::setup();
+
+    let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
+    let sign = |mut tx: Transaction| {
+        tx.proof = SignatureProof::from(keypair.public.clone(), keypair.sign(&tx.serialize_content())).serialize_to_vec();
+        tx
+    };
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    let miner = Address::from(&keypair.public);
+    let block2 = crate::next_block(&blockchain)
+        .with_miner(miner.clone())
+        .with_nonce(34932)
+        .build();
+    assert_eq!(blockchain.push(block2), PushResult::Extended);
+
+    let mined_tx = sign(Transaction::new_basic(miner.clone(), [2u8; Address::SIZE].into(), 10.into(), 0.into(), 1, NetworkId::Main));
+    let block3 = crate::next_block(&blockchain)
+        .with_miner(miner.clone())
+        .with_transactions(vec![mined_tx.clone()])
+        .with_nonce(23026)
+        .build();
+    assert_eq!(blockchain.push(block3), PushResult::Extended);
+
+    // Accepted.
+    let tx = sign(Transaction::new_basic(miner.clone(), [2u8; Address::SIZE].into(), 20.into(), 0.into(), 1, NetworkId::Main));
+    assert_eq!(blockchain.check_transaction(&tx), Ok(()));
+
+    // Invalid.
+    let tx = sign(Transaction::new_basic(miner.clone(), [2u8; Address::SIZE].into(), 20.into(), 0.into(), 1, NetworkId::Test));
+    assert_eq!(blockchain.check_transaction(&tx), Err(TxAdmissionError::Invalid(TransactionError::ForeignNetwork)));
+    let mut tx = Transaction::new_basic(miner.clone(), [2u8; Address::SIZE].into(), 20.into(), 0.into(), 1, NetworkId::Main);
+    tx.proof = sign(tx.clone()).proof;
+    tx.value = 21.into();
+    assert_eq!(blockchain.check_transaction(&tx), Err(TxAdmissionError::Invalid(TransactionError::InvalidProof)));
+
+    // Outside of the validity window.
+    let tx = sign(Transaction::new_basic(miner.clone(), [2u8; Address::SIZE].into(), 20.into(), 0.into(), 1000, NetworkId::Main));
+    assert_eq!(blockchain.check_transaction(&tx), Err(TxAdmissionError::Expired));
+
+    // Already included in the main chain.
+    assert_eq!(blockchain.check_transaction(&mined_tx), Err(TxAdmissionError::AlreadyMined));
+}
+
+#[test]
+fn it_lists_recent_fork_tips() {
+    crate::setup();
+
+    let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let fork_2 = crate::next_block(&blockchain)
+        .with_miner(Address::from(&keypair.public))
+        .with_nonce(34932)
+        .build();
+
+    // Build a three block fork on a separate chain.
+    let fork_env = VolatileEnvironment::new(10).unwrap();
+    let fork_chain = new_blockchain(&fork_env);
+    let mut long_fork = vec![];
+    for nonce in [83054, 23192, 39719].iter() {
+        let block = crate::next_block(&fork_chain)
+            .with_nonce(*nonce)
+            .build();
+        assert_eq!(fork_chain.push(block.clone()), PushResult::Extended);
+        long_fork.push(block);
+    }
+
+    for block in &[BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5] {
+        assert_eq!(blockchain.push(block_from_hex(block)), PushResult::Extended);
+    }
+    assert!(blockchain.recent_fork_tips(10).is_empty());
+
+    assert_eq!(blockchain.push(fork_2.clone()), PushResult::Forked);
+    assert_eq!(blockchain.push(long_fork[0].clone()), PushResult::Forked);
+    assert_eq!(blockchain.push(long_fork[1].clone()), PushResult::ForkExtended);
+    assert_eq!(blockchain.push(long_fork[2].clone()), PushResult::ForkExtended);
+
+    let long_tip = (fork_chain.head_hash(), 4, fork_chain.chain_work());
+    assert_eq!(blockchain.recent_fork_tips(1), vec![long_tip.clone()]);
+    let tips = blockchain.recent_fork_tips(3);
+    assert_eq!(tips.len(), 2);
+    assert_eq!((tips[0].0.clone(), tips[0].1), (fork_2.header.hash(), 2));
+    assert_eq!(tips[1], long_tip);
+}
+
+#[test]
+fn it_returns_the_difficulty_history() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    let mut blocks = vec![blockchain.head().clone()];
+    for block in &[BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5] {
+        let block = block_from_hex(block);
+        assert_eq!(blockchain.push(block.clone()), PushResult::Extended);
+        blocks.push(block);
+    }
+
+    let expected: Vec<(u32, u32, Difficulty)> = blocks.iter().rev()
+        .map(|block| (block.header.height, block.header.timestamp, Difficulty::from(block.header.n_bits)))
+        .collect();
+
+    assert_eq!(blockchain.difficulty_history(0), vec![]);
+    assert_eq!(blockchain.difficulty_history(2), expected[..2].to_vec());
+    assert_eq!(blockchain.difficulty_history(5), expected);
+    assert_eq!(blockchain.difficulty_history(100), expected);
+}
+
+#[test]
+fn it_returns_the_genesis_block_without_body() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let genesis_hash = blockchain.head_hash();
+
+    let genesis = blockchain.get_block_at(1, true).unwrap();
+    assert!(genesis.body.is_none());
+    assert_eq!(genesis.header.hash::<Blake2bHash>(), genesis_hash);
+    assert_eq!(blockchain.get_block_at(1, false), Some(genesis.clone()));
+    assert_eq!(blockchain.get_block(&genesis_hash, false, true), Some(genesis.clone()));
+    assert_eq!(blockchain.get_block(&genesis_hash, false, false), Some(genesis));
+
+    // Other blocks are still returned with their body.
+    let block = block_from_hex(BLOCK_2);
+    assert_eq!(blockchain.push(block.clone()), PushResult::Extended);
+    assert_eq!(blockchain.get_block_at(2, true), Some(block));
+}
+
+#[test]
+fn it_rejects_blocks_from_denied_miners() {
+    crate::setup();
+
+    let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
+    let miner = Address::from(&keypair.public);
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let block = crate::next_block(&blockchain)
+        .with_miner(miner.clone())
+        .with_nonce(34932)
+        .build();
+
+    assert!(blockchain.add_denied_miner(miner.clone()));
+    assert!(!blockchain.add_denied_miner(miner.clone()));
+    assert_eq!(blockchain.push(block.clone()), PushResult::Invalid(PushError::DeniedMiner(PushErrorContext::from(&block))));
+    assert_eq!(blockchain.height(), 1);
+
+    // Blocks from other miners are still accepted.
+    let other_env = VolatileEnvironment::new(10).unwrap();
+    let other_blockchain = new_blockchain(&other_env);
+    assert!(other_blockchain.add_denied_miner(miner.clone()));
+    let other_block = block_from_hex(BLOCK_2);
+    assert_eq!(other_blockchain.push(other_block), PushResult::Extended);
+
+    assert!(blockchain.remove_denied_miner(&miner));
+    assert!(!blockchain.remove_denied_miner(&miner));
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+}
+
+#[test]
+fn it_detects_equivocating_miners() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let genesis_hash = blockchain.head_hash();
+    assert!(blockchain.detect_equivocation(2).is_empty());
+
+    // Store fork blocks directly, they don't need valid PoW for this.
+    let store = ChainStore::new(&env);
+    let genesis_info = store.get_chain_info(&genesis_hash, true, None).unwrap();
+    let miner1 = Address::from([1u8; Address::SIZE]);
+    let miner2 = Address::from([2u8; Address::SIZE]);
+    let mut hashes = Vec::new();
+    let mut txn = WriteTransaction::new(&env);
+    for (miner, nonce) in vec![(&miner1, 1), (&miner1, 2), (&miner2, 3)] {
+        let mut block = genesis_info.head.clone();
+        block.header.prev_hash = genesis_hash.clone();
+        block.header.height = 2;
+        block.header.nonce = nonce;
+        block.body.as_mut().unwrap().miner = miner.clone();
+
+        let hash: Blake2bHash = block.header.hash();
+        store.put_chain_info(&mut txn, &hash, &genesis_info.next(block), true);
+        hashes.push(hash);
+    }
+    txn.commit();
+
+    let mut equivocations = blockchain.detect_equivocation(2);
+    assert_eq!(equivocations.len(), 1);
+    let (miner, mut miner_hashes) = equivocations.pop().unwrap();
+    miner_hashes.sort();
+    let mut expected = hashes[..2].to_vec();
+    expected.sort();
+    assert_eq!(miner, miner1);
+    assert_eq!(miner_hashes, expected);
+
+    assert!(blockchain.detect_equivocation(1).is_empty());
+    assert!(blockchain.detect_equivocation(3).is_empty());
+}
+
+#[test]
+fn it_removes_invalid_forks_from_the_store() {
+    crate::setup();
+
+    // Build a fork from genesis whose third block includes a transaction of the second block again.
+    let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
+    let miner = Address::from(&keypair.public);
+    let env = VolatileEnvironment::new(10).unwrap();
+    let scratch = new_blockchain(&env);
+    let fork2 = crate::next_block(&scratch)
+        .with_miner(miner.clone())
+        .with_nonce(34932)
+        .build();
+    assert_eq!(scratch.push(fork2.clone()), PushResult::Extended);
+
+    let mut tx = Transaction::new_basic(
+        miner.clone(),
+        [2u8; Address::SIZE].into(),
+        10.into(),
+        0.into(),
+        1,
+        NetworkId::Main
+    );
+    tx.proof = SignatureProof::from(keypair.public.clone(), keypair.sign(&tx.serialize_content())).serialize_to_vec();
+    let fork3 = crate::next_block(&scratch)
+        .with_miner(miner)
+        .with_transactions(vec![tx.clone()])
+        .with_nonce(23026)
+        .build();
+    assert_eq!(scratch.push(fork3.clone()), PushResult::Extended);
+    let fork4 = crate::next_block(&scratch)
+        .with_transactions(vec![tx])
+        .with_nonce(6471)
+        .build();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let block2 = block_from_hex(BLOCK_2);
+    let block3 = block_from_hex(BLOCK_3);
+    let hash3: Blake2bHash = block3.header.hash();
+    assert_eq!(blockchain.push(block2), PushResult::Extended);
+    assert_eq!(blockchain.push(block3), PushResult::Extended);
+
+    // The first two fork blocks are valid, they are stored but not adopted.
+    let hash_fork2: Blake2bHash = fork2.header.hash();
+    let hash_fork3: Blake2bHash = fork3.header.hash();
+    assert_eq!(blockchain.push(fork2), PushResult::Forked);
+    assert_eq!(blockchain.push(fork3), PushResult::ForkExtended);
+
+    // Extending the fork makes it the hardest chain, but the duplicate transaction fails to apply.
+    let hash_fork4: Blake2bHash = fork4.header.hash();
+    assert_eq!(blockchain.push(fork4), PushResult::Invalid(PushError::InvalidFork(PushErrorContext::new(hash_fork4.clone(), 4))));
+
+    // Only the invalid block is removed, the valid fork blocks are kept.
+    let store = ChainStore::new(&env);
+    assert!(store.get_chain_info(&hash_fork4, false, None).is_none());
+    assert!(store.get_blocks_at(4, false, None).is_empty());
+    assert!(store.get_chain_info(&hash_fork2, false, None).is_some());
+    assert!(store.get_chain_info(&hash_fork3, false, None).is_some());
+    assert_eq!(blockchain.head_hash(), hash3);
+}
+
+#[test]
+fn it_does_not_rebranch_too_deep() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let mut config = BlockchainConfig::default();
+    config.set_max_rebranch_depth(1);
+    let blockchain = Blockchain::with_config(&env, NetworkId::Main, Arc::new(NetworkTime::new()), Box::new(HeaviestChain), config);
+    assert_eq!(blockchain.config().max_rebranch_depth(), 1);
+
+    let block1_2 = crate::next_block(&blockchain)
+        .with_nonce(83054)
+        .build();
+    let hash1_2: Blake2bHash = block1_2.header.hash();
+    assert_eq!(blockchain.push(block1_2), PushResult::Extended);
+
+    let block2_2 = block_from_hex(BLOCK_2);
+    assert_eq!(blockchain.push(block2_2), PushResult::Forked);
+
+    // Adopting the fork would apply two blocks. The block is valid though, so it is not counted as invalid.
+    let block2_3 = block_from_hex(BLOCK_3);
+    let hash2_3: Blake2bHash = block2_3.header.hash();
+    assert_eq!(blockchain.push(block2_3.clone()), PushResult::Invalid(PushError::RebranchTooDeep(PushErrorContext::new(hash2_3.clone(), 3))));
+    assert_eq!(blockchain.head_hash(), hash1_2);
+    assert_eq!(blockchain.push_stats().invalid, 0);
+
+    // The block is kept as a fork.
+    let info = ChainStore::new(&env).get_chain_info(&hash2_3, false, None).unwrap();
+    assert!(!info.on_main_chain);
+    assert_eq!(blockchain.push(block2_3), PushResult::Known);
+}
+
+#[test]
+fn it_accepts_blocks_within_the_configured_future_drift() {
+    crate::setup();
+
+    let block = block_from_hex(BLOCK_2);
+
+    // Move the network time so that the block is 15 minutes in the future.
+    let now = NetworkTime::new().now() as i64;
+    let offset = block.header.timestamp_in_millis() as i64 - 15 * 60 * 1000 - now;
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let mut blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::with_offset(offset)));
+    assert_eq!(blockchain.push(block.clone()), PushResult::Invalid(PushError::TimestampInFuture(PushErrorContext::from(&block))));
+
+    blockchain.max_future_drift = 20 * 60 * 1000;
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+}
+
+#[test]
+fn it_rejects_blocks_too_far_in_the_future() {
+    crate::setup();
+
+    let block = block_from_hex(BLOCK_2);
+    let now = NetworkTime::new().now() as i64;
+
+    // One minute beyond the allowed drift.
+    let offset = block.header.timestamp_in_millis() as i64 - policy::BLOCK_MAX_FUTURE_DRIFT as i64 - 60 * 1000 - now;
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::with_offset(offset)));
+    assert_eq!(blockchain.max_future_drift, policy::BLOCK_MAX_FUTURE_DRIFT);
+    assert_eq!(blockchain.push(block.clone()), PushResult::Invalid(PushError::TimestampInFuture(PushErrorContext::from(&block))));
+
+    // Exactly at the allowed drift. The network time only advances until the push, so this stays within it.
+    let offset = block.header.timestamp_in_millis() as i64 - policy::BLOCK_MAX_FUTURE_DRIFT as i64 - now;
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::with_offset(offset)));
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+}
+
+#[test]
+fn it_can_revert_to_a_height() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    let mut blocks = vec![];
+    for block in &[BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5] {
+        let block = block_from_hex(block);
+        assert_eq!(blockchain.push(block.clone()), PushResult::Extended);
+        blocks.push((block.header.hash::<Blake2bHash>(), block));
+    }
+
+    assert_eq!(blockchain.revert_to_height(6), Err(PushError::InvalidHeight(PushErrorContext::new(blocks[3].0.clone(), 5))));
+    let genesis_hash = get_network_info(NetworkId::Main).unwrap().genesis_hash.clone();
+    assert_eq!(blockchain.revert_to_height(0), Err(PushError::CannotRevertGenesis(PushErrorContext::new(genesis_hash, 1))));
+    assert_eq!(blockchain.revert_to_height(5), Ok(vec![]));
+
+    let listener_called = Arc::new(Atomic::new(false));
+    let listener_called1 = listener_called.clone();
+    let reverted_blocks = blocks[2..].to_vec();
+    blockchain.notifier.write().register(move |e: &BlockchainEvent| {
+        assert_eq!(*e, BlockchainEvent::Rebranched(reverted_blocks.clone(), vec![]));
+        listener_called1.store(true, Ordering::Relaxed);
+    });
+
+    assert_eq!(blockchain.revert_to_height(3), Ok(blocks[2..].to_vec()));
+    assert!(listener_called.load(Ordering::Relaxed));
+
+    assert_eq!(blockchain.height(), 3);
+    assert_eq!(blockchain.head_hash(), blocks[1].0);
+    assert_eq!(blockchain.accounts().hash(None), blocks[1].1.header.accounts_hash);
+    assert_eq!(blockchain.transaction_cache().missing_blocks(), policy::TRANSACTION_VALIDITY_WINDOW - 3);
+    assert!(blockchain.contains(&blocks[3].0, true));
+    assert!(!blockchain.contains(&blocks[3].0, false));
+}
+
+#[test]
+fn it_serves_reads_during_a_slow_push() {
+    use std::sync::mpsc;
+    use std::sync::Mutex;
+
+    // Threads need the environment to outlive them.
+    lazy_static! {
+        static ref ENV: Environment = VolatileEnvironment::new(10).unwrap();
+    }
+
+    crate::setup();
+    let blockchain = Arc::new(new_blockchain(&ENV));
+
+    // Listeners are notified before push returns, block in there until the reads are done.
+    let (entered_tx, entered_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let (entered_tx, release_rx) = (Mutex::new(entered_tx), Mutex::new(release_rx));
+    blockchain.notifier.write().register(move |e: &BlockchainEvent| {
+        if let BlockchainEvent::Extended(..) = e {
+            entered_tx.lock().unwrap().send(()).unwrap();
+            release_rx.lock().unwrap().recv().unwrap();
+        }
+    });
+
+    let blockchain1 = blockchain.clone();
+    let pusher = std::thread::spawn(move || {
+        let block = block_from_hex(BLOCK_2);
+        assert_eq!(blockchain1.push(block), PushResult::Extended);
+    });
+    entered_rx.recv().unwrap();
+
+    // Caching a chain proof and reading must not queue behind the push.
+    let (done_tx, done_rx) = mpsc::channel();
+    let blockchain2 = blockchain.clone();
+    let prover_done = done_tx.clone();
+    std::thread::spawn(move || {
+        assert!(blockchain2.get_chain_proof().is_some());
+        prover_done.send(()).unwrap();
+    });
+    for _ in 0..4 {
+        let blockchain = blockchain.clone();
+        let reader_done = done_tx.clone();
+        std::thread::spawn(move || {
+            for _ in 0..100 {
+                assert_eq!(blockchain.height(), 2);
+                let head_hash = blockchain.head_hash();
+                assert_eq!(blockchain.head().header.hash::<Blake2bHash>(), head_hash);
+                assert!(blockchain.get_block(&head_hash, false, true).is_some());
+            }
+            reader_done.send(()).unwrap();
+        });
+    }
+    drop(done_tx);
+
+    // All reads complete while the push is still held up in the listener. A panicking thread drops its sender
+    // without sending, which ends the iteration early.
+    let completed = done_rx.iter().take(5).count();
+    assert_eq!(completed, 5);
+
+    release_tx.send(()).unwrap();
+    pusher.join().unwrap();
+}
+
+#[test]
+fn it_generates_accounts_proofs() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let genesis_miner = blockchain.head().body.as_ref().unwrap().miner.clone();
+    let absent = Address::from([0xffu8; Address::SIZE]);
+
+    let mut proof = blockchain.get_accounts_proof(&[genesis_miner.clone(), absent.clone()]);
+    assert_eq!(proof.verify(), Ok(()));
+    assert_eq!(proof.root_hash(), blockchain.head().header.accounts_hash);
+    assert_eq!(proof.get_account(&genesis_miner), Some(blockchain.accounts().get(&genesis_miner, None)));
+    assert_eq!(proof.get_account(&absent), None);
+}
+
+/// Breaks ties in total difficulty in favour of the lower head hash.
+struct LowerHashTieBreak;
+
+impl ForkChoice for LowerHashTieBreak {
+    fn should_rebranch(&self, main_chain: &ChainInfo, candidate: &ChainInfo) -> bool {
+        if candidate.total_difficulty != main_chain.total_difficulty {
+            return candidate.total_difficulty > main_chain.total_difficulty;
+        }
+        candidate.head.header.hash::<Blake2bHash>() < main_chain.head.header.hash::<Blake2bHash>()
+    }
+}
+
+#[test]
+fn it_uses_the_configured_fork_choice() {
+    crate::setup();
+
+    let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let block_2 = block_from_hex(BLOCK_2);
+    let fork_2 = crate::next_block(&blockchain)
+        .with_miner(Address::from(&keypair.public))
+        .with_nonce(34932)
+        .build();
+    assert_eq!(block_2.header.n_bits, fork_2.header.n_bits);
+
+    // Push the block with the higher hash first, so the tie break prefers the second one.
+    let (higher, lower) = if block_2.header.hash::<Blake2bHash>() > fork_2.header.hash::<Blake2bHash>() {
+        (block_2, fork_2)
+    } else {
+        (fork_2, block_2)
+    };
+
+    // The default fork choice keeps the main chain on a tie.
+    assert_eq!(blockchain.push(higher.clone()), PushResult::Extended);
+    assert_eq!(blockchain.push(lower.clone()), PushResult::Forked);
+    assert_eq!(blockchain.head_hash(), higher.header.hash());
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::with_fork_choice(&env, NetworkId::Main, Arc::new(NetworkTime::new()), Box::new(LowerHashTieBreak));
+    assert_eq!(blockchain.push(higher.clone()), PushResult::Extended);
+    assert_eq!(blockchain.push(lower.clone()), PushResult::Rebranched);
+    assert_eq!(blockchain.head_hash(), lower.header.hash());
+}
+
+#[test]
+fn it_purges_a_block_and_its_descendants() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let block2 = block_from_hex(BLOCK_2);
+    let hash2: Blake2bHash = block2.header.hash();
+    assert_eq!(blockchain.push(block2.clone()), PushResult::Extended);
+    assert_eq!(blockchain.purge_block(&hash2), Err(PushError::BlockOnMainChain(PushErrorContext::new(hash2.clone(), 2))));
+
+    // Store a fork subtree directly: root <- (child_a <- grandchild, child_b), plus an unrelated sibling of root.
+    let store = ChainStore::new(&env);
+    let genesis_hash = blockchain.head().header.prev_hash.clone();
+    let genesis_info = store.get_chain_info(&genesis_hash, true, None).unwrap();
+    let mut txn = WriteTransaction::new(&env);
+    let mut put_child = |prev_hash: &Blake2bHash, prev_info: &ChainInfo, nonce: u32| {
+        let mut block = block2.clone();
+        block.header.prev_hash = prev_hash.clone();
+        block.header.height = prev_info.head.header.height + 1;
+        block.header.nonce = nonce;
+        let hash: Blake2bHash = block.header.hash();
+        let info = prev_info.next(block);
+        store.put_chain_info(&mut txn, &hash, &info, true);
+        (hash, info)
+    };
+    let (root_hash, root_info) = put_child(&genesis_hash, &genesis_info, 1);
+    let (sibling_hash, _) = put_child(&genesis_hash, &genesis_info, 2);
+    let (child_a_hash, child_a_info) = put_child(&root_hash, &root_info, 3);
+    let (child_b_hash, _) = put_child(&root_hash, &root_info, 4);
+    let (grandchild_hash, _) = put_child(&child_a_hash, &child_a_info, 5);
+    txn.commit();
+
+    assert_eq!(blockchain.purge_block(&root_hash), Ok(4));
+    for hash in &[root_hash.clone(), child_a_hash, child_b_hash, grandchild_hash] {
+        assert!(store.get_chain_info(hash, false, None).is_none());
+    }
+    assert!(store.get_chain_info(&sibling_hash, false, None).is_some());
+    assert_eq!(store.get_blocks_at(2, false, None).len(), 2);
+    assert!(store.get_blocks_at(3, false, None).is_empty());
+    assert_eq!(blockchain.head_hash(), hash2);
+
+    // Purging an unknown block is a no-op.
+    assert_eq!(blockchain.purge_block(&root_hash), Ok(0));
+}
+
+/// Verifies a block and commits it to `accounts`, propagating errors with `?`.
+fn verify_and_commit(env: &Environment, accounts: &Accounts, block: &Block) -> Result<(), PushError> {
+    let genesis_hash = get_network_info(NetworkId::Main).unwrap().genesis_hash.clone();
+    block.verify(block.header.timestamp_in_millis(), NetworkId::Main, genesis_hash)?;
+
+    let mut txn = WriteTransaction::new(env);
+    let result = accounts.commit_block(&mut txn, block);
+    txn.abort();
+    result?;
+    Ok(())
+}
+
+#[test]
+fn it_propagates_errors_with_the_question_mark_operator() {
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let accounts = Accounts::new(&env);
+    let block2 = block_from_hex(BLOCK_2);
+
+    let mut tampered = block2.clone();
+    tampered.body.as_mut().unwrap().extra_data = b"tampered".to_vec();
+    assert_eq!(verify_and_commit(&env, &accounts, &tampered), Err(PushError::InvalidBlock(BlockError::BodyHashMismatch, None)));
+
+    // The accounts tree is empty, so committing yields a different accounts hash.
+    let error = verify_and_commit(&env, &accounts, &block2).unwrap_err();
+    assert_eq!(error, PushError::AccountsHashMismatch(None));
+    assert_eq!(error.to_string(), "accounts hash mismatch");
+    let context = PushErrorContext::from(&block2);
+    assert_eq!(error.with_context(context.clone()), PushError::AccountsHashMismatch(Some(context)));
+
+    let mut unfunded = block2.clone();
+    unfunded.body.as_mut().unwrap().transactions.push(Transaction::new_basic(
+        [1u8; Address::SIZE].into(),
+        [2u8; Address::SIZE].into(),
+        10.into(),
+        0.into(),
+        2,
+        NetworkId::Main
+    ));
+    let mut txn = WriteTransaction::new(&env);
+    let context = PushErrorContext::from(&unfunded);
+    let error = PushError::accounts_error(accounts.commit_block(&mut txn, &unfunded).unwrap_err(), context.clone());
+    txn.abort();
+    assert_eq!(error, PushError::AccountsError(AccountError::InsufficientFunds, Some(context.clone())));
+    assert_eq!(error.context().unwrap().height, 2);
+
+    // The errors expose their causes and the block they refer to.
+    assert_eq!(error.to_string(), format!("commit failed ({}) - block {} at height #2", AccountError::InsufficientFunds, context.block_hash));
+    let error: Box<dyn std::error::Error> = Box::new(PushError::invalid_block(BlockError::from(TransactionError::InvalidProof), context));
+    let block_error = error.source().unwrap();
+    assert_eq!(block_error.to_string(), format!("invalid transaction ({})", TransactionError::InvalidProof));
+    assert_eq!(block_error.source().unwrap().to_string(), TransactionError::InvalidProof.to_string());
+}
+
+#[test]
+fn it_pushes_a_batch_of_blocks() {
+    crate::setup();
+
+    let blocks: Vec<Block> = [BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5].iter()
+        .map(|block| block_from_hex(block))
+        .collect();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let expected_height = Arc::new(Atomic::new(2u32));
+    let expected_height1 = expected_height.clone();
+    blockchain.notifier.write().register(move |e: &BlockchainEvent| {
+        match e {
+            BlockchainEvent::Extended(_, block) => {
+                assert_eq!(block.header.height, expected_height1.load(Ordering::Relaxed));
+                expected_height1.store(block.header.height + 1, Ordering::Relaxed);
+            },
+            e => panic!("Unexpected event {:?}", e),
+        }
+    });
+
+    assert_eq!(blockchain.push_batch(blocks.clone()), vec![PushResult::Extended; 4]);
+    assert_eq!(expected_height.load(Ordering::Relaxed), 6);
+
+    // The result matches pushing the blocks one by one.
+    let env2 = VolatileEnvironment::new(10).unwrap();
+    let reference = new_blockchain(&env2);
+    for block in blocks.iter() {
+        assert_eq!(reference.push(block.clone()), PushResult::Extended);
+    }
+    assert_eq!(blockchain.head_hash(), reference.head_hash());
+    assert_eq!(blockchain.accounts().hash(None), reference.accounts().hash(None));
+    assert_eq!(blockchain.transaction_cache().head_hash(), reference.transaction_cache().head_hash());
+    assert_eq!(blockchain.get_block_at(3, false).unwrap().header.hash::<Blake2bHash>(), blocks[1].header.hash::<Blake2bHash>());
+
+    // Pushing the blocks again falls back to pushing them one by one.
+    assert_eq!(blockchain.push_batch(blocks), vec![PushResult::Known; 4]);
+}
+
+#[test]
+fn it_falls_back_to_single_pushes_when_a_batch_is_interrupted() {
+    crate::setup();
+
+    let blocks: Vec<Block> = [BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5].iter()
+        .map(|block| block_from_hex(block))
+        .collect();
+
+    // An out-of-order block ends the batch, the remaining blocks are pushed one by one.
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let results = blockchain.push_batch(vec![blocks[0].clone(), blocks[2].clone(), blocks[1].clone(), blocks[3].clone()]);
+    assert_eq!(results, vec![PushResult::Extended, PushResult::Orphan, PushResult::Extended, PushResult::Extended]);
+    assert_eq!(blockchain.height(), 5);
+
+    // An invalid block is rejected, the blocks before it are kept.
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let mut invalid = blocks[1].clone();
+    invalid.header.nonce = 1;
+    let context = PushErrorContext::from(&invalid);
+    let results = blockchain.push_batch(vec![blocks[0].clone(), invalid, blocks[2].clone()]);
+    assert_eq!(results, vec![PushResult::Extended, PushResult::Invalid(PushError::InvalidBlock(BlockError::InvalidPoW, Some(context))), PushResult::Orphan]);
+    assert_eq!(blockchain.head_hash(), blocks[0].header.hash::<Blake2bHash>());
+}
+
+#[test]
+fn it_keeps_the_applied_blocks_when_a_commit_fails_mid_batch() {
+    crate::setup();
+
+    let blocks: Vec<Block> = [BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5].iter()
+        .map(|block| block_from_hex(block))
+        .collect();
+
+    // Build a block on top of block 3 that commits to a wrong accounts hash.
+    let env = VolatileEnvironment::new(10).unwrap();
+    let scratch = new_blockchain(&env);
+    assert_eq!(scratch.push(blocks[0].clone()), PushResult::Extended);
+    assert_eq!(scratch.push(blocks[1].clone()), PushResult::Extended);
+    let mut mismatch = crate::next_block(&scratch).build();
+    mismatch.header.accounts_hash = [1u8; Blake2bHash::SIZE].into();
+    crate::mine_header(&mut mismatch.header);
+    let context = PushErrorContext::from(&mismatch);
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+    let results = blockchain.push_batch(vec![blocks[0].clone(), blocks[1].clone(), mismatch, blocks[3].clone()]);
+    assert_eq!(results, vec![PushResult::Extended, PushResult::Extended, PushResult::Invalid(PushError::AccountsHashMismatch(Some(context))), PushResult::Orphan]);
+
+    // The blocks before the failed one are committed and the accounts match them.
+    assert_eq!(blockchain.head_hash(), blocks[1].header.hash::<Blake2bHash>());
+    assert_eq!(blockchain.accounts().hash(None), blocks[1].header.accounts_hash);
+    assert_eq!(blockchain.push(blocks[2].clone()), PushResult::Extended);
+    assert_eq!(blockchain.height(), 5);
+}
+
+#[test]
+fn it_notifies_subscribers_of_a_height_range() {
+    use std::sync::Mutex;
+
+    crate::setup();
+
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = new_blockchain(&env);
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events1 = events.clone();
+    blockchain.subscribe_range(3, Some(4), move |e: &BlockchainEvent| {
+        let heights = match e {
+            BlockchainEvent::Extended(_, block) => vec![block.header.height],
+            BlockchainEvent::Rebranched(reverted, _) => reverted.iter().map(|(_, block)| block.header.height).collect(),
+            e => panic!("Unexpected event {:?}", e),
+        };
+        events1.lock().unwrap().push(heights);
+    });
+
+    for block in &[BLOCK_2, BLOCK_3, BLOCK_4, BLOCK_5] {
+        assert_eq!(blockchain.push(block_from_hex(block)), PushResult::Extended);
+    }
+    assert_eq!(*events.lock().unwrap(), vec![vec![3], vec![4]]);
+
+    // Reverting block 5 is outside of the range, reverting block 4 is not.
+    assert_eq!(blockchain.revert_to_height(4).unwrap().len(), 1);
+    assert_eq!(blockchain.revert_to_height(3).unwrap().len(), 1);
+    assert_eq!(*events.lock().unwrap(), vec![vec![3], vec![4], vec![4]]);
+
+    // Open-ended ranges include every later block.
+    let event = BlockchainEvent::Rebranched(vec![], vec![]);
+    assert!(!event.touches_heights(0, None));
+    let block5 = block_from_hex(BLOCK_5);
+    let event = BlockchainEvent::Extended(block5.header.hash(), UniquePtr::new(&block5));
+    assert!(event.touches_heights(3, None));
+    assert!(!event.touches_heights(3, Some(4)));
+    assert!(!BlockchainEvent::Forked(block5.header.hash(), UniquePtr::new(&block5)).touches_heights(0, None));
+}
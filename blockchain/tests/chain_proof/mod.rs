@@ -0,0 +1,263 @@
+use std::sync::Arc;
+
+use beserial::{Deserialize, Serialize};
+use nimiq_blockchain::{Blockchain, BlockchainConfig, HeaviestChain, PushResult};
+use nimiq_blockchain::chain_proof::{verify_chain_proof, CancellationToken, ChainProof, ChainProofError};
+use nimiq_blockchain::chain_store::ChainStore;
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_network_primitives::networks::get_network_info;
+use nimiq_network_primitives::time::NetworkTime;
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_primitives::block::{Block, BlockHeader, BlockInterlink};
+use nimiq_primitives::networks::NetworkId;
+
+#[test]
+fn it_accepts_the_proof_of_a_fresh_chain() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+
+    let proof = blockchain.get_chain_proof().unwrap();
+    assert_eq!(proof.verify(), Ok(()));
+}
+
+#[test]
+fn it_proves_a_genesis_only_chain() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    let genesis_block = &get_network_info(NetworkId::Main).unwrap().genesis_block;
+    assert_eq!(blockchain.height(), 1);
+
+    let proof = blockchain.get_chain_proof().unwrap();
+    assert_eq!(proof.prefix.len(), 1);
+    assert_eq!(proof.prefix[0].header, genesis_block.header);
+    assert!(proof.prefix[0].body.is_none());
+    assert!(proof.suffix.is_empty());
+    assert_eq!(proof.head_hash(), Some(genesis_block.header.hash()));
+    assert_eq!(proof.verify(), Ok(()));
+    assert!(proof.is_current(&blockchain));
+}
+
+#[test]
+fn it_rejects_overlong_suffixes_early() {
+    let mut genesis_block = get_network_info(NetworkId::Main).unwrap().genesis_block.clone();
+    genesis_block.body = None;
+
+    // The suffix headers are garbage, so any check beyond the length check would fail differently.
+    let proof = ChainProof {
+        prefix: vec![genesis_block],
+        suffix: vec![BlockHeader::default(); ChainProof::MAX_SUFFIX_LENGTH + 1],
+    };
+    assert_eq!(proof.verify(), Err(ChainProofError::SuffixTooLong { length: ChainProof::MAX_SUFFIX_LENGTH + 1, max: ChainProof::MAX_SUFFIX_LENGTH }));
+
+    let proof = ChainProof {
+        prefix: vec![],
+        suffix: vec![BlockHeader::default(); 5],
+    };
+    assert_eq!(proof.verify_with_limits(4, 10), Err(ChainProofError::SuffixTooLong { length: 5, max: 4 }));
+}
+
+#[test]
+fn it_discards_chain_proofs_for_stale_heads() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+
+    let (head_hash, stale_proof) = blockchain.compute_chain_proof(&CancellationToken::new()).unwrap();
+    assert_eq!(head_hash, blockchain.head_hash());
+
+    // The head changes while the proof is being computed.
+    let block = crate::next_block(&blockchain)
+        .with_nonce(83054)
+        .build();
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+    assert!(!blockchain.store_chain_proof(&head_hash, stale_proof));
+
+    // The next request computes a fresh proof for the new head.
+    let proof = blockchain.get_chain_proof().unwrap();
+    assert_eq!(proof.suffix.last().unwrap().hash::<Blake2bHash>(), blockchain.head_hash());
+    assert_eq!(proof.verify(), Ok(()));
+}
+
+#[test]
+fn it_can_cancel_chain_proof_computation() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    assert!(blockchain.get_chain_proof_cancellable(&cancel).is_none());
+    assert!(blockchain.get_chain_proof_cancellable(&CancellationToken::new()).is_some());
+}
+
+#[test]
+fn it_does_not_serve_chain_proofs_when_disabled() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    {
+        // Persist a chain proof for the head.
+        let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+        assert!(blockchain.get_chain_proof().is_some());
+    }
+
+    let mut config = BlockchainConfig::default();
+    config.set_serve_chain_proofs(false);
+    let blockchain = Blockchain::with_config(&env, NetworkId::Main, Arc::new(NetworkTime::new()), Box::new(HeaviestChain), config);
+
+    // The persisted proof is not loaded.
+    assert!(blockchain.cached_chain_proof().is_none());
+    assert!(blockchain.get_chain_proof().is_none());
+
+    // Proofs computed elsewhere are not cached either.
+    let (head_hash, proof) = blockchain.compute_chain_proof(&CancellationToken::new()).unwrap();
+    assert!(!blockchain.store_chain_proof(&head_hash, proof));
+    assert!(blockchain.cached_chain_proof().is_none());
+}
+
+#[test]
+fn it_round_trips_chain_proofs_through_the_compressed_codec() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    for block in &[crate::blockchain::BLOCK_2, crate::blockchain::BLOCK_3, crate::blockchain::BLOCK_4] {
+        assert_eq!(blockchain.push(Block::deserialize_from_vec(&hex::decode(block).unwrap()).unwrap()), PushResult::Extended);
+    }
+
+    let proof = blockchain.get_chain_proof().unwrap();
+    assert_eq!(proof.suffix.len(), 3);
+    let compressed = proof.serialize_compressed();
+    assert_eq!(ChainProof::deserialize_compressed(&compressed).unwrap(), proof);
+    assert!(compressed.len() < proof.serialized_size());
+
+    // Prefix blocks share their interlinks.
+    let proof = ChainProof {
+        prefix: (1..=4).map(|height| blockchain.get_block_at(height, false).unwrap()).collect(),
+        suffix: vec![],
+    };
+    let compressed = proof.serialize_compressed();
+    assert_eq!(ChainProof::deserialize_compressed(&compressed).unwrap(), proof);
+    assert!(compressed.len() < proof.serialized_size() * 3 / 4);
+
+    assert!(ChainProof::deserialize_compressed(&compressed[..compressed.len() - 1]).is_err());
+}
+
+#[test]
+fn it_computes_the_serialized_size() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    for block in &[crate::blockchain::BLOCK_2, crate::blockchain::BLOCK_3, crate::blockchain::BLOCK_4, crate::blockchain::BLOCK_5] {
+        let block = Block::deserialize_from_vec(&hex::decode(block).unwrap()).unwrap();
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+
+    let proof = blockchain.get_chain_proof().unwrap();
+    assert_eq!(proof.serialized_size(), proof.serialize_to_vec().len());
+    assert!(!proof.is_oversized());
+}
+
+#[test]
+fn it_detects_stale_proofs() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    let block = Block::deserialize_from_vec(&hex::decode(crate::blockchain::BLOCK_2).unwrap()).unwrap();
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+
+    let proof = blockchain.get_chain_proof().unwrap();
+    assert_eq!(proof.head_hash(), Some(blockchain.head_hash()));
+    assert!(proof.is_current(&blockchain));
+
+    let block = Block::deserialize_from_vec(&hex::decode(crate::blockchain::BLOCK_3).unwrap()).unwrap();
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+    assert!(!proof.is_current(&blockchain));
+    assert!(blockchain.get_chain_proof().unwrap().is_current(&blockchain));
+
+    let empty_proof = ChainProof { prefix: vec![], suffix: vec![] };
+    assert_eq!(empty_proof.head_hash(), None);
+    assert!(!empty_proof.is_current(&blockchain));
+}
+
+#[test]
+fn it_verifies_prover_generated_proofs() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+
+    let genesis_proof = blockchain.get_chain_proof().unwrap();
+    assert_eq!(verify_chain_proof(&genesis_proof, NetworkId::Main), Ok(()));
+
+    for block in &[crate::blockchain::BLOCK_2, crate::blockchain::BLOCK_3, crate::blockchain::BLOCK_4, crate::blockchain::BLOCK_5] {
+        let block = Block::deserialize_from_vec(&hex::decode(block).unwrap()).unwrap();
+        assert_eq!(blockchain.push(block), PushResult::Extended);
+    }
+    let proof = blockchain.get_chain_proof().unwrap();
+    assert_eq!(verify_chain_proof(&proof, NetworkId::Main), Ok(()));
+
+    // The longer chain wins.
+    assert!(proof.is_better_than(&genesis_proof));
+    assert!(!genesis_proof.is_better_than(&proof));
+    assert!(!proof.is_better_than(&proof));
+
+    // Tampered proofs are rejected.
+    let mut tampered = proof.clone();
+    tampered.suffix.last_mut().unwrap().nonce += 1;
+    assert_eq!(verify_chain_proof(&tampered, NetworkId::Main), Err(ChainProofError::InvalidPoW));
+
+    let mut tampered = proof.clone();
+    tampered.suffix.remove(1);
+    assert_eq!(verify_chain_proof(&tampered, NetworkId::Main), Err(ChainProofError::InvalidSuffixChain));
+
+    // The proof must start at the genesis block of the expected network.
+    assert_eq!(verify_chain_proof(&proof, NetworkId::Test), Err(ChainProofError::InvalidGenesis));
+}
+
+#[test]
+fn it_rejects_proofs_with_invalid_interlinks_or_incomplete_superchains() {
+    let mut prefix = vec![get_network_info(NetworkId::Main).unwrap().genesis_block.clone()];
+    for block in &[crate::blockchain::BLOCK_2, crate::blockchain::BLOCK_3, crate::blockchain::BLOCK_4, crate::blockchain::BLOCK_5] {
+        prefix.push(Block::deserialize_from_vec(&hex::decode(block).unwrap()).unwrap());
+    }
+    let proof = ChainProof { prefix, suffix: vec![] };
+    assert_eq!(verify_chain_proof(&proof, NetworkId::Main), Ok(()));
+
+    // Block 4 references block 2 through its interlink, so the prefix still links up without block 3.
+    // The superchain at depth 0 contains every block though, so block 3 must not be omitted.
+    let mut incomplete = proof.clone();
+    let block3 = incomplete.prefix.remove(2);
+    assert!(incomplete.prefix[2].interlink.hashes.contains(&block3.header.prev_hash));
+    assert_eq!(verify_chain_proof(&incomplete, NetworkId::Main), Err(ChainProofError::IncompleteSuperchain));
+
+    // The interlink of a prefix block must match the interlink hash in its header.
+    let mut tampered = proof.clone();
+    let prev_hash = tampered.prefix[3].header.prev_hash.clone();
+    tampered.prefix[3].interlink = BlockInterlink::new(vec![prev_hash.clone()], &prev_hash);
+    assert_eq!(verify_chain_proof(&tampered, NetworkId::Main), Err(ChainProofError::InvalidInterlink));
+}
+
+#[test]
+fn it_restores_the_persisted_chain_proof() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let block2 = Block::deserialize_from_vec(&hex::decode(crate::blockchain::BLOCK_2).unwrap()).unwrap();
+    let block3 = Block::deserialize_from_vec(&hex::decode(crate::blockchain::BLOCK_3).unwrap()).unwrap();
+
+    let proof = {
+        let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+        assert_eq!(blockchain.push(block2), PushResult::Extended);
+        assert!(blockchain.cached_chain_proof().is_none());
+        let proof = blockchain.get_chain_proof().unwrap();
+
+        // The proof is persisted right away, not only when the blockchain is dropped.
+        assert_eq!(ChainStore::new(&env).get_chain_proof(None), Some((blockchain.head_hash(), proof.clone())));
+        proof
+    };
+
+    {
+        let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+        assert_eq!(blockchain.cached_chain_proof(), Some(proof.clone()));
+
+        // The proof becomes stale once the head moves on.
+        assert_eq!(blockchain.push(block3), PushResult::Extended);
+        assert!(blockchain.cached_chain_proof().is_none());
+    }
+
+    // A persisted proof for an older head is not restored.
+    let blockchain = Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new()));
+    assert!(blockchain.cached_chain_proof().is_none());
+    let new_proof = blockchain.get_chain_proof().unwrap();
+    assert!(new_proof.is_current(&blockchain));
+    assert_eq!(blockchain.cached_chain_proof(), Some(new_proof));
+}
@@ -1,12 +1,14 @@
-use nimiq_blockchain::{chain_info::ChainInfo, chain_store::ChainStore};
-use nimiq_database::{volatile::VolatileEnvironment, WriteTransaction};
+use nimiq_blockchain::{chain_info::ChainInfo, chain_store::ChainStore, Direction};
+use nimiq_database::{volatile::VolatileEnvironment, ReadTransaction, WriteTransaction};
 use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_keys::Address;
 use nimiq_network_primitives::networks::{get_network_info, NetworkId};
-use nimiq_primitives::block::Difficulty;
+use nimiq_primitives::account::{Account, PrunedAccount};
+use nimiq_primitives::block::{Block, Difficulty};
 
 #[test]
 fn it_can_store_the_chain_head() {
-    let env = VolatileEnvironment::new(3).unwrap();
+    let env = VolatileEnvironment::new(4).unwrap();
     let store = ChainStore::new(&env);
     assert!(store.get_head(None).is_none());
 
@@ -20,7 +22,7 @@ fn it_can_store_the_chain_head() {
 
 #[test]
 fn it_can_store_chain_info_with_body() {
-    let env = VolatileEnvironment::new(3).unwrap();
+    let env = VolatileEnvironment::new(4).unwrap();
     let store = ChainStore::new(&env);
     let genesis_block = get_network_info(NetworkId::Main).unwrap().genesis_block.clone();
     let genesis_hash = genesis_block.header.hash();
@@ -41,7 +43,7 @@ fn it_can_store_chain_info_with_body() {
 
 #[test]
 fn it_can_store_chain_info_without_body() {
-    let env = VolatileEnvironment::new(3).unwrap();
+    let env = VolatileEnvironment::new(4).unwrap();
     let store = ChainStore::new(&env);
     let genesis_block = get_network_info(NetworkId::Main).unwrap().genesis_block.clone();
     let genesis_hash = genesis_block.header.hash();
@@ -61,7 +63,7 @@ fn it_can_store_chain_info_without_body() {
 
 #[test]
 fn it_can_retrieve_chain_info_by_height() {
-    let env = VolatileEnvironment::new(3).unwrap();
+    let env = VolatileEnvironment::new(4).unwrap();
     let store = ChainStore::new(&env);
 
     let block1 = get_network_info(NetworkId::Main).unwrap().genesis_block.clone();
@@ -110,7 +112,7 @@ fn it_can_retrieve_chain_info_by_height() {
 
 #[test]
 fn it_can_get_blocks_backward() {
-    let env = VolatileEnvironment::new(3).unwrap();
+    let env = VolatileEnvironment::new(4).unwrap();
     let store = ChainStore::new(&env);
 
     let mut txn = WriteTransaction::new(&env);
@@ -176,7 +178,7 @@ fn it_can_get_blocks_backward() {
 
 #[test]
 fn it_can_get_blocks_forward() {
-    let env = VolatileEnvironment::new(3).unwrap();
+    let env = VolatileEnvironment::new(4).unwrap();
     let store = ChainStore::new(&env);
 
     let mut txn = WriteTransaction::new(&env);
@@ -250,3 +252,126 @@ fn it_can_get_blocks_forward() {
     blocks = store.get_blocks_forward(&chain_infos[20].head.header.hash(), 20, false, None);
     assert_eq!(blocks.len(), 0);
 }
+
+#[test]
+fn it_can_iterate_blocks_with_a_cursor() {
+    let env = VolatileEnvironment::new(4).unwrap();
+    let store = ChainStore::new(&env);
+
+    let mut txn = WriteTransaction::new(&env);
+    let mut block = get_network_info(NetworkId::Main).unwrap().genesis_block.clone();
+    store.put_chain_info(&mut txn, &block.header.hash::<Blake2bHash>(), &ChainInfo::initial(block.clone()), true);
+
+    for _ in 0..20 {
+        let mut b = block.clone();
+        b.header.prev_hash = block.header.hash();
+        b.header.height = block.header.height + 1;
+        let hash = b.header.hash::<Blake2bHash>();
+        store.put_chain_info(&mut txn, &hash, &ChainInfo::initial(b.clone()), true);
+        block = b;
+    }
+    txn.commit();
+
+    let head_hash = block.header.hash::<Blake2bHash>();
+    let txn = ReadTransaction::new(&env);
+
+    for &(count, include_body) in &[(10, true), (20, false), (25, true)] {
+        let expected = store.get_blocks_backward(&head_hash, count, include_body, None);
+        let blocks: Vec<Block> = store.block_cursor(&head_hash, Direction::Backward, include_body, &txn)
+            .take(count as usize)
+            .collect();
+        assert_eq!(blocks, expected);
+    }
+
+    // Iteration stops at the genesis block.
+    assert_eq!(store.block_cursor(&head_hash, Direction::Backward, false, &txn).count(), 20);
+    let genesis_hash = get_network_info(NetworkId::Main).unwrap().genesis_hash.clone();
+    assert_eq!(store.block_cursor(&genesis_hash, Direction::Backward, false, &txn).count(), 0);
+}
+
+#[test]
+fn it_can_find_the_block_that_pruned_an_account() {
+    let env = VolatileEnvironment::new(4).unwrap();
+    let store = ChainStore::new(&env);
+    let pruned_address = Address::from([7u8; Address::SIZE]);
+
+    let mut txn = WriteTransaction::new(&env);
+    let mut block = get_network_info(NetworkId::Main).unwrap().genesis_block.clone();
+    store.put_chain_info(&mut txn, &block.header.hash::<Blake2bHash>(), &ChainInfo::initial(block.clone()), true);
+
+    let mut hashes = vec![block.header.hash::<Blake2bHash>()];
+    for _ in 0..10 {
+        let mut b = block.clone();
+        b.header.prev_hash = block.header.hash();
+        b.header.height = block.header.height + 1;
+        let body = b.body.as_mut().unwrap();
+        body.pruned_accounts = if b.header.height == 5 {
+            vec![PrunedAccount { address: pruned_address.clone(), account: Account::INITIAL }]
+        } else {
+            Vec::new()
+        };
+        let hash = b.header.hash::<Blake2bHash>();
+        store.put_chain_info(&mut txn, &hash, &ChainInfo::initial(b.clone()), true);
+        hashes.push(hash);
+        block = b;
+    }
+    txn.commit();
+
+    let head_hash = hashes.last().unwrap().clone();
+    assert_eq!(store.find_pruning_block(&head_hash, &pruned_address, 100, None), Some((hashes[4].clone(), 5)));
+    assert_eq!(store.find_pruning_block(&hashes[4], &pruned_address, 1, None), Some((hashes[4].clone(), 5)));
+    assert_eq!(store.find_pruning_block(&hashes[3], &pruned_address, 100, None), None);
+    assert_eq!(store.find_pruning_block(&head_hash, &Address::from([8u8; Address::SIZE]), 100, None), None);
+
+    // The block is out of reach if the search window is too small.
+    assert_eq!(store.find_pruning_block(&head_hash, &pruned_address, 6, None), None);
+    assert_eq!(store.find_pruning_block(&head_hash, &pruned_address, 7, None), Some((hashes[4].clone(), 5)));
+
+    // Pruned block bodies end the search.
+    let mut txn = WriteTransaction::new(&env);
+    assert!(store.remove_block_body(&mut txn, &hashes[7]));
+    txn.commit();
+    assert_eq!(store.find_pruning_block(&head_hash, &pruned_address, 100, None), None);
+}
+
+#[test]
+fn it_finds_fork_tips() {
+    let env = VolatileEnvironment::new(4).unwrap();
+    let store = ChainStore::new(&env);
+    let mut genesis_block = get_network_info(NetworkId::Main).unwrap().genesis_block.clone();
+    genesis_block.body = None;
+
+    let mut txn = WriteTransaction::new(&env);
+    let mut put = |prev: &Block, nonce: u32, on_main_chain: bool| {
+        let mut block = prev.clone();
+        block.header.prev_hash = prev.header.hash();
+        block.header.height = prev.header.height + 1;
+        block.header.nonce = nonce;
+        let hash = block.header.hash::<Blake2bHash>();
+        let mut chain_info = ChainInfo::initial(block.clone());
+        chain_info.on_main_chain = on_main_chain;
+        store.put_chain_info(&mut txn, &hash, &chain_info, false);
+        (hash, block)
+    };
+
+    // Main chain up to height 5.
+    let (_, main_2) = put(&genesis_block, 1, true);
+    let (_, main_3) = put(&main_2, 1, true);
+    let (_, main_4) = put(&main_3, 1, true);
+    put(&main_4, 1, true);
+
+    // An old fork at height 2 and two recent forks, one of which is two blocks long.
+    put(&genesis_block, 2, false);
+    let (recent_hash, _) = put(&main_3, 2, false);
+    let (_, long_4) = put(&main_3, 3, false);
+    let (long_hash, _) = put(&long_4, 3, false);
+    txn.commit();
+
+    let tips: Vec<Blake2bHash> = store.get_fork_tips(4, None).into_iter().map(|(hash, _)| hash).collect();
+    assert_eq!(tips.len(), 2);
+    assert!(tips.contains(&recent_hash));
+    assert!(tips.contains(&long_hash));
+
+    assert_eq!(store.get_fork_tips(2, None).len(), 3);
+    assert!(store.get_fork_tips(6, None).is_empty());
+}
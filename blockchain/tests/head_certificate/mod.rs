@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use beserial::Deserialize;
+use nimiq_blockchain::{Blockchain, HeadCertificate, PushResult};
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_hash::Blake2bHash;
+use nimiq_keys::{KeyPair, PrivateKey};
+use nimiq_network_primitives::time::NetworkTime;
+use nimiq_primitives::block::{Block, Difficulty};
+use nimiq_primitives::networks::NetworkId;
+
+#[test]
+fn it_can_produce_and_verify_head_certificates() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let network_time = Arc::new(NetworkTime::new());
+    let blockchain = Blockchain::new(&env, NetworkId::Main, network_time.clone());
+    let block = Block::deserialize_from_vec(&hex::decode(crate::blockchain::BLOCK_2).unwrap()).unwrap();
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+
+    let key_pair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
+    let before = network_time.now();
+    let certificate = blockchain.head_certificate(&key_pair);
+    let after = network_time.now();
+    assert_eq!(certificate.network_id, NetworkId::Main);
+    assert_eq!(certificate.head_hash, blockchain.head_hash());
+    assert_eq!(certificate.height, 2);
+    assert_eq!(certificate.total_difficulty, blockchain.chain_work());
+    assert!(certificate.issued_at >= before && certificate.issued_at <= after);
+    assert!(certificate.verify(&key_pair.public, NetworkId::Main));
+
+    // Certificates are bound to their network.
+    assert!(!certificate.verify(&key_pair.public, NetworkId::Test));
+    let mut replayed = certificate.clone();
+    replayed.network_id = NetworkId::Test;
+    assert!(!replayed.verify(&key_pair.public, NetworkId::Test));
+
+    let other_key_pair: KeyPair = PrivateKey::from([2u8; PrivateKey::SIZE]).into();
+    assert!(!certificate.verify(&other_key_pair.public, NetworkId::Main));
+
+    let mut tampered = certificate.clone();
+    tampered.height = 3;
+    assert!(!tampered.verify(&key_pair.public, NetworkId::Main));
+    let mut tampered = certificate.clone();
+    tampered.issued_at += 1;
+    assert!(!tampered.verify(&key_pair.public, NetworkId::Main));
+}
+
+#[test]
+fn it_checks_the_freshness_of_head_certificates() {
+    let key_pair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
+    let certificate = HeadCertificate::new(NetworkId::Main, Blake2bHash::from([1u8; Blake2bHash::SIZE]), 2, Difficulty::from(1), 10_000, &key_pair);
+
+    assert!(certificate.is_fresh(10_000, 0));
+    assert!(certificate.is_fresh(15_000, 5_000));
+    assert!(!certificate.is_fresh(15_001, 5_000));
+    // Certificates from the future are not fresh either.
+    assert!(!certificate.is_fresh(9_999, 5_000));
+}
@@ -1,4 +1,6 @@
 extern crate hex;
+#[macro_use]
+extern crate lazy_static;
 
 use nimiq_blockchain::Blockchain;
 use nimiq_hash::{Blake2bHash, Hash};
@@ -9,10 +11,15 @@ use nimiq_primitives::block::*;
 use nimiq_primitives::policy;
 use nimiq_primitives::transaction::Transaction;
 
+mod block_queue;
 mod blockchain;
 mod chain_info;
+mod chain_proof;
 mod chain_store;
+mod head_certificate;
+mod orphan_pool;
 mod super_block_counts;
+mod target_cache;
 mod transaction_cache;
 
 
@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+
+use beserial::Deserialize;
+use nimiq_blockchain::orphan_pool::OrphanPool;
+use nimiq_hash::Blake2bHash;
+use nimiq_network_primitives::address::PeerId;
+use nimiq_primitives::block::Block;
+use nimiq_primitives::policy;
+
+fn orphan(prev: u8, height: u32) -> Block {
+    let mut block = Block::deserialize_from_vec(&hex::decode(crate::blockchain::BLOCK_2).unwrap()).unwrap();
+    block.header.prev_hash = Blake2bHash::from([prev; Blake2bHash::SIZE]);
+    block.header.height = height;
+    block
+}
+
+fn peer(i: u8) -> PeerId {
+    PeerId::from([i; PeerId::SIZE])
+}
+
+#[test]
+fn it_evicts_the_oldest_orphan_when_full() {
+    let mut pool = OrphanPool::new();
+    let now = Instant::now();
+    assert!(pool.add(orphan(1, 10), None, 1, 2, now));
+    assert!(pool.add(orphan(2, 10), None, 1, 2, now));
+    assert!(!pool.add(orphan(2, 10), None, 1, 2, now));
+    assert_eq!(pool.len(), 2);
+
+    assert!(pool.add(orphan(3, 10), None, 1, 2, now));
+    assert_eq!(pool.len(), 2);
+    assert!(pool.take_successors(&Blake2bHash::from([1u8; Blake2bHash::SIZE])).is_empty());
+    assert_eq!(pool.take_successors(&Blake2bHash::from([2u8; Blake2bHash::SIZE])), vec![orphan(2, 10)]);
+    assert_eq!(pool.take_successors(&Blake2bHash::from([3u8; Blake2bHash::SIZE])), vec![orphan(3, 10)]);
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn it_caps_the_orphans_of_a_single_peer() {
+    let mut pool = OrphanPool::new();
+    let now = Instant::now();
+    for i in 0..OrphanPool::MAX_PER_ORIGIN {
+        assert!(pool.add(orphan(1, 10 + i as u32), Some(&peer(1)), 1, 1000, now));
+    }
+    assert!(!pool.add(orphan(2, 10), Some(&peer(1)), 1, 1000, now));
+
+    // Other peers and local blocks are not affected.
+    assert!(pool.add(orphan(2, 10), Some(&peer(2)), 1, 1000, now));
+    assert!(pool.add(orphan(3, 10), None, 1, 1000, now));
+
+    // Once its orphans are connected, the peer may buffer orphans again.
+    assert_eq!(pool.take_successors(&Blake2bHash::from([1u8; Blake2bHash::SIZE])).len(), OrphanPool::MAX_PER_ORIGIN);
+    assert!(pool.add(orphan(4, 10), Some(&peer(1)), 1, 1000, now));
+    assert_eq!(pool.len(), 3);
+}
+
+#[test]
+fn it_rejects_orphans_far_above_the_head() {
+    let mut pool = OrphanPool::new();
+    let now = Instant::now();
+    assert!(!pool.add(orphan(1, 100 + OrphanPool::MAX_HEIGHT_AHEAD + 1), None, 100, 10, now));
+    assert!(pool.add(orphan(1, 100 + OrphanPool::MAX_HEIGHT_AHEAD), None, 100, 10, now));
+    assert_eq!(pool.len(), 1);
+}
+
+#[test]
+fn it_prunes_old_and_buried_orphans() {
+    let mut pool = OrphanPool::new();
+    let now = Instant::now();
+    let later = now + Duration::from_secs(60);
+    assert!(pool.add(orphan(1, 10), Some(&peer(1)), 1, 10, now));
+    assert!(pool.add(orphan(2, 10), Some(&peer(1)), 1, 10, later));
+    assert!(pool.add(orphan(3, 200), None, 1, 10, later));
+
+    // Orphans expire some time after they were added.
+    pool.prune(1, now + OrphanPool::MAX_AGE);
+    assert_eq!(pool.len(), 2);
+    assert!(pool.take_successors(&Blake2bHash::from([1u8; Blake2bHash::SIZE])).is_empty());
+
+    // Orphans too far below the head are dropped.
+    pool.prune(10 + policy::TRANSACTION_VALIDITY_WINDOW, later);
+    assert_eq!(pool.len(), 1);
+    assert_eq!(pool.take_successors(&Blake2bHash::from([3u8; Blake2bHash::SIZE])), vec![orphan(3, 200)]);
+    assert!(pool.is_empty());
+}
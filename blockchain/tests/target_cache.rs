@@ -0,0 +1,52 @@
+use nimiq_blockchain::target_cache::TargetCache;
+use nimiq_hash::Blake2bHash;
+use nimiq_primitives::block::{Target, TargetCompact};
+use nimiq_primitives::policy::BLOCK_TIME;
+
+fn hash(i: u8) -> Blake2bHash {
+    Blake2bHash::from([i; Blake2bHash::SIZE])
+}
+
+fn target(i: u32) -> Target {
+    Target::from(TargetCompact::from(0x1f010000 + i))
+}
+
+#[test]
+fn it_evicts_the_least_recently_used_target() {
+    let mut cache = TargetCache::new(2);
+    assert!(cache.is_empty());
+
+    cache.insert(hash(1), BLOCK_TIME, target(1));
+    cache.insert(hash(2), BLOCK_TIME, target(2));
+    assert_eq!(cache.len(), 2);
+
+    // Using 1 makes 2 the least recently used entry.
+    assert_eq!(cache.get(&hash(1), BLOCK_TIME), Some(target(1)));
+    cache.insert(hash(3), BLOCK_TIME, target(3));
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get(&hash(2), BLOCK_TIME), None);
+    assert_eq!(cache.get(&hash(1), BLOCK_TIME), Some(target(1)));
+    assert_eq!(cache.get(&hash(3), BLOCK_TIME), Some(target(3)));
+
+    // Replacing an entry doesn't evict anything.
+    cache.insert(hash(1), BLOCK_TIME, target(4));
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get(&hash(1), BLOCK_TIME), Some(target(4)));
+    assert_eq!(cache.get(&hash(3), BLOCK_TIME), Some(target(3)));
+
+    cache.clear();
+    assert!(cache.is_empty());
+    assert_eq!(cache.get(&hash(1), BLOCK_TIME), None);
+}
+
+#[test]
+fn it_keeps_targets_for_different_block_times_apart() {
+    let mut cache = TargetCache::new(2);
+    cache.insert(hash(1), BLOCK_TIME, target(1));
+    assert_eq!(cache.get(&hash(1), BLOCK_TIME / 2), None);
+
+    cache.insert(hash(1), BLOCK_TIME / 2, target(2));
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get(&hash(1), BLOCK_TIME), Some(target(1)));
+    assert_eq!(cache.get(&hash(1), BLOCK_TIME / 2), Some(target(2)));
+}
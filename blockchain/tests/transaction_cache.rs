@@ -1,3 +1,4 @@
+use beserial::{Deserialize, Serialize};
 use nimiq_blockchain::transaction_cache::TransactionCache;
 use nimiq_hash::{Blake2bHash, Hash};
 use nimiq_keys::Address;
@@ -268,3 +269,42 @@ fn it_can_prepend_blocks() {
         assert!(!cache.contains_any(b));
     }
 }
+
+#[test]
+fn it_can_be_serialized_and_restored() {
+    let mut cache = TransactionCache::new();
+    let mut block = get_network_info(NetworkId::Main).unwrap().genesis_block.clone();
+    cache.push_block(&block);
+
+    let mut blocks = vec![block.clone()];
+    for i in 0..5 {
+        let mut b = block.clone();
+        b.header.prev_hash = block.header.hash();
+        b.header.height = block.header.height + 1;
+        b.body.as_mut().unwrap().transactions = vec![Transaction::new_basic(
+            [1u8; Address::SIZE].into(),
+            [2u8; Address::SIZE].into(),
+            Coin::from((i + 1) * 50),
+            Coin::ZERO,
+            b.header.height,
+            NetworkId::Main
+        )];
+        cache.push_block(&b);
+        blocks.push(b.clone());
+        block = b;
+    }
+
+    // A block whose transaction is not in the cache.
+    let mut unknown_block = block.clone();
+    unknown_block.body.as_mut().unwrap().transactions[0].value = Coin::from(1);
+    blocks.push(unknown_block);
+
+    let restored: TransactionCache = Deserialize::deserialize_from_vec(&cache.serialize_to_vec()).unwrap();
+    assert_eq!(restored.missing_blocks(), cache.missing_blocks());
+    assert_eq!(restored.head_hash(), cache.head_hash());
+    assert_eq!(restored.tail_hash(), cache.tail_hash());
+    for b in blocks.iter().skip(1) {
+        assert_eq!(restored.contains_any(b), cache.contains_any(b));
+    }
+    assert!(!restored.contains_any(blocks.last().unwrap()));
+}
@@ -118,7 +118,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         s::Network::Dummy => NetworkId::Dummy,
         s::Network::Bounty => NetworkId::Bounty
     };
-    info!("Nimiq Core starting: network={:?}, peer_address={}", network_id, network_config.peer_address());
+    info!("Nimiq Core starting: network={}, peer_address={}", network_id.name(), network_config.peer_address());
 
     // Start consensus
     let consensus = Consensus::new(&ENV, network_id, network_config);
@@ -150,7 +150,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             let ip = IpAddr::from_str("127.0.0.1").unwrap();
             let port = metrics_settings.port.unwrap_or(s::DEFAULT_METRICS_PORT);
             info!("Starting metrics server listening on port {}", port);
-            other_futures.push(metrics_server(Arc::clone(&consensus), ip, port, metrics_settings.password))
+            other_futures.push(metrics_server(Arc::clone(&consensus), ip, port, metrics_settings.password, vec![]))
         });
     }
     // If the metrics server is enabled, but the client is not compiled with it, inform the user
@@ -174,5 +174,8 @@ fn main() -> Result<(), Box<dyn Error>> {
             .map(|_| info!("Other futures finished"))
     );
 
+    // Spare the next start from rebuilding the transaction cache.
+    consensus.blockchain.persist_transaction_cache();
+
     Ok(())
 }
@@ -65,6 +65,7 @@ impl Consensus {
     pub fn new(env: &'static Environment, network_id: NetworkId, network_config: NetworkConfig) -> Arc<Self> {
         let network_time = Arc::new(NetworkTime::new());
         let blockchain = Arc::new(Blockchain::new(env, network_id, network_time.clone()));
+        Blockchain::spawn_queue_drain(&blockchain);
         let mempool = Mempool::new(blockchain.clone());
         let network = Network::new(blockchain.clone(), network_config, network_time, network_id);
 
@@ -198,7 +199,9 @@ impl Consensus {
             },
             BlockchainEvent::Rebranched(_, ref adopted_blocks) => {
                 blocks = adopted_blocks.iter().map(|(_, block)| block).collect();
-            }
+            },
+            // Only blocks on the main chain are relayed.
+            BlockchainEvent::Forked(..) => return,
         }
 
         for agent in state.agents.values() {
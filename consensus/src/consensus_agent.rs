@@ -6,7 +6,7 @@ use parking_lot::MutexGuard;
 use parking_lot::RwLock;
 use rand::Rng;
 
-use blockchain::{Blockchain, PushResult};
+use blockchain::{Blockchain, PushError, PushResult};
 use hash::Blake2bHash;
 use mempool::Mempool;
 use network::connection::close_type::CloseType;
@@ -231,7 +231,7 @@ impl ConsensusAgent {
 
             locators = match on_fork {
                 true => vec![state.fork_head.as_ref().unwrap().clone()],
-                false => self.blockchain.get_block_locators(GetBlocksMessage::LOCATORS_MAX_COUNT),
+                false => self.blockchain.get_block_locators(GetBlocksMessage::LOCATORS_MAX_COUNT, None),
             };
         }
 
@@ -282,6 +282,10 @@ impl ConsensusAgent {
 
     fn on_block_processed(&self, hash: &Blake2bHash, result: &PushResult) {
         match result {
+            // The block is valid, we just refuse to rebranch that deep.
+            PushResult::Invalid(PushError::RebranchTooDeep(_)) => {
+                debug!("Not rebranching to block {} from {} - rebranch too deep", hash, self.peer.peer_address());
+            },
             PushResult::Invalid(_) => {
                 self.peer.channel.close(CloseType::InvalidBlock);
             },
@@ -291,7 +295,7 @@ impl ConsensusAgent {
                     state.num_blocks_extending += 1;
                 }
             },
-            PushResult::Forked => {
+            PushResult::Forked | PushResult::ForkExtended => {
                 let mut state = self.state.write();
                 if state.syncing {
                     state.num_blocks_forking += 1;
@@ -319,8 +323,18 @@ impl ConsensusAgent {
     fn on_get_chain_proof(&self) {
         debug!("[GET-CHAIN-PROOF]");
 
+        if !self.blockchain.config().serve_chain_proofs() {
+            info!("Not serving chain proof to {} - disabled", self.peer.peer_address());
+            return;
+        }
+
         // TODO rate limit
-        let chain_proof = self.blockchain.get_chain_proof();
-        self.peer.channel.send_or_close(Message::ChainProof(chain_proof));
+        match self.blockchain.get_chain_proof() {
+            Some(ref chain_proof) if chain_proof.is_oversized() => {
+                warn!("Not serving chain proof to {} - too large ({} bytes)", self.peer.peer_address(), chain_proof.serialized_size());
+            },
+            Some(chain_proof) => self.peer.channel.send_or_close(Message::ChainProof(chain_proof)),
+            None => warn!("Not serving chain proof to {} - computation aborted", self.peer.peer_address()),
+        }
     }
 }
@@ -500,7 +500,7 @@ impl InventoryAgent {
         let height = block.header.height;
         let num_txs = block.body.as_ref().unwrap().transactions.len();
 
-        let result = self.blockchain.push(block);
+        let result = self.blockchain.push_from(block, &self.peer.peer_address().peer_id);
 
         debug!("Block #{} ({} txs) took {}ms to process", height, num_txs, utils::time::duration_as_millis(&(Instant::now() - start)));
 
@@ -94,6 +94,7 @@ as_lmdb_bytes!(str);
 as_lmdb_bytes!(CStr);
 as_lmdb_bytes!(char);
 
+as_lmdb_bytes!([u8]);
 as_lmdb_bytes!([u16]);
 as_lmdb_bytes!([i16]);
 as_lmdb_bytes!([u32]);
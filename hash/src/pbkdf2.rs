@@ -1,6 +1,8 @@
 use super::{Sha512Hash, SHA512_LENGTH};
 use super::hmac::compute_hmac_sha512;
 use byteorder::{BigEndian, WriteBytesExt};
+use std::error;
+use std::fmt;
 use std::io::{Write, Error};
 
 #[derive(Debug)]
@@ -9,6 +11,24 @@ pub enum Pbkdf2Error {
     IOError(Error),
 }
 
+impl fmt::Display for Pbkdf2Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pbkdf2Error::KeyTooLong => write!(f, "Derived key too long"),
+            Pbkdf2Error::IOError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for Pbkdf2Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Pbkdf2Error::KeyTooLong => None,
+            Pbkdf2Error::IOError(e) => Some(e),
+        }
+    }
+}
+
 pub fn compute_pbkdf2_sha512(password: &[u8], salt: &[u8], iterations: usize, derived_key_length: usize) -> Result<Vec<u8>, Pbkdf2Error> {
     // Following https://www.ietf.org/rfc/rfc2898.txt
     if (derived_key_length as u64) > (u32::max_value() as u64) * (Sha512Hash::len() as u64) {
@@ -1,7 +1,9 @@
 use crate::PublicKey;
-use crate::hash::{Blake2bHash, Blake2bHasher, Hasher, SerializeContent};
+use crate::hash::{Blake2bHash, SerializeContent};
 use std::convert::From;
 use std::char;
+use std::error::Error;
+use std::fmt;
 use std::io;
 use std::iter::Iterator;
 use hex::FromHex;
@@ -14,7 +16,25 @@ add_hex_io_fns_typed_arr!(Address, Address::SIZE);
 pub enum FriendlyAddressError {
     WrongCountryCode,
     WrongLength,
-    InvalidChecksum
+    InvalidChecksum,
+    InvalidEncoding,
+}
+
+impl fmt::Display for FriendlyAddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+impl Error for FriendlyAddressError {
+    fn description(&self) -> &str {
+        match self {
+            FriendlyAddressError::WrongCountryCode => "Wrong country code",
+            FriendlyAddressError::WrongLength => "Wrong length",
+            FriendlyAddressError::InvalidChecksum => "Invalid checksum",
+            FriendlyAddressError::InvalidEncoding => "Invalid encoding",
+        }
+    }
 }
 
 impl Address {
@@ -37,11 +57,34 @@ impl Address {
             return Err(FriendlyAddressError::InvalidChecksum);
         }
 
+        return Address::decode_friendly_body(&friendly_addr_wospace[4..]);
+    }
+
+    /// Like `from_user_friendly_address`, but does not verify the checksum.
+    /// Only meant for migrating legacy data, never use this on user input:
+    /// a typo in the address will go unnoticed.
+    pub fn from_user_friendly_address_unchecked(friendly_addr: &str) -> Result<Address, FriendlyAddressError> {
+        let friendly_addr_wospace = str::replace(friendly_addr, " ", "");
+
+        if friendly_addr_wospace.len() != 36 {
+            return Err(FriendlyAddressError::WrongLength);
+        }
+        if friendly_addr_wospace[0..2].to_uppercase() != Address::CCODE {
+            return Err(FriendlyAddressError::WrongCountryCode);
+        }
+
+        return Address::decode_friendly_body(&friendly_addr_wospace[4..]);
+    }
+
+    fn decode_friendly_body(body: &str) -> Result<Address, FriendlyAddressError> {
         let mut spec = data_encoding::Specification::new();
         spec.symbols.push_str(Address::NIMIQ_ALPHABET);
         let encoding = spec.encoding().unwrap();
 
-        let b_vec = encoding.decode(friendly_addr_wospace[4..].as_bytes()).unwrap();
+        let b_vec = encoding.decode(body.to_uppercase().as_bytes()).map_err(|_| FriendlyAddressError::InvalidEncoding)?;
+        if b_vec.len() != Address::SIZE {
+            return Err(FriendlyAddressError::InvalidEncoding);
+        }
         let mut b = [0; 20];
         b.copy_from_slice(&b_vec[..b_vec.len()]);
         return Ok(Address(b));
@@ -96,8 +139,7 @@ impl From<Blake2bHash> for Address {
 
 impl<'a> From<&'a PublicKey> for Address {
     fn from(public_key: &'a PublicKey) -> Self {
-        let hash = Blake2bHasher::default().digest(public_key.as_bytes());
-        return Address::from(hash);
+        return public_key.address();
     }
 }
 
@@ -119,3 +161,26 @@ fn it_computes_friendly_addresses() {
     assert_eq!(addr.0, addr2.0);
     assert_eq!(addr.to_user_friendly_address(), addr2.to_user_friendly_address());
 }
+
+#[test]
+fn it_decodes_friendly_addresses_without_checksum() {
+    let expected = Address::from(&::hex::decode("2987c28c1ff373ba1e18a9a2efe6dc101ee25ed9").unwrap()[..]);
+
+    // Same body as "NQ05 563U 530Y XDRT L7GQ M6HE YRNU 20FE 4PNR", but with a wrong checksum.
+    let friendly_addr = "NQ06 563U 530Y XDRT L7GQ M6HE YRNU 20FE 4PNR";
+    match Address::from_user_friendly_address(&friendly_addr.to_string()) {
+        Err(FriendlyAddressError::InvalidChecksum) => (),
+        r => panic!("Expected InvalidChecksum, got {:?}", r),
+    }
+    assert_eq!(Address::from_user_friendly_address_unchecked(friendly_addr).unwrap(), expected);
+
+    // The country code and length are still checked.
+    match Address::from_user_friendly_address_unchecked("XX06 563U 530Y XDRT L7GQ M6HE YRNU 20FE 4PNR") {
+        Err(FriendlyAddressError::WrongCountryCode) => (),
+        r => panic!("Expected WrongCountryCode, got {:?}", r),
+    }
+    match Address::from_user_friendly_address_unchecked("NQ06 563U 530Y XDRT L7GQ M6HE YRNU 20FE") {
+        Err(FriendlyAddressError::WrongLength) => (),
+        r => panic!("Expected WrongLength, got {:?}", r),
+    }
+}
@@ -16,12 +16,12 @@ impl KeyPair {
         let mut cspring: OsRng = OsRng::new().unwrap();
         let key_pair = ed25519_dalek::Keypair::generate(&mut cspring);
         let priv_key = PrivateKey(key_pair.secret);
-        let pub_key = PublicKey(key_pair.public);
+        let pub_key = PublicKey::from_dalek(key_pair.public);
         return KeyPair { private: priv_key, public: pub_key };
     }
 
     pub fn sign(&self, data: &[u8]) -> Signature {
-        let ext_signature = ed25519_dalek::ExpandedSecretKey::from(&self.private.0).sign(data, &self.public.0);
+        let ext_signature = ed25519_dalek::ExpandedSecretKey::from(&self.private.0).sign(data, self.public.as_dalek());
         return Signature(ext_signature);
     }
 }
@@ -5,35 +5,63 @@ use ed25519_dalek;
 
 use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
 
-use crate::{PrivateKey, Signature};
-use hash::{Hash, SerializeContent};
+use crate::{Address, PrivateKey, Signature};
+use hash::{Blake2bHasher, Hash, Hasher, SerializeContent};
 use crate::errors::KeysError;
 
-#[derive(Eq, PartialEq, Debug, Clone, Copy)]
-pub struct PublicKey(pub(in super) ed25519_dalek::PublicKey);
+#[derive(Debug, Clone, Copy)]
+pub struct PublicKey {
+    key: ed25519_dalek::PublicKey,
+    // The address is derived once on construction, raw bytes keep the key `Copy`.
+    address: [u8; Address::SIZE],
+}
 
 impl PublicKey {
     pub const SIZE: usize = 32;
 
+    pub(crate) fn from_dalek(key: ed25519_dalek::PublicKey) -> Self {
+        #[cfg(test)]
+        ADDRESS_HASH_COUNT.with(|count| count.set(count.get() + 1));
+
+        let hash: [u8; 32] = Blake2bHasher::default().digest(key.as_bytes()).into();
+        let mut address = [0u8; Address::SIZE];
+        address.copy_from_slice(&hash[..Address::SIZE]);
+        PublicKey { key, address }
+    }
+
+    /// The address derived from this key.
+    #[inline]
+    pub fn address(&self) -> Address {
+        Address::from(self.address)
+    }
+
     pub fn verify(&self, signature: &Signature, data: &[u8]) -> bool {
         return self.as_dalek().verify(data, signature.as_dalek()).is_ok();
     }
 
     #[inline]
-    pub fn as_bytes(&self) -> &[u8; PublicKey::SIZE] { self.0.as_bytes() }
+    pub fn as_bytes(&self) -> &[u8; PublicKey::SIZE] { self.key.as_bytes() }
 
     #[inline]
-    pub(crate) fn as_dalek(&self) -> &ed25519_dalek::PublicKey { &self.0 }
+    pub(crate) fn as_dalek(&self) -> &ed25519_dalek::PublicKey { &self.key }
 
     #[inline]
     pub fn from_bytes(bytes: &[u8; PublicKey::SIZE]) -> Result<Self, KeysError> {
-        Ok(PublicKey(ed25519_dalek::PublicKey::from_bytes(bytes).map_err(|e| KeysError(e))?))
+        Ok(PublicKey::from_dalek(ed25519_dalek::PublicKey::from_bytes(bytes).map_err(|e| KeysError(e))?))
     }
 }
 
+impl PartialEq for PublicKey {
+    fn eq(&self, other: &PublicKey) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for PublicKey {}
+
 impl Ord for PublicKey {
     fn cmp(&self, other: &PublicKey) -> Ordering {
-        return self.0.as_bytes().cmp(other.0.as_bytes());
+        return self.key.as_bytes().cmp(other.key.as_bytes());
     }
 }
 
@@ -46,13 +74,13 @@ impl PartialOrd for PublicKey {
 impl<'a> From<&'a PrivateKey> for PublicKey {
     fn from(private_key: &'a PrivateKey) -> Self {
         let public_key = ed25519_dalek::PublicKey::from(private_key.as_dalek());
-        return PublicKey(public_key);
+        return PublicKey::from_dalek(public_key);
     }
 }
 
 impl<'a> From<&'a [u8; PublicKey::SIZE]> for PublicKey {
     fn from(bytes: &'a [u8; PublicKey::SIZE]) -> Self {
-        PublicKey(ed25519_dalek::PublicKey::from_bytes(bytes).unwrap())
+        PublicKey::from_dalek(ed25519_dalek::PublicKey::from_bytes(bytes).unwrap())
     }
 }
 
@@ -87,3 +115,24 @@ impl SerializeContent for PublicKey {
 
 impl Hash for PublicKey { }
 
+
+#[cfg(test)]
+thread_local! {
+    static ADDRESS_HASH_COUNT: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
+#[test]
+fn it_derives_the_address_once() {
+    let public_key = PublicKey::from([0x3bu8; PublicKey::SIZE]);
+    let count = ADDRESS_HASH_COUNT.with(|count| count.get());
+
+    let address = public_key.address();
+    assert_eq!(public_key.address(), address);
+    assert_eq!(Address::from(&public_key), address);
+    assert_eq!(Address::from(Blake2bHasher::default().digest(public_key.as_bytes())), address);
+
+    // Copies share the cached address.
+    let copy = public_key;
+    assert_eq!(copy.address(), address);
+    assert_eq!(ADDRESS_HASH_COUNT.with(|count| count.get()), count);
+}
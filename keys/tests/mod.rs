@@ -1,6 +1,7 @@
 mod multisig;
 
-use nimiq_keys::{PrivateKey,PublicKey,Signature,KeyPair};
+use nimiq_keys::{Address,PrivateKey,PublicKey,Signature,KeyPair};
+use nimiq_macros::TypedArrayError;
 
 #[test]
 fn verify_created_signature() {
@@ -82,3 +83,15 @@ fn verify_rfc8032_test_vectors() {
         assert!(public_key.verify(&signature, data));
     }
 }
+
+#[test]
+fn address_can_be_created_from_slices_without_panicking() {
+    let bytes = [7u8; Address::SIZE + 1];
+
+    let address = Address::try_from_slice(&bytes[..Address::SIZE]).unwrap();
+    assert_eq!(address, Address::from([7u8; Address::SIZE]));
+
+    assert_eq!(Address::try_from_slice(&bytes[..]), Err(TypedArrayError::InvalidLength { expected: Address::SIZE, actual: Address::SIZE + 1 }));
+    assert_eq!(Address::try_from_slice(&bytes[..3]), Err(TypedArrayError::InvalidLength { expected: Address::SIZE, actual: 3 }));
+    assert_eq!(Address::try_from_slice(&[]), Err(TypedArrayError::InvalidLength { expected: Address::SIZE, actual: 0 }));
+}
@@ -1,3 +1,16 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypedArrayError {
+    InvalidLength { expected: usize, actual: usize },
+}
+
+impl ::std::fmt::Display for TypedArrayError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match self {
+            TypedArrayError::InvalidLength { expected, actual } => write!(f, "Invalid length: expected {}, got {}", expected, actual),
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! create_typed_array {
     ($name: ident, $t: ty, $len: expr) => {
@@ -14,6 +27,17 @@ macro_rules! create_typed_array {
             }
         }
 
+        impl $name {
+            pub fn try_from_slice(slice: &[$t]) -> Result<Self, $crate::TypedArrayError> {
+                if slice.len() != $len {
+                    return Err($crate::TypedArrayError::InvalidLength { expected: $len, actual: slice.len() });
+                }
+                let mut a = [0 as $t; $len];
+                a.clone_from_slice(slice);
+                return Ok($name(a));
+            }
+        }
+
         impl ::beserial::Deserialize for $name {
             fn deserialize<R: ::beserial::ReadBytesExt>(reader: &mut R) -> Result<Self, ::beserial::SerializingError> {
                 let mut a = [0 as $t; $len];
@@ -248,6 +248,28 @@ impl<'env> Mempool<'env> {
         return txs;
     }
 
+    /// Computes the 10th, 50th and 90th percentile (nearest rank) of the fee/byte over all pending transactions.
+    pub fn fee_percentiles(&self) -> FeePercentiles {
+        let mut fees: Vec<f64> = {
+            let state = self.state.read();
+            state.transactions_by_hash.values().map(|tx| tx.fee_per_byte()).collect()
+        };
+        if fees.is_empty() {
+            return FeePercentiles::default();
+        }
+        fees.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+        let percentile = |p: usize| {
+            let rank = (p * fees.len() + 99) / 100;
+            fees[rank.max(1) - 1]
+        };
+        FeePercentiles {
+            p10: percentile(10),
+            p50: percentile(50),
+            p90: percentile(90),
+        }
+    }
+
     pub fn get_transactions_for_block(&self, max_size: usize) -> Vec<Arc<Transaction>> {
         let transactions = self.get_transactions(max_size, 0f64);
         // TODO get to be pruned accounts and remove transactions to fit max_size
@@ -281,6 +303,7 @@ impl<'env> Mempool<'env> {
         match event {
             BlockchainEvent::Extended(_, _) => self.evict_transactions(),
             BlockchainEvent::Rebranched(reverted_blocks, _) => self.restore_transactions(reverted_blocks),
+            BlockchainEvent::Forked(..) => {},
         }
     }
 
@@ -513,6 +536,13 @@ impl<'env> Mempool<'env> {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FeePercentiles {
+    pub p10: f64,
+    pub p50: f64,
+    pub p90: f64,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ReturnCode {
     FeeTooLow,
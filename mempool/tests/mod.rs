@@ -9,7 +9,7 @@ use nimiq_database::WriteTransaction;
 use nimiq_hash::Hash;
 use nimiq_keys::Address;
 use nimiq_keys::KeyPair;
-use nimiq_mempool::{Mempool, ReturnCode};
+use nimiq_mempool::{FeePercentiles, Mempool, ReturnCode};
 use nimiq_network_primitives::time::NetworkTime;
 use nimiq_primitives::block::BlockBody;
 use nimiq_primitives::coin::Coin;
@@ -159,3 +159,37 @@ fn reject_free_tx_beyond_limit() {
         }
     }
 }
+
+#[test]
+fn it_computes_fee_percentiles() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())));
+    let mempool = Mempool::new(blockchain.clone());
+
+    assert_eq!(mempool.fee_percentiles(), FeePercentiles::default());
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit();
+
+    // Push transactions with a fee/byte of 1 to 10.
+    for i in 1..=10u64 {
+        let mut tx = Transaction::new_basic(address_a.clone(), address_b.clone(), Coin::from(10), Coin::from(0), 1, NetworkId::Main);
+        tx.fee = Coin::from(i * 138);
+        let signature_proof = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx.serialize_content()));
+        tx.proof = signature_proof.serialize_to_vec();
+        assert_eq!(tx.serialized_size(), 138);
+        assert_eq!(mempool.push_transaction(tx), ReturnCode::Accepted);
+    }
+
+    let percentiles = mempool.fee_percentiles();
+    assert_eq!(percentiles.p10, 1f64);
+    assert_eq!(percentiles.p50, 5f64);
+    assert_eq!(percentiles.p90, 9f64);
+}
@@ -1,5 +1,7 @@
 use beserial::{Deserialize, Serialize};
+use nimiq_blockchain::chain_proof::ChainProof;
 use nimiq_messages::*;
+use nimiq_network_primitives::{MAX_MESSAGE_SIZE, MESSAGE_HEADER_SIZE};
 
 const VERSION_MESSAGE: &str = "42042042000000010ee4e19ae300000001040000000400000167aaa7c40d02a84eaf654fe5f3b0bb45d0dd9a70c78fc24d134f5e302aa8270ea107752a6b860053e4c4966637a7de44500e8df82d7b541f578ab25a9e147fed9066361081826337f5511fa27762ecd0e328488e48bcbc4c6e2ded7b552039832768e4f137d809096c6f63616c686f737420fb264aaf8a4f9828a76c550635da078eb466306a189fcc03710bee9f649c869d12c6efcae1d34d135ff562bd75a62ffbcaab81f578ad23da8a02ccf59c7f8b6baa97fabe9dbd9db0acb5e1539bf3155ca1c9565f3363c5c8f1e1cc5b99ba3902c921636f72652d6a732f312e342e3120286e6f64656a733b204c696e75782078363429";
 const INV_MESSAGE: &str = "42042042010000007b268c0610000300000002324dcf027dd4a30a932c441f365a25e86b173defa4b8e58948253471b81b72cf00000002b8b37c1d034e371c7a3b834f9476a746eb62259ff9558ab715b4bff79ebf58e100000001f823f66ba1026e7f711ea5aa4719837bb378fc615b50516b8dabdaff78e8168e";
@@ -252,3 +254,11 @@ fn reserialize_messages() {
         assert!(message.serialize_to_vec() == vec);
     }
 }
+
+#[test]
+fn chain_proof_messages_fit_the_maximum_message_size() {
+    let proof = ChainProof { prefix: Vec::new(), suffix: Vec::new() };
+    let message = Message::ChainProof(proof.clone());
+    assert_eq!(message.serialized_size(), proof.serialized_size() + MESSAGE_HEADER_SIZE);
+    assert_eq!(ChainProof::MAX_SERIALIZED_SIZE + MESSAGE_HEADER_SIZE, MAX_MESSAGE_SIZE);
+}
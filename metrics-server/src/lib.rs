@@ -55,15 +55,20 @@ macro_rules! attributes {
 pub mod server;
 pub mod metrics;
 
-pub fn metrics_server(consensus: Arc<Consensus>, ip: IpAddr, port: u16, password: Option<String>) -> Box<dyn Future<Item=(), Error=()> + Send + Sync> {
+/// Starts a metrics server exporting the built-in chain, mempool and network metrics
+/// followed by the given additional `collectors`.
+pub fn metrics_server(consensus: Arc<Consensus>, ip: IpAddr, port: u16, password: Option<String>, collectors: Vec<Arc<dyn server::Metrics>>) -> Box<dyn Future<Item=(), Error=()> + Send + Sync> {
     Box::new(Server::bind(&SocketAddr::new(ip, port))
         .serve(move || {
+            let mut metrics: Vec<Arc<dyn server::Metrics>> = vec![
+                Arc::new(ChainMetrics::new(consensus.blockchain.clone())),
+                Arc::new(MempoolMetrics::new(consensus.mempool.clone())),
+                Arc::new(NetworkMetrics::new(consensus.network.clone()))
+            ];
+            metrics.extend(collectors.iter().cloned());
+
             server::MetricsServer::new(
-                vec![
-                    Arc::new(ChainMetrics::new(consensus.blockchain.clone())),
-                    Arc::new(MempoolMetrics::new(consensus.mempool.clone())),
-                    Arc::new(NetworkMetrics::new(consensus.network.clone()))
-                ],
+                metrics,
                 attributes!{ "peer" => consensus.network.network_config.peer_address() },
             password.clone())
         })
@@ -2,6 +2,7 @@ use std::io;
 use std::sync::Arc;
 
 use blockchain::Blockchain;
+use blockchain::chain_metrics::{DurationHistogram, DURATION_BUCKETS_MICROS};
 use primitives::block::Difficulty;
 
 use crate::server;
@@ -31,13 +32,38 @@ impl server::Metrics for ChainMetrics {
         }
         serializer.metric("chain_total_work", self.blockchain.total_work().clone())?;
 
-        serializer.metric_with_attributes("chain_block", self.blockchain.metrics.block_forked_count(), attributes!{"action" => "forked"})?;
-        serializer.metric_with_attributes("chain_block", self.blockchain.metrics.block_rebranched_count(), attributes!{"action" => "rebranched"})?;
-        serializer.metric_with_attributes("chain_block", self.blockchain.metrics.block_extended_count(), attributes!{"action" => "extended"})?;
-        serializer.metric_with_attributes("chain_block", self.blockchain.metrics.block_orphan_count(), attributes!{"action" => "orphan"})?;
-        serializer.metric_with_attributes("chain_block", self.blockchain.metrics.block_invalid_count(), attributes!{"action" => "invalid"})?;
-        serializer.metric_with_attributes("chain_block", self.blockchain.metrics.block_known_count(), attributes!{"action" => "known"})?;
+        let push_stats = self.blockchain.push_stats();
+        serializer.metric_with_attributes("chain_block", push_stats.forked, attributes!{"action" => "forked"})?;
+        serializer.metric_with_attributes("chain_block", push_stats.rebranched, attributes!{"action" => "rebranched"})?;
+        serializer.metric_with_attributes("chain_block", push_stats.extended, attributes!{"action" => "extended"})?;
+        serializer.metric_with_attributes("chain_block", push_stats.orphan, attributes!{"action" => "orphan"})?;
+        serializer.metric_with_attributes("chain_block", push_stats.invalid, attributes!{"action" => "invalid"})?;
+        serializer.metric_with_attributes("chain_block", push_stats.known, attributes!{"action" => "known"})?;
 
+        let timings = self.blockchain.metrics.push_timings();
+        Self::histogram(serializer, "verify", &timings.verify)?;
+        Self::histogram(serializer, "difficulty", &timings.difficulty)?;
+        Self::histogram(serializer, "transaction_cache", &timings.transaction_cache)?;
+        Self::histogram(serializer, "accounts_commit", &timings.accounts_commit)?;
+        Self::histogram(serializer, "store_write", &timings.store_write)?;
+
+        Ok(())
+    }
+}
+
+impl ChainMetrics {
+    fn histogram(serializer: &mut server::MetricsSerializer<SerializationType>, phase: &str, histogram: &DurationHistogram) -> Result<(), io::Error> {
+        let counts = histogram.cumulative_counts();
+        for (bound, count) in DURATION_BUCKETS_MICROS.iter().zip(counts.iter()) {
+            let le = *bound as f64 / 1_000_000f64;
+            serializer.metric_with_attributes("chain_push_duration_seconds_bucket", count, attributes!{"phase" => phase, "le" => le})?;
+        }
+        serializer.metric_with_attributes("chain_push_duration_seconds_bucket", histogram.count(), attributes!{"phase" => phase, "le" => "+Inf"})?;
+
+        let sum = histogram.sum();
+        let sum = sum.as_secs() as f64 + f64::from(sum.subsec_nanos()) / 1_000_000_000f64;
+        serializer.metric_with_attributes("chain_push_duration_seconds_sum", sum, attributes!{"phase" => phase})?;
+        serializer.metric_with_attributes("chain_push_duration_seconds_count", histogram.count(), attributes!{"phase" => phase})?;
         Ok(())
     }
 }
\ No newline at end of file
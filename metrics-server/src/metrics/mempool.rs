@@ -38,6 +38,10 @@ impl server::Metrics for MempoolMetrics {
             txs.iter().filter(|tx| (tx.fee_per_byte() as usize) >= lower_bound).count(),
             attributes!{"fee_per_byte" => format!(">={}", lower_bound)}
         )?;
+        let percentiles = self.mempool.fee_percentiles();
+        serializer.metric_with_attributes("mempool_fee_per_byte", percentiles.p10, attributes!{"percentile" => 10})?;
+        serializer.metric_with_attributes("mempool_fee_per_byte", percentiles.p50, attributes!{"percentile" => 50})?;
+        serializer.metric_with_attributes("mempool_fee_per_byte", percentiles.p90, attributes!{"percentile" => 90})?;
         serializer.metric(
             "mempool_size",
             txs.iter().map(|tx| tx.serialized_size()).sum::<usize>(),
@@ -147,3 +147,24 @@ impl hyper::service::Service for MetricsServer {
         Box::new(future::ok(Response::new(self.serve())))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CustomMetrics;
+
+    impl Metrics for CustomMetrics {
+        fn metrics(&self, serializer: &mut MetricsSerializer<SerializationType>) -> Result<(), io::Error> {
+            serializer.metric("custom_counter", 42)
+        }
+    }
+
+    #[test]
+    fn it_serves_custom_collectors() {
+        let server = MetricsServer::new(vec![Arc::new(CustomMetrics) as Arc<Metrics>], CachedAttributes::new(), None);
+        let body = server.serve().concat2().wait().unwrap();
+        let output = String::from_utf8(body.to_vec()).unwrap();
+        assert!(output.contains("custom_counter{} 42"));
+    }
+}
@@ -35,3 +35,7 @@ pub const OUTBOUND_PEER_COUNT_PER_SUBNET_MAX: usize = 2;
 pub const INBOUND_PEER_COUNT_PER_SUBNET_MAX: usize = 100;
 pub const PEER_COUNT_MAX: usize = 4000;
 pub const PEER_COUNT_DUMB_MAX: usize = 1000;
+/// Default maximum size of a message in bytes, including its header.
+pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024 * 10; // 10 mb
+/// Size of a message header in bytes: magic, type, length and checksum. All message types fit into a single byte.
+pub const MESSAGE_HEADER_SIZE: usize = 4 + 1 + 4 + 4;
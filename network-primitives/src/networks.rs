@@ -19,6 +19,9 @@ pub struct NetworkInfo {
     pub genesis_block: Block,
     pub genesis_hash: Blake2bHash,
     pub genesis_accounts: String, // FIXME
+    /// Height from which blocks have to pass the median time past and difficulty window timespan checks.
+    /// `u32::MAX` if these rules are not active on the network.
+    pub timestamp_rules_height: u32,
 }
 
 fn create_seed_peer_addr(url: &str, port: u16, pubkey_hex: &str) -> PeerAddress {
@@ -1194,6 +1197,8 @@ lazy_static! {
                     ebd4ec914becf85c41585d50a81dcc0f4bae4d3d000000010001fa4000000008a7c8e7e000000011\
                     4f91cfc0ef60558d333e77839026fa1b0574e1b0f1e4d0790000000029ea5e4814f3a531509d46df\
                     87e058c2762672a51366c3ce3b000000000014345918".into(),
+                // Not activated, the existing chain predates these rules.
+                timestamp_rules_height: u32::max_value(),
             },
         );
 
@@ -1374,7 +1379,9 @@ lazy_static! {
                 0f7c77899b8b4ec5a3b2d0fd583024a08c963da6d8a0725b7b00000001000016800000010f7c7789\
                 9b0000010f7c77899b1702dc1b2cb05e372a24f84ece01c4835c6f1c800100001e9dbaf7b6df9ac9\
                 4a1a46736363a20b8e52074ca76981cc43390000000100000b400000051a49d3f3d000001e9dbaf7\
-                b6df".into()
+                b6df".into(),
+                // Not activated, the existing chain predates these rules.
+                timestamp_rules_height: u32::max_value(),
             }
         );
 
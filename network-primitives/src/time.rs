@@ -4,8 +4,10 @@ use utils::time::systemtime_to_timestamp;
 use atomic::Atomic;
 use atomic::Ordering;
 
+/// The local system time corrected by the median offset of our peers' clocks.
 #[derive(Debug)]
 pub struct NetworkTime {
+    /// Offset of the network time to the local system time in milliseconds.
     offset: Atomic<i64>
 }
 
@@ -14,23 +16,29 @@ impl NetworkTime {
         NetworkTime::with_offset(0)
     }
 
+    /// `offset` is in milliseconds, like the peer timestamps it is derived from.
     pub fn with_offset(offset: i64) -> Self {
         NetworkTime {
             offset: Atomic::new(offset)
         }
     }
 
+    /// Sets the offset to the local system time in milliseconds.
     pub fn set_offset(&self, new_offset: i64) {
         self.offset.store(new_offset, Ordering::Relaxed);
     }
 
+    pub fn offset(&self) -> i64 {
+        self.offset.load(Ordering::Relaxed)
+    }
+
     pub fn now(&self) -> u64 {
-        let offset = self.offset.load(Ordering::Relaxed);
+        let offset = self.offset();
         let abs_offset = offset.abs() as u64;
         let system_time = if offset > 0 {
-            SystemTime::now() + Duration::from_secs(abs_offset)
+            SystemTime::now() + Duration::from_millis(abs_offset)
         } else {
-            SystemTime::now() - Duration::from_secs(abs_offset)
+            SystemTime::now() - Duration::from_millis(abs_offset)
         };
 
         return systemtime_to_timestamp(system_time);
@@ -32,6 +32,7 @@ use crate::connection::close_type::CloseType;
 use crate::network_config::NetworkConfig;
 use crate::peer_channel::PeerChannel;
 
+use super::peer_address_state::PeerAddressBackoff;
 use super::peer_address_state::PeerAddressInfo;
 use super::peer_address_state::PeerAddressState;
 
@@ -688,6 +689,40 @@ impl PeerAddressBook {
         }
     }
 
+    /// Backoff of the addresses whose last connection attempt failed, e.g. to carry it over into a new address book.
+    pub fn export_backoff(&self) -> HashMap<Arc<PeerAddress>, PeerAddressBackoff> {
+        self.state.read().info_by_address.iter()
+            .filter(|(_, info)| info.state == PeerAddressState::Failed)
+            .map(|(peer_address, info)| (Arc::clone(peer_address), PeerAddressBackoff {
+                failed_attempts: info.failed_attempts,
+                banned_until: info.banned_until,
+                ban_backoff: info.ban_backoff,
+            }))
+            .collect()
+    }
+
+    /// Restores backoff exported from another address book. Unknown addresses are added,
+    /// addresses that are established or banned in the meantime are left alone.
+    pub fn import_backoff(&self, backoff: &HashMap<Arc<PeerAddress>, PeerAddressBackoff>) {
+        let _guard = self.change_lock.lock();
+
+        let mut state = self.state.write();
+        for (peer_address, backoff) in backoff {
+            if state.info_by_address.get(peer_address).is_none() {
+                state.add_to_store(PeerAddressInfo::new(Arc::clone(peer_address)));
+            }
+
+            let info = state.info_by_address.get_mut(peer_address).expect("Code above guarantees that this will never be None");
+            if info.state == PeerAddressState::Established || info.state == PeerAddressState::Banned {
+                continue;
+            }
+            info.state = PeerAddressState::Failed;
+            info.failed_attempts = backoff.failed_attempts;
+            info.banned_until = backoff.banned_until;
+            info.ban_backoff = backoff.ban_backoff;
+        }
+    }
+
     pub fn seeded(&self) -> bool {
         self.seeded.load(Ordering::Acquire)
     }
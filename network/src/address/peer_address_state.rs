@@ -66,6 +66,14 @@ impl PeerAddressInfo {
     }
 }
 
+/// How connections to an address are backed off after failed attempts, see `PeerAddressBook::close`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeerAddressBackoff {
+    pub failed_attempts: u32,
+    pub banned_until: Option<Instant>,
+    pub ban_backoff: Duration,
+}
+
 #[derive(PartialEq, Eq, Copy, Clone)]
 pub enum PeerAddressState {
     New = 1,
@@ -63,6 +63,7 @@ pub enum CloseType {
     BannedIp = 116,
 
     RateLimitExceeded = 120,
+    MessageTooLarge = 121,
 
     ManualPeerBan = 190,
 
@@ -79,6 +80,8 @@ pub enum CloseType {
     ConnectionLimitPerIp = 208,
     ChannelClosing = 209,
     ConnectionLimitDumb = 210,
+    HandshakeTimeout = 211,
+    TlsHandshakeFailed = 212,
 
     ManualPeerFail = 290,
 }
@@ -30,6 +30,7 @@ pub struct ConnectionInfo {
     state: ConnectionState,
     network_agent: Option<Arc<RwLock<NetworkAgent>>>,
     connection_handle: Option<Arc<ConnectionHandle>>,
+    connected_since: Option<Instant>,
     established_since: Option<Instant>,
     statistics: ConnectionStatistics,
 }
@@ -44,6 +45,7 @@ impl ConnectionInfo {
             state: ConnectionState::New,
             network_agent: None,
             connection_handle: None,
+            connected_since: None,
             established_since: None,
             statistics: ConnectionStatistics::new(),
         }
@@ -75,6 +77,7 @@ impl ConnectionInfo {
     pub fn peer_channel(&self) -> Option<&PeerChannel> { self.peer_channel.as_ref() }
     pub fn network_agent(&self) -> Option<&Arc<RwLock<NetworkAgent>>> { self.network_agent.as_ref() }
     pub fn connection_handle(&self) -> Option<&Arc<ConnectionHandle>> { self.connection_handle.as_ref() }
+    pub fn connected_since(&self) -> Option<Instant> { self.connected_since }
     pub fn age_established(&self) -> Duration { self.established_since.expect("No peer has been set yet").elapsed() }
     pub fn statistics(&self) -> &ConnectionStatistics { &self.statistics }
 
@@ -82,6 +85,7 @@ impl ConnectionInfo {
     pub fn set_network_connection(&mut self, network_connection: NetworkConnection) {
         self.network_connection = Some(network_connection);
         self.state = ConnectionState::Connected;
+        self.connected_since = Some(Instant::now());
     }
     pub fn set_peer(&mut self, peer: Peer) {
         self.peer = Some(peer);
@@ -93,6 +97,11 @@ impl ConnectionInfo {
     pub fn set_connection_handle(&mut self, handle: Arc<ConnectionHandle>) { self.connection_handle = Some(handle); }
     pub fn drop_connection_handle(&mut self) { self.connection_handle = None; }
 
+    #[cfg(test)]
+    pub(crate) fn set_state(&mut self, state: ConnectionState) { self.state = state; }
+    #[cfg(test)]
+    pub(crate) fn set_connected_since(&mut self, connected_since: Instant) { self.connected_since = Some(connected_since); }
+
     pub fn negotiating(&mut self) {
         assert_eq!(self.state, ConnectionState::Connected);
         self.state = ConnectionState::Negotiating;
@@ -2,21 +2,27 @@ use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::LinkedList;
+use std::collections::VecDeque;
 use std::sync::Arc;
 use std::sync::Weak;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
+use native_tls::TlsConnector;
 use parking_lot::{Mutex, RwLock, RwLockReadGuard};
 
 use blockchain::Blockchain;
 use network_primitives::address::net_address::{NetAddress, NetAddressType};
 use network_primitives::address::peer_address::PeerAddress;
-use network_primitives::protocol::Protocol;
+use network_primitives::address::PeerId;
+use network_primitives::protocol::{Protocol, ProtocolFlags};
+use network_primitives::services::ServiceFlags;
 use utils::mutable_once::MutableOnce;
 use utils::observer::PassThroughNotifier;
+use utils::timers::Timers;
 use utils::unique_ptr::UniquePtr;
 
 use crate::address::peer_address_book::PeerAddressBook;
+use crate::address::peer_address_state::PeerAddressBackoff;
 use crate::connection::network_agent::{NetworkAgent, NetworkAgentEvent};
 use crate::connection::NetworkConnection;
 use crate::network_config::NetworkConfig;
@@ -39,11 +45,25 @@ macro_rules! update_checked {
 
 pub type ConnectionId = usize;
 
+/// The logical state of a connection pool without its live connections.
+/// It can be carried over into a fresh pool, e.g. when restarting the network.
+/// The pool has no allowlist, so there is none to carry over; peers are only restricted by bans.
+#[derive(Clone, Debug)]
+pub struct ConnectionPoolSnapshot {
+    pub allow_inbound_connections: bool,
+    pub allow_inbound_exchange: bool,
+    pub banned_ips: HashMap<NetAddress, SystemTime>,
+    pub close_history: Vec<(PeerId, CloseType, SystemTime)>,
+    /// Backoff of addresses that failed to connect, kept by the `PeerAddressBook`.
+    pub backoff: HashMap<Arc<PeerAddress>, PeerAddressBackoff>,
+}
+
 pub struct ConnectionPoolState {
     connections: SparseVec<ConnectionInfo>,
     connections_by_peer_address: HashMap<Arc<PeerAddress>, ConnectionId>,
     connections_by_net_address: HashMap<NetAddress, HashSet<ConnectionId>>,
     connections_by_subnet: HashMap<NetAddress, HashSet<ConnectionId>>,
+    connections_by_peer_id: HashMap<PeerId, HashSet<ConnectionId>>,
 
     pub peer_count_ws: usize,
     pub peer_count_wss: usize,
@@ -65,9 +85,46 @@ pub struct ConnectionPoolState {
     pub allow_inbound_exchange: bool,
 
     banned_ips: HashMap<NetAddress, SystemTime>,
+
+    close_history: VecDeque<(PeerId, CloseType, SystemTime)>,
 }
 
 impl ConnectionPoolState {
+    const CLOSE_HISTORY_MAX: usize = 1000;
+
+    fn new() -> Self {
+        ConnectionPoolState {
+            connections: SparseVec::new(),
+            connections_by_peer_address: HashMap::new(),
+            connections_by_net_address: HashMap::new(),
+            connections_by_subnet: HashMap::new(),
+            connections_by_peer_id: HashMap::new(),
+
+            peer_count_ws: 0,
+            peer_count_wss: 0,
+            peer_count_rtc: 0,
+            peer_count_dumb: 0,
+
+            peer_count_full: 0,
+            peer_count_light: 0,
+            peer_count_nano: 0,
+
+            peer_count_outbound: 0,
+            peer_count_full_ws_outbound: 0,
+
+            connecting_count: 0,
+
+            inbound_count: 0,
+
+            allow_inbound_connections: false,
+            allow_inbound_exchange: false,
+
+            banned_ips: HashMap::new(),
+
+            close_history: VecDeque::new(),
+        }
+    }
+
     pub fn connection_iter(&self) -> Vec<&ConnectionInfo> {
         return self.connections_by_peer_address.values().map(|connection_id| {
             self.connections.get(*connection_id).expect("Missing connection")
@@ -118,6 +175,22 @@ impl ConnectionPoolState {
         })
     }
 
+    /// Get a list of connection info for all connections to a PeerId, except for the given connection.
+    pub fn get_other_connections_by_peer_id(&self, connection_id: ConnectionId, peer_id: &PeerId) -> Vec<&ConnectionInfo> {
+        self.connections_by_peer_id.get(peer_id).map_or(Vec::new(), |s| {
+            s.iter()
+                .filter(|i| **i != connection_id)
+                .map(|i| self.connections.get(*i).expect("Missing connection"))
+                .collect()
+        })
+    }
+
+    /// Get the number of connections for a PeerId.
+    #[inline]
+    pub fn get_num_connections_by_peer_id(&self, peer_id: &PeerId) -> usize {
+        self.connections_by_peer_id.get(peer_id).map_or(0, |s| s.len())
+    }
+
     /// Get the number of connections for a subnet.
     #[inline]
     pub fn get_num_connections_by_subnet(&self, net_address: &NetAddress) -> usize {
@@ -145,28 +218,107 @@ impl ConnectionPoolState {
         self.get_outbound_connections_by_subnet(net_address).map_or(0, |v| v.len())
     }
 
+    /// Get the peer addresses of all established connections.
+    pub fn established_peer_addresses(&self) -> Vec<Arc<PeerAddress>> {
+        self.connection_iter().iter()
+            .filter(|info| info.state() == ConnectionState::Established)
+            .filter_map(|info| info.peer_address())
+            .collect()
+    }
+
+    /// Get the peer addresses of all established connections that use one of the given protocols
+    /// and provide any of the given services.
+    pub fn established_peer_addresses_for(&self, protocol_mask: ProtocolFlags, service_mask: ServiceFlags) -> Vec<Arc<PeerAddress>> {
+        let mut addresses = self.established_peer_addresses();
+        addresses.retain(|peer_address| {
+            protocol_mask.contains(ProtocolFlags::from(peer_address.protocol()))
+                && peer_address.services.intersects(service_mask)
+        });
+        addresses
+    }
+
     /// Total peer count.
     #[inline]
     pub fn peer_count(&self) -> usize {
         self.peer_count_ws + self.peer_count_wss + self.peer_count_rtc + self.peer_count_dumb
     }
 
+    /// Connections that have been connected for at least `timeout` without completing the handshake.
+    pub fn timed_out_handshakes(&self, now: Instant, timeout: Duration) -> Vec<ConnectionId> {
+        self.connections.iter()
+            .filter(|(_, info)| info.state() == ConnectionState::Connected || info.state() == ConnectionState::Negotiating)
+            .filter(|(_, info)| info.connected_since().map_or(false, |since| now.duration_since(since) >= timeout))
+            .map(|(connection_id, _)| connection_id)
+            .collect()
+    }
+
+    /// How the most recent connections to the given peer ended, oldest first.
+    pub fn recent_closes_for(&self, peer_id: &PeerId) -> Vec<(CloseType, SystemTime)> {
+        self.close_history.iter()
+            .filter(|(id, _, _)| id == peer_id)
+            .map(|&(_, ty, time)| (ty, time))
+            .collect()
+    }
+
+    fn record_close(&mut self, peer_id: PeerId, ty: CloseType, time: SystemTime) {
+        if self.close_history.len() >= Self::CLOSE_HISTORY_MAX {
+            self.close_history.pop_front();
+        }
+        self.close_history.push_back((peer_id, ty, time));
+    }
+
+    /// Picks the seeds to dial when bootstrapping: websocket seeds that we are not connected to yet,
+    /// as many as `max_connecting` allows.
+    pub fn bootstrap_candidates(&self, seeds: &[Arc<PeerAddress>], max_connecting: usize) -> Vec<Arc<PeerAddress>> {
+        let mut candidates: Vec<Arc<PeerAddress>> = Vec::new();
+        for seed in seeds {
+            if self.connecting_count + candidates.len() >= max_connecting {
+                break;
+            }
+
+            match seed.protocol() {
+                Protocol::Ws | Protocol::Wss => {},
+                _ => continue,
+            }
+
+            if self.get_connection_by_peer_address(seed).is_some() || candidates.contains(seed) {
+                continue;
+            }
+            candidates.push(seed.clone());
+        }
+        candidates
+    }
+
     /// Add a new connection to the connection pool.
-    fn add(&mut self, info: ConnectionInfo) -> ConnectionId {
+    /// Returns `None` if there already is a connection for its peer address.
+    fn add(&mut self, info: ConnectionInfo) -> Option<ConnectionId> {
         let peer_address = info.peer_address();
+        if let Some(ref peer_address) = peer_address {
+            if self.connections_by_peer_address.contains_key(peer_address) {
+                return None;
+            }
+        }
+
         let connection_id = self.connections.insert(info);
 
         // Add to peer address map if available.
         if let Some(peer_address) = peer_address {
             self.connections_by_peer_address.insert(peer_address, connection_id);
         }
-        connection_id
+        Some(connection_id)
     }
 
-    /// Add a new connection to the connection pool.
-    fn add_peer_address(&mut self, connection_id: ConnectionId, peer_address: Arc<PeerAddress>) {
+    /// Associates a connection with its peer address.
+    /// Returns false if another connection is already stored for the peer address.
+    fn add_peer_address(&mut self, connection_id: ConnectionId, peer_address: Arc<PeerAddress>) -> bool {
         // Add to peer address map.
-        self.connections_by_peer_address.insert(peer_address, connection_id);
+        match self.connections_by_peer_address.get(&peer_address) {
+            Some(stored_connection_id) => *stored_connection_id == connection_id,
+            None => {
+                self.connections_by_peer_address.insert(peer_address, connection_id);
+                true
+            },
+        }
     }
 
     /// Remove a connection from the connection pool.
@@ -182,9 +334,22 @@ impl ConnectionPoolState {
             self.remove_net_address(connection_id, &network_connection.net_address());
         }
 
+        // Inbound connections might not have a peer address set yet, so check all PeerIds.
+        self.connections_by_peer_id.retain(|_, s| {
+            s.remove(&connection_id);
+            !s.is_empty()
+        });
+
         info
     }
 
+    /// Adds the PeerId to a connection.
+    fn add_peer_id(&mut self, connection_id: ConnectionId, peer_id: &PeerId) {
+        self.connections_by_peer_id.entry(peer_id.clone())
+            .or_insert_with(HashSet::new)
+            .insert(connection_id);
+    }
+
     /// Adds the net address to a connection.
     fn add_net_address(&mut self, connection_id: ConnectionId, net_address: &NetAddress) {
         // Only add reliable netAddresses.
@@ -263,6 +428,38 @@ impl ConnectionPoolState {
         !net_address.is_pseudo() && self.banned_ips.contains_key(net_address)
     }
 
+    /// Exports the state that outlives the current connections, except for the backoff kept by the address book.
+    pub fn export(&self) -> ConnectionPoolSnapshot {
+        ConnectionPoolSnapshot {
+            allow_inbound_connections: self.allow_inbound_connections,
+            allow_inbound_exchange: self.allow_inbound_exchange,
+            banned_ips: self.banned_ips.clone(),
+            close_history: self.close_history.iter().cloned().collect(),
+            backoff: HashMap::new(),
+        }
+    }
+
+    /// Imports a previously exported state.
+    /// Existing bans are kept, the later unban time wins if an IP is banned in both.
+    fn import(&mut self, snapshot: ConnectionPoolSnapshot) {
+        self.allow_inbound_connections = snapshot.allow_inbound_connections;
+        self.allow_inbound_exchange = snapshot.allow_inbound_exchange;
+
+        for (net_address, unban_time) in snapshot.banned_ips {
+            let entry = self.banned_ips.entry(net_address).or_insert(unban_time);
+            if *entry < unban_time {
+                *entry = unban_time;
+            }
+        }
+
+        for (peer_id, ty, time) in snapshot.close_history {
+            self.record_close(peer_id, ty, time);
+        }
+
+        // Drop bans that expired in the meantime.
+        self.check_unban_ips();
+    }
+
     /// Called to regularly unban IPs.
     fn check_unban_ips(&mut self) {
         let mut now = SystemTime::now();
@@ -319,6 +516,7 @@ pub struct ConnectionPool {
 
     state: RwLock<ConnectionPoolState>,
     change_lock: Mutex<()>,
+    timers: Timers<ConnectionPoolTimer>,
 
     pub notifier: RwLock<PassThroughNotifier<'static, ConnectionPoolEvent>>,
     self_weak: MutableOnce<Weak<ConnectionPool>>,
@@ -326,44 +524,25 @@ pub struct ConnectionPool {
 
 impl ConnectionPool {
     const DEFAULT_BAN_TIME: Duration = Duration::from_secs(60 * 10); // seconds
+    /// Maximum number of dials in flight while bootstrapping. This is deliberately higher than
+    /// `Network::CONNECTING_COUNT_MAX`, which paces the dials of a running node to grow its peer set:
+    /// a fresh node has no peers yet and should try several seeds at once.
+    const BOOTSTRAP_CONNECTING_MAX: usize = 8;
 
     /// Constructor.
-    pub fn new(peer_address_book: Arc<PeerAddressBook>, network_config: Arc<NetworkConfig>, blockchain: Arc<Blockchain<'static>>) -> Arc<Self> {
+    /// `tls_connector` is used to dial WSS peers, e.g. with a custom root store to pin certificates.
+    /// Without one, the system's root certificates are used.
+    pub fn new(peer_address_book: Arc<PeerAddressBook>, network_config: Arc<NetworkConfig>, blockchain: Arc<Blockchain<'static>>, tls_connector: Option<TlsConnector>) -> Arc<Self> {
         let pool = Arc::new(Self {
             blockchain,
             network_config: network_config.clone(),
             addresses: peer_address_book,
 
-            websocket_connector: WebSocketConnector::new(network_config),
-
-            state: RwLock::new(ConnectionPoolState {
-                connections: SparseVec::new(),
-                connections_by_peer_address: HashMap::new(),
-                connections_by_net_address: HashMap::new(),
-                connections_by_subnet: HashMap::new(),
-
-                peer_count_ws: 0,
-                peer_count_wss: 0,
-                peer_count_rtc: 0,
-                peer_count_dumb: 0,
-
-                peer_count_full: 0,
-                peer_count_light: 0,
-                peer_count_nano: 0,
-
-                peer_count_outbound: 0,
-                peer_count_full_ws_outbound: 0,
-
-                connecting_count: 0,
-
-                inbound_count: 0,
+            websocket_connector: WebSocketConnector::new(network_config, tls_connector),
 
-                allow_inbound_connections: false,
-                allow_inbound_exchange: false,
-
-                banned_ips: HashMap::new(),
-            }),
+            state: RwLock::new(ConnectionPoolState::new()),
             change_lock: Mutex::new(()),
+            timers: Timers::new(),
 
             notifier: RwLock::new(PassThroughNotifier::new()),
             self_weak: MutableOnce::new(Weak::new()),
@@ -378,8 +557,8 @@ impl ConnectionPool {
                     WebSocketConnectorEvent::Connection(conn) => {
                         pool.on_connection(conn);
                     },
-                    WebSocketConnectorEvent::Error(peer_address, _) => {
-                        pool.on_connect_error(peer_address);
+                    WebSocketConnectorEvent::Error(peer_address, ty) => {
+                        pool.on_connect_error(peer_address, ty);
                     },
                 }
             });
@@ -405,7 +584,13 @@ impl ConnectionPool {
 
         // Create fresh ConnectionInfo instance.
         let mut state = self.state.write();
-        let connection_id = state.add(ConnectionInfo::outbound(peer_address.clone()));
+        let connection_id = match state.add(ConnectionInfo::outbound(peer_address.clone())) {
+            Some(connection_id) => connection_id,
+            None => {
+                error!("Duplicate connection to {:?}", peer_address);
+                return false;
+            },
+        };
 
         // Choose connector type and call.
         let handle = self.websocket_connector.connect(peer_address);
@@ -417,6 +602,26 @@ impl ConnectionPool {
         return true;
     }
 
+    /// Dials the given seed peers, e.g. when a fresh node comes online.
+    /// Seeds that are still backed off after a failed connection are skipped.
+    /// Returns the number of dials initiated.
+    pub fn bootstrap(&self, seeds: Vec<Arc<PeerAddress>>) -> usize {
+        let now = Instant::now();
+        let seeds: Vec<Arc<PeerAddress>> = {
+            let addresses = self.addresses.state();
+            seeds.into_iter()
+                .filter(|seed| addresses.get_info(seed)
+                    .and_then(|info| info.banned_until)
+                    .map_or(true, |banned_until| banned_until <= now))
+                .collect()
+        };
+
+        let candidates = self.state.read().bootstrap_candidates(&seeds, Self::BOOTSTRAP_CONNECTING_MAX);
+        candidates.into_iter()
+            .filter(|seed| self.connect_outbound(seed.clone()))
+            .count()
+    }
+
     pub fn disconnect(&self) {
         let state = self.state.read();
         for connection in state.connection_iter() {
@@ -519,7 +724,7 @@ impl ConnectionPool {
                 state.connections.get_mut(connection_id).unwrap().set_network_connection(connection);
             } else {
                 // Add connection (without having obtained peer address).
-                connection_id = state.add(ConnectionInfo::inbound(connection));
+                connection_id = state.add(ConnectionInfo::inbound(connection)).expect("Inbound connections have no peer address yet, so they are never rejected as duplicates");
                 state.inbound_count += 1;
             }
 
@@ -544,6 +749,14 @@ impl ConnectionPool {
                 state.add_net_address(connection_id, &net_address);
             }
 
+            // Close the connection if the peer doesn't complete the handshake in time.
+            let weak = self.self_weak.clone();
+            self.timers.reset_delay(ConnectionPoolTimer::Handshake(connection_id), move || {
+                let pool = upgrade_weak!(weak);
+                pool.timers.clear_delay(&ConnectionPoolTimer::Handshake(connection_id));
+                pool.check_handshake_timeouts();
+            }, self.network_config.handshake_timeout());
+
             // The extra lookup is needed to satisfy the borrow checker.
             let info = state.connections.get_mut(connection_id).expect("Missing connection");
             info.drop_connection_handle();
@@ -610,6 +823,16 @@ impl ConnectionPool {
                 }
             }
 
+            // Close connection if we already have an established connection to the same node
+            // using a different peer address (e.g. another protocol or port).
+            let is_duplicate_peer_id = state.get_other_connections_by_peer_id(connection_id, &peer_address.peer_id)
+                .iter()
+                .any(|info| info.state() == ConnectionState::Established);
+            if is_duplicate_peer_id {
+                ConnectionPool::close(info.network_connection(), CloseType::DuplicateConnection);
+                return false;
+            }
+
             // Close connection if we have too many dumb connections.
             if peer_address.protocol() == Protocol::Dumb && state.peer_count_dumb >= network_primitives::PEER_COUNT_DUMB_MAX {
                 ConnectionPool::close(info.network_connection(), CloseType::ConnectionLimitDumb);
@@ -618,7 +841,11 @@ impl ConnectionPool {
         }
 
         // Set peerConnection to NEGOTIATING state.
-        self.state.write().connections.get_mut(connection_id).unwrap().negotiating();
+        {
+            let mut state = self.state.write();
+            state.connections.get_mut(connection_id).unwrap().negotiating();
+            state.add_peer_id(connection_id, &peer.peer_address().peer_id);
+        }
 
         return false;
     }
@@ -629,6 +856,7 @@ impl ConnectionPool {
 
         let peer_address = peer.peer_address();
         let mut is_inbound = false;
+        let mut aborted_connection_id = None;
         // Read lock.
         {
             let state = self.state.read();
@@ -654,11 +882,12 @@ impl ConnectionPool {
                                 assert!(protocol == Protocol::Wss || protocol == Protocol::Ws, "Duplicate connection to non-WS node");
                                 debug!("Aborting connection attempt to {:?}, simultaneous connection succeeded", peer_address);
 
-                                // Abort connection.
+                                // Abort connection. The connector doesn't report aborted attempts, so the stored
+                                // connection is removed below.
                                 stored_connection.connection_handle().map(|handle| {
                                     handle.abort();
                                 });
-                                assert!(state.get_connection_by_peer_address(&peer_address).is_none(), "ConnectionInfo not removed");
+                                aborted_connection_id = Some(*stored_connection_id);
                             },
                             ConnectionState::Established => {
                                 // If we have another established connection to this peer, close this connection.
@@ -689,11 +918,20 @@ impl ConnectionPool {
         }
 
         // Write lock.
+        if let Some(aborted_connection_id) = aborted_connection_id {
+            let mut state = self.state.write();
+            state.remove(aborted_connection_id);
+            update_checked!(state.connecting_count, PeerCountUpdate::Remove);
+        }
         if is_inbound {
             let mut state = self.state.write();
-            assert!(state.get_connection_by_peer_address(&peer_address).is_none(), "ConnectionInfo already exists");
+            if !state.add_peer_address(connection_id, peer_address.clone()) {
+                // Never silently replace the stored connection, close this one instead.
+                let info = state.connections.get(connection_id).expect("Missing connection");
+                ConnectionPool::close(info.network_connection(), CloseType::DuplicateConnection);
+                return;
+            }
             state.connections.get_mut(connection_id).unwrap().set_peer_address(peer_address.clone());
-            state.add_peer_address(connection_id, peer_address.clone());
 
             update_checked!(state.inbound_count, PeerCountUpdate::Remove);
         }
@@ -705,6 +943,8 @@ impl ConnectionPool {
             self.notifier.read().notify(ConnectionPoolEvent::RecyclingRequest);
         }
 
+        self.timers.clear_delay(&ConnectionPoolTimer::Handshake(connection_id));
+
         // Aquire write lock and release it again before notifying listeners.
         {
             let mut state = self.state.write();
@@ -755,6 +995,8 @@ impl ConnectionPool {
             }
         }
 
+        self.timers.clear_delay(&ConnectionPoolTimer::Handshake(connection_id));
+
         let mut established_peer_left = false;
         let mut info;
         // Aquire write lock and release it again before notifying listeners.
@@ -762,6 +1004,10 @@ impl ConnectionPool {
             let mut state = self.state.write();
             info = state.remove(connection_id);
 
+            if let Some(peer_address) = info.peer_address() {
+                state.record_close(peer_address.peer_id.clone(), ty, SystemTime::now());
+            }
+
             // Check if the handshake with this peer has completed.
             if info.state() == ConnectionState::Established {
                 let net_address = info.network_connection().map(|p| p.net_address());
@@ -779,7 +1025,7 @@ impl ConnectionPool {
             } else {
                 match info.network_connection().map(|n| n.inbound()) {
                     Some(true) => {
-                        state.inbound_count.checked_sub(1).expect("inbound_count < 0");
+                        update_checked!(state.inbound_count, PeerCountUpdate::Remove);
                         debug!("Inbound connection #{:?} closed pre-handshake: {:?}", connection_id, ty);
                     },
                     Some(false) => {
@@ -806,12 +1052,27 @@ impl ConnectionPool {
         info.close();
     }
 
+    /// Closes all connections that didn't complete the handshake within the configured timeout.
+    pub fn check_handshake_timeouts(&self) {
+        let state = self.state.read();
+        for connection_id in state.timed_out_handshakes(Instant::now(), self.network_config.handshake_timeout()) {
+            let info = state.get_connection(connection_id).expect("Missing connection");
+            debug!("Closing connection #{} - handshake timed out", connection_id);
+            ConnectionPool::close(info.network_connection(), CloseType::HandshakeTimeout);
+        }
+    }
+
     /// Total peer count.
     pub fn peer_count(&self) -> usize {
         let state = self.state.read();
         state.peer_count()
     }
 
+    /// How the most recent connections to the given peer ended, oldest first.
+    pub fn recent_closes_for(&self, peer_id: &PeerId) -> Vec<(CloseType, SystemTime)> {
+        self.state.read().recent_closes_for(peer_id)
+    }
+
     /// Connecting count.
     pub fn connecting_count(&self) -> usize {
         let state = self.state.read();
@@ -827,6 +1088,15 @@ impl ConnectionPool {
         self.state.read().peer_count_outbound
     }
 
+    /// Peer addresses of established connections, e.g. to answer address requests.
+    pub fn established_peer_addresses(&self) -> Vec<Arc<PeerAddress>> {
+        self.state.read().established_peer_addresses()
+    }
+
+    pub fn established_peer_addresses_for(&self, protocol_mask: ProtocolFlags, service_mask: ServiceFlags) -> Vec<Arc<PeerAddress>> {
+        self.state.read().established_peer_addresses_for(protocol_mask, service_mask)
+    }
+
     pub fn allow_inbound_exchange(&self) -> bool {
         self.state.read().allow_inbound_exchange
     }
@@ -843,10 +1113,24 @@ impl ConnectionPool {
         self.state.write().allow_inbound_connections = allow_inbound_connections;
     }
 
+    /// Exports the pool's bans, close history, inbound settings and address backoff, but not its connections.
+    pub fn export_state(&self) -> ConnectionPoolSnapshot {
+        let mut snapshot = self.state.read().export();
+        snapshot.backoff = self.addresses.export_backoff();
+        snapshot
+    }
+
+    /// Restores the state exported from another pool.
+    /// Connections need to be re-established separately.
+    pub fn import_state(&self, snapshot: ConnectionPoolSnapshot) {
+        self.addresses.import_backoff(&snapshot.backoff);
+        self.state.write().import(snapshot);
+    }
+
     /// Callback on connect error.
-    fn on_connect_error(&self, peer_address: Arc<PeerAddress>) {
+    fn on_connect_error(&self, peer_address: Arc<PeerAddress>, ty: CloseType) {
         let guard = self.change_lock.lock();
-        debug!("Connection to {} failed", peer_address);
+        debug!("Connection to {} failed ({:?})", peer_address, ty);
 
         // Aquire write lock and release it again before notifying listeners.
         {
@@ -858,10 +1142,10 @@ impl ConnectionPool {
 
             update_checked!(state.connecting_count, PeerCountUpdate::Remove);
 
-            self.addresses.close(None, peer_address.clone(), CloseType::ConnectionFailed);
+            self.addresses.close(None, peer_address.clone(), ty);
         }
 
-        self.notifier.read().notify(ConnectionPoolEvent::ConnectError(peer_address, CloseType::ConnectionFailed));
+        self.notifier.read().notify(ConnectionPoolEvent::ConnectError(peer_address, ty));
     }
 
     /// Convert a net address into a subnet according to the configured bitmask.
@@ -915,6 +1199,11 @@ enum PeerCountUpdate {
     Remove
 }
 
+#[derive(Ord, PartialOrd, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+enum ConnectionPoolTimer {
+    Handshake(ConnectionId),
+}
+
 pub enum ConnectionPoolEvent {
     PeerJoined(Peer),
     PeerLeft(Peer),
@@ -963,6 +1252,10 @@ impl<T> SparseVec<T> {
         value
     }
 
+    pub fn iter(&self) -> impl Iterator<Item=(usize, &T)> {
+        self.inner.iter().enumerate().filter_map(|(index, value)| Some((index, value.as_ref()?)))
+    }
+
     pub fn insert(&mut self, value: T) -> usize {
         if let Some(index) = self.free_indices.pop_front() {
             self.inner.get_mut(index).unwrap().get_or_insert(value);
@@ -977,8 +1270,62 @@ impl<T> SparseVec<T> {
 
 #[cfg(test)]
 mod tests {
+    use std::net::ToSocketAddrs;
+
+    use futures::future;
+    use tokio::net::TcpListener;
+    use tokio::prelude::*;
+    use tokio::runtime::Runtime;
+    use tokio_tungstenite::stream::Stream as StreamSwitcher;
+    use url::Url;
+
+    use database::volatile::VolatileEnvironment;
+    use database::Environment;
+    use network_primitives::networks::NetworkId;
+    use network_primitives::time::NetworkTime;
+
+    use hash::Blake2bHash;
+    use keys::{KeyPair, PublicKey};
+    use network_primitives::address::peer_address::PeerAddressType;
+
+    use crate::address::peer_address_book::MAX_FAILED_ATTEMPTS_WS;
+    use crate::connection::AddressInfo;
+    use crate::websocket::{nimiq_accept_async, nimiq_connect_async, NimiqMessageStream, SharedNimiqMessageStream};
+
     use super::*;
 
+    lazy_static! {
+        static ref ENV: Environment = VolatileEnvironment::new(10).unwrap();
+    }
+
+    fn peer_address(ty: PeerAddressType) -> Arc<PeerAddress> {
+        peer_address_of(ty, KeyPair::generate().public)
+    }
+
+    /// A peer address of the node with the given public key.
+    fn peer_address_of(ty: PeerAddressType, public_key: PublicKey) -> Arc<PeerAddress> {
+        Arc::new(PeerAddress {
+            ty,
+            services: ServiceFlags::FULL,
+            timestamp: 0,
+            net_address: NetAddress::Unspecified,
+            peer_id: PeerId::from(&public_key),
+            public_key,
+            distance: 0,
+            signature: None,
+        })
+    }
+
+    /// A pool on the main network that hasn't started listening.
+    fn pool() -> Arc<ConnectionPool> {
+        let mut network_config = NetworkConfig::new_ws_network_config("localhost".to_string(), 8443, None, None);
+        network_config.init_volatile();
+        let network_config = Arc::new(network_config);
+        let addresses = Arc::new(PeerAddressBook::new(network_config.clone(), NetworkId::Main));
+        let blockchain = Arc::new(Blockchain::new(&ENV, NetworkId::Main, Arc::new(NetworkTime::new())));
+        ConnectionPool::new(addresses, network_config, blockchain, None)
+    }
+
     #[test]
     fn sparse_vec_can_store_objects() {
         let mut v = SparseVec::new();
@@ -1013,4 +1360,309 @@ mod tests {
         let i5 = v.insert(4);
         assert_eq!(i5, 2);
     }
+
+    #[test]
+    fn it_tracks_connections_by_peer_id() {
+        let public_key = KeyPair::generate().public;
+        let peer_id = PeerId::from(&public_key);
+
+        let mut state = ConnectionPoolState::new();
+
+        // Two connections to the same node via different protocols.
+        let ws = state.add(ConnectionInfo::outbound(peer_address_of(PeerAddressType::Ws("node.example".to_string(), 8443), public_key.clone()))).unwrap();
+        let wss = state.add(ConnectionInfo::outbound(peer_address_of(PeerAddressType::Wss("node.example".to_string(), 443), public_key))).unwrap();
+        assert_eq!(state.get_num_connections_by_peer_id(&peer_id), 0);
+
+        state.add_peer_id(ws, &peer_id);
+        assert!(state.get_other_connections_by_peer_id(ws, &peer_id).is_empty());
+        assert_eq!(state.get_other_connections_by_peer_id(wss, &peer_id).len(), 1);
+
+        state.add_peer_id(wss, &peer_id);
+        assert_eq!(state.get_num_connections_by_peer_id(&peer_id), 2);
+
+        state.remove(ws);
+        assert_eq!(state.get_num_connections_by_peer_id(&peer_id), 1);
+        assert!(state.get_other_connections_by_peer_id(wss, &peer_id).is_empty());
+
+        state.remove(wss);
+        assert_eq!(state.get_num_connections_by_peer_id(&peer_id), 0);
+    }
+
+    #[test]
+    fn it_lists_established_peer_addresses() {
+        let mut state = ConnectionPoolState::new();
+
+        let ws = peer_address(PeerAddressType::Ws("a.example".to_string(), 8443));
+        let wss = Arc::new(PeerAddress {
+            services: ServiceFlags::LIGHT,
+            ..peer_address(PeerAddressType::Wss("b.example".to_string(), 443)).as_ref().clone()
+        });
+        let connecting = peer_address(PeerAddressType::Wss("c.example".to_string(), 443));
+
+        let ws_id = state.add(ConnectionInfo::outbound(ws.clone())).unwrap();
+        let wss_id = state.add(ConnectionInfo::outbound(wss.clone())).unwrap();
+        state.add(ConnectionInfo::outbound(connecting.clone())).unwrap();
+        assert!(state.established_peer_addresses().is_empty());
+
+        state.connections.get_mut(ws_id).unwrap().set_state(ConnectionState::Established);
+        state.connections.get_mut(wss_id).unwrap().set_state(ConnectionState::Established);
+
+        let mut addresses = state.established_peer_addresses();
+        addresses.sort_by_key(|a| a.as_uri());
+        assert_eq!(addresses, vec![ws.clone(), wss.clone()]);
+
+        assert_eq!(state.established_peer_addresses_for(ProtocolFlags::WSS, ServiceFlags::all()), vec![wss.clone()]);
+        assert_eq!(state.established_peer_addresses_for(ProtocolFlags::WS | ProtocolFlags::WSS, ServiceFlags::FULL), vec![ws.clone()]);
+        assert!(state.established_peer_addresses_for(ProtocolFlags::RTC, ServiceFlags::all()).is_empty());
+    }
+
+    #[test]
+    fn it_detects_timed_out_handshakes() {
+        let wss = |host: &str| peer_address(PeerAddressType::Wss(host.to_string(), 443));
+
+        let timeout = Duration::from_secs(30);
+        let now = Instant::now();
+        let mut state = ConnectionPoolState::new();
+
+        let stuck = state.add(ConnectionInfo::outbound(wss("a.example"))).unwrap();
+        let fresh = state.add(ConnectionInfo::outbound(wss("b.example"))).unwrap();
+        let established = state.add(ConnectionInfo::outbound(wss("c.example"))).unwrap();
+        let connecting = state.add(ConnectionInfo::outbound(wss("d.example"))).unwrap();
+        for &(connection_id, connection_state, age) in &[(stuck, ConnectionState::Negotiating, timeout * 2),
+                (fresh, ConnectionState::Negotiating, timeout / 2), (established, ConnectionState::Established, timeout * 2)] {
+            let info = state.connections.get_mut(connection_id).unwrap();
+            info.set_state(connection_state);
+            info.set_connected_since(now - age);
+        }
+        assert_eq!(state.connections.get(connecting).unwrap().state(), ConnectionState::Connecting);
+
+        assert_eq!(state.timed_out_handshakes(now, timeout), vec![stuck]);
+        assert_eq!(state.timed_out_handshakes(now + timeout, timeout), vec![stuck, fresh]);
+
+        // Closing the stuck connection frees its slot.
+        assert_eq!(state.count(), 4);
+        state.remove(stuck);
+        assert_eq!(state.count(), 3);
+        assert!(state.timed_out_handshakes(now, timeout).is_empty());
+    }
+
+    #[test]
+    fn it_picks_websocket_seeds_for_bootstrapping() {
+        let ws_seeds = vec![
+            peer_address(PeerAddressType::Ws("a.example".to_string(), 8443)),
+            peer_address(PeerAddressType::Wss("b.example".to_string(), 443)),
+            peer_address(PeerAddressType::Wss("c.example".to_string(), 443)),
+        ];
+        let seeds = vec![ws_seeds[0].clone(), peer_address(PeerAddressType::Rtc), ws_seeds[1].clone(),
+            peer_address(PeerAddressType::Dumb), ws_seeds[2].clone(), ws_seeds[0].clone()];
+
+        let mut state = ConnectionPoolState::new();
+        assert_eq!(state.bootstrap_candidates(&seeds, 8), ws_seeds);
+        assert_eq!(state.bootstrap_candidates(&seeds, 2), ws_seeds[..2].to_vec());
+
+        // Seeds we are already dialing are not dialed again and count towards the limit.
+        state.add(ConnectionInfo::outbound(ws_seeds[0].clone())).unwrap();
+        state.connecting_count += 1;
+        assert_eq!(state.bootstrap_candidates(&seeds, 8), ws_seeds[1..].to_vec());
+        assert_eq!(state.bootstrap_candidates(&seeds, 2), ws_seeds[1..2].to_vec());
+    }
+
+    #[test]
+    fn it_keeps_a_bounded_close_history() {
+        let peer_id = PeerId::from(&KeyPair::generate().public);
+        let other_peer_id = PeerId::from(&KeyPair::generate().public);
+        let now = SystemTime::now();
+
+        let mut state = ConnectionPoolState::new();
+        state.record_close(peer_id.clone(), CloseType::ReceivedInvalidBlock, now);
+        state.record_close(other_peer_id.clone(), CloseType::ManualNetworkDisconnect, now + Duration::from_secs(1));
+        state.record_close(peer_id.clone(), CloseType::ManualNetworkDisconnect, now + Duration::from_secs(2));
+
+        assert_eq!(state.recent_closes_for(&peer_id), vec![
+            (CloseType::ReceivedInvalidBlock, now),
+            (CloseType::ManualNetworkDisconnect, now + Duration::from_secs(2)),
+        ]);
+        assert_eq!(state.recent_closes_for(&other_peer_id), vec![(CloseType::ManualNetworkDisconnect, now + Duration::from_secs(1))]);
+
+        // The oldest entries are dropped once the history is full.
+        for _ in 0..ConnectionPoolState::CLOSE_HISTORY_MAX - 1 {
+            state.record_close(other_peer_id.clone(), CloseType::ManualNetworkDisconnect, now);
+        }
+        assert_eq!(state.recent_closes_for(&peer_id), vec![(CloseType::ManualNetworkDisconnect, now + Duration::from_secs(2))]);
+    }
+
+    #[test]
+    fn it_does_not_overwrite_connections_for_the_same_peer_address() {
+        let a = peer_address(PeerAddressType::Wss("a.example".to_string(), 443));
+        let b = peer_address(PeerAddressType::Wss("b.example".to_string(), 443));
+
+        let mut state = ConnectionPoolState::new();
+        let first = state.add(ConnectionInfo::outbound(a.clone())).unwrap();
+
+        // A second connection for the same peer address is rejected instead of replacing the first.
+        assert_eq!(state.add(ConnectionInfo::outbound(a.clone())), None);
+        assert_eq!(state.connections.iter().count(), 1);
+
+        // Re-adding the peer address of the stored connection is a no-op,
+        // associating it with another connection is rejected.
+        let second = state.add(ConnectionInfo::outbound(b.clone())).unwrap();
+        assert!(state.add_peer_address(first, a.clone()));
+        assert!(!state.add_peer_address(second, a.clone()));
+        assert_eq!(state.connections_by_peer_address.get(&a), Some(&first));
+
+        // Every stored connection can still be found and removed.
+        state.remove(first);
+        state.remove(second);
+        assert!(state.connections_by_peer_address.is_empty());
+        assert_eq!(state.connections.iter().count(), 0);
+    }
+
+    #[test]
+    fn it_exports_and_imports_the_pool_state() {
+        use std::net::Ipv4Addr;
+        use network_primitives::networks::get_network_info;
+
+        let banned = NetAddress::IPv4(Ipv4Addr::new(203, 0, 113, 7));
+        let other = NetAddress::IPv4(Ipv4Addr::new(203, 0, 113, 8));
+        let peer_id = PeerId::from(&KeyPair::generate().public);
+        let now = SystemTime::now();
+
+        let pool = pool();
+        pool.set_allow_inbound_connections(true);
+        pool.set_allow_inbound_exchange(true);
+        {
+            let mut state = pool.state.write();
+            state.ban_ip(&banned);
+            state.record_close(peer_id.clone(), CloseType::ReceivedInvalidBlock, now);
+        }
+
+        // Back off a seed until it is banned temporarily.
+        let seed = Arc::new(get_network_info(NetworkId::Main).unwrap().seed_peers[0].clone());
+        for _ in 0..MAX_FAILED_ATTEMPTS_WS {
+            pool.addresses.close(None, seed.clone(), CloseType::ConnectionFailed);
+        }
+        let backoff = pool.addresses.export_backoff().remove(&seed).expect("Seed should be backed off");
+        assert_eq!(backoff.failed_attempts, MAX_FAILED_ATTEMPTS_WS);
+        assert!(backoff.banned_until.is_some());
+
+        let snapshot = pool.export_state();
+        assert_eq!(snapshot.banned_ips.len(), 1);
+        assert_eq!(snapshot.backoff.get(&seed), Some(&backoff));
+
+        let restored = pool();
+        assert!(restored.addresses.export_backoff().is_empty());
+        restored.import_state(snapshot.clone());
+        assert!(restored.allow_inbound_connections());
+        assert!(restored.allow_inbound_exchange());
+        assert!(restored.state().is_ip_banned(&banned));
+        assert!(!restored.state().is_ip_banned(&other));
+        assert_eq!(restored.recent_closes_for(&peer_id), vec![(CloseType::ReceivedInvalidBlock, now)]);
+        assert_eq!(restored.addresses.export_backoff(), snapshot.backoff);
+
+        // Expired bans are not carried over.
+        let mut expired = snapshot;
+        expired.banned_ips.insert(other.clone(), now - Duration::from_secs(1));
+        let restored = pool();
+        restored.import_state(expired);
+        assert!(restored.state().is_ip_banned(&banned));
+        assert!(!restored.state().is_ip_banned(&other));
+    }
+
+    /// Accepts a WebSocket connection from a local client and sets it up as an inbound connection.
+    /// Also returns the client's end of the connection, which has to be kept open.
+    fn inbound_connection(runtime: &mut Runtime) -> (NetworkConnection, NimiqMessageStream) {
+        let addr = ("localhost", 0).to_socket_addrs().unwrap().next().unwrap();
+        let listener = TcpListener::bind(&addr).unwrap();
+        let url = Url::parse(&format!("ws://localhost:{}", listener.local_addr().unwrap().port())).unwrap();
+
+        let client = nimiq_connect_async(url, TlsConnector::new().unwrap(), NetworkConfig::DEFAULT_MAX_MESSAGE_SIZE)
+            .map_err(|_| ());
+        let server = listener.incoming().into_future()
+            .map_err(|_| ())
+            .and_then(|(tcp, _)| nimiq_accept_async(StreamSwitcher::Plain(tcp.unwrap()), NetworkConfig::DEFAULT_MAX_MESSAGE_SIZE)
+                .map_err(|_| ()));
+        let (client, server) = runtime.block_on(client.join(server)).unwrap();
+
+        let shared_stream: SharedNimiqMessageStream = server.into();
+        let net_address = Some(Arc::new(shared_stream.net_address()));
+        let (network_connection, process_connection) = NetworkConnection::new_connection_setup(shared_stream, AddressInfo::new(net_address, None));
+        runtime.spawn(process_connection);
+        (network_connection, client)
+    }
+
+    #[test]
+    fn it_aborts_a_dial_when_a_simultaneous_inbound_connection_succeeds() {
+        let mut runtime = Runtime::new().unwrap();
+        let pool = pool();
+
+        // The peer accepts TCP connections but never completes the WebSocket handshake, so our dial stays pending.
+        let addr = ("localhost", 0).to_socket_addrs().unwrap().next().unwrap();
+        let unresponsive = std::net::TcpListener::bind(&addr).unwrap();
+        let peer_address = peer_address(PeerAddressType::Ws("localhost".to_string(), unresponsive.local_addr().unwrap().port()));
+
+        let (dialing_pool, dialed_address) = (Arc::clone(&pool), Arc::clone(&peer_address));
+        assert!(runtime.block_on(future::lazy(move || Ok::<bool, ()>(dialing_pool.connect_outbound(dialed_address)))).unwrap());
+        assert_eq!(pool.connecting_count(), 1);
+        let handle = Arc::clone(pool.state().get_connection_by_peer_address(&peer_address).unwrap().connection_handle().unwrap());
+
+        // Meanwhile, the same peer connects to us and completes the handshake.
+        let (network_connection, _client) = inbound_connection(&mut runtime);
+        network_connection.set_peer_address(Arc::clone(&peer_address));
+        let peer_channel = PeerChannel::new(&network_connection);
+        let connection_id = {
+            let mut state = pool.state.write();
+            let mut info = ConnectionInfo::inbound(network_connection);
+            info.set_peer_channel(peer_channel.clone());
+            let connection_id = state.add(info).unwrap();
+            state.inbound_count += 1;
+            connection_id
+        };
+        let peer = Peer::new(peer_channel, 1, Blake2bHash::default(), 0);
+        pool.on_handshake(connection_id, &UniquePtr::new(&peer));
+
+        // The dial is aborted and its connection replaced by the inbound one.
+        assert!(handle.is_aborted());
+        assert_eq!(pool.connecting_count(), 0);
+        assert_eq!(pool.state().inbound_count, 0);
+        let state = pool.state();
+        let info = state.get_connection_by_peer_address(&peer_address).unwrap();
+        assert_eq!(info.state(), ConnectionState::Established);
+        assert!(info.network_connection().unwrap().inbound());
+        assert_eq!(state.connections.iter().count(), 1);
+    }
+
+    #[test]
+    fn it_dials_websocket_seeds_when_bootstrapping() {
+        let mut runtime = Runtime::new().unwrap();
+        let pool = pool();
+
+        // The seeds accept TCP connections but never complete the WebSocket handshake, so the dials stay pending.
+        let addr = ("localhost", 0).to_socket_addrs().unwrap().next().unwrap();
+        let listeners: Vec<_> = (0..ConnectionPool::BOOTSTRAP_CONNECTING_MAX + 1)
+            .map(|_| std::net::TcpListener::bind(&addr).unwrap())
+            .collect();
+        let ws_seeds: Vec<_> = listeners.iter()
+            .map(|listener| peer_address(PeerAddressType::Ws("localhost".to_string(), listener.local_addr().unwrap().port())))
+            .collect();
+        let seeds = vec![ws_seeds[0].clone(), peer_address(PeerAddressType::Rtc), ws_seeds[1].clone(),
+            peer_address(PeerAddressType::Dumb), ws_seeds[2].clone()];
+
+        let bootstrap = |pool: &Arc<ConnectionPool>, runtime: &mut Runtime, seeds: Vec<Arc<PeerAddress>>| {
+            let pool = Arc::clone(pool);
+            runtime.block_on(future::lazy(move || Ok::<usize, ()>(pool.bootstrap(seeds)))).unwrap()
+        };
+
+        // Only the websocket seeds are dialed.
+        assert_eq!(bootstrap(&pool, &mut runtime, seeds.clone()), 3);
+        assert_eq!(pool.connecting_count(), 3);
+        for seed in &ws_seeds[..3] {
+            assert_eq!(pool.state().get_connection_by_peer_address(seed).unwrap().state(), ConnectionState::Connecting);
+        }
+
+        // Seeds that are already being dialed are skipped, and at most `BOOTSTRAP_CONNECTING_MAX` dials are in flight.
+        assert_eq!(bootstrap(&pool, &mut runtime, seeds), 0);
+        assert_eq!(bootstrap(&pool, &mut runtime, ws_seeds.clone()), ConnectionPool::BOOTSTRAP_CONNECTING_MAX - 3);
+        assert_eq!(pool.connecting_count(), ConnectionPool::BOOTSTRAP_CONNECTING_MAX);
+        assert!(pool.state().get_connection_by_peer_address(&ws_seeds[ConnectionPool::BOOTSTRAP_CONNECTING_MAX]).is_none());
+    }
 }
@@ -131,14 +131,16 @@ impl ProcessConnectionFuture {
         let (closing_tx, closing_rx) = oneshot::channel::<CloseType>();
         let notifier = peer_stream.notifier.clone();
 
-        // `select` required Item/Error to be the same, that's why we need to map them both to ().
-        let connection = forward_future.map(|_| ()).map_err(|_| ()).select(peer_stream.process_stream().map_err(|_| ())); // TODO: throwing away error info here
+        // `select` requires Item/Error to be the same, that's why we map Item to () and Error to a CloseType.
+        // Errors on the incoming stream determine the close type (e.g. oversized messages).
+        let connection = forward_future.map(|_| ()).map_err(|_| CloseType::ClosedByRemote)
+            .select(peer_stream.process_stream().map_err(|e| e.close_type()));
         // `select` required Item/Error to be the same.
-        // `closing_rx` has Item=ClosingType, which we want to use. But Error will be set to ().
-        let closing_future = closing_rx.map_err(|_| ());
-        // `connection` currently has a tuple type, but we will soon call `select`, so unify Error to ()
+        // `closing_rx` has Item=ClosingType, which we want to use. But Error will be set to ClosedByRemote.
+        let closing_future = closing_rx.map_err(|_| CloseType::ClosedByRemote);
+        // `connection` currently has a tuple type, but we will soon call `select`, so unify Error to CloseType
         // and set Item=CloseType with `CloseType::Regular` to identify a normal shutdown.
-        let connection = connection.map(|_| CloseType::Regular).map_err(|_| ());
+        let connection = connection.map(|_| CloseType::Regular).map_err(|(ty, _)| ty);
         // Types have already been unified, so select over normal connection and `closing_future`.
         let connection = connection.select(closing_future);
         // If the connection is to be dropped because of a forceful close, CloseType != `CloseType::Regular`.
@@ -147,7 +149,7 @@ impl ProcessConnectionFuture {
         let connection = connection.then(move |result| {
             let ty = match result {
                 Ok((ty, _)) => ty,
-                Err(_) => CloseType::ClosedByRemote,
+                Err((ty, _)) => ty,
             };
 
             // XXX Specifically send close frame if CloseType is not Regular. (???)
@@ -10,6 +10,11 @@ extern crate nimiq_utils as utils;
 extern crate nimiq_blockchain as blockchain;
 extern crate nimiq_hash as hash;
 extern crate nimiq_keys as keys;
+#[cfg(test)]
+#[macro_use]
+extern crate lazy_static;
+#[cfg(test)]
+extern crate nimiq_database as database;
 
 pub mod address;
 pub mod websocket;
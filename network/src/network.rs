@@ -64,7 +64,7 @@ impl Network {
     pub fn new(blockchain: Arc<Blockchain<'static>>, network_config: NetworkConfig, network_time: Arc<NetworkTime>, network_id: NetworkId) -> Arc<Self> {
         let net_config = Arc::new(network_config);
         let addresses = Arc::new(PeerAddressBook::new(net_config.clone(), network_id));
-        let connections = ConnectionPool::new(addresses.clone(), net_config.clone(), blockchain);
+        let connections = ConnectionPool::new(addresses.clone(), net_config.clone(), blockchain, None);
         let this = Arc::new(Network {
             network_config: net_config.clone(),
             network_time,
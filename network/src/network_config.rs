@@ -1,5 +1,5 @@
 use std::fs;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use beserial::{Deserialize, Serialize};
 use keys::{KeyPair, PublicKey};
@@ -16,10 +16,17 @@ pub struct NetworkConfig {
     peer_id: Option<PeerId>,
     services: Services,
     protocol_config: ProtocolConfig,
-    user_agent: Option<String>
+    user_agent: Option<String>,
+    handshake_timeout: Duration,
+    max_message_size: usize,
 }
 
 impl NetworkConfig {
+    /// Connections that don't complete the handshake within this time are closed.
+    pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+    /// Connections announcing a message larger than this are closed.
+    pub const DEFAULT_MAX_MESSAGE_SIZE: usize = network_primitives::MAX_MESSAGE_SIZE;
+
     pub fn new_ws_network_config(host: String, port: u16, reverse_proxy_config: Option<ReverseProxyConfig>, user_agent: Option<String>) -> Self {
         Self {
             protocol_mask: ProtocolFlags::WS | ProtocolFlags::WSS,
@@ -31,7 +38,9 @@ impl NetworkConfig {
                 port,
                 reverse_proxy_config,
             },
-            user_agent
+            user_agent,
+            handshake_timeout: NetworkConfig::DEFAULT_HANDSHAKE_TIMEOUT,
+            max_message_size: NetworkConfig::DEFAULT_MAX_MESSAGE_SIZE,
         }
     }
 
@@ -46,7 +55,9 @@ impl NetworkConfig {
                 port,
                 identity_file,
             },
-            user_agent
+            user_agent,
+            handshake_timeout: NetworkConfig::DEFAULT_HANDSHAKE_TIMEOUT,
+            max_message_size: NetworkConfig::DEFAULT_MAX_MESSAGE_SIZE,
         }
     }
 
@@ -57,7 +68,9 @@ impl NetworkConfig {
             peer_id: None,
             services: Services::full(),
             protocol_config: ProtocolConfig::Dumb,
-            user_agent
+            user_agent,
+            handshake_timeout: NetworkConfig::DEFAULT_HANDSHAKE_TIMEOUT,
+            max_message_size: NetworkConfig::DEFAULT_MAX_MESSAGE_SIZE,
         }
     }
 
@@ -110,6 +123,22 @@ impl NetworkConfig {
         self.services = services;
     }
 
+    pub fn handshake_timeout(&self) -> Duration {
+        self.handshake_timeout
+    }
+
+    pub fn set_handshake_timeout(&mut self, handshake_timeout: Duration) {
+        self.handshake_timeout = handshake_timeout;
+    }
+
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
     pub fn can_connect(&self, protocol: Protocol) -> bool {
         self.protocol_mask.contains(ProtocolFlags::from(protocol))
     }
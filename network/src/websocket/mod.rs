@@ -1,15 +1,17 @@
-use std::{collections::VecDeque, fmt, fmt::Debug, io, net, time::Instant};
+use std::{collections::VecDeque, fmt, fmt::Debug, io, net, net::ToSocketAddrs, time::Instant};
 use std::sync::Arc;
 
 use futures::prelude::*;
+use native_tls::{Error as TlsError, TlsConnector};
 use tokio::{
     net::TcpStream,
 };
+use tokio_tls::TlsConnector as TokioTlsConnector;
 use tokio_tungstenite::{
     accept_async,
-    connect_async,
+    client_async,
     MaybeTlsStream,
-    stream::PeerAddr,
+    stream::{PeerAddr, Stream as StreamSwitcher},
     WebSocketStream
 };
 use tungstenite::{
@@ -23,6 +25,7 @@ use network_messages::Message as NimiqMessage;
 use network_primitives::address::net_address::NetAddress;
 use utils::locking::MultiLock;
 
+use crate::connection::close_type::CloseType;
 #[cfg(feature = "metrics")]
 use crate::network_metrics::NetworkMetrics;
 
@@ -50,15 +53,110 @@ pub enum NimiqMessageStreamError {
     FinalChunkSizeExceeded,
 }
 
+impl NimiqMessageStreamError {
+    /// The type with which the connection is closed when the stream fails with this error.
+    pub fn close_type(&self) -> CloseType {
+        match self {
+            NimiqMessageStreamError::MessageSizeExceeded => CloseType::MessageTooLarge,
+            _ => CloseType::ClosedByRemote,
+        }
+    }
+}
+
+/// Errors while dialing another node.
+#[derive(Debug)]
+pub enum NimiqConnectError {
+    IoError(io::Error),
+    TlsHandshakeError(TlsError),
+    WebSocketError(WsError),
+}
+
+impl NimiqConnectError {
+    /// The type with which the failed connection attempt is closed.
+    pub fn close_type(&self) -> CloseType {
+        match self {
+            NimiqConnectError::TlsHandshakeError(_) => CloseType::TlsHandshakeFailed,
+            _ => CloseType::ConnectionFailed,
+        }
+    }
+}
+
+impl fmt::Display for NimiqConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            NimiqConnectError::IoError(e) => write!(f, "{}", e),
+            NimiqConnectError::TlsHandshakeError(e) => write!(f, "TLS handshake failed: {}", e),
+            NimiqConnectError::WebSocketError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
 const MAX_CHUNK_SIZE: usize = 1024 * 16; // 16 kb
-const MAX_MESSAGE_SIZE: usize = 1024 * 1024 * 10; // 10 mb
+
+/// Reassembles Nimiq messages from the chunks received over the WebSocket.
+struct MessageReassembler {
+    receiving_tag: u8,
+    msg_buf: Option<Vec<u8>>,
+    max_message_size: usize,
+}
+
+impl MessageReassembler {
+    fn new(max_message_size: usize) -> Self {
+        MessageReassembler {
+            receiving_tag: 254,
+            msg_buf: None,
+            max_message_size,
+        }
+    }
+
+    /// Adds a chunk (including its tag) to the current message.
+    /// Returns the raw message once all of its chunks have been received.
+    fn push_chunk(&mut self, raw_msg: &[u8]) -> Result<Option<Vec<u8>>, NimiqMessageStreamError> {
+        let tag = raw_msg[0];
+        let chunk = &raw_msg[1..];
+
+        // Detect if this is a new message.
+        if self.msg_buf.is_none() {
+            let msg_size = NimiqMessage::peek_length(chunk);
+            if msg_size > self.max_message_size {
+                error!("Max message size exceeded ({} > {})", msg_size, self.max_message_size);
+                return Err(NimiqMessageStreamError::MessageSizeExceeded);
+            }
+
+            self.msg_buf = Some(Vec::with_capacity(msg_size));
+            // XXX JS implementation quirk: Already wrap at 255 instead of 256
+            self.receiving_tag = (self.receiving_tag + 1) % 255;
+        }
+
+        if self.receiving_tag != tag {
+            error!("Tag mismatch: expected {}, got {}", self.receiving_tag, tag);
+            return Err(NimiqMessageStreamError::TagMismatch);
+        }
+
+        let msg_buf = self.msg_buf.as_mut().unwrap();
+        let remaining = msg_buf.capacity() - msg_buf.len();
+
+        let chunk_size = chunk.len();
+        if chunk_size > remaining {
+            error!("Final chunk size exceeded ({} > {})", chunk_size, remaining);
+            return Err(NimiqMessageStreamError::FinalChunkSizeExceeded);
+        }
+
+        msg_buf.extend_from_slice(chunk);
+
+        if remaining == chunk_size {
+            // Full message read, reset message buffer.
+            return Ok(self.msg_buf.take());
+        }
+        Ok(None)
+    }
+}
 
 pub struct NimiqMessageStream {
     inner: WebSocketLayer,
-    receiving_tag: u8,
+    reassembler: MessageReassembler,
     sending_tag: u8,
     ws_queue: VecDeque<WebSocketMessage>,
-    msg_buf: Option<Vec<u8>>,
     net_address: NetAddress,
     outbound: bool,
     last_chunk_received_at: Option<Instant>,
@@ -67,14 +165,13 @@ pub struct NimiqMessageStream {
 }
 
 impl NimiqMessageStream {
-    fn new(ws_socket: WebSocketStream<MaybeTlsStream<TcpStream>>, outbound: bool) -> Self {
+    fn new(ws_socket: WebSocketStream<MaybeTlsStream<TcpStream>>, outbound: bool, max_message_size: usize) -> Self {
         let peer_addr = ws_socket.get_ref().peer_addr().unwrap();
         return NimiqMessageStream {
             inner: ws_socket,
-            receiving_tag: 254,
+            reassembler: MessageReassembler::new(max_message_size),
             sending_tag: 0,
             ws_queue: VecDeque::new(),
-            msg_buf: None,
             net_address: match peer_addr.ip() {
                 net::IpAddr::V4(ip4) => NetAddress::IPv4(ip4),
                 net::IpAddr::V6(ip6) => NetAddress::IPv6(ip6),
@@ -210,49 +307,15 @@ impl Stream for NimiqMessageStream {
 
         while let Some(ws_msg) = self.ws_queue.pop_front() {
             let raw_msg = ws_msg.into_data();
-            let tag = raw_msg[0];
-            let chunk = &raw_msg[1..];
-
-            // Detect if this is a new message.
-            if self.msg_buf.is_none() {
-                let msg_size = NimiqMessage::peek_length(chunk);
-                if msg_size > MAX_MESSAGE_SIZE {
-                    error!("Max message size exceeded ({} > {})", msg_size, MAX_MESSAGE_SIZE);
-                    return Err(NimiqMessageStreamError::MessageSizeExceeded);
-                }
-
-                self.msg_buf = Some(Vec::with_capacity(msg_size));
-                // XXX JS implementation quirk: Already wrap at 255 instead of 256
-                self.receiving_tag = (self.receiving_tag + 1) % 255;
-            }
-
-            if self.receiving_tag != tag {
-                error!("Tag mismatch: expected {}, got {}", self.receiving_tag, tag);
-                return Err(NimiqMessageStreamError::TagMismatch);
-            }
+            let msg_buf = self.reassembler.push_chunk(&raw_msg)?;
 
             // Update last chunk timestamp
             self.last_chunk_received_at = Some(Instant::now());
 
-            let msg_buf = self.msg_buf.as_mut().unwrap();
-            let mut remaining = msg_buf.capacity() - msg_buf.len();
-
-            let chunk_size = raw_msg.len() - 1;
-            if chunk_size > remaining {
-                error!("Final chunk size exceeded ({} > {})", chunk_size, remaining);
-                return Err(NimiqMessageStreamError::FinalChunkSizeExceeded);
-            }
-
-            msg_buf.extend_from_slice(chunk);
-            remaining -= chunk_size;
-
-            if remaining == 0 {
+            if let Some(msg_buf) = msg_buf {
                 // Full message read, parse it.
                 let msg = Deserialize::deserialize(&mut &msg_buf[..]);
 
-                // Reset message buffer.
-                self.msg_buf = None;
-
                 if let Err(e) = msg {
                     error!("Failed to parse message: {:?}", e);
                     // FIXME Fail on message parse errors
@@ -273,21 +336,43 @@ impl Debug for NimiqMessageStream {
     }
 }
 
-/// Connect to a given URL and return a Future that will resolve to a NimiqMessageStream
-pub fn nimiq_connect_async(url: Url) -> Box<Future<Item = NimiqMessageStream, Error = io::Error> + Send> {
+/// Connect to a given URL and return a Future that will resolve to a NimiqMessageStream.
+/// For `wss` URLs, the connection is wrapped in TLS using `tls_connector`, with the URL's host
+/// used for SNI and certificate validation.
+pub fn nimiq_connect_async(url: Url, tls_connector: TlsConnector, max_message_size: usize) -> Box<Future<Item = NimiqMessageStream, Error = NimiqConnectError> + Send> {
+    let addr = match url.to_socket_addrs().map(|mut addrs| addrs.next()) {
+        Ok(Some(addr)) => addr,
+        Ok(None) => return Box::new(future::err(NimiqConnectError::IoError(io::Error::new(io::ErrorKind::NotFound, "Failed to resolve host")))),
+        Err(e) => return Box::new(future::err(NimiqConnectError::IoError(e))),
+    };
+    let host = url.host_str().unwrap_or_default().to_string();
+    let secure = url.scheme() == "wss";
+
     Box::new(
-        connect_async(url).map(|(ws_stream,_)| NimiqMessageStream::new(ws_stream, true))
+        TcpStream::connect(&addr)
+        .map_err(NimiqConnectError::IoError)
+        .and_then(move |socket| -> Box<Future<Item = MaybeTlsStream<TcpStream>, Error = NimiqConnectError> + Send> {
+            if secure {
+                Box::new(TokioTlsConnector::from(tls_connector).connect(&host, socket)
+                    .map(StreamSwitcher::Tls)
+                    .map_err(NimiqConnectError::TlsHandshakeError))
+            } else {
+                Box::new(future::ok(StreamSwitcher::Plain(socket)))
+            }
+        })
+        .and_then(move |stream| client_async(url, stream).map_err(NimiqConnectError::WebSocketError))
+        .map(move |(ws_stream,_)| NimiqMessageStream::new(ws_stream, true, max_message_size))
         .map_err(|e| {
             println!("Error while trying to connect to another node: {}", e);
-            io::Error::new(io::ErrorKind::Other, e)
+            e
         })
     )
 }
 
 /// Accept an incoming connection and return a Future that will resolve to a NimiqMessageStream
-pub fn nimiq_accept_async(stream: MaybeTlsStream<TcpStream>) -> Box<Future<Item = NimiqMessageStream, Error = io::Error> + Send> {
+pub fn nimiq_accept_async(stream: MaybeTlsStream<TcpStream>, max_message_size: usize) -> Box<Future<Item = NimiqMessageStream, Error = io::Error> + Send> {
     Box::new(
-        accept_async(stream).map(|ws_stream| NimiqMessageStream::new(ws_stream, false))
+        accept_async(stream).map(move |ws_stream| NimiqMessageStream::new(ws_stream, false, max_message_size))
         .map_err(|e| {
             println!("Error while accepting a connection from another node: {}", e);
             io::Error::new(io::ErrorKind::Other, e)
@@ -398,3 +483,30 @@ impl Sink for SharedNimiqMessageStream {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(tag: u8, msg_size: u32, len: usize) -> Vec<u8> {
+        let mut chunk = vec![tag, 0x42, 0x04, 0x20, 0x42, 0];
+        chunk.extend_from_slice(&msg_size.to_be_bytes());
+        chunk.resize(len + /*tag*/ 1, 0);
+        chunk
+    }
+
+    #[test]
+    fn it_closes_on_oversized_messages() {
+        let mut reassembler = MessageReassembler::new(1024);
+
+        // A message within the limit is buffered until it is complete.
+        assert!(reassembler.push_chunk(&chunk(0, 1024, 512)).unwrap().is_none());
+        assert_eq!(reassembler.push_chunk(&vec![0u8; 513]).unwrap().unwrap().len(), 1024);
+
+        // A message announcing more than the limit fails the stream and closes the connection.
+        match reassembler.push_chunk(&chunk(1, 1025, 512)) {
+            Err(e @ NimiqMessageStreamError::MessageSizeExceeded) => assert_eq!(e.close_type(), CloseType::MessageTooLarge),
+            _ => panic!("Oversized message was accepted"),
+        }
+    }
+}
@@ -11,7 +11,7 @@ use std::{
 use futures::{
     prelude::*,
 };
-use native_tls::{Identity, TlsAcceptor};
+use native_tls::{Identity, TlsAcceptor, TlsConnector};
 use parking_lot::RwLock;
 use tokio::{
     io,
@@ -35,6 +35,7 @@ use utils::observer::PassThroughNotifier;
 use crate::{
     connection::{
         AddressInfo,
+        close_type::CloseType,
         NetworkConnection,
     },
     network_config::{
@@ -68,7 +69,7 @@ impl ConnectionHandle {
 #[derive(Clone)]
 pub enum WebSocketConnectorEvent {
     Connection(NetworkConnection),
-    Error(Arc<PeerAddress>, io::ErrorKind),
+    Error(Arc<PeerAddress>, CloseType),
 }
 
 pub fn wrap_stream<S>(socket: S, identity_file: Option<String>, mode: Mode)
@@ -95,15 +96,19 @@ pub fn wrap_stream<S>(socket: S, identity_file: Option<String>, mode: Mode)
 
 pub struct WebSocketConnector {
     network_config: Arc<NetworkConfig>,
+    tls_connector: TlsConnector,
     pub notifier: Arc<RwLock<PassThroughNotifier<'static, WebSocketConnectorEvent>>>,
 }
 
 impl WebSocketConnector {
     const CONNECT_TIMEOUT: Duration = Duration::from_secs(5); // 5 seconds
 
-    pub fn new(network_config: Arc<NetworkConfig>) -> WebSocketConnector {
+    /// `tls_connector` is used to dial WSS peers. Without one, certificates are validated against the
+    /// system's root certificates.
+    pub fn new(network_config: Arc<NetworkConfig>, tls_connector: Option<TlsConnector>) -> WebSocketConnector {
         WebSocketConnector {
             network_config,
+            tls_connector: tls_connector.unwrap_or_else(|| TlsConnector::new().expect("Failed to create TLS connector")),
             notifier: Arc::new(RwLock::new(PassThroughNotifier::new())),
         }
     }
@@ -125,12 +130,13 @@ impl WebSocketConnector {
         let addr = SocketAddr::new("::".parse().unwrap(), port);
         let socket = TcpListener::bind(&addr).unwrap();
         let notifier = Arc::clone(&self.notifier);
+        let max_message_size = self.network_config.max_message_size();
 
         let srv = socket.incoming().for_each(move |tcp| {
             let notifier = Arc::clone(&notifier);
             let identity_file = identity_file.clone();
                 wrap_stream(tcp, identity_file, mode).and_then(move |ss| {
-                    nimiq_accept_async(ss).map(move |msg_stream: NimiqMessageStream| {
+                    nimiq_accept_async(ss, max_message_size).map(move |msg_stream: NimiqMessageStream| {
                         // FIXME: Find a way to provide reverse proxy support (i.e. getting the correct IP
                         // address from the request headers instead of from the socket itself)
                         let shared_stream: SharedNimiqMessageStream = msg_stream.into();
@@ -149,12 +155,24 @@ impl WebSocketConnector {
             tokio::spawn(srv);
     }
 
+    /// Dials the given peer. The outcome is reported through the notifier: a `Connection` event with an
+    /// outbound `NetworkConnection`, or an `Error` event if the peer could not be reached.
+    /// Events are always emitted from a spawned task, never from within this call, so that callers
+    /// can finish their bookkeeping for the connection attempt while holding their locks.
     pub fn connect(&self, peer_address: Arc<PeerAddress>) -> Arc<ConnectionHandle> {
         let notifier = Arc::clone(&self.notifier);
+        let connection_handle = Arc::new(ConnectionHandle(AtomicBool::new(false)));
 
-        if !self.network_config.protocol_mask().contains(ProtocolFlags::from(peer_address.protocol())) {
-            notifier.read().notify(WebSocketConnectorEvent::Error(Arc::clone(&peer_address), io::ErrorKind::InvalidInput));
-        }
+        let url = match Url::parse(&peer_address.as_uri()) {
+            Ok(ref url) if self.network_config.protocol_mask().contains(ProtocolFlags::from(peer_address.protocol())) => url.clone(),
+            _ => {
+                tokio::spawn(future::lazy(move || {
+                    notifier.read().notify(WebSocketConnectorEvent::Error(peer_address, CloseType::ConnectionFailed));
+                    Ok(())
+                }));
+                return connection_handle;
+            },
+        };
 
         // NOTE: We're not checking if we are already connecting to a peer here because
         // that check is already done by the ConnectionPool in the upper layer and doing
@@ -162,13 +180,12 @@ impl WebSocketConnector {
         // implementation where the data structures are there for something else and then you
         // get this check "for free")
 
-        let url = Url::parse(&peer_address.as_uri()).unwrap();
         let error_notifier = Arc::clone(&self.notifier);
         let error_peer_address = Arc::clone(&peer_address);
-        let connection_handle = Arc::new(ConnectionHandle(AtomicBool::new(false)));
         let connection_handle_for_closure = Arc::clone(&connection_handle);
+        let connection_handle_for_error = Arc::clone(&connection_handle);
 
-        let connect = nimiq_connect_async(url)
+        let connect = nimiq_connect_async(url, self.tls_connector.clone(), self.network_config.max_message_size())
             .timeout(Self::CONNECT_TIMEOUT)
             .map(move |msg_stream| {
                 if !connection_handle_for_closure.is_aborted() {
@@ -180,15 +197,14 @@ impl WebSocketConnector {
                 }
             })
             .map_err(move |error| {
-                if error.is_inner() {
-                    let error = error.into_inner().expect("There was no inner_error inside the timeout::Error struct: abort.");
-                    error_notifier.read().notify(WebSocketConnectorEvent::Error(Arc::clone(&error_peer_address), error.kind()));
-                } else if error.is_timer() {
-                    error_notifier.read().notify(WebSocketConnectorEvent::Error(Arc::clone(&error_peer_address), io::ErrorKind::Other));
-                } else if error.is_elapsed() {
-                    error_notifier.read().notify(WebSocketConnectorEvent::Error(Arc::clone(&error_peer_address), io::ErrorKind::TimedOut));
+                // Aborted connection attempts have already been cleaned up by the caller.
+                if connection_handle_for_error.is_aborted() {
+                    return;
                 }
-                ()
+
+                // Timeouts and timer errors are regular connection failures.
+                let ty = error.into_inner().map_or(CloseType::ConnectionFailed, |error| error.close_type());
+                error_notifier.read().notify(WebSocketConnectorEvent::Error(Arc::clone(&error_peer_address), ty));
             });
 
             tokio::spawn(connect);
@@ -196,3 +212,156 @@ impl WebSocketConnector {
             connection_handle
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::ToSocketAddrs;
+    use std::sync::mpsc;
+
+    use native_tls::Certificate;
+    use tokio::runtime::Runtime;
+    use tokio_tungstenite::accept_async;
+
+    use keys::KeyPair;
+    use network_primitives::address::{NetAddress, PeerAddressType, PeerId};
+    use network_primitives::services::ServiceFlags;
+
+    use super::*;
+
+    // Self-signed certificate for `localhost`, the PKCS#12 archive is protected with "hunter2".
+    const CERTIFICATE: &[u8] = include_bytes!("../../tests/fixtures/localhost.crt");
+    const IDENTITY: &[u8] = include_bytes!("../../tests/fixtures/localhost.p12");
+
+    fn peer_address(ty: PeerAddressType) -> Arc<PeerAddress> {
+        let public_key = KeyPair::generate().public;
+        Arc::new(PeerAddress {
+            ty,
+            services: ServiceFlags::FULL,
+            timestamp: 0,
+            net_address: NetAddress::Unspecified,
+            peer_id: PeerId::from(&public_key),
+            public_key,
+            distance: 0,
+            signature: None,
+        })
+    }
+
+    /// Serves a WebSocket endpoint that echoes all messages back and returns its port.
+    /// If an identity is given, connections are wrapped in TLS.
+    fn spawn_echo_server(runtime: &mut Runtime, identity: Option<Identity>) -> u16 {
+        // Bind to the address that `localhost` is dialed at.
+        let addr = ("localhost", 0).to_socket_addrs().unwrap().next().unwrap();
+        let listener = TcpListener::bind(&addr).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let acceptor = identity.map(|identity| TokioTlsAcceptor::from(TlsAcceptor::new(identity).unwrap()));
+        let server = listener.incoming()
+            .map_err(|_| ())
+            .for_each(move |tcp| {
+                let stream: Box<Future<Item=MaybeTlsStream<_>, Error=()> + Send> = match acceptor {
+                    Some(ref acceptor) => Box::new(acceptor.accept(tcp).map(StreamSwitcher::Tls).map_err(|_| ())),
+                    None => Box::new(future::ok(StreamSwitcher::Plain(tcp))),
+                };
+                let echo = stream
+                    .and_then(|stream| accept_async(stream).map_err(|_| ()))
+                    .and_then(|ws_stream| {
+                        let (sink, stream) = ws_stream.split();
+                        stream.forward(sink).map(|_| ()).map_err(|_| ())
+                    });
+                tokio::spawn(echo);
+                Ok(())
+            });
+        runtime.spawn(server);
+        port
+    }
+
+    /// Dials the given peer and waits for the connector to report the outcome.
+    fn connect(runtime: &mut Runtime, peer_address: Arc<PeerAddress>, tls_connector: Option<TlsConnector>) -> WebSocketConnectorEvent {
+        let network_config = NetworkConfig::new_ws_network_config("localhost".to_string(), 0, None, None);
+        let connector = Arc::new(WebSocketConnector::new(Arc::new(network_config), tls_connector));
+        let (tx, rx) = mpsc::channel();
+        let tx = parking_lot::Mutex::new(tx);
+        connector.notifier.write().register(move |event: WebSocketConnectorEvent| {
+            tx.lock().send(event).unwrap();
+        });
+
+        let dialer = Arc::clone(&connector);
+        runtime.block_on(future::lazy(move || {
+            dialer.connect(peer_address);
+            Ok::<(), ()>(())
+        })).unwrap();
+
+        rx.recv_timeout(Duration::from_secs(10)).expect("Connector did not report back")
+    }
+
+    fn pinned_tls_connector() -> TlsConnector {
+        TlsConnector::builder()
+            .add_root_certificate(Certificate::from_pem(CERTIFICATE).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    fn assert_connected(event: WebSocketConnectorEvent, peer_address: Arc<PeerAddress>) {
+        match event {
+            WebSocketConnectorEvent::Connection(conn) => {
+                assert!(conn.outbound());
+                assert_eq!(conn.peer_address(), Some(peer_address));
+                conn.close(CloseType::Regular);
+            },
+            WebSocketConnectorEvent::Error(_, ty) => panic!("Connection failed: {:?}", ty),
+        }
+    }
+
+    #[test]
+    fn it_dials_websocket_peers() {
+        let mut runtime = Runtime::new().unwrap();
+        let port = spawn_echo_server(&mut runtime, None);
+
+        let peer_address = peer_address(PeerAddressType::Ws("localhost".to_string(), port));
+        let event = connect(&mut runtime, peer_address.clone(), None);
+        assert_connected(event, peer_address);
+    }
+
+    #[test]
+    fn it_reports_failed_dials() {
+        let mut runtime = Runtime::new().unwrap();
+
+        // Nothing is listening on this port anymore.
+        let addr = ("localhost", 0).to_socket_addrs().unwrap().next().unwrap();
+        let port = TcpListener::bind(&addr).unwrap().local_addr().unwrap().port();
+
+        let peer_address = peer_address(PeerAddressType::Ws("localhost".to_string(), port));
+        match connect(&mut runtime, peer_address.clone(), None) {
+            WebSocketConnectorEvent::Error(address, ty) => {
+                assert_eq!(address, peer_address);
+                assert_eq!(ty, CloseType::ConnectionFailed);
+            },
+            WebSocketConnectorEvent::Connection(_) => panic!("Connected to a closed port"),
+        }
+    }
+
+    #[test]
+    fn it_dials_secure_websocket_peers_with_a_custom_root_store() {
+        let mut runtime = Runtime::new().unwrap();
+        let port = spawn_echo_server(&mut runtime, Some(Identity::from_pkcs12(IDENTITY, "hunter2").unwrap()));
+
+        let peer_address = peer_address(PeerAddressType::Wss("localhost".to_string(), port));
+        let event = connect(&mut runtime, peer_address.clone(), Some(pinned_tls_connector()));
+        assert_connected(event, peer_address);
+    }
+
+    #[test]
+    fn it_reports_failed_tls_handshakes() {
+        let mut runtime = Runtime::new().unwrap();
+        let port = spawn_echo_server(&mut runtime, Some(Identity::from_pkcs12(IDENTITY, "hunter2").unwrap()));
+
+        // The self-signed certificate is not trusted by the system's root certificates.
+        let peer_address = peer_address(PeerAddressType::Wss("localhost".to_string(), port));
+        match connect(&mut runtime, peer_address.clone(), None) {
+            WebSocketConnectorEvent::Error(address, ty) => {
+                assert_eq!(address, peer_address);
+                assert_eq!(ty, CloseType::TlsHandshakeFailed);
+            },
+            WebSocketConnectorEvent::Connection(_) => panic!("Connected despite an untrusted certificate"),
+        }
+    }
+}
@@ -113,7 +113,7 @@ impl Account {
     pub fn balance_add(balance: Coin, value: Coin) -> Result<Coin, AccountError> {
         return match balance.checked_add(value) {
             Some(result) => Ok(result),
-            None => Err(AccountError::InsufficientFunds)
+            None => Err(AccountError::BalanceOverflow)
         };
     }
 
@@ -274,6 +274,7 @@ impl PartialEq for PrunedAccount {
 #[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Debug)]
 pub enum AccountError {
     InsufficientFunds,
+    BalanceOverflow,
     TypeMismatch,
     InvalidSignature,
     InvalidForSender,
@@ -292,6 +293,17 @@ impl fmt::Display for AccountError {
     }
 }
 
+impl std::error::Error for AccountError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AccountError::InvalidSerialization(e) => Some(e),
+            #[cfg(feature = "transaction")]
+            AccountError::InvalidTransaction(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 impl From<SerializingError> for AccountError {
     fn from(e: SerializingError) -> Self {
         AccountError::InvalidSerialization(e)
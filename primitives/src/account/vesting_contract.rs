@@ -37,7 +37,8 @@ impl VestingContract {
 
     pub fn min_cap(&self, block_height: u32) -> Coin {
         return if self.vesting_step_blocks > 0 && self.vesting_step_amount > Coin::ZERO {
-            let steps = ((block_height - self.vesting_start) as f64 / self.vesting_step_blocks as f64).floor();
+            // Before the vesting start, no step has been released yet.
+            let steps = (block_height.saturating_sub(self.vesting_start) as f64 / self.vesting_step_blocks as f64).floor();
             let min_cap = u64::from(self.vesting_total_amount) as f64 - steps * u64::from(self.vesting_step_amount) as f64;
             Coin::from(min_cap.max(0f64) as u64)
         } else {
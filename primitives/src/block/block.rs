@@ -1,8 +1,9 @@
 use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError};
 use hash::{Argon2dHash, Blake2bHash, Hash};
 
-use crate::block::{BlockBody, BlockError, BlockHeader, BlockInterlink, Target};
+use crate::block::{BlockBody, BlockError, BlockHeader, BlockInterlink, BlockVersion, Target, TargetCompact};
 use crate::networks::NetworkId;
+use crate::policy;
 
 #[derive(Default, Clone, PartialEq, PartialOrd, Eq, Ord, Debug, Serialize)]
 pub struct Block {
@@ -14,29 +15,39 @@ pub struct Block {
 impl Deserialize for Block {
     fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
         let header: BlockHeader = Deserialize::deserialize(reader)?;
-        let interlink = BlockInterlink::deserialize(reader, &header.prev_hash)?;
-        return Ok(Block {
-            header,
-            interlink,
-            body: Deserialize::deserialize(reader)?,
-        });
+        match BlockVersion::from_u16(header.version) {
+            Some(BlockVersion::V1) => {
+                let interlink = BlockInterlink::deserialize(reader, &header.prev_hash)?;
+                return Ok(Block {
+                    header,
+                    interlink,
+                    body: Deserialize::deserialize(reader)?,
+                });
+            },
+            None => return Err(SerializingError::InvalidValue),
+        }
     }
 }
 
 impl Block {
-    pub const VERSION: u16 = 1;
-    const TIMESTAMP_DRIFT_MAX: u64 = 600 * 1000;
+    pub const VERSION: u16 = BlockVersion::CURRENT as u16;
     const MAX_SIZE: usize = 100000; // 100 kb
 
     pub fn verify(&self, timestamp_now: u64, network_id: NetworkId, genesis_hash: Blake2bHash) -> Result<(), BlockError> {
-        // XXX Check that the block version is supported.
-        if self.header.version != Block::VERSION {
-            return Err(BlockError::UnsupportedVersion);
+        self.verify_with_drift(timestamp_now, policy::BLOCK_MAX_FUTURE_DRIFT, network_id, genesis_hash)
+    }
+
+    /// Like `verify`, but accepts timestamps up to `max_drift` milliseconds ahead of `timestamp_now`.
+    pub fn verify_with_drift(&self, timestamp_now: u64, max_drift: u64, network_id: NetworkId, genesis_hash: Blake2bHash) -> Result<(), BlockError> {
+        match BlockVersion::from_u16(self.header.version) {
+            Some(BlockVersion::V1) => self.verify_v1(timestamp_now, max_drift, network_id, genesis_hash),
+            None => Err(BlockError::UnsupportedVersion),
         }
+    }
 
+    fn verify_v1(&self, timestamp_now: u64, max_drift: u64, network_id: NetworkId, genesis_hash: Blake2bHash) -> Result<(), BlockError> {
         // Check that the timestamp is not too far into the future.
-        // XXX Move this check to Blockchain?
-        if self.header.timestamp_in_millis() > timestamp_now + Block::TIMESTAMP_DRIFT_MAX {
+        if self.header.timestamp_in_millis() > timestamp_now.saturating_add(max_drift) {
             return Err(BlockError::FromTheFuture);
         }
 
@@ -62,6 +73,43 @@ impl Block {
         return Ok(());
     }
 
+    /// Verifies this block against its predecessor without access to a blockchain.
+    /// The interlink is not checked as this requires the predecessor's interlink.
+    pub fn verify_against(&self, prev: &BlockHeader, expected_n_bits: TargetCompact, network_id: NetworkId) -> Result<(), BlockError> {
+        // Check that the block version is supported.
+        if BlockVersion::from_u16(self.header.version).is_none() {
+            return Err(BlockError::UnsupportedVersion);
+        }
+
+        // Check that the block is an immediate successor of the given predecessor.
+        if !self.header.is_immediate_successor_of(prev) {
+            return Err(BlockError::InvalidSuccessor);
+        }
+
+        // Check that the difficulty is the expected one.
+        if self.header.n_bits != expected_n_bits {
+            return Err(BlockError::DifficultyMismatch);
+        }
+
+        // Check that the proof of work is valid.
+        if !self.header.verify_proof_of_work() {
+            return Err(BlockError::InvalidPoW);
+        }
+
+        // Check that the maximum block size is not exceeded.
+        if self.serialized_size() > Block::MAX_SIZE {
+            return Err(BlockError::SizeExceeded);
+        }
+
+        // Verify the body if it is present.
+        if let Option::Some(ref body) = self.body {
+            self.verify_body(body, network_id)?;
+        }
+
+        // Everything checks out.
+        return Ok(());
+    }
+
     fn verify_interlink(&self, genesis_hash: Blake2bHash) -> Result<(), BlockError> {
         // Skip check for genesis block due to the cyclic dependency (since the interlink hash contains the genesis block hash).
         if self.header.height == 1 && self.header.interlink_hash == Blake2bHash::from([0u8; Blake2bHash::SIZE]) {
@@ -106,6 +154,22 @@ impl Block {
         return true;
     }
 
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.serialize_to_vec())
+    }
+
+    pub fn from_hex(s: &str) -> Result<Block, BlockError> {
+        let bytes = hex::decode(s).map_err(|_| BlockError::InvalidHex)?;
+        let mut reader = &bytes[..];
+        let block: Block = Deserialize::deserialize(&mut reader).map_err(|_| BlockError::InvalidSerialization)?;
+
+        // Reject trailing garbage after the block.
+        if !reader.is_empty() {
+            return Err(BlockError::InvalidSerialization);
+        }
+        return Ok(block);
+    }
+
     pub fn get_next_interlink(&self, next_target: &Target) -> BlockInterlink {
         let mut hashes: Vec<Blake2bHash> = vec![];
         let hash: Blake2bHash = self.header.hash();
@@ -1,9 +1,9 @@
 use std::{cmp::Ordering, io};
 
 use beserial::{Deserialize, Serialize};
-use hash::{Hash, HashOutput, SerializeContent};
+use hash::{Blake2bHash, Hash, HashOutput, SerializeContent};
 use keys::Address;
-use utils::merkle;
+use utils::merkle::{self, Blake2bMerklePath};
 
 use crate::account::PrunedAccount;
 use crate::block::BlockError;
@@ -27,21 +27,50 @@ impl SerializeContent for BlockBody {
 
 impl Hash for BlockBody {
     fn hash<H: HashOutput>(&self) -> H {
-        let mut vec: Vec<H> = Vec::with_capacity(2 + self.transactions.len() + self.pruned_accounts.len());
-        vec.push(self.miner.hash());
-        vec.push(self.extra_data.hash());
-        for t in &self.transactions {
+        BlockBody::compute_root_generic(&self.miner, &self.extra_data, &self.transactions, &self.pruned_accounts)
+    }
+}
+
+#[allow(unreachable_code)]
+impl BlockBody {
+    /// Computes the body hash for the given contents without constructing a `BlockBody`.
+    pub fn compute_root(miner: &Address, extra_data: &[u8], transactions: &[Transaction], pruned_accounts: &[PrunedAccount]) -> Blake2bHash {
+        BlockBody::compute_root_generic(miner, extra_data, transactions, pruned_accounts)
+    }
+
+    fn compute_root_generic<H: HashOutput>(miner: &Address, extra_data: &[u8], transactions: &[Transaction], pruned_accounts: &[PrunedAccount]) -> H {
+        let leaves: Vec<H> = BlockBody::leaves(miner, extra_data, transactions, pruned_accounts);
+        return merkle::compute_root_from_hashes::<H>(&leaves);
+    }
+
+    /// The leaves of the body's merkle tree: miner, extra data, transactions and pruned accounts, in this order.
+    fn leaves<H: HashOutput>(miner: &Address, extra_data: &[u8], transactions: &[Transaction], pruned_accounts: &[PrunedAccount]) -> Vec<H> {
+        let mut vec: Vec<H> = Vec::with_capacity(2 + transactions.len() + pruned_accounts.len());
+        vec.push(miner.hash());
+        vec.push(extra_data.hash());
+        for t in transactions {
             vec.push(t.hash());
         }
-        for p in &self.pruned_accounts {
+        for p in pruned_accounts {
             vec.push(p.hash());
         }
-        return merkle::compute_root_from_hashes::<H>(&vec);
+        return vec;
+    }
+
+    /// Returns a proof that the transaction with the given hash is part of this body,
+    /// or `None` if the body does not contain it.
+    pub fn get_transaction_proof(&self, tx_hash: &Blake2bHash) -> Option<TransactionProof> {
+        let index = self.transactions.iter().position(|tx| &tx.hash::<Blake2bHash>() == tx_hash)?;
+
+        let leaves: Vec<Blake2bHash> = BlockBody::leaves(&self.miner, &self.extra_data, &self.transactions, &self.pruned_accounts);
+
+        // The miner and extra data leaves precede the transactions.
+        return Some(TransactionProof {
+            transaction_hash: tx_hash.clone(),
+            path: Blake2bMerklePath::from_hashes(&leaves, 2 + index),
+        });
     }
-}
 
-#[allow(unreachable_code)]
-impl BlockBody {
     pub fn verify(&self, block_height: u32, network_id: NetworkId) -> Result<(), BlockError> {
         let mut previous_tx: Option<&Transaction> = None;
         for tx in &self.transactions {
@@ -96,3 +125,17 @@ impl BlockBody {
         return Ok(());
     }
 }
+
+/// Proves that a transaction is included in a block body with a given hash.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionProof {
+    pub transaction_hash: Blake2bHash,
+    pub path: Blake2bMerklePath,
+}
+
+impl TransactionProof {
+    /// Checks that the proven transaction is part of the body with the given hash.
+    pub fn verify(&self, body_hash: &Blake2bHash) -> bool {
+        &self.path.compute_root_from_hash(self.transaction_hash.clone()) == body_hash
+    }
+}
@@ -1,7 +1,7 @@
-use std::io;
+use std::io::{self, Write};
 
 use beserial::{Deserialize, Serialize};
-use hash::{Argon2dHash, Blake2bHash, Hash, SerializeContent};
+use hash::{Argon2dHash, Argon2dHasher, Blake2bHash, Hash, Hasher, SerializeContent};
 
 use crate::block::{Target, TargetCompact};
 
@@ -24,7 +24,34 @@ impl SerializeContent for BlockHeader {
 
 impl Hash for BlockHeader {}
 
+/// The serialized header without the trailing nonce, used to hash many nonces for the same header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrecomputedHeader {
+    prefix: Vec<u8>,
+}
+
+impl<'a> From<&'a BlockHeader> for PrecomputedHeader {
+    fn from(header: &'a BlockHeader) -> Self {
+        let mut prefix = header.serialize_to_vec();
+        // The nonce is serialized last.
+        prefix.truncate(prefix.len() - BlockHeader::NONCE_SIZE);
+        PrecomputedHeader { prefix }
+    }
+}
+
+impl PrecomputedHeader {
+    /// Computes the proof-of-work hash of the header this was computed from with the given nonce.
+    pub fn pow(&self, nonce: u32) -> Argon2dHash {
+        let mut hasher = Argon2dHasher::default();
+        hasher.write_all(&self.prefix).unwrap();
+        hasher.write_all(&nonce.to_be_bytes()).unwrap();
+        hasher.finish()
+    }
+}
+
 impl BlockHeader {
+    const NONCE_SIZE: usize = 4;
+
     pub fn verify_proof_of_work(&self) -> bool {
         let pow: Argon2dHash = self.hash();
         let target: Target = self.n_bits.into();
@@ -35,6 +62,14 @@ impl BlockHeader {
         self.hash()
     }
 
+    /// Returns the serialized header that `pow()` feeds into Argon2d.
+    /// External miners can hash this themselves and only submit a found nonce.
+    pub fn pow_input(&self) -> Vec<u8> {
+        let mut input = Vec::with_capacity(self.serialized_size());
+        self.serialize_content(&mut input).unwrap();
+        input
+    }
+
     pub fn is_immediate_successor_of(&self, prev_header: &BlockHeader) -> bool {
         // Check that the height is one higher than the previous height.
         if self.height != prev_header.height + 1 {
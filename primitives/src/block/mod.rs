@@ -5,13 +5,33 @@ mod interlink;
 mod target;
 
 pub use self::block::Block;
-pub use self::body::BlockBody;
-pub use self::header::BlockHeader;
+pub use self::body::{BlockBody, TransactionProof};
+pub use self::header::{BlockHeader, PrecomputedHeader};
 pub use self::interlink::BlockInterlink;
-pub use self::target::{Target, TargetCompact, Difficulty};
+pub use self::target::{Target, TargetCompact, Difficulty, pow_meets_difficulty};
+
+use std::fmt;
 
 use crate::transaction::TransactionError;
 
+/// The block versions we know how to (de)serialize and verify.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug)]
+#[repr(u16)]
+pub enum BlockVersion {
+    V1 = 1,
+}
+
+impl BlockVersion {
+    pub const CURRENT: BlockVersion = BlockVersion::V1;
+
+    pub fn from_u16(version: u16) -> Option<BlockVersion> {
+        match version {
+            1 => Some(BlockVersion::V1),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Debug)]
 pub enum BlockError {
     UnsupportedVersion,
@@ -20,6 +40,8 @@ pub enum BlockError {
     SizeExceeded,
     InterlinkHashMismatch,
     BodyHashMismatch,
+    InvalidSuccessor,
+    DifficultyMismatch,
 
     DuplicateTransaction,
     InvalidTransaction(TransactionError),
@@ -29,4 +51,46 @@ pub enum BlockError {
     DuplicatePrunedAccount,
     PrunedAccountsNotOrdered,
     InvalidPrunedAccount,
+
+    InvalidHex,
+    InvalidSerialization,
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockError::UnsupportedVersion => write!(f, "unsupported block version"),
+            BlockError::FromTheFuture => write!(f, "timestamp too far in the future"),
+            BlockError::InvalidPoW => write!(f, "invalid proof-of-work"),
+            BlockError::SizeExceeded => write!(f, "block size exceeded"),
+            BlockError::InterlinkHashMismatch => write!(f, "interlink hash mismatch"),
+            BlockError::BodyHashMismatch => write!(f, "body hash mismatch"),
+            BlockError::InvalidSuccessor => write!(f, "not a valid successor"),
+            BlockError::DifficultyMismatch => write!(f, "difficulty mismatch"),
+            BlockError::DuplicateTransaction => write!(f, "duplicate transaction"),
+            BlockError::InvalidTransaction(e) => write!(f, "invalid transaction ({})", e),
+            BlockError::ExpiredTransaction => write!(f, "expired transaction"),
+            BlockError::TransactionsNotOrdered => write!(f, "transactions not ordered"),
+            BlockError::DuplicatePrunedAccount => write!(f, "duplicate pruned account"),
+            BlockError::PrunedAccountsNotOrdered => write!(f, "pruned accounts not ordered"),
+            BlockError::InvalidPrunedAccount => write!(f, "invalid pruned account"),
+            BlockError::InvalidHex => write!(f, "invalid hex"),
+            BlockError::InvalidSerialization => write!(f, "invalid serialization"),
+        }
+    }
+}
+
+impl std::error::Error for BlockError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BlockError::InvalidTransaction(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<TransactionError> for BlockError {
+    fn from(e: TransactionError) -> Self {
+        BlockError::InvalidTransaction(e)
+    }
 }
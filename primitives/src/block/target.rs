@@ -207,6 +207,30 @@ impl fmt::Display for Difficulty {
     }
 }
 
+impl TargetCompact {
+    /// Same as `Target::is_met_by`, but compares the hash against the compact representation directly.
+    pub fn is_met_by_compact(&self, hash: &Argon2dHash) -> bool {
+        let shift_bytes = (self.0 >> 24).saturating_sub(3) as usize;
+        let value = self.0 & 0xffffff;
+        let bytes = hash.as_bytes();
+
+        // The target is zero above its three value bytes, so the hash must be as well.
+        let start = 32 - shift_bytes - 3;
+        if bytes[..start].iter().any(|b| *b != 0) {
+            return false;
+        }
+
+        // The target is zero below its value bytes, so the hash must be strictly smaller on them.
+        let reached = ((bytes[start] as u32) << 16) + ((bytes[start + 1] as u32) << 8) + bytes[start + 2] as u32;
+        return reached < value;
+    }
+}
+
+/// Checks whether a proof-of-work hash meets the target corresponding to `difficulty`.
+pub fn pow_meets_difficulty(pow: &Argon2dHash, difficulty: &Difficulty) -> bool {
+    Target::from(difficulty.clone()).is_met_by(pow)
+}
+
 impl Target {
     pub fn is_met_by(&self, hash: &Argon2dHash) -> bool {
         let reached = Target::from(hash);
@@ -6,6 +6,8 @@ use std::str::FromStr;
 
 use beserial::{Serialize, SerializingError, Deserialize, ReadBytesExt, WriteBytesExt};
 
+use crate::policy;
+
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
 pub struct Coin(u64);
@@ -88,10 +90,13 @@ impl Serialize for Coin {
 }
 
 
-// TODO: This should also check for MAX_SAFE_VALUE
+// TODO: checked_factor should also check for MAX_SAFE_VALUE
 impl Coin {
+    /// Returns `None` on overflow or if the result exceeds `policy::TOTAL_SUPPLY`.
     pub fn checked_add(self, rhs: Coin) -> Option<Coin> {
-        self.0.checked_add(rhs.0).map(|v| Coin(v))
+        self.0.checked_add(rhs.0)
+            .filter(|v| *v <= policy::TOTAL_SUPPLY)
+            .map(|v| Coin(v))
     }
 
     pub fn checked_sub(self, rhs: Coin) -> Option<Coin> {
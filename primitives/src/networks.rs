@@ -8,4 +8,28 @@ pub enum NetworkId {
     Bounty = 3,
     Dummy = 4,
     Main = 42,
-}
\ No newline at end of file
+}
+
+impl NetworkId {
+    /// Returns the network with the given name as used in config files, e.g. `"main"`.
+    pub fn from_name(name: &str) -> Option<NetworkId> {
+        match name {
+            "test" => Some(NetworkId::Test),
+            "dev" => Some(NetworkId::Dev),
+            "bounty" => Some(NetworkId::Bounty),
+            "dummy" => Some(NetworkId::Dummy),
+            "main" => Some(NetworkId::Main),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NetworkId::Test => "test",
+            NetworkId::Dev => "dev",
+            NetworkId::Bounty => "bounty",
+            NetworkId::Dummy => "dummy",
+            NetworkId::Main => "main",
+        }
+    }
+}
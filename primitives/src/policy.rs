@@ -18,14 +18,25 @@ pub const BLOCK_TIME: u32 = 60;
 /// Number of blocks we take into account to calculate next difficulty.
 pub const DIFFICULTY_BLOCK_WINDOW: u32 = 120;
 
+/// Minimum number of seconds a full difficulty window has to span. Windows with (nearly) equal
+/// timestamps indicate timestamp manipulation. Enforced from `NetworkInfo::timestamp_rules_height` on.
+pub const DIFFICULTY_MIN_WINDOW_TIMESPAN: u32 = DIFFICULTY_BLOCK_WINDOW;
+
+/// Maximum number of milliseconds a block's timestamp may be ahead of the network time.
+pub const BLOCK_MAX_FUTURE_DRIFT: u64 = 600 * 1000;
+
+/// Number of preceding blocks whose median timestamp a block's timestamp has to exceed.
+/// Enforced from `NetworkInfo::timestamp_rules_height` on.
+pub const MEDIAN_TIME_PAST_WINDOW: u32 = 11;
+
 /// Limits the rate at which the difficulty is adjusted min/max.
 pub const DIFFICULTY_MAX_ADJUSTMENT_FACTOR: f64 = 2f64;
 
 /// Number of blocks a transaction is valid.
 pub const TRANSACTION_VALIDITY_WINDOW: u32 = 120;
 
-/// Total supply in satoshis.
-const TOTAL_SUPPLY: u64 = 2100000000000000;
+/// Total supply in satoshis, no valid balance can exceed this.
+pub const TOTAL_SUPPLY: u64 = 2100000000000000;
 
 /// Initial supply in satoshis.
 const INITIAL_SUPPLY: u64 = 252000000000000;
@@ -99,6 +110,12 @@ pub fn block_reward_at(block_height: u32) -> Coin {
     Coin::from(compute_block_reward(current_supply, block_height))
 }
 
+/// Total supply after the block at `block_height` has been mined.
+#[cfg(feature = "coin")]
+pub fn total_supply_at(block_height: u32) -> Coin {
+    Coin::from(supply_after(block_height))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -129,4 +146,11 @@ mod tests {
         assert_eq!(supply_after(52888983), 2099999999996000);
         assert_eq!(supply_after(52888984), 2100000000000000);
     }
+
+    #[test]
+    fn it_correctly_computes_total_supply() {
+        assert_eq!(total_supply_at(1), 252000440597534.into());
+        assert_eq!(total_supply_at(100000), 295538674723488.into());
+        assert_eq!(total_supply_at(52888984), TOTAL_SUPPLY.into());
+    }
 }
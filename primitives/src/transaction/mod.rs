@@ -395,6 +395,15 @@ impl std::fmt::Display for TransactionError {
     }
 }
 
+impl std::error::Error for TransactionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TransactionError::InvalidSerialization(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 impl From<SerializingError> for TransactionError {
     fn from(e: SerializingError) -> Self {
         TransactionError::InvalidSerialization(e)
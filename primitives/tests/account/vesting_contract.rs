@@ -282,3 +282,21 @@ fn it_refuses_invalid_transaction() {
     tx.proof = signature_proof.serialize_to_vec();
     assert_eq!(start_contract.with_outgoing_transaction(&tx, 100), Err(AccountError::InsufficientFunds));
 }
+
+#[test]
+fn it_locks_the_total_amount_before_the_vesting_start() {
+    let contract = VestingContract {
+        balance: Coin::from(1000),
+        owner: Address::from([1u8; 20]),
+        vesting_start: 200,
+        vesting_step_blocks: 100,
+        vesting_step_amount: Coin::from(100),
+        vesting_total_amount: Coin::from(1000),
+    };
+
+    assert_eq!(contract.min_cap(0), Coin::from(1000));
+    assert_eq!(contract.min_cap(199), Coin::from(1000));
+    assert_eq!(contract.min_cap(299), Coin::from(1000));
+    assert_eq!(contract.min_cap(300), Coin::from(900));
+    assert_eq!(contract.min_cap(1200), Coin::ZERO);
+}
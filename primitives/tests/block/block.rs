@@ -10,6 +10,7 @@ const GENESIS_BLOCK: &str = "000100000000000000000000000000000000000000000000000
 const BLOCK_108273: &str = "00012c2709b842ffd5d822fb881235a9981dcb4f031562437bbff4641d09e5bfcd11b14f16b209ac32a3d909adcc759b2c53f8f9f478df7dcb74101c57448af2993f8026c8d5f600afa7c0cea5d2163acbd97ba96de9d1cc82007840c06451b6894b913cfeb96831cda39e7a49b5c6af7fb3a5c0b86fd544c78e653e275b7e180f791c20ce8c0001a6f15b3549ec9728d73011c85c00e422eb357417aff6c0b474e682f93b655359494cfebb2cd6b8fa2e9fb3f222268edaecae80ab0404f03984e31f19d696a225f11abbd99bf04f0ee95266dfe32cc81df55a834afd5e2b030cecb9098dc11afcda1d193a11932295ee7643947dd4bf5605eac12bd307ccc00ac55d591d5aeaf62d6b4bf7861f55791525a41d86b970bfa2bf633a1c681a75fb898331fbedea826aee38adcc63b178a504c1f4c7cf3ddb1d7e71258d31ef411f905d249d44b524494360a95cf4705ec7aa52f62e8d8430ab5bfaf2433ce9af81ca4810c98b54c6b2ecdf2c28f043143a18c74ff445805c8084bc95ffa368380978c9a44945a7ffeb053f687f58e5c77d2dc08d46fb7bce4aa80ca24f04870b31bb5f4a4e21d143e21d33e7bcecfa232db3ccf691175d5485f031c039a43f641839186a4716866e655d89c85af79754ad8786c08df001432715a84417723b0889b22b6917de391ab2fa480d736b79706f6f6c2d7368312d3200020042b32159040ebe741316b18107564b28783e89582edc2173917b38ab39d904ee47d18f75e7bc7036e0bb496252acd74f0bf8c6450000000000325aa000000000000000000001a6ef2a1ff786d2f42ad64bd97f139224a5a51f0260c4accce427e14cecb89c87711bbd6a7e7c26fde70eac3c555d8c705b977ce303a13b940a13a3a7470bc2f30d550000fe9bb3282823f79cb03b3b8de3f5fb6e2c66f1959f598442caaf0a680998bccbc1fb2d53a6d7e0011e85c7f81f5b7a76088c154a00000000000cc79000000000000000000001a6ef2aa7eb223b1495cce947e8c6e5113d71c5bf78cb2d6d039db1d374d0fa5954073e8f003b60dc5791a0e20f7ad9ab9df68ffe825d6f8f01ee2d381eee15f5728b0b0000";
 const BLOCK_169500: &str = "0001e5ed6f0aad730ddaa2f17445ea3d93e05ca6484d672217e7bae5d6d9c46c71429647e98d1884240082fe16fb5d89c55dcb25f12895753884fab3922ff5bc945fa65795b408a23693203cb7bbae3206fafcf0f49a8e2832b51df27e904d1f397d3c02ddd12171844cabd7ed21eab37a66151a1393c7b4a4cc7f9b7a5b79de7ffe1c2a9f9e0002961c5b6d93a40002309711d7158085c2b5c21a61184baa85b45dce5f01efb1a7ea406d5a03321e88d5591203facc187ed1f71ae10f387fa470a03b35aa8b5cec149b30677bb1d7f31ee2804712798ebb63ebe968352b3808f08ab03074a2176b13219b6358ed5b25030de1bfe4b9226e3e7c6ae737ee5be7f63c8c6db08fac57068cd69cb5bbca6c9f12fbecf9cefd0b89122748de57ea59b282e10d64b2243ea57b6ff0d8178eeb2bb256c687f889cab12264f6a5320fc74ec9b18275d3db6da35a7a4dcd7c6b0208f49511f84f97475b01e9c763c07fad35245ca795dff12cd162b0b4a40aa108b831e36ba0a201b403040ff02b4a9a4d10f8a4beff71750cd3aa7f324e696d6275732d393630656364346238313936303030303030323266386531313034663066663830323530373939373632320003010000ad8e224835e6cc0cadbcf500a49dae46f67697040224786862babbdb05e7c4430612135eb2a836812300000000000000000100000000000000000002961b2a0000a4010301daebe368963c60d22098a5e9f1ebcb8e54d0b7beca942a2a0a9d95391804fe8f94dc65fbd91fc9f5ed1d92dba86d2e59607774b13e27a556d92a56ade9808d32cebdb4e704995dd63f97429a827541d3c6253bc0c2f472c3341b538e2f4bc60800e930b6408b84cb3a7985c54e742c4a1af3110b865386144a0e1badd0bceefc162cecfda0761ce11e06a076c2882b2e21309602973a4443ba4d672b665d4f3f0100bf703dd4eb71e245cced26f014f40dc29f0f71e6bffce722a7ceecba1b71cb8d44d5c0727a1a08efd376cd005293e33236232fad0000000000004e2000000000000001180002961a2a8f6693b1eb1537aa8eec3f99898d91474e5cfa1de1c81a1ee9e0c04ba0205e310d65708ea6e5ed9e7953f4dce64e73392333a2b83d1b30d0df9eb74bcc68980900725323e8f226e7b9dac90331505844f83e45f28cc6f0534039f55c725536d1ec6573413bec835fa4fb54f4dfc3b273a9e8ecc95b000000000011c7020000000000000000000296192a53ddbfd2dd72a78a8b62699e0012585f4768f07e397ae3bb23d0cd379c0c12c9dbe0aca1ae8f005330bbb33c80ee1449229ad6d0e482a1fc8124233dd953600e0001ad8e224835e6cc0cadbcf500a49dae46f67697040200000000000000001b215589344cf570d36bec770825eae30b73213924786862babbdb05e7c4430612135eb2a836812303daebe368963c60d22098a5e9f1ebcb8e54d0b7beca942a2a0a9d95391804fe8f01000296350000000000000001";
 const BLOCK_67795: &str = "00014c7b3257f87ff064021ac13bfb185375cd28d452035e2fb76d23319f9fdcce8a597e025e7503f068a7b3fb9e91fb56b0f841f8fd5e9e2676ea2fba09050bbd4907ae6f6c95fea18a682017d7b422ae5eada2d41d0280522961b5db68975dc4601c4e9b2923c04ad77ed16aeed1182c7a6553711d2f301938088d3bf2589c206e1c1a9226000108d35b100d03004129d710b1c8e1aed9ca3ca77a351ef819d47cb8e45f21c2b23f61f907369c89b45534d1e2aba15de92a0a0366eb0c561671dc1365eca8a09fe7e2c89efd7ecb14710564bb91c24aa8e523634564f3a490f33fec7b3c6eca1e8f06eaeee2e9af0deb22c95280c77a828d6a41c8dfddb0fc6fd5ac78bad58d37dc95541380e22cce4bca1a54710a727ae796243949f3352f13e9316b8a2cdb52a592568142961859e07baec906dbb9d042488cd060dcbd4ceecd562a695a7953457b3b516867ee9e97ad12e01bac425484037b3ebabbcf477fae6d53dee1e74aaa6f7d7ba5fd018223d2ae700d4e6916f504c23e28de6753ccaa6dba811a6731c21c94edaa90e74e954bb510845d5485f031c039a43f641839186a4716866e655d89c85af79754ad8786c08df0016c4e6bc79391038a786ae11c0a8175c4a29c628125426565702d33627a4b4e587935613972797937793646374d7142726f70716e3730326a526b000200bf703dd4eb71e245cced26f014f40dc29f0f71e6bffce722a7ceecba1b71cb8d8a98acef6877ce586313cf7d46410f195c43fb5e00000000000061a80000000000000096000108d22ae2b8fe84104a06dd7c07987f08c77320704152a17f6c3d2a036fe20929794f5734f37fc9513335cea0844a47558748aae6603e1e4e3f3cc39480bcd4051e080c010000f3e6c569c694030b329b03b06ebbb8bb27586e1702bab04d1e40cc8e99fcc3caacc23e6ddd0a340d150000000000000000010000000000000000000108d32a0000a40103011fe43246c44f12e0d30d9bc3cf8de1bcf5740127e67caeea1459a9422802e42ac22aff463016308427f10d89913917eb9b39b2b422cfa51baa188fb33a61ffa4ca8ac9412bbc05153b1faa9fae61a31d6b6ce09cc9a04c671a31668189dd28d0004b4e09a27985ffe0de96e75dee210658990aed0a269ec8b256b8274d7d8ea4d290f25b7c95e88759c54a7e92a9c53ceba5417e434c79bf5f69e489f58da2420e0001f3e6c569c694030b329b03b06ebbb8bb27586e170200000000000000007d4731244bf358a96a2529488fe40135174cb8e5bab04d1e40cc8e99fcc3caacc23e6ddd0a340d15031fe43246c44f12e0d30d9bc3cf8de1bcf5740127e67caeea1459a9422802e42a01000108e80000000000000001";
+const BLOCK_2: &str = "0001264aaf8a4f9828a76c550635da078eb466306a189fcc03710bee9f649c869d120492e3986e75ac0d1466b5d6a7694c86839767a30980f8ba0d8c6e48631bc9cdd8a3eb957567d76963ad10d11e65453f763928fb9619e5f396a0906e946cce3ca7fcbb5fb2e35055de071e868381ba426a8d79d97cb48dab8345baeb9a9abb091f010000000000025ad23a98000046fe0180010000000000000000000000000000000000000000184d696e65642077697468206c6f766520627920526963687900000000";
 const GENESIS_HASH: &str = "264aaf8a4f9828a76c550635da078eb466306a189fcc03710bee9f649c869d12";
 
 #[test]
@@ -104,6 +105,22 @@ fn it_can_serialize_block_169500() {
 }
 
 
+#[test]
+fn it_can_convert_blocks_to_and_from_hex() {
+    let block = Block::from_hex(BLOCK_169500).unwrap();
+    assert_eq!(block.header.height, 169500);
+    assert_eq!(block.to_hex(), BLOCK_169500);
+    assert_eq!(Block::from_hex(&block.to_hex()).unwrap(), block);
+}
+
+#[test]
+fn it_rejects_malformed_hex_blocks() {
+    assert_eq!(Block::from_hex("not hex"), Err(BlockError::InvalidHex));
+    assert_eq!(Block::from_hex(&GENESIS_BLOCK[1..]), Err(BlockError::InvalidHex));
+    assert_eq!(Block::from_hex(&GENESIS_BLOCK[..100]), Err(BlockError::InvalidSerialization));
+    assert_eq!(Block::from_hex(&format!("{}00", GENESIS_BLOCK)), Err(BlockError::InvalidSerialization));
+}
+
 #[test]
 fn verify_accepts_genesis_block() {
     let v: Vec<u8> = hex::decode(GENESIS_BLOCK).unwrap();
@@ -139,6 +156,18 @@ fn verify_rejects_unsupported_block_versions() {
     assert_eq!(block.verify(block.header.timestamp_in_millis(), NetworkId::Main, GENESIS_HASH.into()), Err(BlockError::UnsupportedVersion));
 }
 
+#[test]
+fn it_dispatches_on_the_block_version() {
+    assert_eq!(BlockVersion::from_u16(Block::VERSION), Some(BlockVersion::CURRENT));
+    assert_eq!(BlockVersion::from_u16(2), None);
+
+    let block = Block::from_hex(GENESIS_BLOCK).unwrap();
+    assert_eq!(block.verify(block.header.timestamp_in_millis(), NetworkId::Main, GENESIS_HASH.into()), Ok(()));
+
+    // Blocks of unknown versions can't be deserialized.
+    assert_eq!(Block::from_hex(&format!("0002{}", &GENESIS_BLOCK[4..])), Err(BlockError::InvalidSerialization));
+}
+
 #[test]
 fn verify_rejects_blocks_from_the_future() {
     let block: Block = Block::deserialize_from_vec(&hex::decode(BLOCK_169500).unwrap()).unwrap();
@@ -193,6 +222,21 @@ fn verify_rejects_invalid_body() {
     assert_eq!(block.verify(block.header.timestamp_in_millis(), NetworkId::Main, GENESIS_HASH.into()), Err(BlockError::ExpiredTransaction));
 }
 
+#[test]
+fn verify_against_accepts_valid_successor() {
+    let genesis: Block = Block::from_hex(GENESIS_BLOCK).unwrap();
+    let block: Block = Block::from_hex(BLOCK_2).unwrap();
+    assert_eq!(block.verify_against(&genesis.header, 0x1f010000u32.into(), NetworkId::Main), Ok(()));
+    assert_eq!(block.verify_against(&genesis.header, 0x1f020000u32.into(), NetworkId::Main), Err(BlockError::DifficultyMismatch));
+}
+
+#[test]
+fn verify_against_rejects_wrong_predecessor() {
+    let genesis: Block = Block::from_hex(GENESIS_BLOCK).unwrap();
+    let mut block: Block = Block::from_hex(BLOCK_2).unwrap();
+    block.header.prev_hash = Blake2bHash::from([1u8; Blake2bHash::SIZE]);
+    assert_eq!(block.verify_against(&genesis.header, 0x1f010000u32.into(), NetworkId::Main), Err(BlockError::InvalidSuccessor));
+}
 
 #[test]
 fn it_correctly_identifies_immediate_successors() {
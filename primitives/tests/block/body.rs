@@ -1,6 +1,6 @@
 use beserial::{Deserialize, Serialize};
 use primitives::account::{Account, AccountType, PrunedAccount, VestingContract};
-use primitives::block::{BlockBody, BlockError};
+use primitives::block::{BlockBody, BlockError, TransactionProof};
 use primitives::coin::Coin;
 use keys::Address;
 use hash::{Blake2bHash, Hash};
@@ -38,6 +38,15 @@ fn it_can_calculate_genesis_body_hash() {
     assert_eq!(Hash::hash::<Blake2bHash>(&body), Blake2bHash::from("7cda9a7fdf06655905ae5dbd9c535451471b078fa6f3df0e287e5b0fb47a573a"));
 }
 
+#[test]
+fn it_can_compute_the_root_without_a_body() {
+    for body in [GENESIS_BODY, B169500_BODY, B67795_BODY].iter() {
+        let body = BlockBody::deserialize_from_vec(&hex::decode(body).unwrap()).unwrap();
+        let root = BlockBody::compute_root(&body.miner, &body.extra_data, &body.transactions, &body.pruned_accounts);
+        assert_eq!(root, Hash::hash::<Blake2bHash>(&body));
+    }
+}
+
 #[test]
 fn it_can_deserialize_b169500_body() {
     let v: Vec<u8> = hex::decode(B169500_BODY).unwrap();
@@ -103,6 +112,31 @@ fn it_can_calculate_b169500_body_hash() {
     assert_eq!(Hash::hash::<Blake2bHash>(&body), Blake2bHash::from("a65795b408a23693203cb7bbae3206fafcf0f49a8e2832b51df27e904d1f397d"));
 }
 
+#[test]
+fn it_can_prove_transactions_of_b169500_body() {
+    let body: BlockBody = BlockBody::deserialize_from_vec(&hex::decode(B169500_BODY).unwrap()).unwrap();
+    let body_hash: Blake2bHash = Hash::hash(&body);
+
+    // First, middle and last transaction.
+    for tx in body.transactions.iter() {
+        let tx_hash: Blake2bHash = tx.hash();
+        let proof = body.get_transaction_proof(&tx_hash).unwrap();
+        assert_eq!(proof.transaction_hash, tx_hash);
+        assert!(proof.verify(&body_hash));
+
+        let proof = TransactionProof::deserialize_from_vec(&proof.serialize_to_vec()).unwrap();
+        assert!(proof.verify(&body_hash));
+        assert!(!proof.verify(&Blake2bHash::from([1u8; Blake2bHash::SIZE])));
+    }
+
+    // A proof for one transaction does not prove another one.
+    let mut proof = body.get_transaction_proof(&body.transactions[0].hash()).unwrap();
+    proof.transaction_hash = body.transactions[1].hash();
+    assert!(!proof.verify(&body_hash));
+
+    assert!(body.get_transaction_proof(&Blake2bHash::from([1u8; Blake2bHash::SIZE])).is_none());
+}
+
 #[test]
 fn verify_accepts_an_empty_body() {
     let body = BlockBody {
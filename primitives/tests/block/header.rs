@@ -1,6 +1,8 @@
+use std::io::Write;
+
 use beserial::{Deserialize, Serialize};
 use primitives::block::*;
-use hash::{Argon2dHash, Blake2bHash, Hash};
+use hash::{Argon2dHash, Argon2dHasher, Blake2bHash, Hash, Hasher};
 use hex;
 
 const GENESIS_HEADER: &str = "0001000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000007cda9a7fdf06655905ae5dbd9c535451471b078fa6f3df0e287e5b0fb47a573a1fefd44f1fa97185fda21e957545c97dc7643fa7e4efdd86e0aa4244d1e0bc5c1f010000000000015ad23a98000219d9";
@@ -63,6 +65,46 @@ fn verify_accepts_valid_proof_of_work() {
     assert!(header3.verify_proof_of_work());
 }
 
+#[test]
+fn it_can_compute_pow_with_precomputed_header() {
+    let mut header = BlockHeader::deserialize_from_vec(&hex::decode(B169500_HEADER).unwrap()).unwrap();
+    let precomputed = PrecomputedHeader::from(&header);
+
+    assert_eq!(precomputed.pow(header.nonce), header.pow());
+    for nonce in [0u32, 1, 0xdeadbeef].iter() {
+        let pow = precomputed.pow(*nonce);
+        header.nonce = *nonce;
+        assert_eq!(pow, header.pow());
+    }
+}
+
+#[test]
+fn it_hashes_pow_input_to_pow() {
+    let header = BlockHeader::deserialize_from_vec(&hex::decode(B169500_HEADER).unwrap()).unwrap();
+    let input = header.pow_input();
+    assert_eq!(input.len(), header.serialized_size());
+
+    let mut hasher = Argon2dHasher::default();
+    hasher.write_all(&input).unwrap();
+    assert_eq!(hasher.finish(), header.pow());
+}
+
+#[test]
+fn it_checks_compact_targets_consistently_with_verify_proof_of_work() {
+    for header_hex in [GENESIS_HEADER, B108273_HEADER, B169500_HEADER].iter() {
+        let mut header = BlockHeader::deserialize_from_vec(&hex::decode(header_hex).unwrap()).unwrap();
+        let difficulty = Difficulty::from(header.n_bits);
+        assert!(header.verify_proof_of_work());
+        assert!(header.n_bits.is_met_by_compact(&header.pow()));
+        assert!(pow_meets_difficulty(&header.pow(), &difficulty));
+
+        header.nonce += 1;
+        assert!(!header.verify_proof_of_work());
+        assert!(!header.n_bits.is_met_by_compact(&header.pow()));
+        assert!(!pow_meets_difficulty(&header.pow(), &difficulty));
+    }
+}
+
 #[test]
 fn verify_rejects_invalid_proof_of_work() {
     let mut header1 = BlockHeader::deserialize_from_vec(&hex::decode(GENESIS_HEADER).unwrap()).unwrap();
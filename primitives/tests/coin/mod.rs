@@ -2,6 +2,7 @@ extern crate hex;
 
 use beserial::{Serialize, Deserialize, SerializingError};
 use primitives::coin::{Coin, CoinParseError};
+use primitives::policy;
 use std::str::FromStr;
 
 
@@ -235,4 +236,24 @@ fn test_empty_frac_part() {
 #[test]
 fn test_integrity_frac_digits_and_lunas_per_coin() {
     assert_eq!(10u64.pow(Coin::FRAC_DIGITS), Coin::LUNAS_PER_COIN);
-}
\ No newline at end of file
+}
+
+#[test]
+fn it_detects_addition_overflow() {
+    assert_eq!(Coin::from(1u64).checked_add(Coin::from(2u64)), Some(Coin::from(3u64)));
+    assert_eq!(Coin::from(u64::max_value()).checked_add(Coin::from(1u64)), None);
+}
+
+#[test]
+fn it_detects_subtraction_underflow() {
+    assert_eq!(Coin::from(3u64).checked_sub(Coin::from(2u64)), Some(Coin::from(1u64)));
+    assert_eq!(Coin::from(2u64).checked_sub(Coin::from(3u64)), None);
+}
+
+#[test]
+fn it_rejects_sums_exceeding_max_supply() {
+    let max_supply = Coin::from(policy::TOTAL_SUPPLY);
+    assert_eq!(max_supply.checked_add(Coin::ZERO), Some(max_supply));
+    assert_eq!(max_supply.checked_add(Coin::from(1u64)), None);
+    assert!(policy::TOTAL_SUPPLY <= Coin::MAX_SAFE_VALUE);
+}
@@ -15,3 +15,5 @@ mod block;
 mod transaction;
 #[cfg(feature = "coin")]
 mod coin;
+#[cfg(feature = "networks")]
+mod networks;
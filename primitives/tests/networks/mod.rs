@@ -0,0 +1,12 @@
+use primitives::networks::NetworkId;
+
+#[test]
+fn it_converts_network_ids_to_and_from_names() {
+    for network_id in &[NetworkId::Test, NetworkId::Dev, NetworkId::Bounty, NetworkId::Dummy, NetworkId::Main] {
+        assert_eq!(NetworkId::from_name(network_id.name()), Some(*network_id));
+    }
+    assert_eq!(NetworkId::Main.name(), "main");
+    assert_eq!(NetworkId::from_name("test"), Some(NetworkId::Test));
+    assert_eq!(NetworkId::from_name("unknown"), None);
+    assert_eq!(NetworkId::from_name("Main"), None);
+}
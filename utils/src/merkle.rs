@@ -66,6 +66,30 @@ impl<H> MerklePath<H> where H: HashOutput {
         return MerklePath { nodes: path };
     }
 
+    /// Computes the path for the leaf at `index` of a tree whose leaves are the given hashes,
+    /// as built by `compute_root_from_hashes`.
+    pub fn from_hashes(hashes: &[H], index: usize) -> MerklePath<H> {
+        assert!(index < hashes.len(), "Leaf index out of bounds");
+        let mut path: Vec<MerklePathNode<H>> = Vec::new();
+        MerklePath::<H>::compute_from_hashes(hashes, index, &mut path);
+        return MerklePath { nodes: path };
+    }
+
+    fn compute_from_hashes(hashes: &[H], index: usize, path: &mut Vec<MerklePathNode<H>>) {
+        if hashes.len() <= 1 {
+            return;
+        }
+
+        let mid = (hashes.len() + 1) / 2; // Equivalent to round(len / 2.0)
+        if index < mid {
+            MerklePath::<H>::compute_from_hashes(&hashes[..mid], index, path);
+            path.push(MerklePathNode { hash: compute_root_from_slice::<H>(&hashes[mid..]).into_owned(), left: false });
+        } else {
+            MerklePath::<H>::compute_from_hashes(&hashes[mid..], index - mid, path);
+            path.push(MerklePathNode { hash: compute_root_from_slice::<H>(&hashes[..mid]).into_owned(), left: true });
+        }
+    }
+
     fn compute<D: Hasher<Output=H>, T: SerializeContent>(values: &[T], leaf_hash: &D::Output, path: &mut Vec<MerklePathNode<H>>) -> (bool, H) {
         let mut hasher = D::default();
         let mut contains_leaf = false;
@@ -98,7 +122,13 @@ impl<H> MerklePath<H> where H: HashOutput {
     }
 
     pub fn compute_root<T: SerializeContent>(&self, leaf_value: &T) -> H {
-        let mut root = H::Builder::default().chain(leaf_value).finish();
+        let leaf_hash = H::Builder::default().chain(leaf_value).finish();
+        return self.compute_root_from_hash(leaf_hash);
+    }
+
+    /// Computes the root starting from an already hashed leaf.
+    pub fn compute_root_from_hash(&self, leaf_hash: H) -> H {
+        let mut root = leaf_hash;
         for node in self.nodes.iter() {
             let mut h = H::Builder::default();
             if node.left {
@@ -1,4 +1,4 @@
-use nimiq_utils::merkle::{compute_root_from_content, compute_root_from_content_slice, MerklePath, MerkleProof};
+use nimiq_utils::merkle::{compute_root_from_content, compute_root_from_content_slice, compute_root_from_hashes, MerklePath, MerkleProof};
 use nimiq_hash::{Hasher, Blake2bHasher, Blake2bHash};
 use beserial::{Serialize, Deserialize};
 
@@ -153,6 +153,24 @@ fn it_correctly_computes_more_complex_paths() {
     assert_eq!(proof.compute_root(&values[5]), root);
 }
 
+#[test]
+fn it_correctly_computes_paths_from_hashes() {
+    let values = vec!["1", "2", "3", "4", "5", "6", "7"];
+    let hashes: Vec<Blake2bHash> = values.iter().map(|v| Blake2bHasher::default().digest(v.as_bytes())).collect();
+    let root = compute_root_from_hashes::<Blake2bHash>(&hashes);
+    assert_eq!(root, compute_root_from_content::<Blake2bHasher, &str>(&values));
+
+    for (i, value) in values.iter().enumerate() {
+        let proof = MerklePath::<Blake2bHash>::from_hashes(&hashes, i);
+        assert_eq!(proof, MerklePath::new::<Blake2bHasher, &str>(&values, value));
+        assert_eq!(proof.compute_root_from_hash(hashes[i].clone()), root);
+    }
+
+    let proof = MerklePath::<Blake2bHash>::from_hashes(&hashes[..1], 0);
+    assert_eq!(proof.len(), 0);
+    assert_eq!(proof.compute_root_from_hash(hashes[0].clone()), hashes[0]);
+}
+
 #[test]
 fn it_correctly_serializes_and_deserializes_path() {
     let values = vec!["1", "2", "3", "4", "5", "6", "7"];